@@ -4,7 +4,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build_client(false)
         .out_dir("src/proto")
         .compile(
-            &["proto/beneficiary.proto", "proto/testator.proto"],
+            &[
+                "proto/attestation.proto",
+                "proto/beneficiary.proto",
+                "proto/errors.proto",
+                "proto/testator.proto",
+                "proto/v2/beneficiary.proto",
+                "proto/v2/testator.proto",
+            ],
             &["proto/"],
         )?;
     tonic_build::configure()
@@ -12,8 +19,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .build_client(true)
         .out_dir("examples/proto")
         .compile(
-            &["proto/beneficiary.proto", "proto/testator.proto"],
+            &[
+                "proto/attestation.proto",
+                "proto/beneficiary.proto",
+                "proto/testator.proto",
+            ],
             &["proto/"],
         )?;
+    tonic_build::configure()
+        .build_server(true)
+        .build_client(true)
+        .out_dir("src/proto")
+        .compile(&["proto/replication.proto"], &["proto/"])?;
     Ok(())
 }
@@ -0,0 +1,61 @@
+use anyhow::{bail, ensure, Context};
+use ring::digest;
+use ring::rand::SecureRandom;
+
+use crate::proto::attestation::Attestation;
+
+/// Must be kept in sync with Will server implementation
+const REPORT_DATA_CONTEXT: &[u8] = b"zengo-will/attestation/v1";
+const MOCK_FORMAT: &str = "mock-v1";
+
+pub fn generate_nonce() -> anyhow::Result<[u8; 32]> {
+    let mut nonce = [0u8; 32];
+    ring::rand::SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow::anyhow!("generate nonce"))?;
+    Ok(nonce)
+}
+
+/// Verifies that attestation is fresh, binds Will's TLS certificate, and reports one of the
+/// expected measurements
+pub fn verify(
+    attestation: &Attestation,
+    nonce: &[u8],
+    will_cert_pem: &[u8],
+    expected_measurements: &[Vec<u8>],
+) -> anyhow::Result<()> {
+    let will_cert = pem::parse(will_cert_pem).context("parse Will certificate")?;
+    let cert_hash = digest::digest(&digest::SHA256, &will_cert.contents);
+    ensure!(
+        cert_hash.as_ref() == attestation.certificate_hash.as_slice(),
+        "attestation is issued for another TLS certificate"
+    );
+
+    let mut report_data = digest::Context::new(&digest::SHA512);
+    report_data.update(REPORT_DATA_CONTEXT);
+    report_data.update(&attestation.certificate_hash);
+    report_data.update(&attestation.share_encryption_key);
+    report_data.update(nonce);
+    let report_data = report_data.finish();
+
+    let (measurement, attested_report_data) = match attestation.format.as_str() {
+        MOCK_FORMAT => {
+            eprintln!("WARN: Server uses mock attestation, it proves nothing");
+            ensure!(attestation.evidence.len() == 96, "malformed mock evidence");
+            attestation.evidence.split_at(32)
+        }
+        format => bail!("unsupported attestation format: {}", format),
+    };
+    ensure!(
+        attested_report_data == report_data.as_ref(),
+        "attestation doesn't bind this request"
+    );
+    ensure!(
+        expected_measurements
+            .iter()
+            .any(|expected| expected.as_slice() == measurement),
+        "unexpected measurement: {}",
+        hex::encode(measurement)
+    );
+    Ok(())
+}
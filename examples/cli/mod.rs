@@ -9,13 +9,15 @@ type Hex = Vec<u8>;
 pub enum App {
     Testator(TestatorCmd),
     Beneficiary(BeneficiaryCmd),
-    GenShare,
+    GenShare(GenShare),
     GetCert(Server),
 }
 
 #[derive(StructOpt, Debug)]
 pub enum TestatorCmd {
     SaveShare(TestatorSaveShare),
+    /// Splits server share between several Wills, any `threshold` of which can restore it
+    DistributeShare(TestatorDistributeShare),
     SendKeepalive(TestatorSendKeepalive),
 }
 
@@ -23,6 +25,20 @@ pub enum TestatorCmd {
 pub enum BeneficiaryCmd {
     Verify(BeneficiaryVerify),
     Claim(BeneficiaryClaim),
+    /// Claims pieces of server share from several Wills and reconstructs it
+    ClaimDistributed(BeneficiaryClaimDistributed),
+    /// Contributes heir's piece to a claim without solving a challenge
+    Contribute(HeirContribute),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct GenShare {
+    /// Splits beneficiary's share between given number of heirs
+    #[structopt(long, requires = "heirs_threshold")]
+    pub heirs: Option<usize>,
+    /// Number of heirs required to claim testator's share
+    #[structopt(long, requires = "heirs")]
+    pub heirs_threshold: Option<usize>,
 }
 
 #[derive(StructOpt, Debug)]
@@ -32,10 +48,32 @@ pub struct TestatorSaveShare {
     #[structopt(long, parse(try_from_str = hex::decode))]
     pub public_key: Hex,
 
+    /// Commitments to beneficiary's share split between heirs, as printed by `gen-share`
+    #[structopt(long, requires_all(&["heirs", "heirs_threshold"]), parse(try_from_str = hex::decode))]
+    pub heir_commitment: Vec<Hex>,
+    #[structopt(long)]
+    pub heirs: Option<u32>,
+    #[structopt(long)]
+    pub heirs_threshold: Option<u32>,
+
     #[structopt(flatten)]
     pub will_server: TestatorServer,
 }
 
+#[derive(StructOpt, Debug)]
+pub struct TestatorDistributeShare {
+    #[structopt(long, parse(try_from_str = hex::decode))]
+    pub secret_share: Hex,
+    #[structopt(long, parse(try_from_str = hex::decode))]
+    pub public_key: Hex,
+    /// Number of Wills required to reconstruct server share
+    #[structopt(long)]
+    pub threshold: u32,
+
+    #[structopt(flatten)]
+    pub will_servers: TestatorServers,
+}
+
 #[derive(StructOpt, Debug)]
 pub struct TestatorSendKeepalive {
     #[structopt(long, parse(try_from_str = parse_duration::parse))]
@@ -58,11 +96,41 @@ pub struct BeneficiaryVerify {
 
 #[derive(StructOpt, Debug)]
 pub struct BeneficiaryClaim {
+    #[structopt(long, required_unless = "heir_piece", parse(try_from_str = hex::decode))]
+    pub secret_share: Option<Hex>,
+    #[structopt(long, parse(try_from_str = hex::decode))]
+    pub public_key: Hex,
+
+    /// Claims as one of heirs holding a piece of beneficiary's share
+    #[structopt(long, conflicts_with = "secret_share", requires = "heir_index", parse(try_from_str = hex::decode))]
+    pub heir_piece: Option<Hex>,
+    #[structopt(long)]
+    pub heir_index: Option<u32>,
+
+    #[structopt(flatten)]
+    pub will_server: BeneficiaryServer,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct BeneficiaryClaimDistributed {
     #[structopt(long, parse(try_from_str = hex::decode))]
     pub secret_share: Hex,
     #[structopt(long, parse(try_from_str = hex::decode))]
     pub public_key: Hex,
 
+    #[structopt(flatten)]
+    pub will_servers: BeneficiaryServers,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct HeirContribute {
+    #[structopt(long, parse(try_from_str = hex::decode))]
+    pub public_key: Hex,
+    #[structopt(long, parse(try_from_str = hex::decode))]
+    pub heir_piece: Hex,
+    #[structopt(long)]
+    pub heir_index: u32,
+
     #[structopt(flatten)]
     pub will_server: BeneficiaryServer,
 }
@@ -75,7 +143,7 @@ pub struct Server {
     pub hostname: String,
 }
 
-#[derive(StructOpt, Debug)]
+#[derive(StructOpt, Debug, Clone)]
 pub struct BeneficiaryServer {
     #[structopt(long, default_value = "https://localhost:4949")]
     pub address: String,
@@ -83,9 +151,12 @@ pub struct BeneficiaryServer {
     pub hostname: Option<String>,
     #[structopt(long)]
     pub will_ca: Option<PathBuf>,
+    /// Requires Will to attest that it runs one of given measurements
+    #[structopt(long, requires = "will_ca", parse(try_from_str = hex::decode))]
+    pub expected_measurement: Vec<Hex>,
 }
 
-#[derive(StructOpt, Debug)]
+#[derive(StructOpt, Debug, Clone)]
 pub struct TestatorServer {
     #[structopt(long, default_value = "https://localhost:4950")]
     pub address: String,
@@ -97,4 +168,71 @@ pub struct TestatorServer {
     pub cert: Option<PathBuf>,
     #[structopt(long)] //, requires_all(&["will_cert", "my_cert"]))]
     pub key: Option<PathBuf>,
+    /// Requires Will to attest that it runs one of given measurements
+    #[structopt(long, requires = "will_ca", parse(try_from_str = hex::decode))]
+    pub expected_measurement: Vec<Hex>,
+}
+
+/// Several Wills sharing the same TLS settings
+#[derive(StructOpt, Debug)]
+pub struct BeneficiaryServers {
+    /// Address of a Will, repeated for every Will holding a piece
+    #[structopt(long = "will-address", required = true)]
+    pub addresses: Vec<String>,
+    #[structopt(long)]
+    pub hostname: Option<String>,
+    #[structopt(long)]
+    pub will_ca: Option<PathBuf>,
+    /// Requires every Will to attest that it runs one of given measurements
+    #[structopt(long, requires = "will_ca", parse(try_from_str = hex::decode))]
+    pub expected_measurement: Vec<Hex>,
+}
+
+impl BeneficiaryServers {
+    pub fn endpoints(&self) -> Vec<BeneficiaryServer> {
+        self.addresses
+            .iter()
+            .map(|address| BeneficiaryServer {
+                address: address.clone(),
+                hostname: self.hostname.clone(),
+                will_ca: self.will_ca.clone(),
+                expected_measurement: self.expected_measurement.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Several Wills sharing the same TLS settings
+#[derive(StructOpt, Debug)]
+pub struct TestatorServers {
+    /// Address of a Will, repeated for every Will to store a piece at
+    #[structopt(long = "will-address", required = true)]
+    pub addresses: Vec<String>,
+    #[structopt(long)]
+    pub hostname: Option<String>,
+    #[structopt(long)]
+    pub will_ca: Option<PathBuf>,
+    #[structopt(long)]
+    pub cert: Option<PathBuf>,
+    #[structopt(long)]
+    pub key: Option<PathBuf>,
+    /// Requires every Will to attest that it runs one of given measurements
+    #[structopt(long, requires = "will_ca", parse(try_from_str = hex::decode))]
+    pub expected_measurement: Vec<Hex>,
+}
+
+impl TestatorServers {
+    pub fn endpoints(&self) -> Vec<TestatorServer> {
+        self.addresses
+            .iter()
+            .map(|address| TestatorServer {
+                address: address.clone(),
+                hostname: self.hostname.clone(),
+                will_ca: self.will_ca.clone(),
+                cert: self.cert.clone(),
+                key: self.key.clone(),
+                expected_measurement: self.expected_measurement.clone(),
+            })
+            .collect()
+    }
 }
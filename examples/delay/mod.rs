@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use ring::digest;
+
+use curv::arithmetic::{Converter, Modulo, One, Primes};
+use curv::BigInt;
+
+use crate::proto::beneficiary::{
+    challenge::Input, obtain_server_secret_share_request::NativeSolution, Challenge,
+    HashChainChallenge, HashChainSolution, VdfChallenge, VdfSolution,
+};
+
+/// Must be kept in sync with Will server implementation
+const HASH_TO_PRIME_CONTEXT: &[u8] = b"zengo-will/wesolowski/v2";
+const HASH_CHAIN_SEGMENTS: u64 = 256;
+
+/// Solves challenge issued by Will with whatever scheme it specifies
+pub fn solve(challenge: &Challenge) -> anyhow::Result<NativeSolution> {
+    if challenge.expected_delay_seconds > 0 {
+        eprintln!(
+            "Will expects it to take about {:?} on reference hardware",
+            Duration::from_secs(challenge.expected_delay_seconds)
+        );
+    }
+    let input = challenge
+        .input
+        .as_ref()
+        .context("Will didn't send protobuf-encoded challenge")?;
+    match (challenge.scheme.as_str(), input) {
+        ("" | "rsa-vdf", Input::Vdf(vdf)) => solve_rsa_vdf(vdf).map(NativeSolution::VdfSolution),
+        ("wesolowski", Input::Vdf(vdf)) => solve_wesolowski(vdf).map(NativeSolution::VdfSolution),
+        ("hash-chain", Input::HashChain(chain)) => {
+            Ok(NativeSolution::HashChainSolution(solve_hash_chain(chain)))
+        }
+        (scheme, _) => bail!("unsupported challenge scheme: {}", scheme),
+    }
+}
+
+fn solve_rsa_vdf(challenge: &VdfChallenge) -> anyhow::Result<VdfSolution> {
+    let setup = challenge.setup.as_ref().context("VDF setup is missing")?;
+    let unsolved = rsa_vdf::UnsolvedVDF {
+        x: BigInt::from_bytes(&challenge.x),
+        setup: rsa_vdf::SetupForVDF {
+            t: BigInt::from(setup.t),
+            N: BigInt::from_bytes(&setup.modulus),
+        },
+    };
+    // `SolvedVDF` doesn't expose its fields, so they're taken from its serialized form
+    let solved = serde_json::to_value(rsa_vdf::UnsolvedVDF::eval(&unsolved))
+        .context("serialize solution")?;
+    let field = |name: &str| -> anyhow::Result<Vec<u8>> {
+        let n: BigInt = serde_json::from_value(solved[name].clone())
+            .with_context(|| format!("parse solution field {}", name))?;
+        Ok(n.to_bytes())
+    };
+    Ok(VdfSolution {
+        y: field("y")?,
+        proof: field("pi")?,
+    })
+}
+
+fn solve_wesolowski(challenge: &VdfChallenge) -> anyhow::Result<VdfSolution> {
+    let setup = challenge.setup.as_ref().context("VDF setup is missing")?;
+    let modulus = BigInt::from_bytes(&setup.modulus);
+    let x = BigInt::from_bytes(&challenge.x);
+    let two = BigInt::from(2u64);
+
+    // Will works in `Z_N^* / {±1}`, elements are represented by `min(n, N - n)`
+    let canonical = |n: BigInt| {
+        let negated = &modulus - &n;
+        if negated < n {
+            negated
+        } else {
+            n
+        }
+    };
+
+    let mut y = x.clone();
+    for _ in 0..setup.t {
+        y = BigInt::mod_mul(&y, &y, &modulus);
+    }
+    let y = canonical(y);
+
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    ctx.update(HASH_TO_PRIME_CONTEXT);
+    for n in &[&x, &y] {
+        let bytes = n.to_bytes();
+        ctx.update(&(bytes.len() as u32).to_le_bytes());
+        ctx.update(&bytes);
+    }
+    let l = BigInt::from_bytes(&ctx.finish().as_ref()[..16]).next_prime();
+
+    // Computes x^floor(2^t / l) by long division of 2^t by l bit by bit
+    let mut proof = BigInt::one();
+    let mut remainder = BigInt::one();
+    for _ in 0..setup.t {
+        let doubled = &remainder * &two;
+        let bit = doubled >= l;
+        remainder = if bit { doubled - &l } else { doubled };
+        proof = BigInt::mod_mul(&proof, &proof, &modulus);
+        if bit {
+            proof = BigInt::mod_mul(&proof, &x, &modulus);
+        }
+    }
+    Ok(VdfSolution {
+        y: y.to_bytes(),
+        proof: canonical(proof).to_bytes(),
+    })
+}
+
+fn solve_hash_chain(challenge: &HashChainChallenge) -> HashChainSolution {
+    let mut output = challenge.seed.clone();
+    let mut checkpoints = Vec::with_capacity(HASH_CHAIN_SEGMENTS as usize - 1);
+    let mut iterations = 0;
+    for segment in 1..=HASH_CHAIN_SEGMENTS {
+        let boundary = (u128::from(challenge.iterations) * u128::from(segment)
+            / u128::from(HASH_CHAIN_SEGMENTS)) as u64;
+        for _ in iterations..boundary {
+            let hash = digest::digest(&digest::SHA256, &output);
+            output = hash.as_ref().to_vec();
+        }
+        iterations = boundary;
+        if segment < HASH_CHAIN_SEGMENTS {
+            checkpoints.push(output.clone());
+        }
+    }
+    HashChainSolution {
+        output,
+        checkpoints,
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{anyhow, bail, Context};
+use anyhow::{anyhow, bail, ensure, Context};
 use structopt::StructOpt;
 
 use tokio::fs;
@@ -6,6 +6,7 @@ use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
 use tonic::Request;
 
 use curv::arithmetic::Converter;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::secp256_k1::{FE, GE};
 use curv::elliptic::curves::traits::{ECPoint, ECScalar};
 use curv::BigInt;
@@ -13,19 +14,29 @@ use curv::BigInt;
 use proto::beneficiary::beneficiary_api_client::BeneficiaryApiClient;
 use proto::testator::testator_api_client::TestatorApiClient;
 use rustls::Session;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
+mod attestation;
 mod cli;
+mod delay;
+mod escrow;
+mod heirs;
 mod proto;
+mod share_encryption;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
     let args: cli::App = StructOpt::from_args();
     match args {
-        cli::App::GenShare => emulate_keygen().await,
+        cli::App::GenShare(args) => emulate_keygen(args).await,
         cli::App::GetCert(args) => get_cert(args).await,
         cli::App::Testator(cli::TestatorCmd::SaveShare(args)) => testator_save_share(args).await,
+        cli::App::Testator(cli::TestatorCmd::DistributeShare(args)) => {
+            testator_distribute_share(args).await
+        }
         cli::App::Testator(cli::TestatorCmd::SendKeepalive(args)) => {
             testator_send_keepalive(args).await
         }
@@ -33,10 +44,14 @@ async fn main() -> anyhow::Result<()> {
             beneficiary_verify_share(args).await
         }
         cli::App::Beneficiary(cli::BeneficiaryCmd::Claim(args)) => beneficiary_claim(args).await,
+        cli::App::Beneficiary(cli::BeneficiaryCmd::ClaimDistributed(args)) => {
+            beneficiary_claim_distributed(args).await
+        }
+        cli::App::Beneficiary(cli::BeneficiaryCmd::Contribute(args)) => heir_contribute(args).await,
     }
 }
 
-async fn emulate_keygen() -> anyhow::Result<()> {
+async fn emulate_keygen(args: cli::GenShare) -> anyhow::Result<()> {
     let testator_secret = FE::new_random();
     let beneficiary_secret = FE::new_random();
     let joint_pk = GE::generator() * testator_secret.clone() * beneficiary_secret.clone();
@@ -52,6 +67,32 @@ async fn emulate_keygen() -> anyhow::Result<()> {
         beneficiary_secret, testator_secret, joint_pk
     );
 
+    if let (Some(heirs), Some(threshold)) = (args.heirs, args.heirs_threshold) {
+        if threshold == 0 || threshold > heirs {
+            bail!("heirs threshold must be in range [1; heirs]")
+        }
+        let beneficiary_secret: FE = ECScalar::from(&BigInt::from_hex(&beneficiary_secret)?);
+        let (commitments, pieces) = VerifiableSS::<GE>::share_at_indices(
+            threshold - 1,
+            heirs,
+            &beneficiary_secret,
+            &(1..=heirs).collect::<Vec<_>>(),
+        );
+        for (i, piece) in pieces.iter().enumerate() {
+            println!(
+                "Heir {} piece:        {}",
+                i + 1,
+                add_leading_zero(piece.to_big_int().to_hex())
+            );
+        }
+        for commitment in commitments.commitments {
+            println!(
+                "Heirs commitment:    {}",
+                hex::encode(&commitment.pk_to_key_slice()[1..])
+            );
+        }
+    }
+
     Ok(())
 }
 
@@ -111,10 +152,12 @@ fn add_leading_zero(hex: String) -> String {
 async fn connect_to_beneficiary_api(
     mut endpoint: cli::BeneficiaryServer,
 ) -> anyhow::Result<BeneficiaryApiClient<Channel>> {
-    let tls_config = if let Some(will_cert) = endpoint.will_ca {
-        let cert = fs::read(will_cert)
+    let mut will_cert_pem = None;
+    let tls_config = if let Some(will_ca) = endpoint.will_ca {
+        let cert = fs::read(will_ca)
             .await
             .context("read Will server certificate")?;
+        will_cert_pem = Some(cert.clone());
         let cert = Certificate::from_pem(cert);
         let config = ClientTlsConfig::new().ca_certificate(cert);
         let config = if let Some(hostname) = endpoint.hostname {
@@ -128,6 +171,7 @@ async fn connect_to_beneficiary_api(
         endpoint.address = endpoint.address.replace("https://", "http://");
         None
     };
+    let endpoint_expected_measurement = endpoint.expected_measurement;
     let endpoint = Channel::from_shared(endpoint.address).context("invalid beneficiary url")?;
     let endpoint = match tls_config {
         Some(tls_config) => endpoint.tls_config(tls_config).context("set tls config")?,
@@ -137,17 +181,42 @@ async fn connect_to_beneficiary_api(
         .connect()
         .await
         .context("connect to beneficiary server")?;
-    Ok(BeneficiaryApiClient::new(channel))
+    let mut client = BeneficiaryApiClient::new(channel);
+
+    if let (Some(will_cert), false) = (will_cert_pem, endpoint_expected_measurement.is_empty()) {
+        let nonce = attestation::generate_nonce()?;
+        let response = client
+            .get_attestation(Request::new(proto::attestation::GetAttestationRequest {
+                nonce: nonce.to_vec(),
+            }))
+            .await
+            .context("retrieve attestation")?
+            .into_inner();
+        attestation::verify(
+            &response,
+            &nonce,
+            &will_cert,
+            &endpoint_expected_measurement,
+        )
+        .context("verify attestation")?;
+        eprintln!("Will attestation verified");
+    }
+
+    Ok(client)
 }
 
+/// Connects to testator API. If Will is attested, returns the share encryption key it attests to
+/// along with the client.
 async fn connect_to_testator_api(
     mut endpoint: cli::TestatorServer,
-) -> anyhow::Result<TestatorApiClient<Channel>> {
+) -> anyhow::Result<(TestatorApiClient<Channel>, Option<Vec<u8>>)> {
+    let mut will_cert_pem = None;
     let tls_config = match (endpoint.will_ca, endpoint.cert, endpoint.key) {
         (Some(will_cert), Some(my_cert), Some(my_key)) => {
             let will_cert = fs::read(will_cert)
                 .await
                 .context("read Will server certificate")?;
+            will_cert_pem = Some(will_cert.clone());
             let my_cert = fs::read(my_cert).await.context("read my certificate")?;
             let my_key = fs::read(my_key).await.context("read my private key")?;
 
@@ -170,6 +239,7 @@ async fn connect_to_testator_api(
             None
         }
     };
+    let endpoint_expected_measurement = endpoint.expected_measurement;
     let endpoint = Channel::from_shared(endpoint.address).context("invalid testator url")?;
     let endpoint = match tls_config {
         Some(tls_config) => endpoint.tls_config(tls_config).context("set tls config")?,
@@ -179,16 +249,57 @@ async fn connect_to_testator_api(
         .connect()
         .await
         .context("connect to testator server")?;
-    Ok(TestatorApiClient::new(channel))
+    let mut client = TestatorApiClient::new(channel);
+
+    let mut attested_key = None;
+    if let (Some(will_cert), false) = (will_cert_pem, endpoint_expected_measurement.is_empty()) {
+        let nonce = attestation::generate_nonce()?;
+        let response = client
+            .get_attestation(Request::new(proto::attestation::GetAttestationRequest {
+                nonce: nonce.to_vec(),
+            }))
+            .await
+            .context("retrieve attestation")?
+            .into_inner();
+        attestation::verify(
+            &response,
+            &nonce,
+            &will_cert,
+            &endpoint_expected_measurement,
+        )
+        .context("verify attestation")?;
+        eprintln!("Will attestation verified");
+        attested_key = Some(response.share_encryption_key);
+    }
+
+    Ok((client, attested_key))
 }
 
 async fn testator_save_share(args: cli::TestatorSaveShare) -> anyhow::Result<()> {
-    let mut server = connect_to_testator_api(args.will_server).await?;
+    let will_cert = read_will_cert(&args.will_server.will_ca).await?;
+    let (mut server, attested_key) = connect_to_testator_api(args.will_server).await?;
+
+    let server_key =
+        retrieve_server_key(&mut server, will_cert.as_deref(), attested_key.as_deref()).await?;
+    let encrypted_share =
+        share_encryption::encrypt_share(&server_key, &args.secret_share, &args.public_key)?;
+
+    let beneficiaries = match (args.heirs, args.heirs_threshold) {
+        (Some(heirs_count), Some(threshold)) => Some(proto::testator::BeneficiaryCommitments {
+            threshold,
+            heirs_count,
+            commitments: args.heir_commitment,
+        }),
+        _ => None,
+    };
 
     server
         .save_server_share(Request::new(proto::testator::SaveServerShareRequest {
             public_key: args.public_key,
-            server_secret_share: args.secret_share,
+            server_secret_share: vec![],
+            encrypted_server_secret_share: Some(encrypted_share),
+            beneficiaries,
+            escrow_piece: None,
         }))
         .await
         .context("sending save share request")?;
@@ -198,6 +309,86 @@ async fn testator_save_share(args: cli::TestatorSaveShare) -> anyhow::Result<()>
     Ok(())
 }
 
+async fn testator_distribute_share(args: cli::TestatorDistributeShare) -> anyhow::Result<()> {
+    let public_key: GE =
+        GE::from_bytes(&args.public_key).map_err(|_e| anyhow!("invalid public key"))?;
+    let secret_share: FE = ECScalar::from(&BigInt::from_bytes(&args.secret_share));
+    let endpoints = args.will_servers.endpoints();
+    let pieces = escrow::split(
+        &secret_share,
+        &public_key,
+        args.threshold,
+        endpoints.len() as u32,
+    )?;
+
+    for (endpoint, (piece, piece_meta)) in endpoints.into_iter().zip(pieces) {
+        let address = endpoint.address.clone();
+        let index = piece_meta.index;
+        let will_cert = read_will_cert(&endpoint.will_ca).await?;
+        let (mut server, attested_key) = connect_to_testator_api(endpoint).await?;
+
+        let server_key =
+            retrieve_server_key(&mut server, will_cert.as_deref(), attested_key.as_deref()).await?;
+        let encrypted_piece = share_encryption::encrypt_share(
+            &server_key,
+            &piece.to_big_int().to_bytes(),
+            &args.public_key,
+        )?;
+        server
+            .save_server_share(Request::new(proto::testator::SaveServerShareRequest {
+                public_key: args.public_key.clone(),
+                server_secret_share: vec![],
+                encrypted_server_secret_share: Some(encrypted_piece),
+                beneficiaries: None,
+                escrow_piece: Some(piece_meta),
+            }))
+            .await
+            .with_context(|| format!("save piece at {}", address))?;
+        println!("Piece {} saved at {}", index, address);
+    }
+
+    Ok(())
+}
+
+async fn read_will_cert(will_ca: &Option<PathBuf>) -> anyhow::Result<Option<Vec<u8>>> {
+    match will_ca {
+        Some(will_ca) => Ok(Some(
+            fs::read(will_ca)
+                .await
+                .context("read Will server certificate")?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Retrieves Will's share encryption key. Key is verified if Will certificate is provided.
+/// Retrieves key that shares are encrypted to. If Will is attested, the key must be the one
+/// attestation is issued for.
+async fn retrieve_server_key(
+    server: &mut TestatorApiClient<Channel>,
+    will_cert: Option<&[u8]>,
+    attested_key: Option<&[u8]>,
+) -> anyhow::Result<GE> {
+    let server_key = server
+        .get_server_key(Request::new(proto::testator::GetServerKeyRequest {}))
+        .await
+        .context("retrieve server key")?
+        .into_inner();
+    if let Some(attested_key) = attested_key {
+        ensure!(
+            server_key.public_key.as_slice() == attested_key,
+            "server key differs from the one Will attests to"
+        );
+    }
+    match will_cert {
+        Some(will_cert) => share_encryption::verify_server_key(&server_key, will_cert),
+        None => {
+            eprintln!("WARN: Server key is not verified");
+            share_encryption::parse_server_key(&server_key)
+        }
+    }
+}
+
 async fn beneficiary_verify_share(args: cli::BeneficiaryVerify) -> anyhow::Result<()> {
     let mut server = connect_to_beneficiary_api(args.will_server).await?;
 
@@ -219,7 +410,19 @@ async fn beneficiary_verify_share(args: cli::BeneficiaryVerify) -> anyhow::Resul
 
     let server_public_share: GE = GE::from_bytes(&response.server_public_share[1..])
         .map_err(|_e| anyhow!("server provided invalid proof"))?;
-    if server_public_share * client_secret_share == public_key_point {
+    if let Some(piece) = &response.escrow_piece {
+        escrow::verify_piece(
+            piece,
+            &public_key_point,
+            &client_secret_share,
+            &server_public_share,
+        )
+        .context("server provided incorrect proof")?;
+        println!(
+            "Server proofed that it owns a valid piece {} ({} of {} pieces are required)",
+            piece.index, piece.threshold, piece.servers_count
+        );
+    } else if server_public_share * client_secret_share == public_key_point {
         println!("Server proofed that it owns a valid share");
     } else {
         bail!("Server provided incorrect proof!");
@@ -228,7 +431,7 @@ async fn beneficiary_verify_share(args: cli::BeneficiaryVerify) -> anyhow::Resul
 }
 
 async fn testator_send_keepalive(args: cli::TestatorSendKeepalive) -> anyhow::Result<()> {
-    let mut server = connect_to_testator_api(args.will_server).await?;
+    let (mut server, _) = connect_to_testator_api(args.will_server).await?;
 
     for i in 1u64.. {
         server
@@ -248,46 +451,267 @@ async fn beneficiary_claim(args: cli::BeneficiaryClaim) -> anyhow::Result<()> {
     let public_key_point: GE =
         GE::from_bytes(&args.public_key).map_err(|_e| anyhow!("invalid public key"))?;
 
-    let client_secret_share: FE = ECScalar::from(&BigInt::from_bytes(&args.secret_share));
-    let client_public_share: GE = GE::generator() * client_secret_share;
-    let client_public_share_bytes = &client_public_share.pk_to_key_slice()[1..];
-
     eprintln!("Retrieving challenge from the server");
     let solving_challenge = server
         .get_challenge(Request::new(proto::beneficiary::GetChallengeRequest {}))
         .await
         .context("get challenge from server")?
         .into_inner();
-    let challenge: rsa_vdf::UnsolvedVDF =
-        serde_json::from_slice(&solving_challenge.challenge).context("parse challenge")?;
-    eprintln!("Solving challenge");
-    let solution = rsa_vdf::UnsolvedVDF::eval(&challenge);
-    let solution = serde_json::to_vec(&solution).context("serialize solution")?;
+    eprintln!("Solving {} challenge", solving_challenge.scheme);
+    let solution = delay::solve(&solving_challenge)?;
     eprintln!("Challenge solved. Sending it to server");
 
+    let (client_public_share, contribution) = match (args.secret_share, args.heir_piece) {
+        (Some(secret_share), _) => {
+            let client_secret_share: FE = ECScalar::from(&BigInt::from_bytes(&secret_share));
+            (Some(GE::generator() * client_secret_share), None)
+        }
+        (None, Some(heir_piece)) => {
+            let heir_index = args.heir_index.context("heir index is not provided")?;
+            let heir_piece: FE = ECScalar::from(&BigInt::from_bytes(&heir_piece));
+            let contribution = heirs::contribute(
+                &args.public_key,
+                &solving_challenge.id,
+                solving_challenge.round,
+                heir_index,
+                &heir_piece,
+            );
+            (None, Some(contribution))
+        }
+        (None, None) => bail!("either secret share or heir piece must be provided"),
+    };
+
     let response = server
         .obtain_server_secret_share(Request::new(
             proto::beneficiary::ObtainServerSecretShareRequest {
                 public_key: args.public_key,
-                client_public_share: client_public_share_bytes.into(),
+                client_public_share: client_public_share
+                    .map(|s| s.pk_to_key_slice()[1..].to_vec())
+                    .unwrap_or_default(),
                 solved_challenge: Some(solving_challenge),
-                solution,
+                solution: vec![],
+                contribution,
+                native_solution: Some(solution),
             },
         ))
         .await
         .context("claiming share")?
         .into_inner();
 
+    if response.server_secret_share.is_empty()
+        && response.rounds_completed < response.rounds_required
+        && response.contributions_collected >= response.contributions_required
+    {
+        println!(
+            "Round {} of {} completed. Claim again in {:?} to solve the next one",
+            response.rounds_completed,
+            response.rounds_required,
+            Duration::from_secs(response.next_round_delay_seconds)
+        );
+        return Ok(());
+    }
+    if response.server_secret_share.is_empty() {
+        println!(
+            "Waiting for other heirs: {} of {} contributed",
+            response.contributions_collected, response.contributions_required
+        );
+        return Ok(());
+    }
+
     let server_secret_share: FE =
         ECScalar::from(&BigInt::from_bytes(&response.server_secret_share));
-    if client_public_share * server_secret_share == public_key_point {
-        println!(
-            "Testator secret share: {}",
-            hex::encode(response.server_secret_share)
+    match client_public_share {
+        Some(client_public_share) if client_public_share * server_secret_share != public_key_point => {
+            bail!("server sent incorrect testator's share")
+        }
+        Some(_) => (),
+        None => eprintln!(
+            "WARN: Testator's share can be verified only after heirs reconstruct beneficiary's share"
+        ),
+    }
+    println!(
+        "Testator secret share: {}",
+        hex::encode(response.server_secret_share)
+    );
+
+    Ok(())
+}
+
+async fn beneficiary_claim_distributed(
+    args: cli::BeneficiaryClaimDistributed,
+) -> anyhow::Result<()> {
+    let public_key: GE =
+        GE::from_bytes(&args.public_key).map_err(|_e| anyhow!("invalid public key"))?;
+    let client_secret_share: FE = ECScalar::from(&BigInt::from_bytes(&args.secret_share));
+
+    let mut commitments = None;
+    let mut pieces = vec![];
+    for endpoint in args.will_servers.endpoints() {
+        let address = endpoint.address.clone();
+        match claim_piece(
+            endpoint,
+            &args.public_key,
+            &public_key,
+            &client_secret_share,
+            &mut commitments,
         )
-    } else {
-        bail!("server sent incorrect testator's share")
+        .await
+        {
+            Ok((index, piece)) => {
+                eprintln!("Obtained piece {} from {}", index, address);
+                pieces.push((index, piece));
+            }
+            Err(e) => eprintln!("WARN: Couldn't claim piece from {}: {:#}", address, e),
+        }
+        if let Some(commitments) = &commitments {
+            if pieces.len() >= commitments.reconstruct_limit() {
+                break;
+            }
+        }
+    }
+
+    let threshold = commitments
+        .as_ref()
+        .map(|c| c.reconstruct_limit())
+        .context("no Will provided a valid piece")?;
+    if pieces.len() < threshold {
+        bail!(
+            "obtained {} pieces, but {} are required",
+            pieces.len(),
+            threshold
+        )
+    }
+    let server_secret_share = escrow::reconstruct(&pieces);
+    if GE::generator() * client_secret_share * server_secret_share != public_key {
+        bail!("reconstructed testator's share doesn't match public key")
     }
+    println!(
+        "Testator secret share: {}",
+        hex::encode(server_secret_share.to_big_int().to_bytes())
+    );
+
+    Ok(())
+}
+
+/// Claims a piece of server share from a single Will
+///
+/// Will's piece is checked against its commitments, which must be the same for all Wills.
+async fn claim_piece(
+    endpoint: cli::BeneficiaryServer,
+    public_key_bytes: &[u8],
+    public_key: &GE,
+    client_secret_share: &FE,
+    commitments: &mut Option<VerifiableSS<GE>>,
+) -> anyhow::Result<(u32, FE)> {
+    let mut server = connect_to_beneficiary_api(endpoint).await?;
+    let client_public_share = GE::generator() * *client_secret_share;
+    let client_public_share_bytes = client_public_share.pk_to_key_slice()[1..].to_vec();
 
+    let response = server
+        .verify_server_share(Request::new(proto::beneficiary::VerifyServerShareRequest {
+            public_key: public_key_bytes.to_vec(),
+            client_public_share: client_public_share_bytes.clone(),
+        }))
+        .await
+        .context("verify piece")?
+        .into_inner();
+    let piece_info = response
+        .escrow_piece
+        .context("Will holds the whole share, not a piece")?;
+    let server_public_piece: GE = response
+        .server_public_share
+        .get(1..)
+        .and_then(|p| GE::from_bytes(p).ok())
+        .context("invalid public piece")?;
+    let piece_commitments = escrow::verify_piece(
+        &piece_info,
+        public_key,
+        client_secret_share,
+        &server_public_piece,
+    )?;
+    match commitments {
+        Some(commitments) if *commitments != piece_commitments => {
+            bail!("Will's commitments differ from ones reported by other Wills")
+        }
+        Some(_) => (),
+        None => *commitments = Some(piece_commitments),
+    }
+
+    eprintln!("Retrieving challenge from the server");
+    let challenge = server
+        .get_challenge(Request::new(proto::beneficiary::GetChallengeRequest {}))
+        .await
+        .context("get challenge from server")?
+        .into_inner();
+    eprintln!("Solving {} challenge", challenge.scheme);
+    let solution = delay::solve(&challenge)?;
+
+    let response = server
+        .obtain_server_secret_share(Request::new(
+            proto::beneficiary::ObtainServerSecretShareRequest {
+                public_key: public_key_bytes.to_vec(),
+                client_public_share: client_public_share_bytes,
+                solved_challenge: Some(challenge),
+                solution: vec![],
+                contribution: None,
+                native_solution: Some(solution),
+            },
+        ))
+        .await
+        .context("claim piece")?
+        .into_inner();
+    if response.server_secret_share.is_empty() {
+        bail!(
+            "Will requires {} rounds of claim, {} completed so far",
+            response.rounds_required,
+            response.rounds_completed
+        )
+    }
+    let piece: FE = ECScalar::from(&BigInt::from_bytes(&response.server_secret_share));
+    if GE::generator() * piece != server_public_piece
+        || response.escrow_piece_index != piece_info.index
+    {
+        bail!("Will sent incorrect piece")
+    }
+    Ok((piece_info.index, piece))
+}
+
+async fn heir_contribute(args: cli::HeirContribute) -> anyhow::Result<()> {
+    let mut server = connect_to_beneficiary_api(args.will_server).await?;
+
+    eprintln!("Retrieving challenge from the server");
+    let challenge = server
+        .get_challenge(Request::new(proto::beneficiary::GetChallengeRequest {}))
+        .await
+        .context("get challenge from server")?
+        .into_inner();
+
+    let heir_piece: FE = ECScalar::from(&BigInt::from_bytes(&args.heir_piece));
+    let contribution = heirs::contribute(
+        &args.public_key,
+        &challenge.id,
+        challenge.round,
+        args.heir_index,
+        &heir_piece,
+    );
+    let response = server
+        .obtain_server_secret_share(Request::new(
+            proto::beneficiary::ObtainServerSecretShareRequest {
+                public_key: args.public_key,
+                client_public_share: vec![],
+                solved_challenge: Some(challenge),
+                solution: vec![],
+                contribution: Some(contribution),
+                native_solution: None,
+            },
+        ))
+        .await
+        .context("contribute to claim")?
+        .into_inner();
+
+    println!(
+        "Contribution accepted: {} of {} heirs contributed",
+        response.contributions_collected, response.contributions_required
+    );
     Ok(())
 }
@@ -0,0 +1,114 @@
+use anyhow::{anyhow, bail, ensure};
+
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::secp256_k1::{FE, GE};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::BigInt;
+
+use crate::proto::beneficiary::EscrowPieceInfo;
+use crate::proto::testator::EscrowPiece;
+
+/// Splits testator's server share between `servers` Wills, any `threshold` of which are required
+/// to reconstruct it
+///
+/// Commitments are computed in base of beneficiary's public share `Q = public_key * s^-1`, so that
+/// every Will can prove its piece is consistent with the joint public key. Returns pieces along
+/// with metadata to be sent with each of them.
+pub fn split(
+    server_share: &FE,
+    public_key: &GE,
+    threshold: u32,
+    servers: u32,
+) -> anyhow::Result<Vec<(FE, EscrowPiece)>> {
+    ensure!(
+        threshold >= 1 && threshold <= servers,
+        "threshold must be in range [1; servers]"
+    );
+    let beneficiary_public_share = *public_key * server_share.invert();
+    let polynomial = VerifiableSS::<GE>::sample_polynomial(threshold as usize - 1, server_share);
+    let indices = (1..=servers as usize).collect::<Vec<_>>();
+    let pieces = VerifiableSS::<GE>::evaluate_polynomial(&polynomial, &indices);
+    let commitments = polynomial
+        .iter()
+        .map(|a| (beneficiary_public_share * *a).pk_to_key_slice()[1..].to_vec())
+        .collect::<Vec<_>>();
+
+    Ok(pieces
+        .into_iter()
+        .zip(1..)
+        .map(|(piece, index)| {
+            let meta = EscrowPiece {
+                index,
+                threshold,
+                servers_count: servers,
+                commitments: commitments.clone(),
+            };
+            (piece, meta)
+        })
+        .collect())
+}
+
+/// Checks that Will holds a piece consistent with `public_key`
+///
+/// `server_public_piece` is a public piece `G * s_i` sent by Will. Returns parsed commitments so
+/// caller can make sure every Will reports the same ones.
+pub fn verify_piece(
+    info: &EscrowPieceInfo,
+    public_key: &GE,
+    client_secret_share: &FE,
+    server_public_piece: &GE,
+) -> anyhow::Result<VerifiableSS<GE>> {
+    let commitments = info
+        .commitments
+        .iter()
+        .map(|c| {
+            c.get(1..)
+                .and_then(|c| GE::from_bytes(c).ok())
+                .ok_or_else(|| anyhow!("invalid piece commitment"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    ensure!(
+        info.threshold >= 1
+            && info.threshold as usize == commitments.len()
+            && info.threshold <= info.servers_count,
+        "commitments don't describe a valid threshold sharing"
+    );
+    ensure!(
+        info.index >= 1 && info.index <= info.servers_count,
+        "piece index is out of range"
+    );
+    ensure!(
+        commitments[0] == *public_key,
+        "commitments are not bound to public key"
+    );
+    let commitments = VerifiableSS {
+        parameters: ShamirSecretSharing {
+            threshold: info.threshold as usize - 1,
+            share_count: info.servers_count as usize,
+        },
+        commitments,
+    };
+    // (G * s_i) * c == Q * s_i
+    if commitments
+        .validate_share_public(
+            &(*server_public_piece * *client_secret_share),
+            info.index as usize,
+        )
+        .is_err()
+    {
+        bail!("piece doesn't match commitments")
+    }
+    Ok(commitments)
+}
+
+/// Reconstructs server share from at least `threshold` pieces given with their indices
+pub fn reconstruct(pieces: &[(u32, FE)]) -> FE {
+    let points = pieces
+        .iter()
+        .map(|(index, _)| ECScalar::from(&BigInt::from(*index)))
+        .collect::<Vec<FE>>();
+    let values = pieces.iter().map(|(_, piece)| *piece).collect::<Vec<_>>();
+    VerifiableSS::<GE>::lagrange_interpolation_at_zero(&points, &values)
+}
@@ -0,0 +1,46 @@
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::secp256_k1::{FE, GE};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::BigInt;
+use ring::digest;
+
+use crate::proto::beneficiary::HeirContribution;
+
+/// Must be kept in sync with Will server implementation
+const CONTRIBUTION_CONTEXT: &[u8] = b"zengo-will/heir-contribution/v2";
+
+/// Proves knowledge of heir's piece for the claim of `public_key` under given challenge and round
+pub fn contribute(
+    public_key: &[u8],
+    challenge_id: &[u8],
+    round: u32,
+    index: u32,
+    piece: &FE,
+) -> HeirContribution {
+    let message = [
+        CONTRIBUTION_CONTEXT,
+        public_key,
+        challenge_id,
+        &round.to_le_bytes(),
+        &index.to_le_bytes(),
+    ]
+    .concat();
+
+    let public_piece = GE::generator() * *piece;
+    let nonce = FE::new_random();
+    let commitment = GE::generator() * nonce;
+
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    ctx.update(&commitment.pk_to_key_slice());
+    ctx.update(&public_piece.pk_to_key_slice());
+    ctx.update(&message);
+    let challenge: FE = ECScalar::from(&BigInt::from_bytes(ctx.finish().as_ref()));
+    let response = nonce + challenge * *piece;
+
+    HeirContribution {
+        index,
+        public_piece: public_piece.pk_to_key_slice()[1..].to_vec(),
+        proof_commitment: commitment.pk_to_key_slice()[1..].to_vec(),
+        proof_response: response.to_big_int().to_bytes(),
+    }
+}
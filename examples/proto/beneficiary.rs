@@ -10,33 +10,172 @@ pub struct VerifyServerShareRequest {
 pub struct VerifyServerShareResponse {
     #[prost(bytes = "vec", tag = "1")]
     pub server_public_share: ::prost::alloc::vec::Vec<u8>,
+    /// Set if Will holds only a piece of server share. Then ServerPublicShare is public piece.
+    #[prost(message, optional, tag = "2")]
+    pub escrow_piece: ::core::option::Option<EscrowPieceInfo>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EscrowPieceInfo {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(uint32, tag = "2")]
+    pub threshold: u32,
+    #[prost(uint32, tag = "3")]
+    pub servers_count: u32,
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub commitments: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 /// GetChallenge
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetChallengeRequest {}
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Challenge {
+    /// Challenge id as 16 bytes little-endian integer, same as Number
     #[prost(bytes = "vec", tag = "1")]
     pub id: ::prost::alloc::vec::Vec<u8>,
+    /// Challenge serialized by Will's Rust implementation. Clients in other languages should use
+    /// Input instead.
     #[prost(bytes = "vec", tag = "2")]
     pub challenge: ::prost::alloc::vec::Vec<u8>,
+    /// Delay scheme the challenge was issued with: "rsa-vdf", "wesolowski" or "hash-chain".
+    /// Empty means "rsa-vdf".
+    #[prost(string, tag = "3")]
+    pub scheme: ::prost::alloc::string::String,
+    /// How long solving the challenge is expected to take on reference hardware, 0 if unknown
+    #[prost(uint64, tag = "4")]
+    pub expected_delay_seconds: u64,
+    /// Round of the claim the challenge is issued for, starting from 0
+    #[prost(uint32, tag = "5")]
+    pub round: u32,
+    /// Number of challenges to solve before server share is released
+    #[prost(uint32, tag = "6")]
+    pub rounds: u32,
+    /// Challenge id as a number
+    #[prost(uint64, tag = "7")]
+    pub number: u64,
+    #[prost(oneof = "challenge::Input", tags = "8, 9")]
+    pub input: ::core::option::Option<challenge::Input>,
+}
+/// Nested message and enum types in `Challenge`.
+pub mod challenge {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Input {
+        /// Set for "rsa-vdf" and "wesolowski" schemes
+        #[prost(message, tag = "8")]
+        Vdf(super::VdfChallenge),
+        /// Set for "hash-chain" scheme
+        #[prost(message, tag = "9")]
+        HashChain(super::HashChainChallenge),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VdfSetup {
+    /// RSA modulus N
+    #[prost(bytes = "vec", tag = "1")]
+    pub modulus: ::prost::alloc::vec::Vec<u8>,
+    /// Number of sequential squarings
+    #[prost(uint64, tag = "2")]
+    pub t: u64,
+}
+/// Solution is y = x^(2^T) mod N along with proof of its correctness
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VdfChallenge {
+    #[prost(message, optional, tag = "1")]
+    pub setup: ::core::option::Option<VdfSetup>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub x: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VdfSolution {
+    #[prost(bytes = "vec", tag = "1")]
+    pub y: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub proof: ::prost::alloc::vec::Vec<u8>,
+}
+/// Solution is SHA-256 applied Iterations times to Seed
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HashChainChallenge {
+    #[prost(bytes = "vec", tag = "1")]
+    pub seed: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub iterations: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HashChainSolution {
+    #[prost(bytes = "vec", tag = "1")]
+    pub output: ::prost::alloc::vec::Vec<u8>,
+    /// Chain values after Iterations * i / 256 applications, for i in 1..255
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub checkpoints: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 /// ObtainServerSecretShare
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ObtainServerSecretShareRequest {
     #[prost(bytes = "vec", tag = "1")]
     pub public_key: ::prost::alloc::vec::Vec<u8>,
+    /// Not used if beneficiary's share is split between heirs
     #[prost(bytes = "vec", tag = "2")]
     pub client_public_share: ::prost::alloc::vec::Vec<u8>,
     #[prost(message, optional, tag = "3")]
     pub solved_challenge: ::core::option::Option<Challenge>,
+    /// Might be empty if heir only contributes to a claim
     #[prost(bytes = "vec", tag = "4")]
     pub solution: ::prost::alloc::vec::Vec<u8>,
+    /// Set if beneficiary's share is split between heirs
+    #[prost(message, optional, tag = "5")]
+    pub contribution: ::core::option::Option<HeirContribution>,
+    /// Solution in protobuf encoding, used instead of Solution if set
+    #[prost(
+        oneof = "obtain_server_secret_share_request::NativeSolution",
+        tags = "6, 7"
+    )]
+    pub native_solution: ::core::option::Option<obtain_server_secret_share_request::NativeSolution>,
+}
+/// Nested message and enum types in `ObtainServerSecretShareRequest`.
+pub mod obtain_server_secret_share_request {
+    /// Solution in protobuf encoding, used instead of Solution if set
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum NativeSolution {
+        #[prost(message, tag = "6")]
+        VdfSolution(super::VdfSolution),
+        #[prost(message, tag = "7")]
+        HashChainSolution(super::HashChainSolution),
+    }
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ObtainServerSecretShareResponse {
+    /// Empty if not enough heirs contributed to the claim yet
     #[prost(bytes = "vec", tag = "1")]
     pub server_secret_share: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint32, tag = "2")]
+    pub contributions_collected: u32,
+    #[prost(uint32, tag = "3")]
+    pub contributions_required: u32,
+    /// Index of the piece if Will holds only a piece of server share, 0 otherwise
+    #[prost(uint32, tag = "4")]
+    pub escrow_piece_index: u32,
+    /// ServerSecretShare is empty until all rounds of the claim are completed. Challenge of the
+    /// next round can be obtained via GetChallenge in NextRoundDelaySeconds.
+    #[prost(uint32, tag = "5")]
+    pub rounds_completed: u32,
+    #[prost(uint32, tag = "6")]
+    pub rounds_required: u32,
+    #[prost(uint64, tag = "7")]
+    pub next_round_delay_seconds: u64,
+}
+/// Heir proves knowledge of its piece `x`, i.e. Schnorr proof for `PublicPiece = G * x` bound to
+/// message "zengo-will/heir-contribution/v2" || PublicKey || challenge id (LE u128) ||
+/// Round (LE u32) || Index (LE u32)
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeirContribution {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub public_piece: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub proof_commitment: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub proof_response: ::prost::alloc::vec::Vec<u8>,
 }
 #[doc = r" Generated client implementations."]
 pub mod beneficiary_api_client {
@@ -119,6 +258,22 @@ pub mod beneficiary_api_client {
             );
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn get_attestation(
+            &mut self,
+            request: impl tonic::IntoRequest<super::super::attestation::GetAttestationRequest>,
+        ) -> Result<tonic::Response<super::super::attestation::Attestation>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/beneficiary.BeneficiaryAPI/GetAttestation");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
     impl<T: Clone> Clone for BeneficiaryApiClient<T> {
         fn clone(&self) -> Self {
@@ -1,2 +1,3 @@
+pub mod attestation;
 pub mod beneficiary;
 pub mod testator;
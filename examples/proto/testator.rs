@@ -3,16 +3,73 @@
 pub struct PingRequest {}
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PongResponse {}
+/// GetServerKey
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetServerKeyRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ServerKey {
+    /// Will's public key used to encrypt server secret share
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: ::prost::alloc::vec::Vec<u8>,
+    /// Signature of the public key made by Will's TLS private key. Empty if Will runs without TLS.
+    #[prost(bytes = "vec", tag = "2")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+}
 /// SaveServerShare
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SaveServerShareRequest {
     #[prost(bytes = "vec", tag = "1")]
     pub public_key: ::prost::alloc::vec::Vec<u8>,
+    /// Plaintext server secret share. Deprecated in favour of EncryptedServerSecretShare.
     #[prost(bytes = "vec", tag = "2")]
     pub server_secret_share: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    pub encrypted_server_secret_share: ::core::option::Option<EncryptedShare>,
+    /// Set if beneficiary's share is split between several heirs
+    #[prost(message, optional, tag = "4")]
+    pub beneficiaries: ::core::option::Option<BeneficiaryCommitments>,
+    /// Set if server share is split between several Wills and the share sent is only a piece of it
+    #[prost(message, optional, tag = "5")]
+    pub escrow_piece: ::core::option::Option<EscrowPiece>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SaveServerShareResponse {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EncryptedShare {
+    #[prost(bytes = "vec", tag = "1")]
+    pub ephemeral_key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub nonce: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub ciphertext: ::prost::alloc::vec::Vec<u8>,
+}
+/// Feldman commitments to polynomial which splits beneficiary's share between heirs. Any
+/// `Threshold` of `HeirsCount` heirs must contribute to claim server share. Commitments[0] is
+/// beneficiary's public share.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BeneficiaryCommitments {
+    #[prost(uint32, tag = "1")]
+    pub threshold: u32,
+    #[prost(uint32, tag = "2")]
+    pub heirs_count: u32,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub commitments: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+/// Describes a piece of server share `s` split between `ServersCount` Wills, any `Threshold` of which
+/// are required to reconstruct it. Commitments are made to splitting polynomial in base of
+/// beneficiary's public share `Q`: `Commitments[j] = Q * a_j`, so Commitments[0] must be equal to
+/// PublicKey.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EscrowPiece {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(uint32, tag = "2")]
+    pub threshold: u32,
+    #[prost(uint32, tag = "3")]
+    pub servers_count: u32,
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub commitments: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
 #[doc = r" Generated client implementations."]
 pub mod testator_api_client {
     #![allow(unused_variables, dead_code, missing_docs)]
@@ -60,6 +117,20 @@ pub mod testator_api_client {
             let path = http::uri::PathAndQuery::from_static("/testator.TestatorAPI/Ping");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn get_server_key(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetServerKeyRequest>,
+        ) -> Result<tonic::Response<super::ServerKey>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/testator.TestatorAPI/GetServerKey");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         pub async fn save_server_share(
             &mut self,
             request: impl tonic::IntoRequest<super::SaveServerShareRequest>,
@@ -75,6 +146,21 @@ pub mod testator_api_client {
                 http::uri::PathAndQuery::from_static("/testator.TestatorAPI/SaveServerShare");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn get_attestation(
+            &mut self,
+            request: impl tonic::IntoRequest<super::super::attestation::GetAttestationRequest>,
+        ) -> Result<tonic::Response<super::super::attestation::Attestation>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/testator.TestatorAPI/GetAttestation");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
     impl<T: Clone> Clone for TestatorApiClient<T> {
         fn clone(&self) -> Self {
@@ -0,0 +1,80 @@
+use anyhow::{anyhow, bail, Context};
+use ring::rand::SecureRandom;
+use ring::{aead, hkdf};
+
+use curv::elliptic::curves::secp256_k1::{FE, GE};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+
+use crate::proto::testator::{EncryptedShare, ServerKey};
+
+/// Must be kept in sync with Will server implementation
+const KDF_INFO: &[u8] = b"zengo-will/share-encryption/v1";
+const SERVER_KEY_SIGNING_CONTEXT: &[u8] = b"zengo-will/server-key/v1";
+
+/// Checks that server key is signed by private key of the given TLS certificate
+///
+/// `will_cert_pem` is the first certificate from the chain presented by Will (the one retrieved by
+/// `get-cert` command).
+pub fn verify_server_key(server_key: &ServerKey, will_cert_pem: &[u8]) -> anyhow::Result<GE> {
+    let will_cert = pem::parse(will_cert_pem).context("parse Will certificate")?;
+    let will_cert =
+        webpki::EndEntityCert::from(&will_cert.contents).context("parse Will certificate")?;
+    let msg = [SERVER_KEY_SIGNING_CONTEXT, &server_key.public_key].concat();
+    let algorithms: &[&webpki::SignatureAlgorithm] = &[
+        &webpki::ECDSA_P256_SHA256,
+        &webpki::ECDSA_P384_SHA384,
+        &webpki::RSA_PKCS1_2048_8192_SHA256,
+    ];
+    if !algorithms.iter().any(|alg| {
+        will_cert
+            .verify_signature(alg, &msg, &server_key.signature)
+            .is_ok()
+    }) {
+        bail!("server key is not signed by Will TLS certificate")
+    }
+    parse_server_key(server_key)
+}
+
+pub fn parse_server_key(server_key: &ServerKey) -> anyhow::Result<GE> {
+    let public_key = server_key
+        .public_key
+        .get(1..)
+        .ok_or_else(|| anyhow!("server key is empty"))?;
+    GE::from_bytes(public_key).map_err(|_| anyhow!("invalid server key"))
+}
+
+/// Encrypts share to Will's key with ECIES, binding it to the joint public key
+pub fn encrypt_share(
+    server_key: &GE,
+    share: &[u8],
+    joint_public_key: &[u8],
+) -> anyhow::Result<EncryptedShare> {
+    let ephemeral_secret = FE::new_random();
+    let ephemeral_key = GE::generator() * ephemeral_secret;
+    let shared_point = *server_key * ephemeral_secret;
+
+    let salt = hkdf::Salt::new(hkdf::HKDF_SHA256, &ephemeral_key.pk_to_key_slice());
+    let prk = salt.extract(&shared_point.pk_to_key_slice());
+    let okm = prk
+        .expand(&[KDF_INFO], &aead::CHACHA20_POLY1305)
+        .map_err(|_| anyhow!("derive encryption key"))?;
+    let key = aead::LessSafeKey::new(aead::UnboundKey::from(okm));
+
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    ring::rand::SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("generate nonce"))?;
+    let mut ciphertext = share.to_vec();
+    key.seal_in_place_append_tag(
+        aead::Nonce::assume_unique_for_key(nonce),
+        aead::Aad::from(joint_public_key),
+        &mut ciphertext,
+    )
+    .map_err(|_| anyhow!("encrypt share"))?;
+
+    Ok(EncryptedShare {
+        ephemeral_key: ephemeral_key.pk_to_key_slice()[1..].to_vec(),
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+}
@@ -0,0 +1,135 @@
+use ring::digest;
+
+use crate::proto::attestation::Attestation;
+
+/// Domain separator of attestation report data. Must be kept in sync with client implementation.
+const REPORT_DATA_CONTEXT: &[u8] = b"zengo-will/attestation/v1";
+
+/// Produces evidence that Will runs inside a trusted execution environment
+///
+/// Evidence must bind `report_data` so clients can check that it was produced for their request
+/// and for keys of Will they're talking to.
+pub trait AttestationProvider: Send + Sync {
+    /// Evidence format name, tells clients how to verify evidence
+    fn format(&self) -> &'static str;
+
+    /// Produces evidence binding given report data
+    fn attest(&self, report_data: &[u8; 64]) -> Result<Vec<u8>, AttestationError>;
+}
+
+/// Attests Will's TLS certificate and share encryption key using underlying provider
+pub struct Attestor {
+    provider: Box<dyn AttestationProvider>,
+    certificate_hash: Vec<u8>,
+    share_encryption_key: Vec<u8>,
+}
+
+impl Attestor {
+    /// Constructs attestor
+    ///
+    /// `certificate_der` is Will's DER-encoded TLS certificate, `share_encryption_key` is serialized
+    /// share encryption public key.
+    pub fn new(
+        provider: Box<dyn AttestationProvider>,
+        certificate_der: &[u8],
+        share_encryption_key: Vec<u8>,
+    ) -> Self {
+        Self {
+            provider,
+            certificate_hash: digest::digest(&digest::SHA256, certificate_der)
+                .as_ref()
+                .to_vec(),
+            share_encryption_key,
+        }
+    }
+
+    pub fn attest(&self, nonce: &[u8]) -> Result<Attestation, AttestationError> {
+        let report_data = report_data(&self.certificate_hash, &self.share_encryption_key, nonce);
+        let evidence = self.provider.attest(&report_data)?;
+        Ok(Attestation {
+            format: self.provider.format().to_owned(),
+            evidence,
+            certificate_hash: self.certificate_hash.clone(),
+            share_encryption_key: self.share_encryption_key.clone(),
+        })
+    }
+}
+
+/// Computes `SHA-512(context || certificate_hash || share_encryption_key || nonce)`
+pub fn report_data(certificate_hash: &[u8], share_encryption_key: &[u8], nonce: &[u8]) -> [u8; 64] {
+    let mut ctx = digest::Context::new(&digest::SHA512);
+    ctx.update(REPORT_DATA_CONTEXT);
+    ctx.update(certificate_hash);
+    ctx.update(share_encryption_key);
+    ctx.update(nonce);
+    let mut report_data = [0u8; 64];
+    report_data.copy_from_slice(ctx.finish().as_ref());
+    report_data
+}
+
+/// Attestation provider for development and tests
+///
+/// Evidence is `measurement || report_data`, where measurement is SHA-256 of Will executable.
+/// Evidence is not signed by any hardware key, so it proves nothing and must never be trusted
+/// in production.
+pub struct MockAttestationProvider {
+    measurement: [u8; 32],
+}
+
+impl MockAttestationProvider {
+    pub const FORMAT: &'static str = "mock-v1";
+
+    pub fn new(measurement: [u8; 32]) -> Self {
+        Self { measurement }
+    }
+
+    /// Measures currently running executable
+    pub fn measure_current_exe() -> std::io::Result<Self> {
+        let exe = std::fs::read(std::env::current_exe()?)?;
+        let mut measurement = [0u8; 32];
+        measurement.copy_from_slice(digest::digest(&digest::SHA256, &exe).as_ref());
+        Ok(Self::new(measurement))
+    }
+
+    pub fn measurement(&self) -> &[u8; 32] {
+        &self.measurement
+    }
+}
+
+impl AttestationProvider for MockAttestationProvider {
+    fn format(&self) -> &'static str {
+        Self::FORMAT
+    }
+
+    fn attest(&self, report_data: &[u8; 64]) -> Result<Vec<u8>, AttestationError> {
+        Ok([&self.measurement[..], &report_data[..]].concat())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("attestation failed: {0}")]
+pub struct AttestationError(pub String);
+
+#[cfg(test)]
+mod tests {
+    use super::{report_data, Attestor, MockAttestationProvider};
+
+    #[test]
+    fn mock_evidence_binds_certificate_key_and_nonce() {
+        let attestor = Attestor::new(
+            Box::new(MockAttestationProvider::new([7; 32])),
+            b"certificate",
+            b"share key".to_vec(),
+        );
+        let attestation = attestor.attest(b"nonce").unwrap();
+
+        assert_eq!(attestation.format, MockAttestationProvider::FORMAT);
+        assert_eq!(&attestation.evidence[..32], &[7; 32]);
+        let expected_report_data = report_data(
+            &attestation.certificate_hash,
+            &attestation.share_encryption_key,
+            b"nonce",
+        );
+        assert_eq!(&attestation.evidence[32..], &expected_report_data[..]);
+    }
+}
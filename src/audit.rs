@@ -0,0 +1,453 @@
+//! Tamper-evident audit log of share and claim events
+//!
+//! Every record is chained to the previous one: its hash covers the hash of the previous record,
+//! so modifying, reordering or removing a record breaks the chain from that point on. Log is kept
+//! in the persistent store and replicated to standbys.
+//!
+//! Chain alone doesn't reveal the log rewritten from scratch, so Will serves [Checkpoint]s signed
+//! with its TLS key. Checkpoint obtained earlier proves that the log had given length and head,
+//! and `zengo-will audit verify` checks the log against it.
+
+use std::fmt;
+use std::str::FromStr;
+
+use curv::elliptic::curves::traits::ECPoint;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::delay::rounds::unix_time;
+use crate::persistent_store::{PersistentStore, StoreError};
+
+/// Domain separator of record hashes
+const RECORD_CONTEXT: &[u8] = b"zengo-will/audit-record";
+/// Domain separator of checkpoint signatures. Must be kept in sync with client implementation.
+pub const CHECKPOINT_CONTEXT: &[u8] = b"zengo-will/audit-checkpoint";
+
+/// Hash of the record preceding the first one
+const GENESIS_HASH: [u8; 32] = [0u8; 32];
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// Testator saved server share
+    ShareSaved,
+    /// Authenticated beneficiary verified that Will holds server share
+    ShareVerified,
+    /// Challenge is issued to beneficiary
+    ChallengeIssued,
+    /// Beneficiary completed a claim round, but more rounds are required
+    ClaimRoundCompleted,
+    /// Server share is released to beneficiary
+    ShareReleased,
+}
+
+impl AuditEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEvent::ShareSaved => "share_saved",
+            AuditEvent::ShareVerified => "share_verified",
+            AuditEvent::ChallengeIssued => "challenge_issued",
+            AuditEvent::ClaimRoundCompleted => "claim_round_completed",
+            AuditEvent::ShareReleased => "share_released",
+        }
+    }
+}
+
+/// Audited event before it's chained into the log
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct AuditEntry {
+    pub event: AuditEvent,
+    /// SHA-256 of public key of the share, or `None` if event concerns all shares (e.g. challenge
+    /// is issued)
+    pub public_key_hash: Option<[u8; 32]>,
+    /// Who made the request, e.g. `device <fingerprint> of account <id>`
+    pub caller: String,
+    /// Unix time (in seconds) of the event
+    pub at: u64,
+}
+
+impl AuditEntry {
+    /// Constructs entry of event happened now. `public_key` is serialized with `pk_to_key_slice`.
+    pub fn new(event: AuditEvent, public_key: Option<&[u8]>, caller: impl fmt::Display) -> Self {
+        Self {
+            event,
+            public_key_hash: public_key.map(sha256),
+            caller: caller.to_string(),
+            at: unix_time(),
+        }
+    }
+}
+
+/// Entry chained into the log
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct AuditRecord {
+    /// Position of the record in the log, starting from 0
+    pub seq: u64,
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+    /// Hash of the previous record, or zeros for the first one
+    pub prev_hash: [u8; 32],
+    /// Hash of this record, see [AuditRecord::compute_hash]
+    pub hash: [u8; 32],
+}
+
+impl AuditRecord {
+    /// Chains `entry` to the `last` record of the log, or starts the log if it's empty
+    pub fn chain(last: Option<&AuditRecord>, entry: AuditEntry) -> Self {
+        let (seq, prev_hash) = match last {
+            Some(last) => (last.seq + 1, last.hash),
+            None => (0, GENESIS_HASH),
+        };
+        let mut record = Self {
+            seq,
+            entry,
+            prev_hash,
+            hash: GENESIS_HASH,
+        };
+        record.hash = record.compute_hash();
+        record
+    }
+
+    /// Computes `SHA-256(context || seq || prev_hash || at || event || public_key_hash ||
+    /// caller)`, where integers are big-endian, strings are prefixed with their length, and
+    /// missing public key hash is a single zero byte
+    pub fn compute_hash(&self) -> [u8; 32] {
+        let mut hash = digest::Context::new(&digest::SHA256);
+        hash.update(RECORD_CONTEXT);
+        hash.update(&self.seq.to_be_bytes());
+        hash.update(&self.prev_hash);
+        hash.update(&self.entry.at.to_be_bytes());
+        update_with_str(&mut hash, self.entry.event.as_str());
+        match &self.entry.public_key_hash {
+            Some(public_key_hash) => {
+                hash.update(&[1]);
+                hash.update(public_key_hash);
+            }
+            None => hash.update(&[0]),
+        }
+        update_with_str(&mut hash, &self.entry.caller);
+        to_array(hash.finish())
+    }
+}
+
+impl fmt::Display for AuditRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} at {}: {} by {}",
+            self.seq,
+            self.entry.at,
+            self.entry.event.as_str(),
+            self.entry.caller
+        )?;
+        if let Some(public_key_hash) = &self.entry.public_key_hash {
+            write!(f, " on share {}", hex(public_key_hash))?;
+        }
+        Ok(())
+    }
+}
+
+/// Appends entry to the audit log of `store`. Entry is flushed to disk once it returns.
+///
+/// Operation that mustn't take effect unaudited, like release of the share, is audited with it
+/// before it takes effect.
+pub async fn append<S, P>(
+    store: &S,
+    event: AuditEvent,
+    public_key: Option<&[u8]>,
+    caller: impl fmt::Display,
+) -> Result<AuditRecord, StoreError>
+where
+    S: PersistentStore<P>,
+    P: ECPoint,
+{
+    let entry = AuditEntry::new(event, public_key, caller);
+    store.append_audit_entry(entry).await
+}
+
+/// Like [append], but failure to append is logged rather than returned: the audited operation
+/// has already taken effect, so failing the request would only make client retry it.
+pub async fn record<S, P>(
+    store: &S,
+    event: AuditEvent,
+    public_key: Option<&[u8]>,
+    caller: impl fmt::Display,
+) where
+    S: PersistentStore<P>,
+    P: ECPoint,
+{
+    if let Err(e) = append(store, event, public_key, caller).await {
+        error!(event = event.as_str(), "Append to audit log: {}", e);
+    }
+}
+
+/// Length of the log and hash of its last record at some point
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Checkpoint {
+    /// Number of records in the log
+    pub length: u64,
+    /// Hash of the last record, or zeros if the log is empty
+    pub head: [u8; 32],
+}
+
+impl Checkpoint {
+    /// Checkpoint of the log ending with `last` record
+    pub fn of(last: Option<&AuditRecord>) -> Self {
+        match last {
+            Some(last) => Self {
+                length: last.seq + 1,
+                head: last.hash,
+            },
+            None => Self {
+                length: 0,
+                head: GENESIS_HASH,
+            },
+        }
+    }
+
+    /// Message signed by Will: `length || head || signed_at`, integers are big-endian. It's
+    /// signed along with [CHECKPOINT_CONTEXT].
+    pub fn message(&self, signed_at: u64) -> Vec<u8> {
+        [
+            &self.length.to_be_bytes()[..],
+            &self.head[..],
+            &signed_at.to_be_bytes()[..],
+        ]
+        .concat()
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = InvalidCheckpoint;
+
+    /// Parses `<length>:<head in hex>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let length = parts.next().ok_or(InvalidCheckpoint)?;
+        let head = parts.next().ok_or(InvalidCheckpoint)?;
+        let length = length.trim().parse().map_err(|_| InvalidCheckpoint)?;
+        let head = head.trim();
+        if head.len() != 64 || !head.is_ascii() {
+            return Err(InvalidCheckpoint);
+        }
+        let mut bytes = [0u8; 32];
+        for (byte, digits) in bytes.iter_mut().zip(head.as_bytes().chunks(2)) {
+            let digits = std::str::from_utf8(digits).map_err(|_| InvalidCheckpoint)?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| InvalidCheckpoint)?;
+        }
+        Ok(Self {
+            length,
+            head: bytes,
+        })
+    }
+}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.length, hex(&self.head))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("checkpoint must be `<length>:<head>`, where head is 32 bytes in hex")]
+pub struct InvalidCheckpoint;
+
+/// Verifies the chain record by record, so the log doesn't have to fit in memory
+pub struct ChainVerifier {
+    last: Checkpoint,
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl ChainVerifier {
+    /// Constructs verifier of the log that is expected to match every checkpoint
+    pub fn new(checkpoints: Vec<Checkpoint>) -> Self {
+        Self {
+            last: Checkpoint::of(None),
+            checkpoints,
+        }
+    }
+
+    /// Checks the next record of the log
+    pub fn push(&mut self, record: &AuditRecord) -> Result<(), ChainError> {
+        if record.seq != self.last.length {
+            return Err(ChainError::Gap {
+                expected: self.last.length,
+                found: record.seq,
+            });
+        }
+        if record.prev_hash != self.last.head {
+            return Err(ChainError::BrokenLink { seq: record.seq });
+        }
+        if record.compute_hash() != record.hash {
+            return Err(ChainError::Modified { seq: record.seq });
+        }
+        self.last = Checkpoint::of(Some(record));
+        if self
+            .checkpoints
+            .iter()
+            .any(|c| c.length == self.last.length && c.head != self.last.head)
+        {
+            return Err(ChainError::CheckpointMismatch {
+                length: self.last.length,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that the log isn't shorter than any checkpoint, and returns checkpoint of the whole
+    /// log
+    pub fn finish(self) -> Result<Checkpoint, ChainError> {
+        let last = self.last;
+        match self.checkpoints.iter().find(|c| c.length > last.length) {
+            Some(checkpoint) => Err(ChainError::Truncated {
+                length: last.length,
+                checkpoint: checkpoint.length,
+            }),
+            None => Ok(last),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ChainError {
+    #[error("record #{found} found where #{expected} is expected")]
+    Gap { expected: u64, found: u64 },
+    #[error("record #{seq} isn't chained to the previous one")]
+    BrokenLink { seq: u64 },
+    #[error("record #{seq} doesn't match its hash")]
+    Modified { seq: u64 },
+    #[error("log diverges from checkpoint of length {length}")]
+    CheckpointMismatch { length: u64 },
+    #[error("log has {length} records, fewer than checkpoint of length {checkpoint}")]
+    Truncated { length: u64, checkpoint: u64 },
+}
+
+fn update_with_str(hash: &mut digest::Context, s: &str) {
+    hash.update(&(s.len() as u64).to_be_bytes());
+    hash.update(s.as_bytes());
+}
+
+fn sha256(bytes: &[u8]) -> [u8; 32] {
+    to_array(digest::digest(&digest::SHA256, bytes))
+}
+
+fn to_array(digest: digest::Digest) -> [u8; 32] {
+    let mut array = [0u8; 32];
+    array.copy_from_slice(digest.as_ref());
+    array
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(event: AuditEvent) -> AuditEntry {
+        AuditEntry {
+            event,
+            public_key_hash: Some(sha256(b"public key")),
+            caller: "anonymous testator".to_string(),
+            at: 10,
+        }
+    }
+
+    fn log() -> Vec<AuditRecord> {
+        let mut log: Vec<AuditRecord> = vec![];
+        for event in &[
+            AuditEvent::ShareSaved,
+            AuditEvent::ChallengeIssued,
+            AuditEvent::ShareReleased,
+        ] {
+            log.push(AuditRecord::chain(log.last(), entry(*event)));
+        }
+        log
+    }
+
+    fn verify(log: &[AuditRecord], checkpoints: Vec<Checkpoint>) -> Result<Checkpoint, ChainError> {
+        let mut verifier = ChainVerifier::new(checkpoints);
+        for record in log {
+            verifier.push(record)?;
+        }
+        verifier.finish()
+    }
+
+    #[test]
+    fn verify_intact_log() {
+        let log = log();
+        let checkpoint = Checkpoint::of(log.get(1));
+        assert_eq!(
+            verify(&log, vec![checkpoint]),
+            Ok(Checkpoint::of(log.last()))
+        );
+        assert_eq!(verify(&[], vec![]), Ok(Checkpoint::of(None)));
+    }
+
+    #[test]
+    fn detect_tampering() {
+        let mut modified = log();
+        modified[1].entry.caller = "someone else".to_string();
+        assert_eq!(
+            verify(&modified, vec![]),
+            Err(ChainError::Modified { seq: 1 })
+        );
+
+        let mut removed = log();
+        removed.remove(1);
+        assert_eq!(
+            verify(&removed, vec![]),
+            Err(ChainError::Gap {
+                expected: 1,
+                found: 2
+            })
+        );
+
+        // Rewritten record matches its hash, but isn't chained to the previous one
+        let mut rewritten = log();
+        rewritten[1].prev_hash = [1u8; 32];
+        rewritten[1].hash = rewritten[1].compute_hash();
+        assert_eq!(
+            verify(&rewritten, vec![]),
+            Err(ChainError::BrokenLink { seq: 1 })
+        );
+    }
+
+    #[test]
+    fn detect_rewritten_log_by_checkpoint() {
+        let log = log();
+        let checkpoint = Checkpoint::of(log.get(1));
+
+        // Log rebuilt from scratch is a valid chain, but diverges from checkpoint
+        let mut forged: Vec<AuditRecord> = vec![];
+        for record in &log {
+            let mut entry = record.entry.clone();
+            entry.at += 1;
+            forged.push(AuditRecord::chain(forged.last(), entry));
+        }
+        assert_eq!(
+            verify(&forged, vec![checkpoint]),
+            Err(ChainError::CheckpointMismatch { length: 2 })
+        );
+        assert_eq!(
+            verify(&log[..1], vec![checkpoint]),
+            Err(ChainError::Truncated {
+                length: 1,
+                checkpoint: 2
+            })
+        );
+    }
+
+    #[test]
+    fn parse_checkpoint() {
+        let checkpoint = Checkpoint::of(log().last());
+        assert_eq!(
+            checkpoint.to_string().parse::<Checkpoint>().ok(),
+            Some(checkpoint)
+        );
+        assert!("3".parse::<Checkpoint>().is_err());
+        assert!("3:abcd".parse::<Checkpoint>().is_err());
+    }
+}
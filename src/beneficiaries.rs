@@ -0,0 +1,333 @@
+//! Threshold beneficiaries
+//!
+//! Beneficiary's share might be itself Shamir-split between several heirs, so that any `k` of `n`
+//! heirs are required to claim testator's share. Testator publishes Feldman commitments to
+//! beneficiary's polynomial when saving server share. Then each heir contributes to a claim by
+//! proving knowledge of its piece, and server releases a share once `k` distinct heirs
+//! contributed and challenge is solved.
+//!
+//! Testator might also register auth keys of beneficiaries with a share. Then beneficiary signs a
+//! nonce issued by Will with its auth key to make a request on the share.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::ECPoint;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::schnorr::SchnorrProof;
+
+/// Domain separator of contribution proofs. Must be kept in sync with client implementation.
+const CONTRIBUTION_CONTEXT: &[u8] = b"zengo-will/heir-contribution/v2";
+/// Domain separator of beneficiary authentication. Must be kept in sync with client implementation.
+const AUTH_CONTEXT: &[u8] = b"zengo-will/beneficiary-auth";
+
+/// How long auth nonce might be used after it's issued
+pub const AUTH_NONCE_TTL: Duration = Duration::from_secs(5 * 60);
+/// Size of random part of auth nonce
+const AUTH_NONCE_RANDOM_SIZE: usize = 16;
+
+/// Beneficiary that made a request
+#[derive(Clone, Debug, Default)]
+pub struct Claimant {
+    /// Auth key beneficiary authenticated with, serialized with `pk_to_key_slice`
+    pub auth_key: Option<Vec<u8>>,
+    /// Address request came from
+    pub peer: Option<IpAddr>,
+}
+
+impl fmt::Display for Claimant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.auth_key {
+            Some(key) => {
+                write!(f, "beneficiary with auth key ")?;
+                key.iter().try_for_each(|b| write!(f, "{:02x}", b))?;
+            }
+            None => write!(f, "unauthenticated beneficiary")?,
+        }
+        if let Some(peer) = self.peer {
+            write!(f, " at {}", peer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Heir's contribution to claim: its index and proof of knowledge of its piece
+pub struct Contribution<P: ECPoint> {
+    pub index: u32,
+    pub public_piece: P,
+    pub proof: SchnorrProof<P>,
+}
+
+impl<P> Contribution<P>
+where
+    P: ECPoint + Clone,
+    P::Scalar: Clone,
+{
+    /// Checks that contribution is made by holder of a valid piece for the claim of `public_key`
+    /// under challenge `challenge_id` in claim round `round`
+    pub fn verify(
+        &self,
+        commitments: &VerifiableSS<P>,
+        public_key: &[u8],
+        challenge_id: u128,
+        round: u32,
+    ) -> Result<(), InvalidContribution> {
+        if self.index == 0 || self.index as usize > commitments.parameters.share_count {
+            return Err(InvalidContribution::IndexOutOfRange);
+        }
+        if commitments
+            .validate_share_public(&self.public_piece, self.index as usize)
+            .is_err()
+        {
+            return Err(InvalidContribution::PieceDoesntMatchCommitments);
+        }
+        let message = contribution_message(public_key, challenge_id, round, self.index);
+        if !self.proof.verify(&self.public_piece, &message) {
+            return Err(InvalidContribution::InvalidProof);
+        }
+        Ok(())
+    }
+}
+
+/// Message that heir signs: `context || public_key || challenge_id || round || index`, numbers
+/// are little-endian
+pub fn contribution_message(
+    public_key: &[u8],
+    challenge_id: u128,
+    round: u32,
+    index: u32,
+) -> Vec<u8> {
+    [
+        CONTRIBUTION_CONTEXT,
+        public_key,
+        &challenge_id.to_le_bytes(),
+        &round.to_le_bytes(),
+        &index.to_le_bytes(),
+    ]
+    .concat()
+}
+
+/// Message that beneficiary signs with its auth key: `context || nonce || public_key`
+pub fn auth_message<P: ECPoint>(nonce: &[u8], public_key: &P) -> Vec<u8> {
+    [AUTH_CONTEXT, nonce, &public_key.pk_to_key_slice()].concat()
+}
+
+/// Issued beneficiary auth nonces
+///
+/// Nonce is `issued_at (LE u64) || random || HMAC(issued_at || random)`, so issuing one keeps no
+/// state, and nobody can exhaust nonces available to beneficiaries by requesting many of them.
+/// Nonce is remembered once it's redeemed by a valid signature, until it expires, so it's still
+/// single-use.
+pub struct AuthNonces {
+    key: hmac::Key,
+    /// Redeemed nonces along with Unix time they expire at
+    redeemed: Mutex<HashMap<Vec<u8>, u64>>,
+}
+
+impl Default for AuthNonces {
+    fn default() -> Self {
+        Self {
+            key: hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+                .expect("system randomness is unavailable"),
+            redeemed: Default::default(),
+        }
+    }
+}
+
+impl AuthNonces {
+    /// Issues a nonce valid for [AUTH_NONCE_TTL] since `now` (Unix time in seconds)
+    pub fn issue(&self, now: u64) -> Result<Vec<u8>, ring::error::Unspecified> {
+        let mut random = [0u8; AUTH_NONCE_RANDOM_SIZE];
+        SystemRandom::new().fill(&mut random)?;
+        let mut nonce = [&now.to_le_bytes()[..], &random[..]].concat();
+        let tag = hmac::sign(&self.key, &nonce);
+        nonce.extend_from_slice(tag.as_ref());
+        Ok(nonce)
+    }
+
+    /// Uses up the nonce at `now`. Returns `false` if nonce wasn't issued by Will, is expired, or
+    /// was already used.
+    pub fn redeem(&self, nonce: &[u8], now: u64) -> bool {
+        let signed_size = 8 + AUTH_NONCE_RANDOM_SIZE;
+        if nonce.len() <= signed_size {
+            return false;
+        }
+        let (signed, tag) = nonce.split_at(signed_size);
+        if hmac::verify(&self.key, signed, tag).is_err() {
+            return false;
+        }
+        let issued_at = u64::from_le_bytes(signed[..8].try_into().expect("8 bytes"));
+        let expires_at = issued_at.saturating_add(AUTH_NONCE_TTL.as_secs());
+        if issued_at > now || expires_at <= now {
+            return false;
+        }
+
+        let mut redeemed = self.redeemed.lock().expect("poisoned");
+        redeemed.retain(|_, expires_at| *expires_at > now);
+        redeemed.insert(nonce.to_vec(), expires_at).is_none()
+    }
+}
+
+/// Checks that commitments describe a valid `k`-of-`n` sharing
+pub fn validate_commitments<P: ECPoint>(commitments: &VerifiableSS<P>) -> bool {
+    let k = commitments.commitments.len();
+    k >= 1
+        && commitments.parameters.threshold.checked_add(1) == Some(k)
+        && k <= commitments.parameters.share_count
+}
+
+/// Checks that commitments are made to beneficiary's share of `public_key`, i.e. that beneficiary's
+/// public share `commitments[0]` multiplied by server share gives the joint public key
+pub fn commitments_match_public_key<P>(
+    commitments: &VerifiableSS<P>,
+    server_share: &P::Scalar,
+    public_key: &P,
+) -> bool
+where
+    P: ECPoint + Clone,
+    P::Scalar: Clone,
+{
+    match commitments.commitments.first() {
+        Some(beneficiary_public_share) => {
+            beneficiary_public_share.clone() * server_share.clone() == *public_key
+        }
+        None => false,
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidContribution {
+    #[error("heir index is out of range")]
+    IndexOutOfRange,
+    #[error("piece doesn't match published commitments")]
+    PieceDoesntMatchCommitments,
+    #[error("invalid proof of piece knowledge")]
+    InvalidProof,
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+    use curv::elliptic::curves::secp256_k1::{FE, GE};
+    use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+
+    use super::{
+        auth_message, commitments_match_public_key, contribution_message, validate_commitments,
+        AuthNonces, Contribution, InvalidContribution, AUTH_NONCE_TTL,
+    };
+    use crate::schnorr::SchnorrProof;
+
+    fn contribute(piece: &FE, index: u32, challenge_id: u128, round: u32) -> Contribution<GE> {
+        let message = contribution_message(b"pk", challenge_id, round, index);
+        Contribution {
+            index,
+            public_piece: GE::generator() * *piece,
+            proof: SchnorrProof::prove(piece, &message),
+        }
+    }
+
+    #[test]
+    fn accept_valid_contribution() {
+        let (commitments, pieces) = VerifiableSS::<GE>::share(1, 3, &FE::new_random());
+        let contribution = contribute(&pieces[1], 2, 5, 1);
+        contribution.verify(&commitments, b"pk", 5, 1).unwrap();
+    }
+
+    #[test]
+    fn reject_contribution_with_wrong_piece() {
+        let (commitments, pieces) = VerifiableSS::<GE>::share(1, 3, &FE::new_random());
+        let contribution = contribute(&pieces[0], 2, 5, 1);
+        assert!(matches!(
+            contribution.verify(&commitments, b"pk", 5, 1),
+            Err(InvalidContribution::PieceDoesntMatchCommitments)
+        ));
+    }
+
+    #[test]
+    fn reject_contribution_made_for_another_challenge() {
+        let (commitments, pieces) = VerifiableSS::<GE>::share(1, 3, &FE::new_random());
+        let contribution = contribute(&pieces[1], 2, 4, 1);
+        assert!(matches!(
+            contribution.verify(&commitments, b"pk", 5, 1),
+            Err(InvalidContribution::InvalidProof)
+        ));
+    }
+
+    #[test]
+    fn reject_contribution_made_for_another_round() {
+        let (commitments, pieces) = VerifiableSS::<GE>::share(1, 3, &FE::new_random());
+        let contribution = contribute(&pieces[1], 2, 5, 0);
+        assert!(matches!(
+            contribution.verify(&commitments, b"pk", 5, 1),
+            Err(InvalidContribution::InvalidProof)
+        ));
+    }
+
+    #[test]
+    fn reject_commitments_with_overflowing_threshold() {
+        let (mut commitments, _) = VerifiableSS::<GE>::share(1, 3, &FE::new_random());
+        assert!(validate_commitments(&commitments));
+        commitments.parameters.threshold = usize::MAX;
+        assert!(!validate_commitments(&commitments));
+    }
+
+    #[test]
+    fn commitments_are_bound_to_public_key() {
+        let beneficiary_share = FE::new_random();
+        let server_share = FE::new_random();
+        let public_key = GE::generator() * beneficiary_share * server_share;
+        let (commitments, _) = VerifiableSS::<GE>::share(1, 3, &beneficiary_share);
+        let (other_commitments, _) = VerifiableSS::<GE>::share(1, 3, &FE::new_random());
+
+        assert!(commitments_match_public_key(
+            &commitments,
+            &server_share,
+            &public_key
+        ));
+        assert!(!commitments_match_public_key(
+            &other_commitments,
+            &server_share,
+            &public_key
+        ));
+    }
+
+    #[test]
+    fn auth_is_bound_to_nonce_and_public_key() {
+        let auth_secret = FE::new_random();
+        let auth_key = GE::generator() * auth_secret;
+        let public_key = GE::generator() * FE::new_random();
+        let other_key = GE::generator() * FE::new_random();
+
+        let proof = SchnorrProof::prove(&auth_secret, &auth_message(b"nonce", &public_key));
+
+        assert!(proof.verify(&auth_key, &auth_message(b"nonce", &public_key)));
+        assert!(!proof.verify(&auth_key, &auth_message(b"other nonce", &public_key)));
+        assert!(!proof.verify(&auth_key, &auth_message(b"nonce", &other_key)));
+    }
+
+    #[test]
+    fn auth_nonce_is_single_use_and_expires() {
+        let nonces = AuthNonces::default();
+        let nonce = nonces.issue(1000).unwrap();
+
+        assert!(nonces.redeem(&nonce, 1001));
+        assert!(!nonces.redeem(&nonce, 1002));
+
+        let nonce = nonces.issue(1000).unwrap();
+        assert!(!nonces.redeem(&nonce, 1000 + AUTH_NONCE_TTL.as_secs()));
+        assert!(!nonces.redeem(&nonce, 999));
+
+        let mut forged = nonces.issue(1000).unwrap();
+        forged[0] ^= 1;
+        assert!(!nonces.redeem(&forged, 1001));
+        assert!(!nonces.redeem(&AuthNonces::default().issue(1000).unwrap(), 1001));
+        assert!(!nonces.redeem(b"short", 1001));
+    }
+}
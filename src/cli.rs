@@ -1,14 +1,50 @@
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
 use structopt::StructOpt;
 
+use crate::audit::Checkpoint;
+use crate::delay::Scheme;
+use crate::notifications::reminders::ReminderStep;
+use crate::rate_limit::Budget;
+
 #[derive(StructOpt, Debug)]
+#[structopt(setting = structopt::clap::AppSettings::SubcommandsNegateReqs)]
 pub struct App {
-    #[structopt(short)]
-    pub t: u64,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+
+    /// Difficulty of challenges: number of sequential squarings for VDFs, or number of hash
+    /// iterations for `hash-chain`
+    #[structopt(
+        short,
+        required_unless = "target_delay",
+        conflicts_with = "target_delay"
+    )]
+    pub t: Option<u64>,
+    /// Derives `-t` so that solving a challenge takes given time (e.g. `72h`)
+    #[structopt(long, parse(try_from_str = parse_duration::parse))]
+    pub target_delay: Option<Duration>,
+    /// Speed measured by `calibrate` on reference hardware. If not set, Will measures its own
+    /// speed at startup, which likely differs from beneficiary's one.
+    #[structopt(long, requires = "target_delay")]
+    pub squarings_per_second: Option<u64>,
+    /// Scheme of challenges issued to beneficiaries: `rsa-vdf`, `wesolowski` or `hash-chain`.
+    /// Challenges issued before switching the scheme remain valid until the next ping.
+    #[structopt(long, default_value = "rsa-vdf")]
+    pub delay_scheme: Scheme,
+    /// Number of challenges beneficiary solves one after another before server share is released.
+    /// Ping aborts the claim at any round.
+    #[structopt(long, default_value = "1")]
+    pub claim_rounds: u32,
+    /// Minimal time between solving challenge of one round and issuing challenge of the next one
+    #[structopt(long, default_value = "0s", parse(try_from_str = parse_duration::parse))]
+    pub claim_round_interval: Duration,
 
+    /// Required unless a subcommand is given
     #[structopt(long)]
-    pub persistent_store: PathBuf,
+    pub persistent_store: Option<PathBuf>,
 
     #[structopt(long, conflicts_with_all(&["insecure", "generate_self_signed"]))]
     pub cert: Option<PathBuf>,
@@ -17,17 +53,217 @@ pub struct App {
 
     #[structopt(long, required_unless = "insecure")]
     pub testator_ca: Option<PathBuf>,
+    /// Rejects testator requests made by devices that aren't enrolled. Revoked devices are
+    /// rejected regardless of this option.
+    #[structopt(long, requires = "testator_ca")]
+    pub require_testator_enrollment: bool,
+    /// Counts only pings signed with liveness keys registered with testator's shares, so a cloned
+    /// device can't keep Will alive without the wallet share. Such pings are available in v2 API
+    /// only.
+    #[structopt(long)]
+    pub require_signed_pings: bool,
+    /// Interval between heartbeats recommended to testators in `KeepAlive` sessions
+    #[structopt(long, default_value = "1h", parse(try_from_str = parse_duration::parse))]
+    pub keepalive_interval: Duration,
+    /// Coalesces pings received while no claim is in progress in memory, persisting them once
+    /// per `--ping-persist-interval` instead of on every ping. Ping aborting a claim is still
+    /// persisted right away. Coalesced ping is acknowledged before it's persisted, so it's lost if
+    /// Will crashes or fails over before the next flush, and testator appears to have pinged
+    /// earlier than they did.
+    #[structopt(long)]
+    pub coalesce_pings: bool,
+    /// How often coalesced pings are persisted, only used with `--coalesce-pings`
+    #[structopt(long, default_value = "10s", parse(try_from_str = parse_duration::parse))]
+    pub ping_persist_interval: Duration,
+    /// CRL issued by testator CA, either PEM or DER. Certificates it revokes are rejected during
+    /// TLS handshake. Might be given several times.
+    #[structopt(long, requires = "testator_ca")]
+    pub testator_crl: Vec<PathBuf>,
+    /// File listing serial numbers of revoked testator certificates in hex, one per line. See
+    /// `revoke-testator-cert` command.
+    #[structopt(long, requires = "testator_ca")]
+    pub testator_revoked_serials: Option<PathBuf>,
+    /// How often `--testator-crl` and `--testator-revoked-serials` are reloaded
+    #[structopt(long, default_value = "1m", parse(try_from_str = parse_duration::parse))]
+    pub revocation_reload_interval: Duration,
+    /// CA that issued beneficiaries' client certificates. Beneficiary API then serves only clients
+    /// presenting such certificate, including health checks.
+    #[structopt(long, conflicts_with = "insecure")]
+    pub beneficiary_ca: Option<PathBuf>,
+    /// Rejects beneficiary requests that aren't signed with auth keys registered by testator with
+    /// the share. Such requests are available in v2 API only.
+    #[structopt(long)]
+    pub require_beneficiary_auth: bool,
+    /// Budget of beneficiary requests from one IP address, e.g. `60/1m`. Unlimited by default.
+    #[structopt(long)]
+    pub beneficiary_rate_limit_per_peer: Option<Budget>,
+    /// Budget of beneficiary requests from one IP address concerning share of one public key, e.g.
+    /// `20/1m`. Unlimited by default.
+    #[structopt(long)]
+    pub beneficiary_rate_limit_per_key: Option<Budget>,
+    /// Number of claims on a share from one IP address in a row rejected for incorrect solution or
+    /// malformed request, after which further claims from that address are locked out. Claims are
+    /// never locked out by default.
+    #[structopt(long)]
+    pub claim_lockout_after: Option<u32>,
+    /// How long claims on a share are locked out
+    #[structopt(long, default_value = "15m", parse(try_from_str = parse_duration::parse))]
+    pub claim_lockout_duration: Duration,
+    /// URL notifications are POSTed to, `http://` or `https://` one
+    #[structopt(long, requires = "notify_webhook_secret")]
+    pub notify_webhook: Option<String>,
+    /// File holding secret key of HMAC-SHA256 signatures of webhook requests
+    #[structopt(long)]
+    pub notify_webhook_secret: Option<PathBuf>,
+    /// CA that issued TLS certificate of `https://` webhook
+    #[structopt(long)]
+    pub notify_webhook_ca: Option<PathBuf>,
+    /// SMTP relay (`host:port`) notifications are emailed through. Session is upgraded to TLS
+    /// with STARTTLS. Relay is reached without authentication.
+    #[structopt(long, requires_all = &["notify_email_from", "notify_email_to"])]
+    pub notify_smtp_relay: Option<String>,
+    /// CA that issued TLS certificate of SMTP relay. Required unless plaintext is allowed.
+    #[structopt(long)]
+    pub notify_smtp_ca: Option<PathBuf>,
+    /// Emails notifications in plaintext. Messages reveal claims and inactivity of testator, so
+    /// it's meant for relay on the same host only.
+    #[structopt(long, conflicts_with = "notify_smtp_ca")]
+    pub notify_smtp_plaintext: bool,
+    /// Sender of email notifications
+    #[structopt(long)]
+    pub notify_email_from: Option<String>,
+    /// Recipient of email notifications. Might be given several times.
+    #[structopt(long)]
+    pub notify_email_to: Vec<String>,
+    /// File notifications are appended to as JSON lines, or `-` for stdout
+    #[structopt(long)]
+    pub notify_file: Option<PathBuf>,
+    /// How long testator may stay without pinging Will before the will becomes claimable.
+    /// Testator is reminded once the deadline is approaching. Not tracked by default.
+    #[structopt(long, parse(try_from_str = parse_duration::parse))]
+    pub inactivity_deadline: Option<Duration>,
+    /// How long before the inactivity deadline testator is reminded, optionally followed by sinks
+    /// the reminder is sent to, e.g. `1d:email,webhook`. Might be given several times, so later
+    /// reminders escalate to more sinks. Sent to all sinks by default.
+    #[structopt(long = "liveness-reminder", default_value = "7d")]
+    pub liveness_reminders: Vec<ReminderStep>,
 
     #[structopt(long, default_value = "4949")]
     pub beneficiary_api_port: u16,
+    /// Maximum number of challenge solutions verified at once. Claims exceeding it are rejected
+    /// with `ResourceExhausted`.
+    #[structopt(long, default_value = "2")]
+    pub max_concurrent_verifications: usize,
     #[structopt(long, default_value = "4950")]
     pub testator_api_port: u16,
+    /// Rejects requests to deprecated v1 API with `Unimplemented` instead of serving them
+    #[structopt(long)]
+    pub disable_v1_api: bool,
 
+    /// Caches `rsa-vdf` parameters in given file
     #[structopt(long)]
     pub vdf_params: Option<PathBuf>,
+    /// Regenerates cached `--vdf-params` instead of failing if they don't match configuration or
+    /// fail integrity check
+    #[structopt(long, requires = "vdf_params")]
+    pub regenerate_vdf_params: bool,
 
     #[structopt(long, conflicts_with = "generate_self_singed")]
     pub insecure: bool,
     #[structopt(long)]
     pub generate_self_signed: Vec<String>,
+
+    /// Attestation provider serving `GetAttestation` requests. Only `mock` is supported for now,
+    /// which must not be used in production.
+    #[structopt(long)]
+    pub attestation: Option<AttestationKind>,
+
+    /// Serves replication stream to standbys on given port
+    #[structopt(long)]
+    pub replication_port: Option<u16>,
+    /// CA that issued standbys' client certificates
+    #[structopt(long, requires = "replication_port")]
+    pub standby_ca: Option<PathBuf>,
+    /// Number of standbys that must acknowledge every store mutation before Will replies to
+    /// the request that made it, at least 1. Request fails if its mutations aren't acknowledged
+    /// in time, e.g. while fewer standbys are connected.
+    #[structopt(long, default_value = "1")]
+    pub sync_standbys: usize,
+
+    /// Runs as a standby replicating store of given primary Will (e.g. `https://primary:4951`).
+    /// Standby authenticates itself with `--cert` and `--key`. To promote the standby, restart
+    /// it without this option.
+    #[structopt(long, conflicts_with = "replication_port")]
+    pub replicate_from: Option<String>,
+    /// CA that issued primary's certificate
+    #[structopt(long, requires = "replicate_from")]
+    pub primary_ca: Option<PathBuf>,
+    /// Primary's domain name, if it differs from one in `--replicate-from`
+    #[structopt(long, requires = "replicate_from")]
+    pub primary_hostname: Option<String>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Measures speed of solving challenges on this machine and derives difficulty
+    Calibrate(Calibrate),
+    /// Revokes testator certificate by adding its serial number to the list of revoked serials.
+    /// Running Will picks it up within `--revocation-reload-interval`.
+    RevokeTestatorCert(RevokeTestatorCert),
+    /// Inspects audit log of share and claim events
+    Audit(Audit),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct Calibrate {
+    #[structopt(long, default_value = "rsa-vdf")]
+    pub delay_scheme: Scheme,
+    /// Prints `-t` that corresponds to given delay (e.g. `72h`)
+    #[structopt(long, parse(try_from_str = parse_duration::parse))]
+    pub target_delay: Option<Duration>,
+    /// How long to run the benchmark
+    #[structopt(long, default_value = "10s", parse(try_from_str = parse_duration::parse))]
+    pub duration: Duration,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct RevokeTestatorCert {
+    /// Serial number in hex, e.g. as printed by `openssl x509 -noout -serial`
+    #[structopt(long)]
+    pub serial: String,
+    /// File listing revoked serials, the one Will is started with `--testator-revoked-serials`
+    #[structopt(long)]
+    pub revoked_serials: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Audit {
+    /// Checks that audit log is intact, and prints its current checkpoint
+    Verify(AuditVerify),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct AuditVerify {
+    /// Store of Will, which must not be running at the moment
+    #[structopt(long)]
+    pub persistent_store: PathBuf,
+    /// Previously obtained checkpoint `<length>:<head hash in hex>` that log must extend. Can be
+    /// given several times.
+    #[structopt(long = "checkpoint")]
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AttestationKind {
+    Mock,
+}
+
+impl FromStr for AttestationKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mock" => Ok(AttestationKind::Mock),
+            _ => Err(format!("unknown attestation provider: {}", s)),
+        }
+    }
 }
@@ -0,0 +1,119 @@
+//! Translates wall-clock delay into difficulty of challenges
+//!
+//! Difficulty `t` is the number of sequential steps needed to solve a challenge: squarings for VDFs,
+//! hash iterations for the hash chain. How long it takes depends on hardware, so speed should be
+//! measured (see [benchmark]) on hardware comparable to what beneficiaries have.
+
+use std::time::{Duration, Instant};
+
+use curv::arithmetic::{Modulo, Samplable};
+use curv::BigInt;
+use ring::digest;
+use tracing::{info, warn};
+
+use super::{wesolowski, Scheme};
+
+/// Steps made between checks of elapsed time
+const BATCH: u64 = 1000;
+/// How long Will measures its own speed if it's not configured
+const SELF_CALIBRATION_DURATION: Duration = Duration::from_secs(5);
+
+/// Configured difficulty of challenges
+#[derive(Debug, Clone, Copy)]
+pub enum Difficulty {
+    /// Exact number of steps (`-t`)
+    Fixed(u64),
+    /// Number of steps is derived from target delay (`--target-delay`) and speed measured on
+    /// reference hardware (`--squarings-per-second`). If speed is not set, it's measured on
+    /// Will's own hardware.
+    Calibrated {
+        target_delay: Duration,
+        steps_per_second: Option<u64>,
+    },
+}
+
+impl Difficulty {
+    /// Returns number of steps, benchmarking this machine if speed is unknown
+    pub fn resolve(&self, scheme: Scheme) -> u64 {
+        match *self {
+            Difficulty::Fixed(t) => t,
+            Difficulty::Calibrated {
+                target_delay,
+                steps_per_second,
+            } => {
+                let speed = steps_per_second.unwrap_or_else(|| {
+                    warn!("Calibrating on Will's hardware, beneficiary's one might be faster");
+                    benchmark(scheme, SELF_CALIBRATION_DURATION)
+                });
+                let t = difficulty(target_delay, speed);
+                info!(
+                    "Derived t={} from target delay {:?} at {} squarings per second",
+                    t, target_delay, speed
+                );
+                t
+            }
+        }
+    }
+
+    pub fn target_delay(&self) -> Option<Duration> {
+        match self {
+            Difficulty::Fixed(_) => None,
+            Difficulty::Calibrated { target_delay, .. } => Some(*target_delay),
+        }
+    }
+}
+
+/// Measures how many sequential steps of `scheme` this machine makes per second
+///
+/// VDF squarings are measured modulo 2048 bits number.
+pub fn benchmark(scheme: Scheme, duration: Duration) -> u64 {
+    let started = Instant::now();
+    let mut steps = 0u64;
+    match scheme {
+        Scheme::RsaVdf | Scheme::Wesolowski => {
+            let modulus = &*wesolowski::MODULUS;
+            let mut x = BigInt::sample_below(modulus);
+            while started.elapsed() < duration {
+                for _ in 0..BATCH {
+                    x = BigInt::mod_mul(&x, &x, modulus);
+                }
+                steps += BATCH;
+            }
+        }
+        Scheme::HashChain => {
+            let mut x = [0u8; 32];
+            while started.elapsed() < duration {
+                for _ in 0..BATCH {
+                    let h = digest::digest(&digest::SHA256, &x);
+                    x.copy_from_slice(h.as_ref());
+                }
+                steps += BATCH;
+            }
+        }
+    }
+    (steps as f64 / started.elapsed().as_secs_f64()) as u64
+}
+
+/// Difficulty that takes `target_delay` to solve at given speed
+pub fn difficulty(target_delay: Duration, steps_per_second: u64) -> u64 {
+    (target_delay.as_secs_f64() * steps_per_second as f64) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difficulty_is_proportional_to_delay() {
+        let hour = Duration::from_secs(3600);
+        assert_eq!(difficulty(hour * 72, 1000), 259_200_000);
+        assert_eq!(difficulty(Duration::from_millis(1500), 1000), 1500);
+    }
+
+    #[test]
+    fn benchmark_measures_nonzero_speed() {
+        for &scheme in &[Scheme::Wesolowski, Scheme::HashChain] {
+            assert!(benchmark(scheme, Duration::from_millis(100)) > 0);
+        }
+    }
+}
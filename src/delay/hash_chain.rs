@@ -0,0 +1,272 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use super::{ChallengeInput, DelayChallenge, InvalidSolution, NativeSolution, Scheme};
+use crate::proto::beneficiary::{HashChainChallenge, HashChainSolution};
+
+/// Number of segments the chain is split into by checkpoints. Must be kept in sync with client
+/// implementation.
+const SEGMENTS: usize = 256;
+
+/// Time lock based on iterated SHA-256
+///
+/// Solution is `SHA256^iterations(seed)` along with checkpoints splitting the chain into
+/// [SEGMENTS] segments. It needs no setup and relies on nothing but the hash function, but
+/// verifier recomputes every segment, so verification costs as much CPU time as solving.
+/// Checkpoints let verifier recompute segments in parallel. Checking only some of them isn't
+/// enough: a single unchecked segment lets solver compute the chain as two halves in parallel.
+pub struct HashChain {
+    iterations: u64,
+}
+
+impl HashChain {
+    pub fn new(iterations: u64) -> Self {
+        Self { iterations }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Challenge {
+    pub seed: [u8; 32],
+    pub iterations: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Solution {
+    pub output: [u8; 32],
+    /// Chain values at the end of every segment but the last one
+    pub checkpoints: Vec<[u8; 32]>,
+}
+
+impl Solution {
+    /// Chain value at the start of `segment`, or at the end of the chain if `segment` is
+    /// [SEGMENTS]
+    fn value_at(&self, challenge: &Challenge, segment: usize) -> [u8; 32] {
+        match segment {
+            0 => challenge.seed,
+            SEGMENTS => self.output,
+            _ => self.checkpoints[segment - 1],
+        }
+    }
+}
+
+impl DelayChallenge for HashChain {
+    type Challenge = Challenge;
+    type Solution = Solution;
+
+    const SCHEME: Scheme = Scheme::HashChain;
+
+    fn pick_challenge(&self) -> Self::Challenge {
+        let mut seed = [0u8; 32];
+        SystemRandom::new()
+            .fill(&mut seed)
+            .expect("system randomness is unavailable");
+        Challenge {
+            seed,
+            iterations: self.iterations,
+        }
+    }
+
+    fn verify(
+        challenge: &Self::Challenge,
+        solution: &Self::Solution,
+    ) -> Result<(), InvalidSolution> {
+        if solution.checkpoints.len() != SEGMENTS - 1 {
+            return Err(InvalidSolution::MalformedSolution);
+        }
+        let threads = thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(SEGMENTS);
+        let incorrect = AtomicBool::new(false);
+        thread::scope(|scope| {
+            for first in 0..threads {
+                let incorrect = &incorrect;
+                scope.spawn(move || {
+                    for segment in (first..SEGMENTS).step_by(threads) {
+                        if incorrect.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        if !segment_is_correct(challenge, solution, segment) {
+                            incorrect.store(true, Ordering::Relaxed);
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        if incorrect.into_inner() {
+            return Err(InvalidSolution::Incorrect);
+        }
+        Ok(())
+    }
+
+    fn encode_challenge(challenge: &Self::Challenge) -> Option<ChallengeInput> {
+        Some(ChallengeInput::HashChain(HashChainChallenge {
+            seed: challenge.seed.to_vec(),
+            iterations: challenge.iterations,
+        }))
+    }
+
+    fn decode_challenge(challenge: &ChallengeInput) -> Option<Self::Challenge> {
+        match challenge {
+            ChallengeInput::HashChain(HashChainChallenge { seed, iterations }) => Some(Challenge {
+                seed: to_array(seed)?,
+                iterations: *iterations,
+            }),
+            _ => None,
+        }
+    }
+
+    fn decode_solution(
+        _challenge: &Self::Challenge,
+        solution: &NativeSolution,
+    ) -> Option<Self::Solution> {
+        match solution {
+            NativeSolution::HashChainSolution(HashChainSolution {
+                output,
+                checkpoints,
+            }) => {
+                if checkpoints.len() != SEGMENTS - 1 {
+                    return None;
+                }
+                Some(Solution {
+                    output: to_array(output)?,
+                    checkpoints: checkpoints
+                        .iter()
+                        .map(|c| to_array(c))
+                        .collect::<Option<_>>()?,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    fn solve(challenge: &Self::Challenge) -> Self::Solution {
+        let mut value = challenge.seed;
+        let mut checkpoints = Vec::with_capacity(SEGMENTS - 1);
+        for segment in 0..SEGMENTS {
+            let length = boundary(challenge, segment + 1) - boundary(challenge, segment);
+            value = hash_iterated(value, length);
+            checkpoints.push(value);
+        }
+        let output = checkpoints.pop().expect("there's at least one segment");
+        Solution {
+            output,
+            checkpoints,
+        }
+    }
+}
+
+fn to_array(bytes: &[u8]) -> Option<[u8; 32]> {
+    let mut array = [0u8; 32];
+    if bytes.len() != array.len() {
+        return None;
+    }
+    array.copy_from_slice(bytes);
+    Some(array)
+}
+
+/// Number of iterations made by the start of `segment`
+fn boundary(challenge: &Challenge, segment: usize) -> u64 {
+    (u128::from(challenge.iterations) * segment as u128 / SEGMENTS as u128) as u64
+}
+
+/// Recomputes `segment` of the chain and checks that it ends with the next checkpoint
+fn segment_is_correct(challenge: &Challenge, solution: &Solution, segment: usize) -> bool {
+    let start = solution.value_at(challenge, segment);
+    let end = solution.value_at(challenge, segment + 1);
+    let length = boundary(challenge, segment + 1) - boundary(challenge, segment);
+    hash_iterated(start, length) == end
+}
+
+fn hash_iterated(mut value: [u8; 32], iterations: u64) -> [u8; 32] {
+    for _ in 0..iterations {
+        let hash = digest::digest(&digest::SHA256, &value);
+        value.copy_from_slice(hash.as_ref());
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delay::assert_protobuf_roundtrips;
+
+    #[test]
+    fn solution_is_verified() {
+        let challenge = HashChain::new(1000).pick_challenge();
+        let solution = HashChain::solve(&challenge);
+        HashChain::verify(&challenge, &solution).unwrap();
+
+        let other_challenge = HashChain::new(1000).pick_challenge();
+        assert!(HashChain::verify(&other_challenge, &solution).is_err());
+    }
+
+    #[test]
+    fn forged_checkpoints_are_detected() {
+        let challenge = HashChain::new(1000).pick_challenge();
+        let mut solution = HashChain::solve(&challenge);
+        for checkpoint in &mut solution.checkpoints {
+            checkpoint[0] ^= 1;
+        }
+        assert!(matches!(
+            HashChain::verify(&challenge, &solution),
+            Err(InvalidSolution::Incorrect)
+        ));
+
+        solution.checkpoints.pop();
+        assert!(matches!(
+            HashChain::verify(&challenge, &solution),
+            Err(InvalidSolution::MalformedSolution)
+        ));
+    }
+
+    #[test]
+    fn every_segment_is_verified() {
+        let challenge = HashChain::new(1000).pick_challenge();
+        let valid_solution = HashChain::solve(&challenge);
+        for segment in 0..SEGMENTS - 1 {
+            let mut solution = valid_solution.clone();
+            solution.checkpoints[segment][0] ^= 1;
+            assert!(matches!(
+                HashChain::verify(&challenge, &solution),
+                Err(InvalidSolution::Incorrect)
+            ));
+        }
+    }
+
+    fn encode_solution(solution: &Solution) -> HashChainSolution {
+        HashChainSolution {
+            output: solution.output.to_vec(),
+            checkpoints: solution.checkpoints.iter().map(|c| c.to_vec()).collect(),
+        }
+    }
+
+    #[test]
+    fn protobuf_encoding_roundtrips() {
+        assert_protobuf_roundtrips(&HashChain::new(1000), |solution| {
+            NativeSolution::HashChainSolution(encode_solution(solution))
+        });
+    }
+
+    #[test]
+    fn truncated_solution_is_rejected() {
+        let challenge = HashChain::new(1000).pick_challenge();
+        let solution = HashChain::solve(&challenge);
+
+        let mut truncated = encode_solution(&solution);
+        truncated.output.pop();
+        let truncated = NativeSolution::HashChainSolution(truncated);
+        assert_eq!(HashChain::decode_solution(&challenge, &truncated), None);
+
+        let mut truncated = encode_solution(&solution);
+        truncated.checkpoints.pop();
+        let truncated = NativeSolution::HashChainSolution(truncated);
+        assert_eq!(HashChain::decode_solution(&challenge, &truncated), None);
+    }
+}
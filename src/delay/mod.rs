@@ -0,0 +1,265 @@
+//! Delay challenges that beneficiary must solve to claim server share
+//!
+//! Every stored challenge records the scheme it was issued with, so Will can switch schemes
+//! without breaking claims in flight: a challenge is always verified by the scheme that issued it.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::proto::beneficiary::{
+    challenge::Input as ChallengeInput, obtain_server_secret_share_request::NativeSolution,
+};
+
+pub mod calibration;
+pub mod hash_chain;
+pub mod rounds;
+pub mod rsa_vdf;
+pub mod setup;
+pub mod verifier;
+pub mod wesolowski;
+
+/// Puzzle that takes a while to solve, but solution of which can be verified
+pub trait DelayChallenge: Send + Sync {
+    type Challenge: Serialize + DeserializeOwned;
+    type Solution: Serialize + DeserializeOwned;
+
+    const SCHEME: Scheme;
+
+    /// Picks a fresh challenge
+    fn pick_challenge(&self) -> Self::Challenge;
+
+    /// Verifies solution of the challenge. Verification mustn't rely on state of the issuer, as
+    /// challenge might be verified by a Will that uses another scheme to issue new challenges.
+    fn verify(
+        challenge: &Self::Challenge,
+        solution: &Self::Solution,
+    ) -> Result<(), InvalidSolution>;
+
+    /// Converts challenge into protobuf message, so clients don't depend on Rust serialization.
+    /// Returns `None` if challenge can't be represented in protobuf.
+    fn encode_challenge(challenge: &Self::Challenge) -> Option<ChallengeInput>;
+
+    /// Parses challenge from protobuf message. Returns `None` if it's malformed or belongs to
+    /// another scheme.
+    fn decode_challenge(challenge: &ChallengeInput) -> Option<Self::Challenge>;
+
+    /// Parses solution of the challenge from protobuf message. Returns `None` if it's malformed or
+    /// belongs to another scheme.
+    fn decode_solution(
+        challenge: &Self::Challenge,
+        solution: &NativeSolution,
+    ) -> Option<Self::Solution>;
+
+    /// Solves the challenge. Used in tests, clients have their own implementation.
+    #[cfg(test)]
+    fn solve(challenge: &Self::Challenge) -> Self::Solution;
+}
+
+/// Object-safe counterpart of [DelayChallenge], allows choosing scheme at runtime
+pub trait PickChallenge: Send + Sync {
+    fn scheme(&self) -> Scheme;
+    fn pick_challenge(&self) -> serde_json::Value;
+}
+
+impl<D: DelayChallenge> PickChallenge for D {
+    fn scheme(&self) -> Scheme {
+        D::SCHEME
+    }
+
+    fn pick_challenge(&self) -> serde_json::Value {
+        serde_json::to_value(DelayChallenge::pick_challenge(self))
+            .expect("challenge is always serializable")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scheme {
+    RsaVdf,
+    Wesolowski,
+    HashChain,
+}
+
+/// Challenges stored before schemes were introduced are RSA VDF ones
+impl Default for Scheme {
+    fn default() -> Self {
+        Scheme::RsaVdf
+    }
+}
+
+impl Scheme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scheme::RsaVdf => "rsa-vdf",
+            Scheme::Wesolowski => "wesolowski",
+            Scheme::HashChain => "hash-chain",
+        }
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Scheme {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rsa-vdf" => Ok(Scheme::RsaVdf),
+            "wesolowski" => Ok(Scheme::Wesolowski),
+            "hash-chain" => Ok(Scheme::HashChain),
+            _ => Err(format!("unknown delay scheme: {}", s)),
+        }
+    }
+}
+
+/// Verifies serialized solution of serialized challenge issued with given scheme
+///
+/// Verification is expensive, consider using [Verifier](verifier::Verifier) instead.
+pub fn verify(
+    scheme: Scheme,
+    challenge: &serde_json::Value,
+    solution: &[u8],
+) -> Result<(), InvalidSolution> {
+    match scheme {
+        Scheme::RsaVdf => verify_serialized::<self::rsa_vdf::RsaVdf>(challenge, solution),
+        Scheme::Wesolowski => verify_serialized::<wesolowski::Wesolowski>(challenge, solution),
+        Scheme::HashChain => verify_serialized::<hash_chain::HashChain>(challenge, solution),
+    }
+}
+
+/// Cheaply checks that serialized challenge and solution are well-formed
+pub fn check_format(
+    scheme: Scheme,
+    challenge: &serde_json::Value,
+    solution: &[u8],
+) -> Result<(), InvalidSolution> {
+    match scheme {
+        Scheme::RsaVdf => parse::<self::rsa_vdf::RsaVdf>(challenge, solution).map(|_| ()),
+        Scheme::Wesolowski => parse::<wesolowski::Wesolowski>(challenge, solution).map(|_| ()),
+        Scheme::HashChain => parse::<hash_chain::HashChain>(challenge, solution).map(|_| ()),
+    }
+}
+
+/// Converts serialized challenge into protobuf message
+pub fn encode_challenge(
+    scheme: Scheme,
+    challenge: &serde_json::Value,
+) -> Result<ChallengeInput, InvalidSolution> {
+    match scheme {
+        Scheme::RsaVdf => encode::<self::rsa_vdf::RsaVdf>(challenge),
+        Scheme::Wesolowski => encode::<wesolowski::Wesolowski>(challenge),
+        Scheme::HashChain => encode::<hash_chain::HashChain>(challenge),
+    }
+}
+
+/// Parses challenge of given scheme from protobuf message, and serializes it the way challenges
+/// are stored
+pub fn decode_challenge(
+    scheme: Scheme,
+    challenge: &ChallengeInput,
+) -> Result<serde_json::Value, InvalidSolution> {
+    match scheme {
+        Scheme::RsaVdf => decode::<self::rsa_vdf::RsaVdf>(challenge),
+        Scheme::Wesolowski => decode::<wesolowski::Wesolowski>(challenge),
+        Scheme::HashChain => decode::<hash_chain::HashChain>(challenge),
+    }
+}
+
+/// Parses solution of serialized challenge from protobuf message, and serializes it the way
+/// [verify] accepts it
+pub fn decode_solution(
+    scheme: Scheme,
+    challenge: &serde_json::Value,
+    solution: &NativeSolution,
+) -> Result<Vec<u8>, InvalidSolution> {
+    match scheme {
+        Scheme::RsaVdf => decode_native_solution::<self::rsa_vdf::RsaVdf>(challenge, solution),
+        Scheme::Wesolowski => decode_native_solution::<wesolowski::Wesolowski>(challenge, solution),
+        Scheme::HashChain => decode_native_solution::<hash_chain::HashChain>(challenge, solution),
+    }
+}
+
+fn encode<D: DelayChallenge>(
+    challenge: &serde_json::Value,
+) -> Result<ChallengeInput, InvalidSolution> {
+    let challenge: D::Challenge = serde_json::from_value(challenge.clone())
+        .map_err(|_| InvalidSolution::MalformedChallenge)?;
+    D::encode_challenge(&challenge).ok_or(InvalidSolution::MalformedChallenge)
+}
+
+fn decode<D: DelayChallenge>(
+    challenge: &ChallengeInput,
+) -> Result<serde_json::Value, InvalidSolution> {
+    let challenge = D::decode_challenge(challenge).ok_or(InvalidSolution::MalformedChallenge)?;
+    serde_json::to_value(challenge).map_err(|_| InvalidSolution::MalformedChallenge)
+}
+
+fn decode_native_solution<D: DelayChallenge>(
+    challenge: &serde_json::Value,
+    solution: &NativeSolution,
+) -> Result<Vec<u8>, InvalidSolution> {
+    let challenge: D::Challenge = serde_json::from_value(challenge.clone())
+        .map_err(|_| InvalidSolution::MalformedChallenge)?;
+    let solution =
+        D::decode_solution(&challenge, solution).ok_or(InvalidSolution::MalformedSolution)?;
+    serde_json::to_vec(&solution).map_err(|_| InvalidSolution::MalformedSolution)
+}
+
+fn verify_serialized<D: DelayChallenge>(
+    challenge: &serde_json::Value,
+    solution: &[u8],
+) -> Result<(), InvalidSolution> {
+    let (challenge, solution) = parse::<D>(challenge, solution)?;
+    D::verify(&challenge, &solution)
+}
+
+fn parse<D: DelayChallenge>(
+    challenge: &serde_json::Value,
+    solution: &[u8],
+) -> Result<(D::Challenge, D::Solution), InvalidSolution> {
+    let challenge: D::Challenge = serde_json::from_value(challenge.clone())
+        .map_err(|_| InvalidSolution::MalformedChallenge)?;
+    let solution: D::Solution =
+        serde_json::from_slice(solution).map_err(|_| InvalidSolution::MalformedSolution)?;
+    Ok((challenge, solution))
+}
+
+/// Checks that challenge picked by `delay` and its solution survive protobuf encoding. Solutions
+/// are only decoded by Will, so `encode_solution` does what clients do.
+#[cfg(test)]
+pub fn assert_protobuf_roundtrips<D: DelayChallenge>(
+    delay: &D,
+    encode_solution: impl Fn(&D::Solution) -> NativeSolution,
+) {
+    let challenge = DelayChallenge::pick_challenge(delay);
+    let encoded = D::encode_challenge(&challenge).expect("challenge can't be encoded");
+    let decoded = D::decode_challenge(&encoded).expect("challenge can't be decoded");
+    assert_eq!(
+        serde_json::to_value(&decoded).unwrap(),
+        serde_json::to_value(&challenge).unwrap()
+    );
+
+    let solution = D::solve(&challenge);
+    let decoded = D::decode_solution(&challenge, &encode_solution(&solution))
+        .expect("solution can't be decoded");
+    assert_eq!(
+        serde_json::to_value(&decoded).unwrap(),
+        serde_json::to_value(&solution).unwrap()
+    );
+    D::verify(&challenge, &decoded).unwrap();
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidSolution {
+    #[error("challenge is malformed")]
+    MalformedChallenge,
+    #[error("solution is malformed")]
+    MalformedSolution,
+    #[error("solution is incorrect")]
+    Incorrect,
+}
@@ -0,0 +1,77 @@
+//! Claims made of several sequential challenges
+//!
+//! Beneficiary solves challenge of every round, and challenge of the next round is issued only once
+//! the previous one is solved and `interval` has passed. Ping aborts the claim, so beneficiary
+//! starts from the first round again.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::persistent_store::ClaimProgress;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClaimRounds {
+    /// Number of challenges to solve before server share is released
+    pub rounds: u32,
+    /// Minimal time between completing a round and issuing challenge of the next one
+    pub interval: Duration,
+}
+
+impl ClaimRounds {
+    /// Checks whether solving challenge of given round releases server share
+    ///
+    /// Rounds beyond configured number (e.g. if it's been decreased during the claim) are final.
+    pub fn is_final(&self, round: u32) -> bool {
+        round + 1 >= self.rounds
+    }
+
+    /// Returns how long is left until challenge of the next round can be issued
+    pub fn wait_before_next_round(&self, progress: &ClaimProgress, now: u64) -> Duration {
+        let next_round_at = progress
+            .last_round_completed_at
+            .saturating_add(self.interval.as_secs());
+        Duration::from_secs(next_round_at.saturating_sub(now))
+    }
+}
+
+/// Current Unix time in seconds
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_round_is_issued_after_interval() {
+        let rounds = ClaimRounds {
+            rounds: 3,
+            interval: Duration::from_secs(60),
+        };
+        let progress = ClaimProgress {
+            challenge_id: 0,
+            rounds_completed: 1,
+            last_round_completed_at: 1000,
+        };
+        assert_eq!(
+            rounds.wait_before_next_round(&progress, 1010),
+            Duration::from_secs(50)
+        );
+        assert_eq!(
+            rounds.wait_before_next_round(&progress, 1060),
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            rounds.wait_before_next_round(&progress, 2000),
+            Duration::from_secs(0)
+        );
+
+        assert!(!rounds.is_final(0));
+        assert!(!rounds.is_final(1));
+        assert!(rounds.is_final(2));
+        assert!(rounds.is_final(3));
+    }
+}
@@ -0,0 +1,316 @@
+use std::time::Duration;
+
+use curv::arithmetic::{BitManipulation, Converter};
+use curv::BigInt;
+use ring::{hkdf, hmac};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use super::calibration::{self, Difficulty};
+use super::{ChallengeInput, DelayChallenge, InvalidSolution, NativeSolution, Scheme};
+use crate::proto::beneficiary::{VdfChallenge, VdfSetup};
+
+/// VDF in RSA group which parameters are generated at Will setup (see [rsa_vdf] crate)
+pub struct RsaVdf {
+    setup: rsa_vdf::SetupForVDF,
+}
+
+impl RsaVdf {
+    pub fn new(setup: rsa_vdf::SetupForVDF) -> Self {
+        Self { setup }
+    }
+}
+
+/// Current version of `--vdf-params` file format
+const VDF_PARAMS_VERSION: u32 = 1;
+/// Domain separator for derivation of key authenticating `--vdf-params` file
+const MAC_KEY_INFO: &[u8] = b"zengo-will/vdf-params-mac/v1";
+
+/// VDF setup along with configuration it was generated for
+#[derive(Serialize, Deserialize)]
+pub struct VdfParams {
+    pub version: u32,
+    /// Number of squarings
+    pub t: u64,
+    pub modulus_bits: usize,
+    /// Wall-clock delay `t` was calibrated for, if it was derived from `--target-delay`
+    pub target_delay: Option<Duration>,
+    pub setup: rsa_vdf::SetupForVDF,
+}
+
+/// Content of `--vdf-params` file
+///
+/// `params` are kept as raw JSON, so MAC is verified over the exact bytes it was computed over
+/// rather than over params serialized once again.
+#[derive(Serialize, Deserialize)]
+struct VdfParamsFile<P> {
+    params: P,
+    /// HMAC-SHA256 of serialized `params`
+    mac: Vec<u8>,
+}
+
+impl VdfParams {
+    pub fn new(t: u64, target_delay: Option<Duration>, setup: rsa_vdf::SetupForVDF) -> Self {
+        Self {
+            version: VDF_PARAMS_VERSION,
+            t,
+            modulus_bits: setup.N.bit_length(),
+            target_delay,
+            setup,
+        }
+    }
+
+    /// Derives key authenticating params file
+    ///
+    /// Will has no hardware sealing key of its own, so the key is derived from `secret` which is
+    /// only kept in the persistent store, e.g. share encryption key.
+    pub fn mac_key(secret: &[u8]) -> hmac::Key {
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &[]).extract(secret);
+        prk.expand(&[MAC_KEY_INFO], hmac::HMAC_SHA256)
+            .expect("HMAC key length is valid for HKDF-SHA256")
+            .into()
+    }
+
+    /// Serializes params along with their MAC
+    pub fn to_file(&self, key: &hmac::Key) -> serde_json::Result<Vec<u8>> {
+        let serialized = serde_json::to_string(self)?;
+        let mac = hmac::sign(key, serialized.as_bytes()).as_ref().to_vec();
+        let params = RawValue::from_string(serialized)?;
+        serde_json::to_vec(&VdfParamsFile { params, mac })
+    }
+
+    /// Parses params file written by [to_file](Self::to_file), checking its integrity
+    pub fn from_file(bytes: &[u8], key: &hmac::Key) -> Result<Self, InvalidVdfParams> {
+        let file: VdfParamsFile<&RawValue> =
+            serde_json::from_slice(bytes).map_err(InvalidVdfParams::Malformed)?;
+        hmac::verify(key, file.params.get().as_bytes(), &file.mac)
+            .map_err(|_| InvalidVdfParams::Mac)?;
+        let params: VdfParams =
+            serde_json::from_str(file.params.get()).map_err(InvalidVdfParams::Malformed)?;
+        if params.version != VDF_PARAMS_VERSION {
+            return Err(InvalidVdfParams::UnsupportedVersion(params.version));
+        }
+        if params.modulus_bits != params.setup.N.bit_length() {
+            return Err(InvalidVdfParams::MismatchedModulus {
+                recorded: params.modulus_bits,
+                actual: params.setup.N.bit_length(),
+            });
+        }
+        if BigInt::from(params.t) != params.setup.t {
+            return Err(InvalidVdfParams::MismatchedSetup);
+        }
+        Ok(params)
+    }
+
+    /// Checks that params were generated for configured difficulty
+    pub fn check(&self, difficulty: &Difficulty) -> Result<(), InvalidVdfParams> {
+        match *difficulty {
+            Difficulty::Fixed(t) if t != self.t => Err(InvalidVdfParams::MismatchedT {
+                stored: self.t,
+                configured: t,
+            }),
+            Difficulty::Fixed(_) => Ok(()),
+            Difficulty::Calibrated { target_delay, .. }
+                if Some(target_delay) != self.target_delay =>
+            {
+                Err(InvalidVdfParams::MismatchedTargetDelay {
+                    stored: self.target_delay,
+                    configured: target_delay,
+                })
+            }
+            Difficulty::Calibrated {
+                target_delay,
+                steps_per_second: Some(speed),
+            } if calibration::difficulty(target_delay, speed) != self.t => {
+                Err(InvalidVdfParams::MismatchedT {
+                    stored: self.t,
+                    configured: calibration::difficulty(target_delay, speed),
+                })
+            }
+            Difficulty::Calibrated { .. } => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidVdfParams {
+    #[error("file is malformed or has outdated format: {0}")]
+    Malformed(serde_json::Error),
+    #[error("integrity check failed")]
+    Mac,
+    #[error("unsupported version {0}")]
+    UnsupportedVersion(u32),
+    #[error("recorded modulus size ({recorded} bits) doesn't match setup ({actual} bits)")]
+    MismatchedModulus { recorded: usize, actual: usize },
+    #[error("recorded t doesn't match setup")]
+    MismatchedSetup,
+    #[error("params are generated for t={stored}, but t={configured} is configured")]
+    MismatchedT { stored: u64, configured: u64 },
+    #[error("params are generated for target delay {stored:?}, but {configured:?} is configured")]
+    MismatchedTargetDelay {
+        stored: Option<Duration>,
+        configured: Duration,
+    },
+}
+
+impl DelayChallenge for RsaVdf {
+    type Challenge = rsa_vdf::UnsolvedVDF;
+    type Solution = rsa_vdf::SolvedVDF;
+
+    const SCHEME: Scheme = Scheme::RsaVdf;
+
+    fn pick_challenge(&self) -> Self::Challenge {
+        rsa_vdf::SetupForVDF::pick_challenge(&self.setup)
+    }
+
+    fn verify(
+        challenge: &Self::Challenge,
+        solution: &Self::Solution,
+    ) -> Result<(), InvalidSolution> {
+        solution
+            .verify(challenge)
+            .map_err(|_| InvalidSolution::Incorrect)
+    }
+
+    fn encode_challenge(challenge: &Self::Challenge) -> Option<ChallengeInput> {
+        Some(ChallengeInput::Vdf(VdfChallenge {
+            setup: Some(VdfSetup {
+                modulus: challenge.setup.N.to_bytes(),
+                t: to_u64(&challenge.setup.t)?,
+            }),
+            x: challenge.x.to_bytes(),
+        }))
+    }
+
+    fn decode_challenge(challenge: &ChallengeInput) -> Option<Self::Challenge> {
+        match challenge {
+            ChallengeInput::Vdf(VdfChallenge {
+                setup: Some(setup),
+                x,
+            }) => Some(rsa_vdf::UnsolvedVDF {
+                x: BigInt::from_bytes(x),
+                setup: rsa_vdf::SetupForVDF {
+                    t: BigInt::from(setup.t),
+                    N: BigInt::from_bytes(&setup.modulus),
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    fn decode_solution(
+        challenge: &Self::Challenge,
+        solution: &NativeSolution,
+    ) -> Option<Self::Solution> {
+        let solution = match solution {
+            NativeSolution::VdfSolution(solution) => solution,
+            _ => return None,
+        };
+        // `SolvedVDF` doesn't expose its fields, so it's built from its serialized form
+        serde_json::from_value(serde_json::json!({
+            "vdf_instance": challenge,
+            "y": BigInt::from_bytes(&solution.y),
+            "pi": BigInt::from_bytes(&solution.proof),
+        }))
+        .ok()
+    }
+
+    #[cfg(test)]
+    fn solve(challenge: &Self::Challenge) -> Self::Solution {
+        rsa_vdf::UnsolvedVDF::eval(challenge)
+    }
+}
+
+fn to_u64(n: &BigInt) -> Option<u64> {
+    let bytes = n.to_bytes();
+    let mut be_bytes = [0u8; 8];
+    if bytes.len() > be_bytes.len() {
+        return None;
+    }
+    be_bytes[8 - bytes.len()..].copy_from_slice(&bytes);
+    Some(u64::from_be_bytes(be_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delay::assert_protobuf_roundtrips;
+    use crate::proto::beneficiary::VdfSolution;
+
+    #[test]
+    fn params_file_is_authenticated() {
+        let key = VdfParams::mac_key(b"secret");
+        let params = VdfParams::new(
+            10,
+            None,
+            rsa_vdf::SetupForVDF::public_setup(&BigInt::from(10u64)),
+        );
+        let file = params.to_file(&key).unwrap();
+
+        let parsed = VdfParams::from_file(&file, &key).unwrap();
+        assert_eq!(parsed.t, 10);
+        assert_eq!(parsed.modulus_bits, params.modulus_bits);
+
+        let another_key = VdfParams::mac_key(b"another secret");
+        assert!(matches!(
+            VdfParams::from_file(&file, &another_key),
+            Err(InvalidVdfParams::Mac)
+        ));
+
+        // MAC covers params as they're written, regardless of how the rest of the file is laid out
+        let raw: VdfParamsFile<&RawValue> = serde_json::from_slice(&file).unwrap();
+        let reordered = format!(
+            "{{\n  \"mac\": {},\n  \"params\": {}\n}}",
+            serde_json::to_string(&raw.mac).unwrap(),
+            raw.params.get()
+        );
+        assert!(VdfParams::from_file(reordered.as_bytes(), &key).is_ok());
+
+        let mut tampered: serde_json::Value = serde_json::from_slice(&file).unwrap();
+        tampered["params"]["t"] = 11.into();
+        let tampered = serde_json::to_vec(&tampered).unwrap();
+        assert!(matches!(
+            VdfParams::from_file(&tampered, &key),
+            Err(InvalidVdfParams::Mac)
+        ));
+    }
+
+    #[test]
+    fn params_are_checked_against_configuration() {
+        let hour = Duration::from_secs(3600);
+        let params = VdfParams::new(
+            3600,
+            Some(hour),
+            rsa_vdf::SetupForVDF::public_setup(&BigInt::from(3600u64)),
+        );
+
+        assert!(params.check(&Difficulty::Fixed(3600)).is_ok());
+        assert!(params.check(&Difficulty::Fixed(3601)).is_err());
+        let calibrated = |target_delay, steps_per_second| Difficulty::Calibrated {
+            target_delay,
+            steps_per_second,
+        };
+        assert!(params.check(&calibrated(hour, None)).is_ok());
+        assert!(params.check(&calibrated(hour, Some(1))).is_ok());
+        assert!(params.check(&calibrated(hour, Some(2))).is_err());
+        assert!(params.check(&calibrated(hour * 2, None)).is_err());
+    }
+
+    #[test]
+    fn protobuf_encoding_roundtrips() {
+        let delay = RsaVdf::new(rsa_vdf::SetupForVDF::public_setup(&BigInt::from(10u64)));
+        assert_protobuf_roundtrips(&delay, |solution| {
+            // Solution fields are private, but they're exposed via serde
+            let solution = serde_json::to_value(solution).unwrap();
+            let field = |name: &str| -> Vec<u8> {
+                serde_json::from_value::<BigInt>(solution[name].clone())
+                    .unwrap()
+                    .to_bytes()
+            };
+            NativeSolution::VdfSolution(VdfSolution {
+                y: field("y"),
+                proof: field("pi"),
+            })
+        });
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use super::PickChallenge;
+
+/// Scheme issuing challenges along with what beneficiaries are told about it
+pub struct ReadyDelay {
+    pub challenges: Box<dyn PickChallenge>,
+    /// How long solving a challenge is expected to take on reference hardware
+    pub expected_delay: Option<Duration>,
+}
+
+/// Delay scheme which setup might still be running in background
+///
+/// Setup (e.g. generating VDF parameters) might take a long while, so Will serves requests that
+/// don't need to issue challenges in the meantime.
+#[derive(Clone)]
+pub struct DelaySetup {
+    state: Arc<RwLock<State>>,
+}
+
+enum State {
+    InProgress { started: Instant },
+    Ready(Arc<ReadyDelay>),
+    Failed(String),
+}
+
+impl DelaySetup {
+    /// Constructs setup that is in progress
+    pub fn in_progress() -> Self {
+        Self {
+            state: Arc::new(RwLock::new(State::InProgress {
+                started: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn complete(&self, delay: ReadyDelay) {
+        *self.state.write().expect("lock is poisoned") = State::Ready(Arc::new(delay))
+    }
+
+    pub fn fail(&self, reason: String) {
+        *self.state.write().expect("lock is poisoned") = State::Failed(reason)
+    }
+
+    /// Returns set up scheme, or explains why it's not available yet
+    pub fn get(&self) -> Result<Arc<ReadyDelay>, NotReady> {
+        match &*self.state.read().expect("lock is poisoned") {
+            State::Ready(delay) => Ok(delay.clone()),
+            State::InProgress { started } => Err(NotReady::InProgress {
+                elapsed: started.elapsed(),
+            }),
+            State::Failed(reason) => Err(NotReady::Failed(reason.clone())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NotReady {
+    InProgress { elapsed: Duration },
+    Failed(String),
+}
+
+impl fmt::Display for NotReady {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotReady::InProgress { elapsed } => write!(
+                f,
+                "challenge setup is in progress for {}s, retry later",
+                elapsed.as_secs()
+            ),
+            NotReady::Failed(reason) => write!(f, "challenge setup failed: {}", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delay::hash_chain::HashChain;
+
+    #[test]
+    fn setup_becomes_ready_once_completed() {
+        let setup = DelaySetup::in_progress();
+        assert!(matches!(setup.get(), Err(NotReady::InProgress { .. })));
+
+        setup.clone().complete(ReadyDelay {
+            challenges: Box::new(HashChain::new(10)),
+            expected_delay: None,
+        });
+        assert!(setup.get().is_ok());
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::sync::Arc;
+
+use tokio::sync::Semaphore;
+
+use super::{check_format, verify, InvalidSolution, Scheme};
+use crate::persistent_store::Challenge;
+
+/// Solutions larger than that are rejected without parsing
+const MAX_SOLUTION_SIZE: usize = 64 * 1024;
+
+/// Verifies solutions on blocking threads, limiting how many of them are verified at once
+///
+/// Verification is CPU-heavy, running it on async runtime would stall other requests (e.g. pings).
+#[derive(Clone)]
+pub struct Verifier {
+    permits: Arc<Semaphore>,
+}
+
+impl Verifier {
+    pub fn new(max_concurrent_verifications: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent_verifications)),
+        }
+    }
+
+    /// Verifies solution of the challenge
+    ///
+    /// Malformed solutions are rejected before the expensive check. Returns [VerifyError::Busy]
+    /// if too many solutions are being verified at the moment.
+    pub async fn verify(
+        &self,
+        challenge: &Challenge,
+        solution: Vec<u8>,
+    ) -> Result<VerifiedSolution, VerifyError> {
+        if solution.len() > MAX_SOLUTION_SIZE {
+            return Err(VerifyError::Invalid(InvalidSolution::MalformedSolution));
+        }
+        check_format(challenge.scheme, &challenge.challenge, &solution)
+            .map_err(VerifyError::Invalid)?;
+
+        let permit = self
+            .permits
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| VerifyError::Busy)?;
+        let scheme = challenge.scheme;
+        let challenge = challenge.challenge.clone();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            verify(scheme, &challenge, &solution).map(|()| VerifiedSolution { scheme, challenge })
+        })
+        .await
+        .map_err(|_| VerifyError::Aborted)?
+        .map_err(VerifyError::Invalid)
+    }
+}
+
+/// Proof that solution of the challenge was verified
+pub struct VerifiedSolution {
+    scheme: Scheme,
+    challenge: serde_json::Value,
+}
+
+impl VerifiedSolution {
+    /// Checks that it was the given challenge that's been solved
+    pub fn solves(&self, challenge: &Challenge) -> bool {
+        self.scheme == challenge.scheme && self.challenge == challenge.challenge
+    }
+}
+
+#[derive(Debug)]
+pub enum VerifyError {
+    Invalid(InvalidSolution),
+    Busy,
+    Aborted,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyError::Invalid(e) => write!(f, "{}", e),
+            VerifyError::Busy => write!(f, "too many solutions are being verified"),
+            VerifyError::Aborted => write!(f, "verification was aborted"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delay::hash_chain::HashChain;
+    use crate::delay::{DelayChallenge, PickChallenge};
+
+    fn solved_challenge() -> (Challenge, Vec<u8>) {
+        let delay = HashChain::new(1000);
+        let challenge = DelayChallenge::pick_challenge(&delay);
+        let solution = serde_json::to_vec(&HashChain::solve(&challenge)).unwrap();
+        let challenge = Challenge {
+            id: 0,
+            scheme: delay.scheme(),
+            challenge: serde_json::to_value(challenge).unwrap(),
+            round: 0,
+        };
+        (challenge, solution)
+    }
+
+    #[tokio::test]
+    async fn verify_solution() {
+        let (challenge, solution) = solved_challenge();
+        let verified = Verifier::new(1).verify(&challenge, solution).await.unwrap();
+        assert!(verified.solves(&challenge));
+
+        let (another_challenge, _) = solved_challenge();
+        assert!(!verified.solves(&another_challenge));
+    }
+
+    #[tokio::test]
+    async fn reject_malformed_solution_even_if_busy() {
+        let (challenge, _) = solved_challenge();
+        let result = Verifier::new(0).verify(&challenge, b"{}".to_vec()).await;
+        assert!(matches!(
+            result,
+            Err(VerifyError::Invalid(InvalidSolution::MalformedSolution))
+        ));
+    }
+
+    #[tokio::test]
+    async fn refuse_to_verify_when_saturated() {
+        let (challenge, solution) = solved_challenge();
+        let result = Verifier::new(0).verify(&challenge, solution).await;
+        assert!(matches!(result, Err(VerifyError::Busy)));
+    }
+}
@@ -0,0 +1,246 @@
+use curv::arithmetic::{Converter, Modulo, One, Primes, Samplable};
+use curv::BigInt;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+use super::{ChallengeInput, DelayChallenge, InvalidSolution, NativeSolution, Scheme};
+use crate::proto::beneficiary::{VdfChallenge, VdfSetup, VdfSolution};
+
+/// Must be kept in sync with clients
+const HASH_TO_PRIME_CONTEXT: &[u8] = b"zengo-will/wesolowski/v2";
+
+lazy_static::lazy_static! {
+    /// RSA-2048 number from RSA Factoring Challenge. Its factorization is unknown, so the group
+    /// needs no trusted setup.
+    pub(crate) static ref MODULUS: BigInt = BigInt::from_str_radix(
+        "25195908475657893494027183240048398571429282126204032027777137836043662020707595556264018525880784406918290641249515082189298559149176184502808489120072844992687392807287776735971418347270261896375014971824691165077613379859095700097330459748808428401797429100642458691817195118746121515172654632282216869987549182422433637259085141865462043576798423387184774447920739934236584823824281198163815010674810451660377306056201619676256133844143603833904414952634432190114657544454178424020924616515723350778707749817125772467962926386356373289912154831438167899885040445364023527381951378636564391212010397122822120720357",
+        10,
+    )
+    .expect("valid decimal number");
+}
+
+/// Wesolowski VDF in RSA group with fixed public modulus
+///
+/// Unlike [RsaVdf](super::rsa_vdf::RsaVdf) it doesn't need any setup, `t` is the number of
+/// sequential squarings required to solve a challenge.
+///
+/// Works in quotient group `Z_N^* / {±1}`, as `-1` is an element of known order in `Z_N^*`.
+/// Elements are represented by `min(n, N - n)`, and trivial elements (`0` and `1`) are rejected.
+pub struct Wesolowski {
+    t: u64,
+}
+
+impl Wesolowski {
+    pub fn new(t: u64) -> Self {
+        Self { t }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Challenge {
+    pub x: BigInt,
+    pub t: u64,
+}
+
+/// `y = x^(2^t)` and proof `pi = x^floor(2^t / l)`, where `l = hash_to_prime(x, y)`, both in
+/// canonical form
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Solution {
+    pub y: BigInt,
+    pub proof: BigInt,
+}
+
+impl DelayChallenge for Wesolowski {
+    type Challenge = Challenge;
+    type Solution = Solution;
+
+    const SCHEME: Scheme = Scheme::Wesolowski;
+
+    fn pick_challenge(&self) -> Self::Challenge {
+        loop {
+            let x = canonical(&BigInt::sample_below(&MODULUS));
+            if is_nontrivial_element(&x) {
+                return Challenge { x, t: self.t };
+            }
+        }
+    }
+
+    fn verify(
+        challenge: &Self::Challenge,
+        solution: &Self::Solution,
+    ) -> Result<(), InvalidSolution> {
+        if !is_nontrivial_element(&challenge.x)
+            || !is_nontrivial_element(&solution.y)
+            || !is_nontrivial_element(&solution.proof)
+        {
+            return Err(InvalidSolution::Incorrect);
+        }
+        let l = hash_to_prime(&challenge.x, &solution.y);
+        let r = BigInt::mod_pow(&BigInt::from(2u64), &BigInt::from(challenge.t), &l);
+        let expected = BigInt::mod_mul(
+            &BigInt::mod_pow(&solution.proof, &l, &MODULUS),
+            &BigInt::mod_pow(&challenge.x, &r, &MODULUS),
+            &MODULUS,
+        );
+        if canonical(&expected) == solution.y {
+            Ok(())
+        } else {
+            Err(InvalidSolution::Incorrect)
+        }
+    }
+
+    fn encode_challenge(challenge: &Self::Challenge) -> Option<ChallengeInput> {
+        Some(ChallengeInput::Vdf(VdfChallenge {
+            setup: Some(VdfSetup {
+                modulus: MODULUS.to_bytes(),
+                t: challenge.t,
+            }),
+            x: challenge.x.to_bytes(),
+        }))
+    }
+
+    fn decode_challenge(challenge: &ChallengeInput) -> Option<Self::Challenge> {
+        match challenge {
+            ChallengeInput::Vdf(VdfChallenge {
+                setup: Some(setup),
+                x,
+            }) if BigInt::from_bytes(&setup.modulus) == *MODULUS => Some(Challenge {
+                x: BigInt::from_bytes(x),
+                t: setup.t,
+            }),
+            _ => None,
+        }
+    }
+
+    fn decode_solution(
+        _challenge: &Self::Challenge,
+        solution: &NativeSolution,
+    ) -> Option<Self::Solution> {
+        match solution {
+            NativeSolution::VdfSolution(VdfSolution { y, proof }) => Some(Solution {
+                y: BigInt::from_bytes(y),
+                proof: BigInt::from_bytes(proof),
+            }),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    fn solve(challenge: &Self::Challenge) -> Self::Solution {
+        let two = BigInt::from(2u64);
+        let mut y = challenge.x.clone();
+        for _ in 0..challenge.t {
+            y = BigInt::mod_mul(&y, &y, &MODULUS);
+        }
+        let y = canonical(&y);
+        let l = hash_to_prime(&challenge.x, &y);
+
+        // Computes x^floor(2^t / l) by long division of 2^t by l bit by bit
+        let mut proof = BigInt::one();
+        let mut remainder = BigInt::one();
+        for _ in 0..challenge.t {
+            let doubled = &remainder * &two;
+            let bit = doubled >= l;
+            remainder = if bit { doubled - &l } else { doubled };
+            proof = BigInt::mod_mul(&proof, &proof, &MODULUS);
+            if bit {
+                proof = BigInt::mod_mul(&proof, &challenge.x, &MODULUS);
+            }
+        }
+        Solution {
+            y,
+            proof: canonical(&proof),
+        }
+    }
+}
+
+/// Representative of `n`'s class in `Z_N^* / {±1}`, i.e. `min(n, N - n)`
+fn canonical(n: &BigInt) -> BigInt {
+    let negated = &*MODULUS - n;
+    if negated < *n {
+        negated
+    } else {
+        n.clone()
+    }
+}
+
+/// Checks that `n` is canonical representative of an element of `Z_N^* / {±1}` other than `1`
+fn is_nontrivial_element(n: &BigInt) -> bool {
+    *n > BigInt::one() && *n < *MODULUS && canonical(n) == *n
+}
+
+/// Derives 128 bits prime from the challenge and its output (Fiat-Shamir)
+fn hash_to_prime(x: &BigInt, y: &BigInt) -> BigInt {
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    ctx.update(HASH_TO_PRIME_CONTEXT);
+    for n in &[x, y] {
+        let bytes = n.to_bytes();
+        ctx.update(&(bytes.len() as u32).to_le_bytes());
+        ctx.update(&bytes);
+    }
+    BigInt::from_bytes(&ctx.finish().as_ref()[..16]).next_prime()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delay::assert_protobuf_roundtrips;
+
+    #[test]
+    fn solution_is_verified() {
+        let challenge = Wesolowski::new(100).pick_challenge();
+        let solution = Wesolowski::solve(&challenge);
+        Wesolowski::verify(&challenge, &solution).unwrap();
+    }
+
+    #[test]
+    fn forged_solution_is_rejected() {
+        let challenge = Wesolowski::new(100).pick_challenge();
+        let mut solution = Wesolowski::solve(&challenge);
+        solution.proof = BigInt::mod_mul(&solution.proof, &BigInt::from(2u64), &MODULUS);
+        assert!(Wesolowski::verify(&challenge, &solution).is_err());
+
+        let other_challenge = Challenge {
+            t: 101,
+            ..challenge.clone()
+        };
+        let solution = Wesolowski::solve(&challenge);
+        assert!(Wesolowski::verify(&other_challenge, &solution).is_err());
+    }
+
+    #[test]
+    fn negated_and_trivial_elements_are_rejected() {
+        let challenge = Wesolowski::new(100).pick_challenge();
+        let solution = Wesolowski::solve(&challenge);
+
+        let negated = Solution {
+            y: &*MODULUS - &solution.y,
+            ..solution.clone()
+        };
+        assert!(Wesolowski::verify(&challenge, &negated).is_err());
+        let negated = Solution {
+            proof: &*MODULUS - &solution.proof,
+            ..solution.clone()
+        };
+        assert!(Wesolowski::verify(&challenge, &negated).is_err());
+
+        for trivial in &[BigInt::from(0u64), BigInt::one(), &*MODULUS - BigInt::one()] {
+            let challenge = Challenge {
+                x: trivial.clone(),
+                ..challenge.clone()
+            };
+            let solution = Wesolowski::solve(&challenge);
+            assert!(Wesolowski::verify(&challenge, &solution).is_err());
+        }
+    }
+
+    #[test]
+    fn protobuf_encoding_roundtrips() {
+        assert_protobuf_roundtrips(&Wesolowski::new(100), |solution| {
+            NativeSolution::VdfSolution(VdfSolution {
+                y: solution.y.to_bytes(),
+                proof: solution.proof.to_bytes(),
+            })
+        });
+    }
+}
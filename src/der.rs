@@ -0,0 +1,69 @@
+//! Minimal DER reader, just enough to pick fields out of X.509 certificates and CRLs
+
+use std::mem::size_of;
+
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OID: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+pub const UTC_TIME: u8 = 0x17;
+pub const GENERALIZED_TIME: u8 = 0x18;
+/// `[0] EXPLICIT`, e.g. version of certificate
+pub const CONTEXT_0: u8 = 0xa0;
+
+pub struct Element<'a> {
+    pub tag: u8,
+    pub contents: &'a [u8],
+    /// Whole element, including tag and length
+    pub raw: &'a [u8],
+}
+
+/// Splits element at the beginning of `input` from the rest of input
+pub fn read(input: &[u8]) -> Option<(Element, &[u8])> {
+    let (&tag, after_tag) = input.split_first()?;
+    let (&length, mut after_length) = after_tag.split_first()?;
+    let length = if length < 0x80 {
+        usize::from(length)
+    } else {
+        let octets = usize::from(length & 0x7f);
+        if octets == 0 || octets > size_of::<usize>() || after_length.len() < octets {
+            return None;
+        }
+        let (length, rest) = after_length.split_at(octets);
+        after_length = rest;
+        length
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | usize::from(*b))
+    };
+    if after_length.len() < length {
+        return None;
+    }
+    let (contents, rest) = after_length.split_at(length);
+    let element = Element {
+        tag,
+        contents,
+        raw: &input[..input.len() - rest.len()],
+    };
+    Some((element, rest))
+}
+
+/// Reads element at the beginning of `input`, which must be of given `tag`
+pub fn expect(input: &[u8], tag: u8) -> Option<(Element, &[u8])> {
+    read(input).filter(|(element, _)| element.tag == tag)
+}
+
+/// Skips element at the beginning of `input` if it's of given `tag`
+pub fn skip_optional(input: &[u8], tag: u8) -> &[u8] {
+    match expect(input, tag) {
+        Some((_, rest)) => rest,
+        None => input,
+    }
+}
+
+/// Returns fields of X.509 TBSCertificate following the version: serialNumber, signature, issuer,
+/// validity, subject, subjectPublicKeyInfo, ...
+pub fn tbs_certificate_fields(certificate: &[u8]) -> Option<&[u8]> {
+    let (certificate, _) = expect(certificate, SEQUENCE)?;
+    let (tbs, _) = expect(certificate.contents, SEQUENCE)?;
+    Some(skip_optional(tbs.contents, CONTEXT_0))
+}
@@ -0,0 +1,118 @@
+//! Server share distributed across several Wills
+//!
+//! Testator might Shamir-split server share `s` between `n` independent Wills, so that any `k` of
+//! them are required to reconstruct it. Each Will stores its piece `s_i` along with Feldman
+//! commitments to the splitting polynomial computed in base of beneficiary's public share
+//! `Q = G * c`, i.e. `D_j = Q * a_j`. Then `D_0 = Q * s` equals to joint public key, and any Will
+//! can prove that its piece is consistent with the key by checking `Q * s_i == Σ D_j * i^j`.
+
+use serde::{Deserialize, Serialize};
+
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::ECPoint;
+
+use crate::beneficiaries;
+
+/// Piece of server share held by this Will
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EscrowPiece<P: ECPoint> {
+    pub index: u32,
+    pub commitments: VerifiableSS<P>,
+}
+
+impl<P> EscrowPiece<P>
+where
+    P: ECPoint + Clone,
+    P::Scalar: Clone,
+{
+    /// Checks that piece metadata is well-formed and bound to `public_key`
+    pub fn validate(&self, public_key: &P) -> Result<(), InvalidEscrowPiece> {
+        if !beneficiaries::validate_commitments(&self.commitments) {
+            return Err(InvalidEscrowPiece::InvalidCommitments);
+        }
+        if self.index == 0 || self.index as usize > self.commitments.parameters.share_count {
+            return Err(InvalidEscrowPiece::IndexOutOfRange);
+        }
+        if self.commitments.commitments[0] != *public_key {
+            return Err(InvalidEscrowPiece::CommitmentsDontMatchPublicKey);
+        }
+        Ok(())
+    }
+
+    /// Checks that `piece` lies on committed polynomial, given beneficiary's public share
+    pub fn verify_piece(&self, piece: &P::Scalar, beneficiary_public_share: P) -> bool {
+        let public_piece = beneficiary_public_share * piece.clone();
+        self.commitments
+            .validate_share_public(&public_piece, self.index as usize)
+            .is_ok()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidEscrowPiece {
+    #[error("commitments don't describe a valid threshold sharing")]
+    InvalidCommitments,
+    #[error("piece index is out of range")]
+    IndexOutOfRange,
+    #[error("free coefficient commitment doesn't match public key")]
+    CommitmentsDontMatchPublicKey,
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+        ShamirSecretSharing, VerifiableSS,
+    };
+    use curv::elliptic::curves::secp256_k1::{FE, GE};
+    use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+
+    use super::{EscrowPiece, InvalidEscrowPiece};
+
+    /// Splits `server_share` into 2-of-3 pieces committed in base `beneficiary_public_share`
+    fn split(server_share: &FE, beneficiary_public_share: &GE) -> (VerifiableSS<GE>, Vec<FE>) {
+        let polynomial = VerifiableSS::<GE>::sample_polynomial(1, server_share);
+        let pieces = VerifiableSS::<GE>::evaluate_polynomial(&polynomial, &[1, 2, 3]);
+        let commitments = VerifiableSS {
+            parameters: ShamirSecretSharing {
+                threshold: 1,
+                share_count: 3,
+            },
+            commitments: polynomial
+                .iter()
+                .map(|a| *beneficiary_public_share * *a)
+                .collect(),
+        };
+        (commitments, pieces)
+    }
+
+    #[test]
+    fn verify_consistent_piece() {
+        let (client_share, server_share) = (FE::new_random(), FE::new_random());
+        let client_public_share = GE::generator() * client_share;
+        let public_key = client_public_share * server_share;
+        let (commitments, pieces) = split(&server_share, &client_public_share);
+
+        let piece = EscrowPiece {
+            index: 2,
+            commitments,
+        };
+        piece.validate(&public_key).unwrap();
+        assert!(piece.verify_piece(&pieces[1], client_public_share));
+        assert!(!piece.verify_piece(&pieces[0], client_public_share));
+        assert!(!piece.verify_piece(&pieces[1], GE::generator()));
+    }
+
+    #[test]
+    fn reject_piece_bound_to_another_key() {
+        let client_public_share = GE::generator() * FE::new_random();
+        let (commitments, _pieces) = split(&FE::new_random(), &client_public_share);
+        let piece = EscrowPiece {
+            index: 1,
+            commitments,
+        };
+        assert!(matches!(
+            piece.validate(&(GE::generator() * FE::new_random())),
+            Err(InvalidEscrowPiece::CommitmentsDontMatchPublicKey)
+        ));
+    }
+}
@@ -0,0 +1,145 @@
+//! Signed keepalives
+//!
+//! A plain ping proves only possession of a device's TLS key. Testator might additionally register
+//! a liveness key with every share, derived from its wallet share, and sign pings with it. Signed
+//! message includes a nonce issued by Will shortly before the ping, so a signature can't be
+//! replayed, and joint public keys the ping is made for.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use curv::elliptic::curves::traits::ECPoint;
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// How long ping nonce might be used after it's issued
+pub const PING_NONCE_TTL: Duration = Duration::from_secs(5 * 60);
+/// Number of outstanding nonces beyond which no more are issued until some expire or are used
+const MAX_ISSUED_NONCES: usize = 4096;
+
+/// Domain separation prefix of signed ping message
+const PING_CONTEXT: &[u8] = b"zengo-will/ping";
+
+/// Issued ping nonces
+///
+/// Like enrollment tokens, nonces are short-lived and kept in memory only.
+pub struct PingNonces {
+    issued: Mutex<HashMap<[u8; 32], Instant>>,
+    capacity: usize,
+}
+
+impl Default for PingNonces {
+    fn default() -> Self {
+        Self::with_capacity(MAX_ISSUED_NONCES)
+    }
+}
+
+impl PingNonces {
+    /// Constructs nonces of which at most `capacity` might be outstanding
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            issued: Default::default(),
+            capacity,
+        }
+    }
+
+    /// Issues a nonce valid for [PING_NONCE_TTL]
+    pub fn issue(&self) -> Result<[u8; 32], IssueNonceError> {
+        let nonce = random_nonce().map_err(IssueNonceError::Rng)?;
+        let now = Instant::now();
+        let mut issued = self.issued.lock().expect("poisoned");
+        if issued.len() >= self.capacity {
+            issued.retain(|_, expires_at| *expires_at > now);
+            if issued.len() >= self.capacity {
+                return Err(IssueNonceError::TooMany);
+            }
+        }
+        issued.insert(nonce, now + PING_NONCE_TTL);
+        Ok(nonce)
+    }
+
+    /// Uses up the nonce. Returns `false` if nonce is unknown or expired.
+    pub fn redeem(&self, nonce: &[u8]) -> bool {
+        if nonce.len() != 32 {
+            return false;
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(nonce);
+        match self.issued.lock().expect("poisoned").remove(&key) {
+            Some(expires_at) => expires_at > Instant::now(),
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IssueNonceError {
+    #[error("too many nonces are outstanding")]
+    TooMany,
+    #[error("generate nonce: {0}")]
+    Rng(ring::error::Unspecified),
+}
+
+/// Generates a nonce which isn't tracked by [PingNonces], e.g. one bound to a keepalive stream
+pub fn random_nonce() -> Result<[u8; 32], ring::error::Unspecified> {
+    let mut nonce = [0u8; 32];
+    SystemRandom::new().fill(&mut nonce)?;
+    Ok(nonce)
+}
+
+/// Message signed by liveness key: `"zengo-will/ping" || nonce || public_keys[0] || ...`
+pub fn ping_message<P: ECPoint>(nonce: &[u8], public_keys: &[P]) -> Vec<u8> {
+    let mut message = [PING_CONTEXT, nonce].concat();
+    for public_key in public_keys {
+        message.extend_from_slice(&public_key.pk_to_key_slice());
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::elliptic::curves::secp256_k1::{FE, GE};
+    use curv::elliptic::curves::traits::ECScalar;
+
+    use super::*;
+    use crate::schnorr::SchnorrProof;
+
+    #[test]
+    fn signature_is_bound_to_nonce_and_public_keys() {
+        let liveness_secret = FE::new_random();
+        let liveness_key = GE::generator() * liveness_secret;
+        let public_keys = [
+            GE::generator() * FE::new_random(),
+            GE::generator() * FE::new_random(),
+        ];
+
+        let message = ping_message(b"nonce", &public_keys);
+        let proof = SchnorrProof::<GE>::prove(&liveness_secret, &message);
+
+        assert!(proof.verify(&liveness_key, &message));
+        assert!(!proof.verify(&liveness_key, &ping_message(b"other nonce", &public_keys)));
+        assert!(!proof.verify(&liveness_key, &ping_message(b"nonce", &public_keys[..1])));
+    }
+
+    #[test]
+    fn ping_nonce_is_single_use() {
+        let nonces = PingNonces::default();
+        let nonce = nonces.issue().unwrap();
+
+        assert!(nonces.redeem(&nonce));
+        assert!(!nonces.redeem(&nonce));
+        assert!(!nonces.redeem(&[0u8; 32]));
+        assert!(!nonces.redeem(b"short"));
+    }
+
+    #[test]
+    fn outstanding_nonces_are_limited() {
+        let nonces = PingNonces::with_capacity(2);
+        let nonce = nonces.issue().unwrap();
+        nonces.issue().unwrap();
+        assert!(matches!(nonces.issue(), Err(IssueNonceError::TooMany)));
+
+        assert!(nonces.redeem(&nonce));
+        nonces.issue().unwrap();
+    }
+}
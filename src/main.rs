@@ -1,31 +1,90 @@
-use anyhow::{Context, Result};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context, Result};
 
 use futures::future::FutureExt;
 use tokio::fs;
-use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
-use tracing::{info, warn};
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity, Server, ServerTlsConfig};
+use tonic_health::ServingStatus;
+use tower::Layer;
+use tracing::{error, info, warn};
 
 use structopt::StructOpt;
 
+use curv::arithmetic::Converter;
 use curv::elliptic::curves::secp256_k1::GE;
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
 
+use crate::attestation::{Attestor, MockAttestationProvider};
+use crate::audit::ChainVerifier;
+use crate::delay::{
+    calibration::{self, Difficulty},
+    hash_chain::HashChain,
+    rounds::ClaimRounds,
+    rsa_vdf::{RsaVdf, VdfParams},
+    setup::{DelaySetup, ReadyDelay},
+    verifier::Verifier,
+    wesolowski::Wesolowski,
+    Scheme,
+};
+use crate::notifications::email::Email;
+use crate::notifications::file::FileSink;
+use crate::notifications::reminders::Reminders;
+use crate::notifications::webhook::Webhook;
+use crate::notifications::Notifier;
 use crate::persistent_store::{sled::SledDB, PersistentStore};
 use crate::proto::{
     beneficiary::beneficiary_api_server::BeneficiaryApiServer,
+    beneficiary::v2::beneficiary_api_server::BeneficiaryApiServer as BeneficiaryApiV2Server,
+    replication::replication_api_server::ReplicationApiServer,
     testator::testator_api_server::TestatorApiServer,
+    testator::v2::testator_api_server::TestatorApiServer as TestatorApiV2Server,
 };
+use crate::replication::{ReplicatedStore, ReplicationLog, ReplicationServer};
+use crate::revocation::{RevocationSources, RevokedSerials, Serial};
+use crate::share_encryption::{ShareDecryptionKey, TlsKeySigner};
 
+mod attestation;
+mod audit;
+mod beneficiaries;
 mod cli;
+mod delay;
+mod der;
+mod escrow;
+mod liveness;
+mod notifications;
 mod persistent_store;
 mod proto;
+mod rate_limit;
+mod replication;
+mod revocation;
+mod schnorr;
 mod sealed;
 mod server;
+mod share_encryption;
+mod testators;
+
+/// Names of beneficiary API versions in gRPC health service
+const BENEFICIARY_API_SERVICES: [&str; 2] = [
+    "beneficiary.BeneficiaryAPI",
+    "beneficiary.v2.BeneficiaryAPI",
+];
+/// How often it's checked whether liveness reminder is due
+const INACTIVITY_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
     let args: cli::App = StructOpt::from_args();
 
+    match args.command {
+        Some(cli::Command::Calibrate(args)) => return calibrate(args),
+        Some(cli::Command::RevokeTestatorCert(args)) => return revoke_testator_cert(args).await,
+        Some(cli::Command::Audit(cli::Audit::Verify(args))) => return audit_verify(args).await,
+        None => (),
+    }
+
     if args.insecure {
         warn!("Running in insecure mode")
     }
@@ -37,25 +96,75 @@ async fn main() -> anyhow::Result<()> {
             .serialize_pem()
             .context("serialize self signed certificate")?;
         let key = certificate.serialize_private_key_pem();
-        Some(Identity::from_pem(cert, key))
+        Some((cert.into_bytes(), key.into_bytes()))
     } else {
         match (args.cert, args.key) {
             (Some(cert), Some(key)) => {
                 let cert = fs::read(cert).await.context("read server certificate")?;
                 let key = fs::read(key).await.context("read server private key")?;
-                Some(Identity::from_pem(cert, key))
+                Some((cert, key))
             }
             _ => None,
         }
     };
-
-    let testator_ca = match args.testator_ca {
-        Some(testator_ca) => {
-            let testator_ca = fs::read(testator_ca).await.context("read testator ca")?;
-            Some(Certificate::from_pem(testator_ca))
+    let tls_key_signer = match &server_identity {
+        Some((_cert, key)) => {
+            Some(TlsKeySigner::from_pem(key).context("parse server private key")?)
         }
         None => None,
     };
+    let server_certificate = match &server_identity {
+        Some((cert, _key)) => Some(
+            pem::parse(cert)
+                .context("parse server certificate")?
+                .contents,
+        ),
+        None => None,
+    };
+    let server_identity_pem = server_identity.clone();
+    let server_identity = server_identity.map(|(cert, key)| Identity::from_pem(cert, key));
+
+    let testator_ca_pem = match args.testator_ca {
+        Some(testator_ca) => Some(fs::read(testator_ca).await.context("read testator ca")?),
+        None => None,
+    };
+    let testator_ca = testator_ca_pem.clone().map(Certificate::from_pem);
+    let beneficiary_ca = match args.beneficiary_ca {
+        Some(beneficiary_ca) => Some(Certificate::from_pem(
+            fs::read(beneficiary_ca)
+                .await
+                .context("read beneficiary ca")?,
+        )),
+        None => None,
+    };
+
+    let revoked_testator_certs =
+        if !args.testator_crl.is_empty() || args.testator_revoked_serials.is_some() {
+            let testator_ca_pem = testator_ca_pem
+                .as_ref()
+                .expect("guaranteed by cli: revocation requires testator ca");
+            let sources = RevocationSources::new(
+                args.testator_crl,
+                args.testator_revoked_serials,
+                testator_ca_pem,
+            )
+            .context("construct revocation sources")?;
+            let revoked = RevokedSerials::default();
+            revoked.replace(
+                sources
+                    .load()
+                    .await
+                    .context("load revoked testator certificates")?,
+            );
+            tokio::spawn(revocation::reload_periodically(
+                sources,
+                revoked.clone(),
+                args.revocation_reload_interval,
+            ));
+            Some(revoked)
+        } else {
+            None
+        };
 
     let beneficiary_addr = format!("0.0.0.0:{}", args.beneficiary_api_port)
         .parse()
@@ -64,85 +173,477 @@ async fn main() -> anyhow::Result<()> {
         .parse()
         .context("construct testator addr")?;
 
-    let vdf_setup = if args.vdf_params.is_some()
-        && args
-            .vdf_params
-            .as_ref()
-            .map(|p| p.exists())
-            .unwrap_or(false)
-    {
-        info!("Using cached VDF params");
-        let vdf_params = fs::read(
-            args.vdf_params
-                .as_ref()
-                .expect("guaranteed by wrapping if-expression"),
-        )
-        .await
-        .context("read vdf parameters from file")?;
-        serde_json::from_slice(&vdf_params).context("parse vdf params from file")?
-    } else {
-        info!("Computing VDF parameters, this might take a while");
-        let vdf_setup = rsa_vdf::SetupForVDF::public_setup(&args.t.into());
-        info!("VDF parameters are ready");
-        if let Some(vdf_path) = args.vdf_params.as_ref() {
-            let vdf_params =
-                serde_json::to_vec(&vdf_setup).context("serialize vdf setup params")?;
-            fs::write(vdf_path, vdf_params)
-                .await
-                .context("save vdf setup params to file")?
-        }
-        vdf_setup
+    let persistent_store = match args.persistent_store {
+        Some(path) => path,
+        None => bail!("--persistent-store is required"),
     };
-
-    if let Some(dir) = args.persistent_store.parent() {
+    if let Some(dir) = persistent_store.parent() {
         fs::create_dir_all(dir)
             .await
             .context("create parent dir for persistent store")?
     }
 
-    let store = SledDB::<GE>::open(args.persistent_store)
+    let store = SledDB::<GE>::open(persistent_store)
         .await
         .context("open persistent store")?;
-    let beneficiary_server = server::BeneficiaryServer::new(vdf_setup, store.clone());
-    let testator_server = server::TestatorServer::new(store);
 
-    let mut beneficiary_server_builder = match server_identity.clone() {
-        Some(server_identity) => Server::builder()
-            .tls_config(ServerTlsConfig::new().identity(server_identity.clone()))
-            .context("set TLS config")?,
-        None => Server::builder(),
+    if let Some(primary) = args.replicate_from {
+        let primary = Endpoint::from_shared(primary).context("invalid primary url")?;
+        let primary = match (server_identity, args.primary_ca) {
+            (Some(identity), Some(primary_ca)) => {
+                let primary_ca = fs::read(primary_ca).await.context("read primary ca")?;
+                let config = ClientTlsConfig::new()
+                    .ca_certificate(Certificate::from_pem(primary_ca))
+                    .identity(identity);
+                let config = match args.primary_hostname {
+                    Some(hostname) => config.domain_name(hostname),
+                    None => config,
+                };
+                primary.tls_config(config).context("set TLS config")?
+            }
+            _ if args.insecure => primary,
+            _ => bail!("standby requires --cert, --key and --primary-ca to authenticate"),
+        };
+        info!("Running as standby. Restart without --replicate-from to promote it.");
+        futures::select! {
+            _ = replication::follow::<_, GE>(primary, store).fuse() => (),
+            _ = tokio::signal::ctrl_c().fuse() => warn!("Execution terminated by Ctrl-C"),
+        }
+        return Ok(());
+    }
+
+    let sync_standbys = args.sync_standbys;
+    if args.replication_port.is_some() {
+        ensure!(
+            sync_standbys > 0,
+            "--sync-standbys must be at least 1, otherwise acknowledged pings might be lost on failover"
+        );
+    }
+    let replication_log = args
+        .replication_port
+        .map(|_| Arc::new(ReplicationLog::new(sync_standbys)));
+    let replication_server = match (args.replication_port, &replication_log) {
+        (Some(port), Some(log)) => {
+            let addr = format!("0.0.0.0:{}", port)
+                .parse()
+                .context("construct replication addr")?;
+            let mut builder = match (server_identity.clone(), args.standby_ca) {
+                (Some(server_identity), Some(standby_ca)) => {
+                    let standby_ca = fs::read(standby_ca).await.context("read standby ca")?;
+                    Server::builder()
+                        .tls_config(
+                            ServerTlsConfig::new()
+                                .identity(server_identity)
+                                .client_ca_root(Certificate::from_pem(standby_ca)),
+                        )
+                        .context("set TLS config")?
+                }
+                _ if args.insecure => Server::builder(),
+                _ => bail!("replication requires TLS and --standby-ca to authenticate standbys"),
+            };
+            let server = ReplicationServer::<_, GE>::new(store.clone(), log.clone());
+            Some(
+                builder
+                    .add_service(ReplicationApiServer::new(server))
+                    .serve(addr),
+            )
+        }
+        _ => None,
+    };
+    let store = ReplicatedStore::new(store, replication_log);
+
+    let share_key = store
+        .get_or_generate_share_encryption_key()
+        .await
+        .context("retrieve share encryption key")?;
+    let vdf_params_mac_key = VdfParams::mac_key(&share_key.to_big_int().to_bytes());
+    let share_key = ShareDecryptionKey::<GE>::from_secret(share_key);
+    let tls_key_signer = tls_key_signer.map(Arc::new);
+    let share_key_signature = match &tls_key_signer {
+        Some(signer) => signer
+            .sign(&share_key.public_key().pk_to_key_slice())
+            .map_err(|_| anyhow::anyhow!("sign share encryption key"))?,
+        None => {
+            warn!("Share encryption key is not bound to TLS certificate");
+            vec![]
+        }
+    };
+
+    let difficulty = match (args.t, args.target_delay) {
+        (Some(t), _) => Difficulty::Fixed(t),
+        (None, Some(target_delay)) => Difficulty::Calibrated {
+            target_delay,
+            steps_per_second: args.squarings_per_second,
+        },
+        (None, None) => bail!("either -t or --target-delay must be set"),
+    };
+    if args.claim_rounds == 0 {
+        bail!("--claim-rounds must be at least 1")
+    }
+    let claim_rounds = ClaimRounds {
+        rounds: args.claim_rounds,
+        interval: args.claim_round_interval,
+    };
+    let cached_vdf_params = match &args.vdf_params {
+        Some(path)
+            if args.delay_scheme == Scheme::RsaVdf
+                && path.exists()
+                && !args.regenerate_vdf_params =>
+        {
+            info!("Using cached VDF params");
+            let vdf_params = fs::read(path)
+                .await
+                .context("read vdf parameters from file")?;
+            let vdf_params = VdfParams::from_file(&vdf_params, &vdf_params_mac_key)
+                .and_then(|params| params.check(&difficulty).map(|()| params))
+                .context(
+                    "cached VDF params are rejected, \
+                     pass --regenerate-vdf-params to regenerate them",
+                )?;
+            Some(vdf_params)
+        }
+        _ => None,
+    };
+    let delay_scheme = args.delay_scheme;
+    let vdf_params_path = args.vdf_params;
+    let set_up_delay = move || match delay_scheme {
+        Scheme::RsaVdf => {
+            let vdf_params = match cached_vdf_params {
+                Some(vdf_params) => vdf_params,
+                None => {
+                    let t = difficulty.resolve(Scheme::RsaVdf);
+                    info!("Computing VDF parameters, this might take a while");
+                    let setup = rsa_vdf::SetupForVDF::public_setup(&t.into());
+                    info!("VDF parameters are ready");
+                    let vdf_params = VdfParams::new(t, difficulty.target_delay(), setup);
+                    if let Some(path) = vdf_params_path {
+                        let saved = vdf_params
+                            .to_file(&vdf_params_mac_key)
+                            .context("serialize vdf setup params")
+                            .and_then(|serialized| {
+                                std::fs::write(path, serialized)
+                                    .context("save vdf setup params to file")
+                            });
+                        if let Err(e) = saved {
+                            warn!("VDF params are not cached: {:#}", e)
+                        }
+                    }
+                    vdf_params
+                }
+            };
+            ReadyDelay {
+                challenges: Box::new(RsaVdf::new(vdf_params.setup)),
+                expected_delay: vdf_params.target_delay,
+            }
+        }
+        scheme @ Scheme::Wesolowski => ReadyDelay {
+            challenges: Box::new(Wesolowski::new(difficulty.resolve(scheme))),
+            expected_delay: difficulty.target_delay(),
+        },
+        scheme @ Scheme::HashChain => ReadyDelay {
+            challenges: Box::new(HashChain::new(difficulty.resolve(scheme))),
+            expected_delay: difficulty.target_delay(),
+        },
+    };
+
+    // Testator API doesn't depend on challenges, so it's started right away. Beneficiary API
+    // reports that it's not serving until setup is done.
+    let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
+    for service in &BENEFICIARY_API_SERVICES {
+        health_reporter
+            .set_service_status(service, ServingStatus::NotServing)
+            .await;
+    }
+    let delay_setup = DelaySetup::in_progress();
+    let setup = tokio::task::spawn_blocking(set_up_delay);
+    tokio::spawn({
+        let delay_setup = delay_setup.clone();
+        async move {
+            match setup.await {
+                Ok(delay) => {
+                    info!("Issuing {} challenges", delay.challenges.scheme());
+                    delay_setup.complete(delay);
+                    for service in &BENEFICIARY_API_SERVICES {
+                        health_reporter
+                            .set_service_status(service, ServingStatus::Serving)
+                            .await;
+                    }
+                }
+                Err(e) => {
+                    error!("Challenge setup failed: {}", e);
+                    delay_setup.fail(e.to_string());
+                }
+            }
+        }
+    });
+
+    let attestor = match (args.attestation, server_certificate) {
+        (Some(cli::AttestationKind::Mock), Some(certificate)) => {
+            warn!("Using mock attestation, it must not be used in production");
+            let provider = MockAttestationProvider::measure_current_exe()
+                .context("measure Will executable")?;
+            info!(
+                "Mock attestation measurement: {}",
+                provider
+                    .measurement()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>()
+            );
+            Some(Arc::new(Attestor::new(
+                Box::new(provider),
+                &certificate,
+                share_key.public_key().pk_to_key_slice(),
+            )))
+        }
+        (Some(_), None) => bail!("attestation requires TLS to be configured"),
+        (None, _) => None,
+    };
+
+    let challenge_events = server::ChallengeEvents::default();
+    let beneficiary_server = server::BeneficiaryServer::new(
+        delay_setup,
+        claim_rounds,
+        Verifier::new(args.max_concurrent_verifications),
+        store.clone(),
+        attestor.clone(),
+    )
+    .with_challenge_events(challenge_events.clone());
+    let testator_server =
+        server::TestatorServer::new(store.clone(), share_key, share_key_signature, attestor)
+            .with_challenge_events(challenge_events)
+            .with_keepalive_interval(args.keepalive_interval);
+    let coalesced_pings = if args.coalesce_pings {
+        let pings = server::CoalescedPings::default();
+        // Flushed through the replicated store, so coalesced pings reach standbys
+        tokio::spawn(
+            pings
+                .clone()
+                .persist_periodically::<_, GE>(store.clone(), args.ping_persist_interval),
+        );
+        Some(pings)
+    } else {
+        None
+    };
+    let (beneficiary_server, testator_server) = match &coalesced_pings {
+        Some(pings) => (
+            beneficiary_server.with_coalesced_pings(pings.clone()),
+            testator_server.with_coalesced_pings(pings.clone()),
+        ),
+        None => (beneficiary_server, testator_server),
+    };
+    let testator_server = match tls_key_signer {
+        Some(signer) => testator_server.with_checkpoint_signer(signer),
+        None => testator_server,
+    };
+    let testator_server = if args.require_testator_enrollment {
+        testator_server.with_required_enrollment()
+    } else {
+        testator_server
+    };
+    let testator_server = if args.require_signed_pings {
+        testator_server.with_required_ping_signatures()
+    } else {
+        testator_server
+    };
+    let beneficiary_server = if args.require_beneficiary_auth {
+        beneficiary_server.with_required_auth()
+    } else {
+        beneficiary_server
+    };
+    let mut limits = server::BeneficiaryLimits::default();
+    if let Some(budget) = args.beneficiary_rate_limit_per_peer {
+        limits = limits.per_peer(budget);
+    }
+    if let Some(budget) = args.beneficiary_rate_limit_per_key {
+        limits = limits.per_key(budget);
+    }
+    if let Some(max_failures) = args.claim_lockout_after {
+        limits = limits.claim_lockout(max_failures, args.claim_lockout_duration);
+    }
+    let limits = server::BeneficiaryLimitsLayer::<GE>::new(Arc::new(limits));
+
+    let mut notifier = Notifier::<_, GE>::new(store.clone());
+    if let Some(url) = &args.notify_webhook {
+        let secret_path = args
+            .notify_webhook_secret
+            .as_ref()
+            .expect("guaranteed by cli: webhook requires secret");
+        let secret = fs::read(secret_path).await.context("read webhook secret")?;
+        let tls = match &args.notify_webhook_ca {
+            Some(ca) => {
+                let ca = fs::read(ca).await.context("read webhook ca")?;
+                let mut tls_config = rustls::ClientConfig::new();
+                let (valid, _invalid) = tls_config
+                    .root_store
+                    .add_pem_file(&mut &ca[..])
+                    .map_err(|()| anyhow::anyhow!("parse webhook ca"))?;
+                ensure!(valid > 0, "webhook ca doesn't contain valid certificates");
+                Some(Arc::new(tls_config))
+            }
+            None => None,
+        };
+        // Trailing newline isn't part of the secret
+        let secret_len = secret
+            .iter()
+            .rposition(|b| !b.is_ascii_whitespace())
+            .map_or(0, |i| i + 1);
+        let webhook = Webhook::new(url, &secret[..secret_len], tls).context("set up webhook")?;
+        notifier = notifier.with_sink("webhook", webhook);
+    }
+    if let Some(relay) = args.notify_smtp_relay {
+        let from = args
+            .notify_email_from
+            .expect("guaranteed by cli: email requires sender");
+        let to = args.notify_email_to;
+        let email = match &args.notify_smtp_ca {
+            Some(ca) => {
+                let ca = fs::read(ca).await.context("read smtp ca")?;
+                let mut tls_config = rustls::ClientConfig::new();
+                let (valid, _invalid) = tls_config
+                    .root_store
+                    .add_pem_file(&mut &ca[..])
+                    .map_err(|()| anyhow::anyhow!("parse smtp ca"))?;
+                ensure!(valid > 0, "smtp ca doesn't contain valid certificates");
+                Email::new(relay, from, to, Arc::new(tls_config))
+            }
+            None if args.notify_smtp_plaintext => Email::plaintext(relay, from, to),
+            None => {
+                bail!("--notify-smtp-relay requires --notify-smtp-ca or --notify-smtp-plaintext")
+            }
+        };
+        notifier = notifier.with_sink("email", email);
+    }
+    if let Some(path) = args.notify_file {
+        let sink = if path.as_os_str() == "-" {
+            FileSink::Stdout
+        } else {
+            FileSink::File(path)
+        };
+        notifier = notifier.with_sink("file", sink);
+    }
+    let (beneficiary_server, testator_server) = if notifier.is_enabled() {
+        tokio::spawn(notifier.clone().deliver_queued());
+        let testator_server = if let Some(deadline) = args.inactivity_deadline {
+            for step in &args.liveness_reminders {
+                for sink in step.sinks.iter().flatten() {
+                    ensure!(
+                        notifier.sink_names().any(|name| name == sink.as_str()),
+                        "liveness reminder refers to sink `{}` which isn't configured",
+                        sink
+                    );
+                }
+            }
+            let reminders = Reminders::new(notifier.clone(), deadline, args.liveness_reminders);
+            let reminders = match &coalesced_pings {
+                Some(pings) => reminders.with_coalesced_pings(pings.clone()),
+                None => reminders,
+            };
+            tokio::spawn(reminders.clone().run(INACTIVITY_CHECK_INTERVAL));
+            testator_server.with_reminders(reminders)
+        } else {
+            testator_server
+        };
+        (beneficiary_server.with_notifier(notifier), testator_server)
+    } else {
+        if args.inactivity_deadline.is_some() {
+            warn!("Liveness reminders aren't sent as no notification sink is configured");
+        }
+        (beneficiary_server, testator_server)
+    };
+    let beneficiary_server = match args.inactivity_deadline {
+        Some(deadline) => beneficiary_server.with_inactivity_deadline(deadline),
+        None => beneficiary_server,
+    };
+    let (beneficiary_server, testator_server) = if args.disable_v1_api {
+        let hook = server::reject_v1_calls();
+        (
+            beneficiary_server.with_deprecation_hook(hook.clone()),
+            testator_server.with_deprecation_hook(hook),
+        )
+    } else {
+        (beneficiary_server, testator_server)
+    };
+
+    let mut beneficiary_server_builder = match (server_identity.clone(), beneficiary_ca) {
+        (Some(server_identity), beneficiary_ca) => {
+            let tls_config = ServerTlsConfig::new().identity(server_identity);
+            let tls_config = match beneficiary_ca {
+                Some(beneficiary_ca) => tls_config.client_ca_root(beneficiary_ca),
+                None => tls_config,
+            };
+            Server::builder()
+                .tls_config(tls_config)
+                .context("set TLS config")?
+        }
+        (None, Some(_)) => bail!("beneficiary CA requires TLS"),
+        (None, None) => Server::builder(),
     };
     let beneficiary_server = beneficiary_server_builder
-        .add_service(BeneficiaryApiServer::new(beneficiary_server))
+        .add_service(limits.layer(BeneficiaryApiServer::with_interceptor(
+            beneficiary_server.clone(),
+            limits.interceptor(),
+        )))
+        .add_service(limits.layer(BeneficiaryApiV2Server::with_interceptor(
+            beneficiary_server,
+            limits.interceptor(),
+        )))
+        .add_service(health_service)
         .serve(beneficiary_addr)
         .fuse();
 
     let mut testator_server_builder = match (server_identity, testator_ca) {
-        (Some(server_identity), Some(testator_ca)) => Server::builder()
-            .tls_config(
-                ServerTlsConfig::new()
-                    .identity(server_identity)
-                    .client_ca_root(testator_ca),
-            )
-            .context("set TLS config")?,
+        (Some(server_identity), Some(testator_ca)) => {
+            let mut tls_config = ServerTlsConfig::new()
+                .identity(server_identity)
+                .client_ca_root(testator_ca);
+            if let (Some(revoked), Some((cert, key)), Some(testator_ca)) = (
+                revoked_testator_certs,
+                &server_identity_pem,
+                &testator_ca_pem,
+            ) {
+                // Tonic can't check revocation on its own, so rustls is configured directly
+                let config = revocation::testator_tls_config(cert, key, testator_ca, revoked)
+                    .context("construct testator TLS config")?;
+                tls_config.rustls_server_config(config);
+            }
+            Server::builder()
+                .tls_config(tls_config)
+                .context("set TLS config")?
+        }
+        _ if revoked_testator_certs.is_some() => {
+            bail!("revocation of testator certificates requires TLS")
+        }
         _ => Server::builder(),
     };
     let testator_server = testator_server_builder
-        .add_service(TestatorApiServer::new(testator_server))
+        .add_service(TestatorApiServer::new(testator_server.clone()))
+        .add_service(TestatorApiV2Server::new(testator_server))
         .serve(testator_addr)
         .fuse();
 
+    let servers_count = if replication_server.is_some() { 3 } else { 2 };
+    let replication_server = async {
+        match replication_server {
+            Some(server) => server.await,
+            None => futures::future::pending().await,
+        }
+    }
+    .fuse();
+
     let ctrl_c = tokio::signal::ctrl_c().fuse();
 
     futures::pin_mut!(beneficiary_server);
     futures::pin_mut!(testator_server);
+    futures::pin_mut!(replication_server);
     futures::pin_mut!(ctrl_c);
 
     info!("Server started. Use Ctrl-C to exit.");
-    for _ in 0u8..2 {
+    for _ in 0u8..servers_count {
         let (which_server, result): (&str, Result<(), tonic::transport::Error>) = futures::select! {
             result = beneficiary_server => ("beneficiary", result),
             result = testator_server => ("testator", result),
+            result = replication_server => ("replication", result),
             _ = ctrl_c => {
                 warn!("Execution terminated by Ctrl-C");
                 break
@@ -150,6 +651,67 @@ async fn main() -> anyhow::Result<()> {
         };
         warn!("{} server terminated: {:?}", which_server, result)
     }
+    if let Some(pings) = coalesced_pings {
+        if let Err(e) = pings.persist::<_, GE>(&store).await {
+            error!("Persist coalesced pings: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+async fn revoke_testator_cert(args: cli::RevokeTestatorCert) -> anyhow::Result<()> {
+    let serial = Serial::from_hex(&args.serial).context("invalid serial number")?;
+    if revocation::add_revoked_serial(&args.revoked_serials, &serial).await? {
+        println!("Certificate {} is revoked", serial);
+    } else {
+        println!("Certificate {} is already revoked", serial);
+    }
+    Ok(())
+}
+
+async fn audit_verify(args: cli::AuditVerify) -> anyhow::Result<()> {
+    const BATCH_SIZE: usize = 1000;
 
+    let store = SledDB::<GE>::open(args.persistent_store)
+        .await
+        .context("open persistent store")?;
+    let mut verifier = ChainVerifier::new(args.checkpoints);
+    let mut from = 0;
+    loop {
+        let records = store
+            .audit_records(from, BATCH_SIZE)
+            .await
+            .context("read audit log")?;
+        for record in &records {
+            verifier.push(record)?;
+        }
+        match records.last() {
+            Some(last) if records.len() == BATCH_SIZE => from = last.seq + 1,
+            _ => break,
+        }
+    }
+    let checkpoint = verifier.finish()?;
+    println!(
+        "Audit log of {} records is intact, checkpoint: {}",
+        checkpoint.length, checkpoint
+    );
+    Ok(())
+}
+
+fn calibrate(args: cli::Calibrate) -> anyhow::Result<()> {
+    eprintln!(
+        "Measuring speed of {} for {:?}",
+        args.delay_scheme, args.duration
+    );
+    let speed = calibration::benchmark(args.delay_scheme, args.duration);
+    println!("Squarings per second: {}", speed);
+    if let Some(target_delay) = args.target_delay {
+        println!(
+            "t for {:?}: {}",
+            target_delay,
+            calibration::difficulty(target_delay, speed)
+        );
+    }
     Ok(())
 }
@@ -0,0 +1,289 @@
+//! Email sink: notifications sent as plain text email through SMTP relay
+//!
+//! Session with relay is upgraded to TLS with STARTTLS, and mail isn't sent if relay doesn't
+//! support it. Plaintext session must be explicitly opted into, e.g. for a local MTA. Relay is
+//! reached without authentication, and is expected to take care of the rest of delivery.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use super::{DeliveryError, Notification, Sink};
+
+/// Replies longer than that are considered malformed
+const MAX_REPLY_LINE: usize = 1024;
+
+pub struct Email {
+    /// Relay address, `host:port`
+    relay: String,
+    from: String,
+    to: Vec<String>,
+    /// `None` if session is plaintext
+    tls: Option<Arc<rustls::ClientConfig>>,
+}
+
+impl Email {
+    /// Constructs sink sending email from `from` to every address in `to` through `relay`
+    /// (`host:port`). Session is upgraded to TLS with `tls` config.
+    pub fn new(
+        relay: String,
+        from: String,
+        to: Vec<String>,
+        tls: Arc<rustls::ClientConfig>,
+    ) -> Self {
+        Self {
+            relay,
+            from,
+            to,
+            tls: Some(tls),
+        }
+    }
+
+    /// Like [new](Self::new), but email is sent in plaintext
+    pub fn plaintext(relay: String, from: String, to: Vec<String>) -> Self {
+        Self {
+            relay,
+            from,
+            to,
+            tls: None,
+        }
+    }
+
+    /// Sends message of `notification` in session that's already greeted
+    async fn transfer<S>(
+        &self,
+        mut smtp: Smtp<S>,
+        notification: &Notification,
+    ) -> Result<(), DeliveryError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        smtp.command(&format!("MAIL FROM:<{}>", self.from), 2)
+            .await?;
+        for to in &self.to {
+            smtp.command(&format!("RCPT TO:<{}>", to), 2).await?;
+        }
+        smtp.command("DATA", 3).await?;
+        smtp.send(&self.message(notification)).await?;
+        smtp.expect(2).await?;
+        // Message is accepted, failure to quit gracefully doesn't matter
+        let _ = smtp.command("QUIT", 2).await;
+        Ok(())
+    }
+
+    fn message(&self, notification: &Notification) -> String {
+        // Lines starting with a dot are escaped, as a single dot ends the message
+        let body = notification
+            .to_string()
+            .lines()
+            .map(|line| {
+                if line.starts_with('.') {
+                    format!(".{}\r\n", line)
+                } else {
+                    format!("{}\r\n", line)
+                }
+            })
+            .collect::<String>();
+        format!(
+            "From: {from}\r\n\
+             To: {to}\r\n\
+             Subject: {subject}\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\
+             \r\n\
+             {body}.\r\n",
+            from = self.from,
+            to = self.to.join(", "),
+            subject = notification.summary(),
+            body = body,
+        )
+    }
+}
+
+#[async_trait]
+impl Sink for Email {
+    async fn deliver(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let stream = TcpStream::connect(&self.relay).await?;
+        let mut smtp = Smtp {
+            stream: BufReader::new(stream),
+        };
+        smtp.expect(2).await?;
+        smtp.command("EHLO zengo-will", 2).await?;
+        let tls = match &self.tls {
+            Some(tls) => tls,
+            None => return self.transfer(smtp, notification).await,
+        };
+
+        smtp.command("STARTTLS", 2).await?;
+        // Anything relay sent before handshake would be taken as sent over TLS
+        if !smtp.stream.buffer().is_empty() {
+            return Err(DeliveryError::Rejected(
+                "relay sent data before TLS handshake".to_string(),
+            ));
+        }
+        let host = self.relay.rsplitn(2, ':').last().unwrap_or_default();
+        let name = webpki::DNSNameRef::try_from_ascii_str(host)
+            .map_err(|_| DeliveryError::Rejected("relay host is not a DNS name".to_string()))?;
+        let stream = tokio_rustls::TlsConnector::from(tls.clone())
+            .connect(name, smtp.stream.into_inner())
+            .await?;
+        let mut smtp = Smtp {
+            stream: BufReader::new(stream),
+        };
+        // Capabilities told before handshake can't be trusted
+        smtp.command("EHLO zengo-will", 2).await?;
+        self.transfer(smtp, notification).await
+    }
+}
+
+struct Smtp<S> {
+    stream: BufReader<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Smtp<S> {
+    async fn command(&mut self, command: &str, class: u8) -> Result<(), DeliveryError> {
+        self.send(&format!("{}\r\n", command)).await?;
+        self.expect(class).await
+    }
+
+    async fn send(&mut self, data: &str) -> Result<(), DeliveryError> {
+        self.stream.get_mut().write_all(data.as_bytes()).await?;
+        Ok(self.stream.get_mut().flush().await?)
+    }
+
+    /// Reads reply, which might span several lines, and checks that its code is `class`xx
+    async fn expect(&mut self, class: u8) -> Result<(), DeliveryError> {
+        loop {
+            let mut line = String::new();
+            let n = self.stream.read_line(&mut line).await?;
+            if n == 0 || n > MAX_REPLY_LINE || line.len() < 4 || !line.is_char_boundary(4) {
+                return Err(DeliveryError::Rejected(format!(
+                    "malformed reply: {:?}",
+                    line
+                )));
+            }
+            if line.as_bytes()[0] != b'0' + class {
+                return Err(DeliveryError::Rejected(line.trim().to_string()));
+            }
+            // Last line of reply is `250 OK`, previous ones are `250-...`
+            if line.as_bytes()[3] != b'-' {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::notifications::Event;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Accepts a single session, and returns commands and message it received
+    async fn serve_once(listener: TcpListener) -> Result<(Vec<String>, String)> {
+        let (stream, _) = listener.accept().await?;
+        let mut stream = BufReader::new(stream);
+        stream
+            .get_mut()
+            .write_all(b"220 localhost ready\r\n")
+            .await?;
+        let mut commands = vec![];
+        let mut message = String::new();
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 {
+                return Err("session ended before QUIT".into());
+            }
+            let command = line.trim_end().to_string();
+            let reply: &[u8] = match command.as_str() {
+                c if c.starts_with("EHLO") => b"250-localhost\r\n250 8BITMIME\r\n",
+                "STARTTLS" => b"454 TLS not available\r\n",
+                "DATA" => {
+                    stream.get_mut().write_all(b"354 go ahead\r\n").await?;
+                    loop {
+                        let mut line = String::new();
+                        stream.read_line(&mut line).await?;
+                        if line == ".\r\n" {
+                            break;
+                        }
+                        message.push_str(&line);
+                    }
+                    b"250 queued\r\n"
+                }
+                "QUIT" => {
+                    stream.get_mut().write_all(b"221 bye\r\n").await?;
+                    commands.push(command);
+                    return Ok((commands, message));
+                }
+                _ => b"250 OK\r\n",
+            };
+            stream.get_mut().write_all(reply).await?;
+            commands.push(command);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let relay = listener.local_addr()?.to_string();
+        let server = tokio::spawn(serve_once(listener));
+
+        let email = Email::plaintext(
+            relay,
+            "will@example.com".to_string(),
+            vec![
+                "testator@example.com".to_string(),
+                "backup@example.com".to_string(),
+            ],
+        );
+        let notification = Notification {
+            at: 10,
+            event: Event::ChallengeIssued { round: 0 },
+        };
+        email.deliver(&notification).await?;
+
+        let (commands, message) = server.await??;
+        assert_eq!(
+            commands,
+            vec![
+                "EHLO zengo-will",
+                "MAIL FROM:<will@example.com>",
+                "RCPT TO:<testator@example.com>",
+                "RCPT TO:<backup@example.com>",
+                "DATA",
+                "QUIT",
+            ]
+        );
+        assert!(message.contains("To: testator@example.com, backup@example.com\r\n"));
+        assert!(message.contains(&format!("Subject: {}\r\n", notification.summary())));
+        assert!(message.ends_with(&format!("\r\n\r\n{}\r\n", notification)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mail_is_not_sent_without_tls() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let relay = listener.local_addr()?.to_string();
+        let server = tokio::spawn(serve_once(listener));
+
+        let email = Email::new(
+            relay,
+            "will@example.com".to_string(),
+            vec!["testator@example.com".to_string()],
+            Arc::new(rustls::ClientConfig::new()),
+        );
+        let notification = Notification {
+            at: 10,
+            event: Event::ChallengeIssued { round: 0 },
+        };
+        let result = email.deliver(&notification).await;
+        assert!(matches!(result, Err(DeliveryError::Rejected(_))));
+        // Session ends right after relay refuses STARTTLS
+        assert!(server.await?.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+//! File sink: notifications appended to a file or written to stdout as JSON lines
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+
+use super::{DeliveryError, Notification, Sink};
+
+pub enum FileSink {
+    Stdout,
+    File(PathBuf),
+}
+
+#[async_trait]
+impl Sink for FileSink {
+    async fn deliver(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let mut line = serde_json::to_vec(notification).expect("notification is serializable");
+        line.push(b'\n');
+        match self {
+            FileSink::Stdout => {
+                let stdout = std::io::stdout();
+                let mut stdout = stdout.lock();
+                stdout.write_all(&line)?;
+                stdout.flush()?;
+            }
+            FileSink::File(path) => {
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?;
+                file.write_all(&line).await?;
+                file.sync_data().await?;
+            }
+        }
+        Ok(())
+    }
+}
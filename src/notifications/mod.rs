@@ -0,0 +1,469 @@
+//! Notifications sent to testator about claims and approaching inactivity deadline
+//!
+//! Every notification is queued in the store once per configured [Sink], and delivered by
+//! [Notifier::deliver_queued]. Failed deliveries are retried with exponential backoff, including
+//! after restart, until [MAX_ATTEMPTS] is reached. Liveness reminders are scheduled by
+//! [Reminders](reminders::Reminders).
+
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use curv::elliptic::curves::traits::ECPoint;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, Notify};
+use tracing::{error, info, warn};
+
+use crate::delay::rounds::unix_time;
+use crate::persistent_store::{DeliveryOutcome, PersistentStore, QueuedNotification, StoreError};
+
+pub mod email;
+pub mod file;
+pub mod reminders;
+pub mod webhook;
+
+/// Number of delivery attempts after which notification is dropped
+pub const MAX_ATTEMPTS: u32 = 10;
+/// Delay before the first retry, doubled on every next one
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Retries are never delayed for longer than that
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// Delivery taking longer than that is considered failed
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Event testator is notified about
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Testator hasn't pinged since `last_ping_at`, and is going to be considered inactive at
+    /// `deadline` (both are Unix time in seconds). It's the `reminder`-th reminder (starting from
+    /// 0) since the ping, which can be acknowledged with `token`.
+    LivenessReminder {
+        last_ping_at: u64,
+        deadline: u64,
+        reminder: u32,
+        token: String,
+    },
+    /// Beneficiary is claiming the share: challenge of `round` (starting from 0) is issued
+    ChallengeIssued { round: u32 },
+    /// Beneficiary solved challenge of a claim round, but more rounds are required
+    ClaimRoundCompleted {
+        rounds_completed: u32,
+        rounds_required: u32,
+    },
+    /// Server share of `public_key` (hex-encoded) is released to beneficiary
+    ShareReleased { public_key: String },
+}
+
+impl Event {
+    pub fn share_released(public_key: &[u8]) -> Self {
+        Event::ShareReleased {
+            public_key: public_key.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+/// Event along with time it happened at
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Notification {
+    /// Unix time (in seconds) the event happened at
+    pub at: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl Notification {
+    /// One-line summary of the event, e.g. email subject
+    pub fn summary(&self) -> &'static str {
+        match self.event {
+            Event::LivenessReminder { .. } => "Inactivity deadline is approaching",
+            Event::ChallengeIssued { .. } => "Beneficiary is claiming your share",
+            Event::ClaimRoundCompleted { .. } => "Beneficiary completed a claim round",
+            Event::ShareReleased { .. } => "Server share is released to beneficiary",
+        }
+    }
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.event {
+            Event::LivenessReminder {
+                last_ping_at,
+                deadline,
+                token,
+                ..
+            } => write!(
+                f,
+                "Will hasn't received a ping since {} (Unix time). You will be considered \
+                 inactive at {} unless your device pings Will or acknowledges this reminder \
+                 with token {}.",
+                last_ping_at, deadline, token
+            ),
+            Event::ChallengeIssued { round } => write!(
+                f,
+                "Beneficiary requested challenge of round {} of the claim. Ping Will to abort \
+                 the claim.",
+                round + 1
+            ),
+            Event::ClaimRoundCompleted {
+                rounds_completed,
+                rounds_required,
+            } => write!(
+                f,
+                "Beneficiary completed {} of {} claim rounds. Ping Will to abort the claim.",
+                rounds_completed, rounds_required
+            ),
+            Event::ShareReleased { public_key } => write!(
+                f,
+                "Server share of public key {} is released to beneficiary.",
+                public_key
+            ),
+        }
+    }
+}
+
+/// Destination notifications are delivered to
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Delivers notification. Returned error means delivery should be retried later.
+    async fn deliver(&self, notification: &Notification) -> Result<(), DeliveryError>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeliveryError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// Recipient's server refused to accept notification
+    #[error("rejected: {0}")]
+    Rejected(String),
+    #[error("timed out")]
+    Timeout,
+}
+
+/// Queues notifications and delivers them to sinks
+pub struct Notifier<S, P> {
+    store: S,
+    sinks: Vec<(&'static str, Arc<dyn Sink>)>,
+    queued: Arc<Notify>,
+    /// Serializes updates of reminder records
+    reminders: Arc<Mutex<()>>,
+    _ph: PhantomData<fn() -> P>,
+}
+
+impl<S: Clone, P> Clone for Notifier<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            sinks: self.sinks.clone(),
+            queued: self.queued.clone(),
+            reminders: self.reminders.clone(),
+            _ph: PhantomData,
+        }
+    }
+}
+
+impl<S, P> Notifier<S, P>
+where
+    S: PersistentStore<P>,
+    P: ECPoint,
+{
+    /// Constructs notifier without sinks, which drops every notification
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            sinks: vec![],
+            queued: Default::default(),
+            reminders: Default::default(),
+            _ph: PhantomData,
+        }
+    }
+
+    /// Adds sink. Notifications queued for it are identified by `name`, so it must be unique and
+    /// stay the same across restarts.
+    pub fn with_sink(mut self, name: &'static str, sink: impl Sink + 'static) -> Self {
+        self.sinks.push((name, Arc::new(sink)));
+        self
+    }
+
+    /// Tells whether notifier has any sinks
+    pub fn is_enabled(&self) -> bool {
+        !self.sinks.is_empty()
+    }
+
+    /// Names of all sinks
+    pub fn sink_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.sinks.iter().map(|(name, _sink)| *name)
+    }
+
+    /// Queues notification about `event` for every sink
+    ///
+    /// Failure to queue notification is logged rather than returned, so it doesn't fail the
+    /// operation that triggered the event.
+    pub async fn notify(&self, event: Event) {
+        let sinks = self.sink_names().collect::<Vec<_>>();
+        self.notify_sinks(event, &sinks).await
+    }
+
+    /// Like [notify](Self::notify), but queues notification only for sinks named in `sinks`
+    pub async fn notify_sinks(&self, event: Event, sinks: &[&str]) {
+        let notification = Notification {
+            at: unix_time(),
+            event,
+        };
+        let serialized = match serde_json::to_value(&notification) {
+            Ok(n) => n,
+            Err(e) => {
+                error!("Serialize notification: {}", e);
+                return;
+            }
+        };
+        for name in self.sink_names().filter(|name| sinks.contains(name)) {
+            let queued = QueuedNotification {
+                sink: name.to_string(),
+                notification: serialized.clone(),
+                attempts: 0,
+                next_attempt_at: notification.at,
+            };
+            if let Err(e) = self.store.enqueue_notification(queued).await {
+                error!(sink = %name, "Queue notification: {}", e);
+            }
+        }
+        self.queued.notify_one();
+    }
+
+    /// Delivers queued notifications as they become due. Never returns.
+    pub async fn deliver_queued(self) {
+        loop {
+            let next_attempt_at = match self.deliver_due().await {
+                Ok(next) => next,
+                Err(e) => {
+                    warn!("Deliver notifications: {}", e);
+                    Some(unix_time() + FIRST_RETRY_DELAY.as_secs())
+                }
+            };
+            let wait = match next_attempt_at {
+                Some(at) => Duration::from_secs(at.saturating_sub(unix_time())),
+                None => MAX_RETRY_DELAY,
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => (),
+                _ = self.queued.notified() => (),
+            }
+        }
+    }
+
+    /// Makes an attempt to deliver every due notification
+    ///
+    /// Returns time of the next attempt, or `None` if the queue is empty.
+    async fn deliver_due(&self) -> Result<Option<u64>, StoreError> {
+        let mut next_attempt_at: Option<u64> = None;
+        for (id, mut queued) in self.store.queued_notifications().await? {
+            let now = unix_time();
+            if queued.next_attempt_at > now {
+                next_attempt_at = Some(
+                    next_attempt_at
+                        .map_or(queued.next_attempt_at, |at| at.min(queued.next_attempt_at)),
+                );
+                continue;
+            }
+            let sink = match self.sinks.iter().find(|(name, _)| *name == queued.sink) {
+                Some((_name, sink)) => sink,
+                None => {
+                    warn!(sink = %queued.sink, "Notification is dropped as sink isn't configured");
+                    self.store.update_queued_notification(id, None).await?;
+                    continue;
+                }
+            };
+            let notification: Notification =
+                match serde_json::from_value(queued.notification.clone()) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        error!(sink = %queued.sink, "Malformed notification is dropped: {}", e);
+                        self.store.update_queued_notification(id, None).await?;
+                        continue;
+                    }
+                };
+
+            let result = tokio::time::timeout(DELIVERY_TIMEOUT, sink.deliver(&notification))
+                .await
+                .unwrap_or(Err(DeliveryError::Timeout));
+            match result {
+                Ok(()) => {
+                    info!(sink = %queued.sink, "Notification delivered");
+                    let outcome = DeliveryOutcome::Delivered { at: unix_time() };
+                    self.record_outcome(&notification, &queued.sink, outcome)
+                        .await?;
+                    self.store.update_queued_notification(id, None).await?;
+                }
+                Err(e) if queued.attempts + 1 >= MAX_ATTEMPTS => {
+                    error!(sink = %queued.sink, "Notification is dropped after {} attempts: {}", MAX_ATTEMPTS, e);
+                    let outcome = DeliveryOutcome::Failed {
+                        at: unix_time(),
+                        error: e.to_string(),
+                    };
+                    self.record_outcome(&notification, &queued.sink, outcome)
+                        .await?;
+                    self.store.update_queued_notification(id, None).await?;
+                }
+                Err(e) => {
+                    warn!(sink = %queued.sink, "Notification delivery failed, will retry: {}", e);
+                    let outcome = DeliveryOutcome::Retrying {
+                        attempts: queued.attempts + 1,
+                        error: e.to_string(),
+                    };
+                    self.record_outcome(&notification, &queued.sink, outcome)
+                        .await?;
+                    queued.next_attempt_at = unix_time() + retry_delay(queued.attempts).as_secs();
+                    queued.attempts += 1;
+                    next_attempt_at = Some(
+                        next_attempt_at
+                            .map_or(queued.next_attempt_at, |at| at.min(queued.next_attempt_at)),
+                    );
+                    self.store
+                        .update_queued_notification(id, Some(queued))
+                        .await?;
+                }
+            }
+        }
+        Ok(next_attempt_at)
+    }
+
+    /// Records outcome of delivering liveness reminder to `sink`. Other notifications aren't
+    /// tracked.
+    async fn record_outcome(
+        &self,
+        notification: &Notification,
+        sink: &str,
+        outcome: DeliveryOutcome,
+    ) -> Result<(), StoreError> {
+        let (last_ping_at, index) = match notification.event {
+            Event::LivenessReminder {
+                last_ping_at,
+                reminder,
+                ..
+            } => (last_ping_at, reminder),
+            _ => return Ok(()),
+        };
+        let _lock = self.reminders.lock().await;
+        let reminder = self
+            .store
+            .list_reminders(last_ping_at)
+            .await?
+            .into_iter()
+            .find(|r| r.index == index);
+        match reminder {
+            Some(mut reminder) => {
+                reminder.deliveries.insert(sink.to_string(), outcome);
+                self.store.save_reminder(reminder).await
+            }
+            None => {
+                warn!(%sink, "Delivered reminder is missing in the store");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Delay before retrying notification that failed `attempts` times
+fn retry_delay(attempts: u32) -> Duration {
+    FIRST_RETRY_DELAY
+        .checked_mul(1 << attempts.min(16))
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use curv::elliptic::curves::secp256_k1::GE;
+
+    use super::*;
+    use crate::persistent_store::sled::SledDB;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    /// Sink failing given number of deliveries before accepting them
+    struct FlakySink {
+        failures: usize,
+        attempts: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Sink for FlakySink {
+        async fn deliver(
+            &self,
+            _notification: &Notification,
+        ) -> std::result::Result<(), DeliveryError> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst);
+            if attempt < self.failures {
+                Err(DeliveryError::Rejected("unavailable".to_string()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_failed_delivery() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = SledDB::<GE>::open(dir.path().join("store")).await?;
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let notifier = Notifier::new(store.clone()).with_sink(
+            "flaky",
+            FlakySink {
+                failures: 1,
+                attempts: attempts.clone(),
+            },
+        );
+
+        notifier.notify(Event::ChallengeIssued { round: 0 }).await;
+        let next_attempt_at = notifier.deliver_due().await?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        let queued = store.queued_notifications().await?;
+        assert_eq!(queued.len(), 1);
+        let (id, queued) = queued.into_iter().next().unwrap();
+        assert_eq!(queued.attempts, 1);
+        assert_eq!(next_attempt_at, Some(queued.next_attempt_at));
+
+        // Retry isn't due yet
+        notifier.deliver_due().await?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        store
+            .update_queued_notification(
+                id,
+                Some(QueuedNotification {
+                    next_attempt_at: 0,
+                    ..queued
+                }),
+            )
+            .await?;
+        assert_eq!(notifier.deliver_due().await?, None);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(store.queued_notifications().await?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn retry_delay_grows_up_to_limit() {
+        assert_eq!(retry_delay(0), FIRST_RETRY_DELAY);
+        assert_eq!(retry_delay(1), FIRST_RETRY_DELAY * 2);
+        assert_eq!(retry_delay(MAX_ATTEMPTS), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn serialize_notification() {
+        let notification = Notification {
+            at: 10,
+            event: Event::share_released(&[0xab, 0x01]),
+        };
+        assert_eq!(
+            serde_json::to_value(&notification).unwrap(),
+            serde_json::json!({"at": 10, "event": "share_released", "public_key": "ab01"})
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! Liveness reminders sent to testator before the inactivity deadline
+//!
+//! Reminders follow a schedule of steps, e.g. 30, 7 and 1 day before the deadline. Every step
+//! might be sent to its own sinks, so later reminders escalate to more channels. Outcome of every
+//! delivery is recorded in the store along with the reminder. Reminder carries a token, and
+//! acknowledging it counts as a ping.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use curv::elliptic::curves::traits::ECPoint;
+use ring::constant_time::verify_slices_are_equal;
+use tracing::{info, warn};
+
+use crate::delay::rounds::unix_time;
+use crate::liveness::random_nonce;
+use crate::persistent_store::{DeliveryOutcome, PersistentStore, Reminder, StoreError};
+use crate::server::CoalescedPings;
+
+use super::{Event, Notifier};
+
+/// Step of reminders schedule
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReminderStep {
+    /// How long before the deadline reminder is sent
+    pub before: Duration,
+    /// Sinks reminder is sent to, or `None` for all of them
+    pub sinks: Option<Vec<String>>,
+}
+
+impl FromStr for ReminderStep {
+    type Err = InvalidReminderStep;
+
+    /// Parses `<before>` or `<before>:<sink>,<sink>,...`, e.g. `1d:email,webhook`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let before = parts.next().ok_or(InvalidReminderStep)?;
+        let before = parse_duration::parse(before.trim()).map_err(|_| InvalidReminderStep)?;
+        let sinks = match parts.next() {
+            Some(sinks) => {
+                let sinks = sinks
+                    .split(',')
+                    .map(|sink| sink.trim().to_string())
+                    .collect::<Vec<_>>();
+                if sinks.iter().any(String::is_empty) {
+                    return Err(InvalidReminderStep);
+                }
+                Some(sinks)
+            }
+            None => None,
+        };
+        Ok(Self { before, sinks })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("reminder must be `<before deadline>[:<sink>,...]`, e.g. `1d:email,webhook`")]
+pub struct InvalidReminderStep;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SendReminderError {
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error("generate token: {0}")]
+    Rng(ring::error::Unspecified),
+}
+
+/// Sends reminders following the schedule, and accepts their acknowledgements
+pub struct Reminders<S, P> {
+    notifier: Notifier<S, P>,
+    deadline: Duration,
+    steps: Vec<ReminderStep>,
+    pings: Option<CoalescedPings>,
+}
+
+impl<S: Clone, P> Clone for Reminders<S, P> {
+    fn clone(&self) -> Self {
+        Self {
+            notifier: self.notifier.clone(),
+            deadline: self.deadline,
+            steps: self.steps.clone(),
+            pings: self.pings.clone(),
+        }
+    }
+}
+
+impl<S, P> Reminders<S, P>
+where
+    S: PersistentStore<P>,
+    P: ECPoint,
+{
+    /// Constructs reminders of testator being considered inactive once no ping is received for
+    /// `deadline`. Steps might go in any order.
+    pub fn new(notifier: Notifier<S, P>, deadline: Duration, mut steps: Vec<ReminderStep>) -> Self {
+        // The earliest reminder goes first
+        steps.sort_by(|a, b| b.before.cmp(&a.before));
+        Self {
+            notifier,
+            deadline,
+            steps,
+            pings: None,
+        }
+    }
+
+    /// Takes pings coalesced by [TestatorServer](crate::server::TestatorServer) into account, so
+    /// reminders don't count from the latest persisted ping
+    pub fn with_coalesced_pings(self, pings: CoalescedPings) -> Self {
+        Self {
+            pings: Some(pings),
+            ..self
+        }
+    }
+
+    async fn last_ping_time(&self) -> Result<Option<u64>, StoreError> {
+        match &self.pings {
+            Some(pings) => pings.last_ping_time(&self.notifier.store).await,
+            None => self.notifier.store.get_last_ping_time().await,
+        }
+    }
+
+    /// Sends reminders as they become due, checking every `interval`. Never returns.
+    pub async fn run(self, interval: Duration) {
+        loop {
+            if let Err(e) = self.send_due(unix_time()).await {
+                warn!("Send liveness reminder: {}", e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Sends reminder of the latest step that is due at `now`, unless it or a later one was
+    /// already sent since the last ping
+    ///
+    /// Earlier steps that became due meanwhile (e.g. while Will was down) are skipped, so testator
+    /// doesn't receive a burst of reminders.
+    async fn send_due(&self, now: u64) -> Result<(), SendReminderError> {
+        let last_ping_at = match self.last_ping_time().await? {
+            Some(at) => at,
+            // Testator never pinged, there's no deadline yet
+            None => return Ok(()),
+        };
+        let deadline = match last_ping_at.checked_add(self.deadline.as_secs()) {
+            Some(deadline) => deadline,
+            // Deadline is never reached
+            None => return Ok(()),
+        };
+        let due = self
+            .steps
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| {
+                // Step that far before the deadline is overdue
+                now.checked_add(step.before.as_secs())
+                    .map_or(true, |at| at >= deadline)
+            })
+            .last();
+        let (index, step) = match due {
+            Some((index, step)) => (index as u32, step),
+            None => return Ok(()),
+        };
+
+        let _lock = self.notifier.reminders.lock().await;
+        let sent = self.notifier.store.list_reminders(last_ping_at).await?;
+        if sent.iter().any(|reminder| reminder.index >= index) {
+            return Ok(());
+        }
+
+        let sinks = match &step.sinks {
+            Some(sinks) => sinks.iter().map(String::as_str).collect(),
+            None => self.notifier.sink_names().collect::<Vec<_>>(),
+        };
+        let token = random_nonce()
+            .map_err(SendReminderError::Rng)?
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let reminder = Reminder {
+            last_ping_at,
+            index,
+            sent_at: now,
+            token: token.clone(),
+            acknowledged_at: None,
+            deliveries: sinks
+                .iter()
+                .map(|sink| (sink.to_string(), DeliveryOutcome::Pending))
+                .collect(),
+        };
+        // Reminder is saved before it's queued, so its delivery outcomes can be recorded
+        self.notifier.store.save_reminder(reminder).await?;
+        info!(reminder = index, ?sinks, "Sending liveness reminder");
+        self.notifier
+            .notify_sinks(
+                Event::LivenessReminder {
+                    last_ping_at,
+                    deadline,
+                    reminder: index,
+                    token,
+                },
+                &sinks,
+            )
+            .await;
+        Ok(())
+    }
+
+    /// Marks reminder sent since the last ping as acknowledged. Returns `false` if `token`
+    /// doesn't match any of them.
+    ///
+    /// Caller is expected to count acknowledgement as a ping.
+    pub async fn acknowledge(&self, token: &str) -> Result<bool, StoreError> {
+        let last_ping_at = match self.last_ping_time().await? {
+            Some(at) => at,
+            None => return Ok(false),
+        };
+        let _lock = self.notifier.reminders.lock().await;
+        let reminder = self
+            .notifier
+            .store
+            .list_reminders(last_ping_at)
+            .await?
+            .into_iter()
+            .find(|reminder| {
+                verify_slices_are_equal(reminder.token.as_bytes(), token.as_bytes()).is_ok()
+            });
+        match reminder {
+            Some(mut reminder) => {
+                if reminder.acknowledged_at.is_none() {
+                    reminder.acknowledged_at = Some(unix_time());
+                    info!(
+                        reminder = reminder.index,
+                        "Liveness reminder is acknowledged"
+                    );
+                    self.notifier.store.save_reminder(reminder).await?;
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::elliptic::curves::secp256_k1::GE;
+
+    use super::*;
+    use crate::notifications::file::FileSink;
+    use crate::persistent_store::sled::SledDB;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    async fn reminders(store: SledDB<GE>, steps: &[&str]) -> Result<Reminders<SledDB<GE>, GE>> {
+        let notifier = Notifier::new(store)
+            .with_sink("email", FileSink::Stdout)
+            .with_sink("webhook", FileSink::Stdout);
+        let steps = steps
+            .iter()
+            .map(|step| step.parse())
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Reminders::new(
+            notifier,
+            Duration::from_secs(30 * DAY),
+            steps,
+        ))
+    }
+
+    #[test]
+    fn parse_step() -> Result<()> {
+        assert_eq!(
+            "7d".parse::<ReminderStep>()?,
+            ReminderStep {
+                before: Duration::from_secs(7 * DAY),
+                sinks: None
+            }
+        );
+        assert_eq!(
+            "1d:email, webhook".parse::<ReminderStep>()?,
+            ReminderStep {
+                before: Duration::from_secs(DAY),
+                sinks: Some(vec!["email".to_string(), "webhook".to_string()])
+            }
+        );
+        assert!("1d:".parse::<ReminderStep>().is_err());
+        assert!("soon".parse::<ReminderStep>().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn escalate_reminders() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = SledDB::<GE>::open(dir.path().join("store")).await?;
+        let reminders = reminders(store.clone(), &["1d:email,webhook", "7d:email"]).await?;
+        store.increase_ping_counter(0).await?;
+
+        // No reminder is due yet
+        reminders.send_due(22 * DAY).await?;
+        assert!(store.list_reminders(0).await?.is_empty());
+
+        reminders.send_due(23 * DAY).await?;
+        reminders.send_due(24 * DAY).await?;
+        let sent = store.list_reminders(0).await?;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].index, 0);
+        assert_eq!(sent[0].deliveries.keys().collect::<Vec<_>>(), vec!["email"]);
+        assert_eq!(store.queued_notifications().await?.len(), 1);
+
+        reminders.send_due(29 * DAY).await?;
+        let sent = store.list_reminders(0).await?;
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            sent[1].deliveries.keys().collect::<Vec<_>>(),
+            vec!["email", "webhook"]
+        );
+        assert_eq!(store.queued_notifications().await?.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skip_overdue_reminders() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = SledDB::<GE>::open(dir.path().join("store")).await?;
+        let reminders = reminders(store.clone(), &["7d", "1d"]).await?;
+        store.increase_ping_counter(0).await?;
+
+        reminders.send_due(29 * DAY).await?;
+        reminders.send_due(29 * DAY).await?;
+        let sent = store.list_reminders(0).await?;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].index, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn huge_durations_dont_overflow() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = SledDB::<GE>::open(dir.path().join("store")).await?;
+        let notifier = Notifier::new(store.clone()).with_sink("email", FileSink::Stdout);
+        let step = ReminderStep {
+            before: Duration::from_secs(u64::MAX),
+            sinks: None,
+        };
+        store.increase_ping_counter(DAY).await?;
+
+        // Step is due at once
+        let reminders = Reminders::new(notifier.clone(), Duration::from_secs(DAY), vec![step]);
+        reminders.send_due(2 * DAY).await?;
+        assert_eq!(store.list_reminders(DAY).await?.len(), 1);
+
+        // Deadline is never reached
+        let reminders = Reminders::new(notifier, Duration::from_secs(u64::MAX), vec![]);
+        reminders.send_due(u64::MAX).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn acknowledge_reminder() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = SledDB::<GE>::open(dir.path().join("store")).await?;
+        let reminders = reminders(store.clone(), &["7d"]).await?;
+        store.increase_ping_counter(0).await?;
+
+        reminders.send_due(23 * DAY).await?;
+        let token = store.list_reminders(0).await?[0].token.clone();
+        assert!(!reminders.acknowledge("wrong token").await?);
+        assert!(reminders.acknowledge(&token).await?);
+        assert!(store.list_reminders(0).await?[0].acknowledged_at.is_some());
+
+        // Token of a reminder sent before the latest ping is outdated
+        store.increase_ping_counter(DAY).await?;
+        assert!(!reminders.acknowledge(&token).await?);
+        Ok(())
+    }
+}
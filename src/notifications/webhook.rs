@@ -0,0 +1,280 @@
+//! Webhook sink: notifications POSTed as JSON and signed with HMAC-SHA256
+//!
+//! Request carries `X-Will-Timestamp` header (Unix time in seconds) and `X-Will-Signature` header
+//! `sha256=<hex>`, which is HMAC of `<timestamp>.<body>` keyed with the shared secret. Receiver
+//! should check the signature and reject stale timestamps.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ring::hmac;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::delay::rounds::unix_time;
+
+use super::{DeliveryError, Notification, Sink};
+
+/// Response is read up to that size, only its status line matters
+const MAX_RESPONSE_SIZE: u64 = 16 * 1024;
+
+pub struct Webhook {
+    url: Url,
+    key: hmac::Key,
+    tls: Option<Arc<rustls::ClientConfig>>,
+}
+
+impl Webhook {
+    /// Constructs webhook of `url`, either `http://` or `https://` one. Requests are signed with
+    /// `secret`. `tls` config is required for `https://` url.
+    pub fn new(
+        url: &str,
+        secret: &[u8],
+        tls: Option<Arc<rustls::ClientConfig>>,
+    ) -> Result<Self, InvalidWebhook> {
+        let url = Url::parse(url)?;
+        if url.https && tls.is_none() {
+            return Err(InvalidWebhook::TlsNotConfigured);
+        }
+        if secret.is_empty() {
+            return Err(InvalidWebhook::EmptySecret);
+        }
+        Ok(Self {
+            url,
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            tls,
+        })
+    }
+
+    fn request(&self, notification: &Notification, timestamp: u64) -> Vec<u8> {
+        let body = serde_json::to_string(notification).expect("notification is serializable");
+        let signature = hmac::sign(&self.key, format!("{}.{}", timestamp, body).as_bytes());
+        format!(
+            "POST {path} HTTP/1.1\r\n\
+             Host: {host}:{port}\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {length}\r\n\
+             X-Will-Timestamp: {timestamp}\r\n\
+             X-Will-Signature: sha256={signature}\r\n\
+             Connection: close\r\n\
+             \r\n\
+             {body}",
+            path = self.url.path,
+            host = self.url.host,
+            port = self.url.port,
+            length = body.len(),
+            timestamp = timestamp,
+            signature = hex(signature.as_ref()),
+            body = body,
+        )
+        .into_bytes()
+    }
+}
+
+#[async_trait]
+impl Sink for Webhook {
+    async fn deliver(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let request = self.request(notification, unix_time());
+        let stream = TcpStream::connect((self.url.host.as_str(), self.url.port)).await?;
+        match &self.tls {
+            Some(tls) if self.url.https => {
+                let name = webpki::DNSNameRef::try_from_ascii_str(&self.url.host)
+                    .map_err(|_| DeliveryError::Rejected("host is not a DNS name".to_string()))?;
+                let stream = tokio_rustls::TlsConnector::from(tls.clone())
+                    .connect(name, stream)
+                    .await?;
+                post(stream, &request).await
+            }
+            _ => post(stream, &request).await,
+        }
+    }
+}
+
+/// Sends request and checks that response has 2xx status
+async fn post<S>(mut stream: S, request: &[u8]) -> Result<(), DeliveryError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(request).await?;
+    stream.flush().await?;
+    let mut response = vec![];
+    stream
+        .take(MAX_RESPONSE_SIZE)
+        .read_to_end(&mut response)
+        .await?;
+    let status_line = response
+        .split(|b| *b == b'\n')
+        .next()
+        .map(|line| String::from_utf8_lossy(line).trim().to_string())
+        .unwrap_or_default();
+    // Status line is `HTTP/1.1 204 No Content`
+    let status = status_line.split(' ').nth(1).unwrap_or_default();
+    if status.len() == 3 && status.starts_with('2') {
+        Ok(())
+    } else {
+        Err(DeliveryError::Rejected(status_line))
+    }
+}
+
+struct Url {
+    https: bool,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Result<Self, InvalidWebhook> {
+        let (https, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            return Err(InvalidWebhook::Url("scheme must be http or https"));
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rfind(':') {
+            Some(i) => (
+                &authority[..i],
+                authority[i + 1..]
+                    .parse()
+                    .map_err(|_| InvalidWebhook::Url("invalid port"))?,
+            ),
+            None if https => (authority, 443),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(InvalidWebhook::Url("host is missing"));
+        }
+        Ok(Self {
+            https,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidWebhook {
+    #[error("invalid webhook url: {0}")]
+    Url(&'static str),
+    #[error("https webhook requires TLS to be configured")]
+    TlsNotConfigured,
+    #[error("webhook secret is empty")]
+    EmptySecret,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::notifications::Event;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Accepts a single request and replies with `status`. Returns the request.
+    async fn serve_once(listener: TcpListener, status: &'static str) -> Result<String> {
+        let (mut stream, _) = listener.accept().await?;
+        let mut request = vec![];
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stream.read(&mut buf).await?;
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some(headers_end) = text.find("\r\n\r\n") {
+                let length: usize = text
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .ok_or("no content length")?
+                    .parse()?;
+                if request.len() >= headers_end + 4 + length {
+                    break;
+                }
+            }
+            if n == 0 {
+                return Err("request is truncated".into());
+            }
+        }
+        stream
+            .write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).as_bytes())
+            .await?;
+        Ok(String::from_utf8(request)?)
+    }
+
+    fn notification() -> Notification {
+        Notification {
+            at: 10,
+            event: Event::ChallengeIssued { round: 0 },
+        }
+    }
+
+    #[tokio::test]
+    async fn post_signed_notification() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/hooks/will", listener.local_addr()?);
+        let server = tokio::spawn(serve_once(listener, "204 No Content"));
+
+        let webhook = Webhook::new(&url, b"secret", None)?;
+        webhook.deliver(&notification()).await?;
+
+        let request = server.await??;
+        assert!(request.starts_with("POST /hooks/will HTTP/1.1\r\n"));
+        let header = |name: &str| {
+            request
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .map(str::to_string)
+        };
+        let timestamp = header("X-Will-Timestamp: ").ok_or("no timestamp")?;
+        let signature = header("X-Will-Signature: sha256=").ok_or("no signature")?;
+        let body = &request[request.find("\r\n\r\n").ok_or("no body")? + 4..];
+        assert_eq!(serde_json::from_str::<Notification>(body)?, notification());
+
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+        hmac::verify(
+            &key,
+            format!("{}.{}", timestamp, body).as_bytes(),
+            &hex::decode(signature)?,
+        )
+        .map_err(|_| "signature doesn't verify")?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fail_on_error_status() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let server = tokio::spawn(serve_once(listener, "503 Service Unavailable"));
+
+        let webhook = Webhook::new(&url, b"secret", None)?;
+        let result = webhook.deliver(&notification()).await;
+        assert!(matches!(result, Err(DeliveryError::Rejected(_))));
+        server.await??;
+        Ok(())
+    }
+
+    #[test]
+    fn parse_url() -> Result<()> {
+        let url = Url::parse("https://example.com/will")?;
+        assert!(url.https);
+        assert_eq!((url.host.as_str(), url.port), ("example.com", 443));
+        assert_eq!(url.path, "/will");
+
+        let url = Url::parse("http://localhost:8080")?;
+        assert_eq!((url.host.as_str(), url.port), ("localhost", 8080));
+        assert_eq!(url.path, "/");
+
+        assert!(Url::parse("ftp://example.com").is_err());
+        assert!(Webhook::new("https://example.com", b"secret", None).is_err());
+        Ok(())
+    }
+}
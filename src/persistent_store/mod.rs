@@ -1,103 +1,382 @@
 pub mod sled;
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::path::PathBuf;
 
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::traits::ECPoint;
 
+use crate::audit::{AuditEntry, AuditRecord};
+use crate::delay::Scheme;
+use crate::escrow::EscrowPiece;
 use crate::sealed::Sealed;
+use crate::testators::{AccountId, DeviceFingerprint};
 
 #[async_trait]
 pub trait PersistentStore<P: ECPoint>: Clone + Sync + Send {
-    type Error;
-
     /// Opens existing persistent_store or creates a new one on file system.
-    async fn open(path: PathBuf) -> Result<Self, Self::Error>;
+    async fn open(path: PathBuf) -> Result<Self, StoreError>;
 
     /// Adds a server's secret share to the persistent_store.
     ///
-    /// Returns `Error::AlreadyExist` if there is a share associated with given `public_key`.
-    async fn add_server_secret_share(
-        &self,
-        public_key: P,
-        server_secret_share: P::Scalar,
-    ) -> Result<(), Self::Error>;
+    /// Adding the same share again succeeds without changing anything. Returns
+    /// [StoreError::AlreadyExists] if a different share is associated with `share.public_key`.
+    async fn add_server_secret_share(&self, share: ShareRecord<P>) -> Result<(), StoreError>;
 
     /// Returns a server's secret share associated with given `public_key`
-    async fn get_server_secret_share(
-        &self,
-        public_key: P,
-    ) -> Result<Option<Sealed<P>>, Self::Error>;
+    async fn get_server_secret_share(&self, public_key: P)
+        -> Result<Option<Sealed<P>>, StoreError>;
+
+    /// Returns public keys of shares that registered a liveness key
+    async fn shares_with_liveness_keys(&self) -> Result<Vec<P>, StoreError>;
+
+    /// Returns public keys of shares that registered beneficiary auth keys
+    async fn shares_with_beneficiary_keys(&self) -> Result<Vec<P>, StoreError>;
 
-    /// Increases ping counter by 1
+    /// Increases ping counter by 1, and records `pinged_at` (Unix time in seconds) as time of the
+    /// latest ping
     ///
-    /// This will reset challenge, i.e. `db.get_challenge().await` will return `Ok(None)` until
-    /// new challenge is set.
+    /// This will reset challenge and claim progress, i.e. `db.get_challenge().await` will return
+    /// `Ok(None)` until new challenge is set.
     ///
     /// Returns increased ping counter.
-    async fn increase_ping_counter(&self) -> Result<u128, Self::Error>;
+    async fn increase_ping_counter(&self, pinged_at: u64) -> Result<u128, StoreError>;
 
     /// Returns ping counter
-    async fn get_ping_counter(&self) -> Result<u128, Self::Error>;
+    async fn get_ping_counter(&self) -> Result<u128, StoreError>;
+
+    /// Returns Unix time (in seconds) of the latest ping, or `None` if testator never pinged
+    async fn get_last_ping_time(&self) -> Result<Option<u64>, StoreError>;
 
     /// Sets a new challenge that will be valid until receiving new ping.
     ///
     /// ## Errors
     /// * [SetChallengeError::AlreadySet] is returned if challenge with the same id is already set
     /// * [SetChallengeError::Outdated] is returned if `challenge.id < db.get_ping_counter()`
-    /// * [SetChallengeError::Io] indicates that some underlying error happened
-    async fn set_challenge(
-        &self,
-        challenge: Challenge,
-    ) -> Result<(), SetChallengeError<Self::Error>>;
+    /// * [SetChallengeError::MismatchedRound] is returned if `challenge.round` isn't the number of
+    ///   rounds completed so far
+    /// * [SetChallengeError::Store] indicates that some underlying error happened
+    async fn set_challenge(&self, challenge: Challenge) -> Result<(), SetChallengeError>;
 
     /// Returns the latest set challenge
     ///
     /// Challenge is guaranteed to be up-to-date, i.e. `challenge.id == db.get_ping_counter()`
-    async fn get_challenge(&self) -> Result<Option<Challenge>, Self::Error>;
+    async fn get_challenge(&self) -> Result<Option<Challenge>, StoreError>;
+
+    /// Records that `challenge` is solved and removes it, so challenge of the next round can be set
+    ///
+    /// Returns updated claim progress, or `None` if `challenge` is not the current one anymore
+    /// (e.g. ping was received or the round was completed concurrently).
+    async fn complete_claim_round(
+        &self,
+        challenge: &Challenge,
+        completed_at: u64,
+    ) -> Result<Option<ClaimProgress>, StoreError>;
+
+    /// Returns progress of claim made since the latest ping
+    ///
+    /// Progress is guaranteed to be up-to-date, i.e. `progress.challenge_id == db.get_ping_counter()`
+    async fn get_claim_progress(&self) -> Result<Option<ClaimProgress>, StoreError>;
+
+    /// Records heir's contribution to claim of share associated with `public_key`
+    ///
+    /// Claim session is bound to a challenge: contribution made for a newer challenge discards
+    /// contributions collected for older ones. Returns the updated claim session.
+    async fn add_claim_contribution(
+        &self,
+        public_key: P,
+        challenge_id: u128,
+        heir_index: u32,
+    ) -> Result<ClaimSession, StoreError>;
+
+    /// Returns secret key used to decrypt server shares sent by testators
+    ///
+    /// Key is generated at first call and persisted, subsequent calls return the same key.
+    async fn get_or_generate_share_encryption_key(&self) -> Result<P::Scalar, StoreError>;
+
+    /// Enrolls testator's device unless it's already known
+    ///
+    /// Returns the device as it's stored, i.e. the existing one if device with the same fingerprint
+    /// was enrolled (or revoked) before.
+    async fn enroll_device(
+        &self,
+        fingerprint: DeviceFingerprint,
+        device: Device,
+    ) -> Result<Device, StoreError>;
+
+    /// Returns testator's device, including revoked one
+    async fn get_device(
+        &self,
+        fingerprint: DeviceFingerprint,
+    ) -> Result<Option<Device>, StoreError>;
+
+    /// Returns all devices enrolled to the account, including revoked ones
+    async fn list_devices(
+        &self,
+        account: AccountId,
+    ) -> Result<Vec<(DeviceFingerprint, Device)>, StoreError>;
+
+    /// Marks device as revoked at `revoked_at` unless it's already revoked
+    ///
+    /// Returns the device as it's stored after revocation, or `None` if it's not enrolled.
+    async fn revoke_device(
+        &self,
+        fingerprint: DeviceFingerprint,
+        revoked_at: u64,
+    ) -> Result<Option<Device>, StoreError>;
+
+    /// Adds notification to delivery queue, returns its id
+    ///
+    /// Queue is local to the store: it's neither replicated nor included into snapshot.
+    async fn enqueue_notification(
+        &self,
+        notification: QueuedNotification,
+    ) -> Result<u64, StoreError>;
+
+    /// Returns notifications waiting for delivery, in order they were queued
+    async fn queued_notifications(&self) -> Result<Vec<(u64, QueuedNotification)>, StoreError>;
+
+    /// Replaces queued notification, or removes it from the queue if `notification` is `None`
+    async fn update_queued_notification(
+        &self,
+        id: u64,
+        notification: Option<QueuedNotification>,
+    ) -> Result<(), StoreError>;
+
+    /// Saves liveness reminder, replacing the one with the same `last_ping_at` and `index`
+    ///
+    /// Reminders are local to the store, like notification queue.
+    async fn save_reminder(&self, reminder: Reminder) -> Result<(), StoreError>;
+
+    /// Returns reminders sent since the ping at `last_ping_at`, ordered by index
+    async fn list_reminders(&self, last_ping_at: u64) -> Result<Vec<Reminder>, StoreError>;
+
+    /// Appends entry to the audit log, chaining it to the last record. Returns the appended record.
+    async fn append_audit_entry(&self, entry: AuditEntry) -> Result<AuditRecord, StoreError>;
+
+    /// Returns up to `limit` audit records starting from the one at position `from`, in order
+    async fn audit_records(&self, from: u64, limit: usize) -> Result<Vec<AuditRecord>, StoreError>;
+
+    /// Returns the last record of the audit log, or `None` if it's empty
+    async fn last_audit_record(&self) -> Result<Option<AuditRecord>, StoreError>;
+
+    /// Returns mutations that reproduce current state of the store when applied to an empty one
+    async fn snapshot(&self) -> Result<Vec<Mutation<P>>, StoreError>;
+
+    /// Applies mutation received from primary Will
+    ///
+    /// Applying is idempotent, and never moves the store back: e.g. ping counter is never decreased,
+    /// outdated challenges and claim sessions are ignored, and revoked devices stay revoked.
+    async fn apply_mutation(&self, mutation: Mutation<P>) -> Result<(), StoreError>;
+}
+
+/// Server's secret share along with everything testator attached to it
+pub struct ShareRecord<P: ECPoint> {
+    pub public_key: P,
+    pub server_secret_share: P::Scalar,
+    /// Commitments to beneficiary's share if it's split between several heirs
+    pub beneficiaries: Option<VerifiableSS<P>>,
+    /// Set if `server_secret_share` is only a piece of server share distributed across several
+    /// Wills
+    pub escrow_piece: Option<EscrowPiece<P>>,
+    /// Verifies pings signed by testator for this share
+    pub liveness_key: Option<P>,
+    /// Authenticate beneficiaries claiming this share
+    pub beneficiary_keys: Vec<P>,
+}
+
+impl<P: ECPoint> ShareRecord<P> {
+    /// Constructs a record of share that has nothing attached to it
+    pub fn new(public_key: P, server_secret_share: P::Scalar) -> Self {
+        Self {
+            public_key,
+            server_secret_share,
+            beneficiaries: None,
+            escrow_piece: None,
+            liveness_key: None,
+            beneficiary_keys: vec![],
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
 pub struct Challenge {
     pub id: u128,
-    pub challenge: rsa_vdf::UnsolvedVDF,
+    /// Scheme the challenge was issued with
+    #[serde(default)]
+    pub scheme: Scheme,
+    /// Challenge serialized by its scheme
+    pub challenge: serde_json::Value,
+    /// Round of the claim the challenge is issued for, starting from 0
+    #[serde(default)]
+    pub round: u32,
+}
+
+/// Rounds of claim completed since the latest ping
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct ClaimProgress {
+    pub challenge_id: u128,
+    pub rounds_completed: u32,
+    /// Unix time (in seconds) the latest round was completed at
+    pub last_round_completed_at: u64,
+}
+
+/// Heirs that contributed to claim of a share under specific challenge
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct ClaimSession {
+    pub challenge_id: u128,
+    pub contributors: BTreeSet<u32>,
+}
+
+/// Testator's device enrolled to an account
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Device {
+    pub account: AccountId,
+    /// Unix time (in seconds) the device was enrolled at
+    pub enrolled_at: u64,
+    /// Unix time (in seconds) the device was revoked at. Revoked device is never enrolled again.
+    pub revoked_at: Option<u64>,
+}
+
+/// Notification waiting for delivery to a sink
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct QueuedNotification {
+    /// Name of the sink notification is delivered to
+    pub sink: String,
+    /// Notification serialized by notifier
+    pub notification: serde_json::Value,
+    /// Number of failed delivery attempts
+    pub attempts: u32,
+    /// Unix time (in seconds) of the next delivery attempt
+    pub next_attempt_at: u64,
+}
+
+/// Reminder sent to testator before the inactivity deadline
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Reminder {
+    /// Unix time (in seconds) of the latest ping when the reminder was sent
+    pub last_ping_at: u64,
+    /// Position of the reminder in schedule, starting from 0
+    pub index: u32,
+    /// Unix time (in seconds) the reminder was sent at
+    pub sent_at: u64,
+    /// Token testator acknowledges the reminder with
+    pub token: String,
+    /// Unix time (in seconds) the reminder was acknowledged at
+    pub acknowledged_at: Option<u64>,
+    /// Outcome of delivery to every sink the reminder was sent to
+    pub deliveries: BTreeMap<String, DeliveryOutcome>,
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryOutcome {
+    /// Delivery isn't attempted yet
+    Pending,
+    /// Delivery failed `attempts` times and will be retried
+    Retrying { attempts: u32, error: String },
+    /// Delivered at Unix time `at`
+    Delivered { at: u64 },
+    /// Delivery failed too many times and was given up at Unix time `at`
+    Failed { at: u64, error: String },
+}
+
+/// Committed change of the store, replicated from primary Will to standbys
+///
+/// Public keys are serialized with `pk_to_key_slice`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "P: Serialize, P::Scalar: Serialize",
+    deserialize = "P: DeserializeOwned, P::Scalar: DeserializeOwned"
+))]
+pub enum Mutation<P: ECPoint> {
+    AddServerSecretShare {
+        public_key: Vec<u8>,
+        server_secret_share: P::Scalar,
+        beneficiaries: Option<VerifiableSS<P>>,
+        escrow_piece: Option<EscrowPiece<P>>,
+        #[serde(default)]
+        liveness_key: Option<P>,
+        #[serde(default)]
+        beneficiary_keys: Vec<P>,
+    },
+    SetPingCounter(u128),
+    SetLastPingTime(u64),
+    SetChallenge(Challenge),
+    SetClaimProgress(ClaimProgress),
+    SetClaimSession {
+        public_key: Vec<u8>,
+        session: ClaimSession,
+    },
+    SetShareEncryptionKey(P::Scalar),
+    SetDevice {
+        fingerprint: DeviceFingerprint,
+        device: Device,
+    },
+    AppendAuditRecord(AuditRecord),
+}
+
+/// Error of persistent store
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    /// A different entry is already stored under the same key
+    #[error("{0} already exists")]
+    AlreadyExists(&'static str),
+    /// Stored data can't be read back
+    #[error("stored data is corrupted: {0}")]
+    Corrupted(String),
+    /// Underlying storage failed
+    #[error(transparent)]
+    Io(Box<dyn std::error::Error + Send + Sync>),
+    /// Store was modified concurrently in a way that prevents the operation
+    #[error("concurrent modification: {0}")]
+    Conflict(&'static str),
+    /// Change is committed, but it isn't replicated to enough standbys
+    #[error("change isn't replicated: {0}")]
+    NotReplicated(Box<dyn std::error::Error + Send + Sync>),
 }
 
 #[derive(Debug)]
-pub enum SetChallengeError<E> {
+pub enum SetChallengeError {
     AlreadySet(Challenge),
     Outdated,
     MismatchedId,
-    Store(E),
+    MismatchedRound,
+    Store(StoreError),
+}
+
+impl From<StoreError> for SetChallengeError {
+    fn from(e: StoreError) -> Self {
+        SetChallengeError::Store(e)
+    }
 }
 
-impl<E> fmt::Display for SetChallengeError<E>
-where
-    E: fmt::Display,
-{
+impl fmt::Display for SetChallengeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             SetChallengeError::AlreadySet(..) => write!(f, "challenge is already set"),
             SetChallengeError::Outdated => write!(f, "testator is not offline"),
             SetChallengeError::MismatchedId => write!(f, "id doesn't match current ping counter"),
+            SetChallengeError::MismatchedRound => {
+                write!(f, "round doesn't match current claim progress")
+            }
             SetChallengeError::Store(e) => write!(f, "{}", e),
         }
     }
 }
 
-impl<E> std::error::Error for SetChallengeError<E>
-where
-    E: fmt::Debug + fmt::Display + std::error::Error + 'static,
-{
+impl std::error::Error for SetChallengeError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             SetChallengeError::Store(e) => Some(e),
             SetChallengeError::Outdated
             | SetChallengeError::AlreadySet(..)
-            | SetChallengeError::MismatchedId => None,
+            | SetChallengeError::MismatchedId
+            | SetChallengeError::MismatchedRound => None,
         }
     }
 }
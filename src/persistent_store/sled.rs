@@ -1,29 +1,59 @@
-use std::io;
 use std::marker::PhantomData;
 use std::mem::size_of;
 use std::path::PathBuf;
 
 use async_trait::async_trait;
 use derivative::Derivative;
+use serde::{de::DeserializeOwned, Serialize};
+use sled::Transactional;
 
 use curv::arithmetic::traits::Converter;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::traits::{ECPoint, ECScalar};
 use curv::BigInt;
 
-use super::{Challenge, PersistentStore, SetChallengeError};
+use super::{
+    Challenge, ClaimProgress, ClaimSession, Device, Mutation, PersistentStore, QueuedNotification,
+    Reminder, SetChallengeError, ShareRecord, StoreError,
+};
+use crate::audit::{AuditEntry, AuditRecord};
+use crate::escrow::EscrowPiece;
 use crate::sealed::Sealed;
+use crate::testators::{AccountId, DeviceFingerprint};
 
 static SECRETS_TABLE: &[u8] = b"secrets";
+static BENEFICIARIES_TABLE: &[u8] = b"beneficiaries";
+static CLAIM_SESSIONS_TABLE: &[u8] = b"claim_sessions";
+static ESCROW_PIECES_TABLE: &[u8] = b"escrow_pieces";
+static DEVICES_TABLE: &[u8] = b"devices";
+static LIVENESS_KEYS_TABLE: &[u8] = b"liveness_keys";
+static BENEFICIARY_KEYS_TABLE: &[u8] = b"beneficiary_keys";
+static NOTIFICATIONS_TABLE: &[u8] = b"notifications";
+static REMINDERS_TABLE: &[u8] = b"reminders";
+static AUDIT_TABLE: &[u8] = b"audit";
 static META_TABLE: &[u8] = b"meta";
 
 static COUNTER_ROW: &[u8] = b"counter";
+static LAST_PING_ROW: &[u8] = b"last_ping";
 static CHALLENGE_ROW: &[u8] = b"challenge";
+static CLAIM_PROGRESS_ROW: &[u8] = b"claim_progress";
+static SHARE_ENCRYPTION_KEY_ROW: &[u8] = b"share_encryption_key";
+static AUDIT_HEAD_ROW: &[u8] = b"audit_head";
 
 #[derive(Derivative)]
 #[derivative(Clone)]
 pub struct SledDB<P> {
     db: sled::Db,
     secrets: sled::Tree,
+    beneficiaries: sled::Tree,
+    claim_sessions: sled::Tree,
+    escrow_pieces: sled::Tree,
+    devices: sled::Tree,
+    liveness_keys: sled::Tree,
+    beneficiary_keys: sled::Tree,
+    notifications: sled::Tree,
+    reminders: sled::Tree,
+    audit: sled::Tree,
     meta: sled::Tree,
     #[derivative(Clone(clone_with = "Self::ph"))]
     _ph: PhantomData<fn() -> P>,
@@ -38,116 +68,203 @@ impl<P> SledDB<P> {
 #[async_trait]
 impl<P> PersistentStore<P> for SledDB<P>
 where
-    P: ECPoint + Send,
-    P::Scalar: Send + Clone,
+    P: ECPoint + Clone + Serialize + DeserializeOwned + Send + Sync,
+    P::Scalar: Send + Sync + Clone + Serialize + DeserializeOwned,
 {
-    type Error = sled::Error;
-
-    async fn open(path: PathBuf) -> sled::Result<Self> {
+    async fn open(path: PathBuf) -> Result<Self, StoreError> {
         let db = sled::open(path)?;
         let secrets = db.open_tree(SECRETS_TABLE)?;
+        let beneficiaries = db.open_tree(BENEFICIARIES_TABLE)?;
+        let claim_sessions = db.open_tree(CLAIM_SESSIONS_TABLE)?;
+        let escrow_pieces = db.open_tree(ESCROW_PIECES_TABLE)?;
+        let devices = db.open_tree(DEVICES_TABLE)?;
+        let liveness_keys = db.open_tree(LIVENESS_KEYS_TABLE)?;
+        let beneficiary_keys = db.open_tree(BENEFICIARY_KEYS_TABLE)?;
+        let notifications = db.open_tree(NOTIFICATIONS_TABLE)?;
+        let reminders = db.open_tree(REMINDERS_TABLE)?;
+        let audit = db.open_tree(AUDIT_TABLE)?;
         let meta = db.open_tree(META_TABLE)?;
         Ok(Self {
             db,
             secrets,
+            beneficiaries,
+            claim_sessions,
+            escrow_pieces,
+            devices,
+            liveness_keys,
+            beneficiary_keys,
+            notifications,
+            reminders,
+            audit,
             meta,
             _ph: PhantomData,
         })
     }
 
-    async fn add_server_secret_share(
-        &self,
-        public_key: P,
-        server_secret_share: P::Scalar,
-    ) -> sled::Result<()> {
-        let public_key_bytes = public_key.pk_to_key_slice();
-        let server_secret_share_bytes: Vec<u8> = server_secret_share.to_big_int().to_bytes();
-        self.secrets
-            .compare_and_swap(
-                public_key_bytes,
-                None::<Vec<u8>>,
-                Some(server_secret_share_bytes),
-            )?
-            .map_err(|_| {
-                io::Error::new(io::ErrorKind::AlreadyExists, "server share already exist")
-            })?;
-        self.secrets.flush_async().await?;
+    async fn add_server_secret_share(&self, share: ShareRecord<P>) -> Result<(), StoreError> {
+        let public_key_bytes = share.public_key.pk_to_key_slice();
+        let server_secret_share_bytes: Vec<u8> = share.server_secret_share.to_big_int().to_bytes();
+        let beneficiaries = share.beneficiaries.map(|b| serialize(&b)).transpose()?;
+        let escrow_piece = share.escrow_piece.map(|p| serialize(&p)).transpose()?;
+        let liveness_key = share.liveness_key.map(|k| k.pk_to_key_slice());
+        let beneficiary_keys = serialize_keys(&share.beneficiary_keys)?;
+
+        let result = (
+            &self.secrets,
+            &self.beneficiaries,
+            &self.escrow_pieces,
+            &self.liveness_keys,
+            &self.beneficiary_keys,
+        )
+            .transaction(|(secrets, heirs, pieces, liveness_keys, auth_keys)| {
+                if let Some(existing) = secrets.get(&public_key_bytes)? {
+                    // Re-uploading the very same share is not an error
+                    let same = existing.as_ref() == server_secret_share_bytes.as_slice()
+                        && heirs.get(&public_key_bytes)?.as_deref() == beneficiaries.as_deref()
+                        && pieces.get(&public_key_bytes)?.as_deref() == escrow_piece.as_deref()
+                        && liveness_keys.get(&public_key_bytes)?.as_deref()
+                            == liveness_key.as_deref()
+                        && auth_keys.get(&public_key_bytes)?.as_deref()
+                            == beneficiary_keys.as_deref();
+                    if same {
+                        return Ok(());
+                    }
+                    return Err(abort(StoreError::AlreadyExists("server share")));
+                }
+                secrets.insert(
+                    public_key_bytes.as_slice(),
+                    server_secret_share_bytes.as_slice(),
+                )?;
+                if let Some(beneficiaries) = &beneficiaries {
+                    heirs.insert(public_key_bytes.as_slice(), beneficiaries.as_slice())?;
+                }
+                if let Some(escrow_piece) = &escrow_piece {
+                    pieces.insert(public_key_bytes.as_slice(), escrow_piece.as_slice())?;
+                }
+                if let Some(liveness_key) = &liveness_key {
+                    liveness_keys.insert(public_key_bytes.as_slice(), liveness_key.as_slice())?;
+                }
+                if let Some(keys) = &beneficiary_keys {
+                    auth_keys.insert(public_key_bytes.as_slice(), keys.as_slice())?;
+                }
+                Ok(())
+            });
+        result.map_err(transaction_error)?;
+        self.db.flush_async().await?;
         Ok(())
     }
 
-    async fn get_server_secret_share(&self, public_key: P) -> sled::Result<Option<Sealed<P>>> {
+    async fn get_server_secret_share(
+        &self,
+        public_key: P,
+    ) -> Result<Option<Sealed<P>>, StoreError> {
         let public_key_bytes = public_key.pk_to_key_slice();
         let secret = match self.secrets.get(public_key_bytes.as_slice())? {
             Some(s) => s,
             None => return Ok(None),
         };
+        let beneficiaries: Option<VerifiableSS<P>> =
+            match self.beneficiaries.get(public_key_bytes.as_slice())? {
+                Some(b) => Some(deserialize(&b)?),
+                None => None,
+            };
+        let escrow_piece: Option<EscrowPiece<P>> =
+            match self.escrow_pieces.get(public_key_bytes.as_slice())? {
+                Some(p) => Some(deserialize(&p)?),
+                None => None,
+            };
+        let liveness_key = match self.liveness_keys.get(public_key_bytes.as_slice())? {
+            Some(k) => Some(read_point(&k)?),
+            None => None,
+        };
+        let beneficiary_keys: Vec<P> =
+            match self.beneficiary_keys.get(public_key_bytes.as_slice())? {
+                Some(k) => deserialize(&k)?,
+                None => vec![],
+            };
         let secret = BigInt::from_bytes(&secret);
-        Ok(Some(Sealed::new(
-            public_key,
-            <P::Scalar as ECScalar>::from(&secret),
-        )))
+        let mut sealed = Sealed::new(public_key, <P::Scalar as ECScalar>::from(&secret));
+        if let Some(b) = beneficiaries {
+            sealed = sealed.with_beneficiaries(b);
+        }
+        if let Some(p) = escrow_piece {
+            sealed = sealed.with_escrow_piece(p);
+        }
+        if let Some(k) = liveness_key {
+            sealed = sealed.with_liveness_key(k);
+        }
+        if !beneficiary_keys.is_empty() {
+            sealed = sealed.with_beneficiary_keys(beneficiary_keys);
+        }
+        Ok(Some(sealed))
+    }
+
+    async fn shares_with_liveness_keys(&self) -> Result<Vec<P>, StoreError> {
+        let mut public_keys = vec![];
+        for entry in self.liveness_keys.iter() {
+            let (public_key, _liveness_key) = entry?;
+            public_keys.push(read_point(&public_key)?);
+        }
+        Ok(public_keys)
+    }
+
+    async fn shares_with_beneficiary_keys(&self) -> Result<Vec<P>, StoreError> {
+        let mut public_keys = vec![];
+        for entry in self.beneficiary_keys.iter() {
+            let (public_key, _auth_keys) = entry?;
+            public_keys.push(read_point(&public_key)?);
+        }
+        Ok(public_keys)
     }
 
-    async fn increase_ping_counter(&self) -> sled::Result<u128> {
+    async fn increase_ping_counter(&self, pinged_at: u64) -> Result<u128, StoreError> {
         let result = self.meta.transaction(|tx| {
             let counter = match tx.get(COUNTER_ROW)? {
-                Some(value) => read_counter(value).ok_or(
-                    sled::transaction::ConflictableTransactionError::Abort(
-                        io::ErrorKind::InvalidData,
-                    ),
-                )?,
+                Some(value) => read_counter(value).ok_or_else(|| abort(invalid_counter()))?,
+                None => 0,
+            };
+            let last_ping = match tx.get(LAST_PING_ROW)? {
+                Some(value) => read_time(value).ok_or_else(|| abort(invalid_time()))?,
                 None => 0,
             };
 
             tx.insert(COUNTER_ROW, &(counter + 1).to_le_bytes())?;
+            tx.insert(LAST_PING_ROW, &last_ping.max(pinged_at).to_le_bytes())?;
             tx.remove(CHALLENGE_ROW)?;
+            tx.remove(CLAIM_PROGRESS_ROW)?;
 
             Ok(counter + 1)
         });
-        let new_counter = match result {
-            Ok(c) => c,
-            Err(sled::transaction::TransactionError::Storage(e)) => return Err(e),
-            Err(sled::transaction::TransactionError::Abort(e)) => Err(io::Error::from(e))?,
-        };
+        let new_counter = result.map_err(transaction_error)?;
         self.meta.flush_async().await?;
 
         Ok(new_counter)
     }
 
-    async fn get_ping_counter(&self) -> sled::Result<u128> {
+    async fn get_ping_counter(&self) -> Result<u128, StoreError> {
         let value = match self.meta.get(COUNTER_ROW)? {
             Some(c) => c,
             None => return Ok(0),
         };
-        read_counter(value).ok_or(
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                "invalid internal counter representation",
-            )
-            .into(),
-        )
+        read_counter(value).ok_or_else(invalid_counter)
     }
 
-    async fn set_challenge(
-        &self,
-        challenge: Challenge,
-    ) -> Result<(), SetChallengeError<sled::Error>> {
-        let serialized = serde_json::to_vec(&challenge)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
-            .map_err(SetChallengeError::Store)?;
+    async fn get_last_ping_time(&self) -> Result<Option<u64>, StoreError> {
+        match self.meta.get(LAST_PING_ROW)? {
+            Some(value) => read_time(value).map(Some).ok_or_else(invalid_time),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_challenge(&self, challenge: Challenge) -> Result<(), SetChallengeError> {
+        let serialized = serialize(&challenge)?;
         let result = self.meta.transaction(|tx| {
             let counter = match tx.get(COUNTER_ROW)? {
-                Some(value) => read_counter(value).ok_or(
+                Some(value) => read_counter(value).ok_or_else(|| {
                     sled::transaction::ConflictableTransactionError::Abort(
-                        SetChallengeError::Store(
-                            io::Error::new(
-                                io::ErrorKind::InvalidData,
-                                "invalid internal counter representation",
-                            )
-                            .into(),
-                        ),
-                    ),
-                )?,
+                        SetChallengeError::Store(invalid_counter()),
+                    )
+                })?,
                 None => 0,
             };
 
@@ -162,8 +279,7 @@ where
             }
 
             let current_challenge: Option<Challenge> = match tx.get(CHALLENGE_ROW)? {
-                Some(c) => serde_json::from_slice(&c)
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+                Some(c) => deserialize(&c)
                     .map_err(SetChallengeError::Store)
                     .map_err(sled::transaction::ConflictableTransactionError::Abort)?,
                 None => None,
@@ -177,6 +293,22 @@ where
                 }
             }
 
+            let progress: Option<ClaimProgress> = match tx.get(CLAIM_PROGRESS_ROW)? {
+                Some(p) => deserialize(&p)
+                    .map_err(SetChallengeError::Store)
+                    .map_err(sled::transaction::ConflictableTransactionError::Abort)?,
+                None => None,
+            };
+            let rounds_completed = progress
+                .filter(|p| p.challenge_id == counter)
+                .map(|p| p.rounds_completed)
+                .unwrap_or(0);
+            if challenge.round != rounds_completed {
+                return Err(sled::transaction::ConflictableTransactionError::Abort(
+                    SetChallengeError::MismatchedRound,
+                ));
+            }
+
             tx.insert(CHALLENGE_ROW, serialized.as_slice())?;
 
             Ok(())
@@ -187,25 +319,631 @@ where
                 self.meta
                     .flush_async()
                     .await
-                    .map_err(SetChallengeError::Store)?;
+                    .map_err(|e| SetChallengeError::Store(e.into()))?;
                 Ok(())
             }
             Err(sled::transaction::TransactionError::Storage(e)) => {
-                Err(SetChallengeError::Store(e))
+                Err(SetChallengeError::Store(e.into()))
             }
             Err(sled::transaction::TransactionError::Abort(e)) => Err(e),
         }
     }
 
-    async fn get_challenge(&self) -> sled::Result<Option<Challenge>> {
+    async fn get_challenge(&self) -> Result<Option<Challenge>, StoreError> {
         let serialized = match self.meta.get(CHALLENGE_ROW)? {
             Some(s) => s,
             None => return Ok(None),
         };
-        let challenge: Challenge = serde_json::from_slice(&serialized)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let challenge: Challenge = deserialize(&serialized)?;
         Ok(Some(challenge))
     }
+
+    async fn complete_claim_round(
+        &self,
+        challenge: &Challenge,
+        completed_at: u64,
+    ) -> Result<Option<ClaimProgress>, StoreError> {
+        let progress = ClaimProgress {
+            challenge_id: challenge.id,
+            rounds_completed: challenge.round + 1,
+            last_round_completed_at: completed_at,
+        };
+        let serialized = serialize(&progress)?;
+        let result = self.meta.transaction(|tx| {
+            let current_challenge: Challenge = match tx.get(CHALLENGE_ROW)? {
+                Some(c) => deserialize(&c).map_err(abort)?,
+                None => return Ok(false),
+            };
+            if current_challenge != *challenge {
+                return Ok(false);
+            }
+            tx.insert(CLAIM_PROGRESS_ROW, serialized.as_slice())?;
+            tx.remove(CHALLENGE_ROW)?;
+            Ok(true)
+        });
+        let completed = result.map_err(transaction_error)?;
+        if !completed {
+            return Ok(None);
+        }
+        self.meta.flush_async().await?;
+        Ok(Some(progress))
+    }
+
+    async fn get_claim_progress(&self) -> Result<Option<ClaimProgress>, StoreError> {
+        let progress: ClaimProgress = match self.meta.get(CLAIM_PROGRESS_ROW)? {
+            Some(p) => deserialize(&p)?,
+            None => return Ok(None),
+        };
+        // Progress made before the latest ping is outdated
+        if progress.challenge_id != self.get_ping_counter().await? {
+            return Ok(None);
+        }
+        Ok(Some(progress))
+    }
+
+    async fn add_claim_contribution(
+        &self,
+        public_key: P,
+        challenge_id: u128,
+        heir_index: u32,
+    ) -> Result<ClaimSession, StoreError> {
+        let public_key_bytes = public_key.pk_to_key_slice();
+        let result = self.claim_sessions.transaction(|tx| {
+            let session: Option<ClaimSession> = match tx.get(&public_key_bytes)? {
+                Some(s) => Some(deserialize(&s).map_err(abort)?),
+                None => None,
+            };
+            let mut session = match session {
+                // Contribution to outdated challenge is ignored
+                Some(session) if session.challenge_id > challenge_id => return Ok(session),
+                Some(session) if session.challenge_id == challenge_id => session,
+                _ => ClaimSession {
+                    challenge_id,
+                    contributors: Default::default(),
+                },
+            };
+            if session.contributors.insert(heir_index) {
+                let serialized = serialize(&session).map_err(abort)?;
+                tx.insert(public_key_bytes.as_slice(), serialized)?;
+            }
+            Ok(session)
+        });
+        let session = result.map_err(transaction_error)?;
+        self.claim_sessions.flush_async().await?;
+        Ok(session)
+    }
+
+    async fn get_or_generate_share_encryption_key(&self) -> Result<P::Scalar, StoreError> {
+        if let Some(key) = self.meta.get(SHARE_ENCRYPTION_KEY_ROW)? {
+            return Ok(<P::Scalar as ECScalar>::from(&BigInt::from_bytes(&key)));
+        }
+
+        let new_key = P::Scalar::new_random();
+        let new_key_bytes = new_key.to_big_int().to_bytes();
+        let key = match self.meta.compare_and_swap(
+            SHARE_ENCRYPTION_KEY_ROW,
+            None::<Vec<u8>>,
+            Some(new_key_bytes),
+        )? {
+            Ok(()) => new_key,
+            // Key was concurrently generated by someone else
+            Err(sled::CompareAndSwapError {
+                current: Some(key), ..
+            }) => <P::Scalar as ECScalar>::from(&BigInt::from_bytes(&key)),
+            Err(sled::CompareAndSwapError { current: None, .. }) => {
+                return Err(StoreError::Conflict(
+                    "share encryption key unexpectedly removed",
+                ))
+            }
+        };
+        self.meta.flush_async().await?;
+        Ok(key)
+    }
+
+    async fn enroll_device(
+        &self,
+        fingerprint: DeviceFingerprint,
+        device: Device,
+    ) -> Result<Device, StoreError> {
+        let serialized = serialize(&device)?;
+        let device =
+            match self
+                .devices
+                .compare_and_swap(fingerprint.0, None::<Vec<u8>>, Some(serialized))?
+            {
+                Ok(()) => device,
+                Err(sled::CompareAndSwapError {
+                    current: Some(existing),
+                    ..
+                }) => return deserialize(&existing),
+                Err(sled::CompareAndSwapError { current: None, .. }) => {
+                    return Err(StoreError::Conflict("device unexpectedly removed"))
+                }
+            };
+        self.devices.flush_async().await?;
+        Ok(device)
+    }
+
+    async fn get_device(
+        &self,
+        fingerprint: DeviceFingerprint,
+    ) -> Result<Option<Device>, StoreError> {
+        match self.devices.get(fingerprint.0)? {
+            Some(device) => Ok(Some(deserialize(&device)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn list_devices(
+        &self,
+        account: AccountId,
+    ) -> Result<Vec<(DeviceFingerprint, Device)>, StoreError> {
+        // Testator has just a few devices, and listing them is rare, so the table is scanned
+        // instead of maintaining index by account
+        let mut devices = vec![];
+        for entry in self.devices.iter() {
+            let (fingerprint, device) = entry?;
+            let device: Device = deserialize(&device)?;
+            if device.account != account {
+                continue;
+            }
+            let fingerprint = DeviceFingerprint::from_bytes(&fingerprint)
+                .ok_or_else(|| StoreError::Corrupted("invalid device fingerprint".to_string()))?;
+            devices.push((fingerprint, device));
+        }
+        Ok(devices)
+    }
+
+    async fn revoke_device(
+        &self,
+        fingerprint: DeviceFingerprint,
+        revoked_at: u64,
+    ) -> Result<Option<Device>, StoreError> {
+        let result = self.devices.transaction(|tx| {
+            let mut device: Device = match tx.get(fingerprint.0)? {
+                Some(d) => deserialize(&d).map_err(abort)?,
+                None => return Ok(None),
+            };
+            if device.revoked_at.is_none() {
+                device.revoked_at = Some(revoked_at);
+                tx.insert(&fingerprint.0[..], serialize(&device).map_err(abort)?)?;
+            }
+            Ok(Some(device))
+        });
+        let device = result.map_err(transaction_error)?;
+        self.devices.flush_async().await?;
+        Ok(device)
+    }
+
+    async fn enqueue_notification(
+        &self,
+        notification: QueuedNotification,
+    ) -> Result<u64, StoreError> {
+        // Big-endian ids keep the table ordered by time notifications were queued at
+        let id = self.db.generate_id()?;
+        self.notifications
+            .insert(id.to_be_bytes(), serialize(&notification)?)?;
+        self.notifications.flush_async().await?;
+        Ok(id)
+    }
+
+    async fn queued_notifications(&self) -> Result<Vec<(u64, QueuedNotification)>, StoreError> {
+        let mut notifications = vec![];
+        for entry in self.notifications.iter() {
+            let (id, notification) = entry?;
+            let id = read_notification_id(&id)
+                .ok_or_else(|| StoreError::Corrupted("invalid notification id".to_string()))?;
+            notifications.push((id, deserialize(&notification)?));
+        }
+        Ok(notifications)
+    }
+
+    async fn update_queued_notification(
+        &self,
+        id: u64,
+        notification: Option<QueuedNotification>,
+    ) -> Result<(), StoreError> {
+        match notification {
+            Some(notification) => {
+                self.notifications
+                    .insert(id.to_be_bytes(), serialize(&notification)?)?;
+            }
+            None => {
+                self.notifications.remove(id.to_be_bytes())?;
+            }
+        }
+        self.notifications.flush_async().await?;
+        Ok(())
+    }
+
+    async fn save_reminder(&self, reminder: Reminder) -> Result<(), StoreError> {
+        // Big-endian key keeps reminders grouped by ping and ordered by index
+        let key = [
+            &reminder.last_ping_at.to_be_bytes()[..],
+            &reminder.index.to_be_bytes()[..],
+        ]
+        .concat();
+        self.reminders.insert(key, serialize(&reminder)?)?;
+        self.reminders.flush_async().await?;
+        Ok(())
+    }
+
+    async fn list_reminders(&self, last_ping_at: u64) -> Result<Vec<Reminder>, StoreError> {
+        let mut reminders = vec![];
+        for entry in self.reminders.scan_prefix(last_ping_at.to_be_bytes()) {
+            let (_key, reminder) = entry?;
+            reminders.push(deserialize(&reminder)?);
+        }
+        Ok(reminders)
+    }
+
+    async fn append_audit_entry(&self, entry: AuditEntry) -> Result<AuditRecord, StoreError> {
+        // The last record is duplicated in meta table, as transaction can't look up the last key
+        let result = (&self.audit, &self.meta).transaction(|(audit, meta)| {
+            let last: Option<AuditRecord> = match meta.get(AUDIT_HEAD_ROW)? {
+                Some(last) => Some(deserialize(&last).map_err(abort)?),
+                None => None,
+            };
+            let record = AuditRecord::chain(last.as_ref(), entry.clone());
+            let serialized = serialize(&record).map_err(abort)?;
+            audit.insert(&record.seq.to_be_bytes()[..], serialized.as_slice())?;
+            meta.insert(AUDIT_HEAD_ROW, serialized)?;
+            Ok(record)
+        });
+        let record = result.map_err(transaction_error)?;
+        self.db.flush_async().await?;
+        Ok(record)
+    }
+
+    async fn audit_records(&self, from: u64, limit: usize) -> Result<Vec<AuditRecord>, StoreError> {
+        let mut records = vec![];
+        for entry in self.audit.range(from.to_be_bytes()..).take(limit) {
+            let (_seq, record) = entry?;
+            records.push(deserialize(&record)?);
+        }
+        Ok(records)
+    }
+
+    async fn last_audit_record(&self) -> Result<Option<AuditRecord>, StoreError> {
+        match self.meta.get(AUDIT_HEAD_ROW)? {
+            Some(last) => Ok(Some(deserialize(&last)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn snapshot(&self) -> Result<Vec<Mutation<P>>, StoreError> {
+        let mut mutations = vec![];
+        if let Some(key) = self.meta.get(SHARE_ENCRYPTION_KEY_ROW)? {
+            mutations.push(Mutation::SetShareEncryptionKey(
+                <P::Scalar as ECScalar>::from(&BigInt::from_bytes(&key)),
+            ));
+        }
+        for entry in self.secrets.iter() {
+            let (public_key, secret) = entry?;
+            let beneficiaries = match self.beneficiaries.get(&public_key)? {
+                Some(b) => Some(deserialize(&b)?),
+                None => None,
+            };
+            let escrow_piece = match self.escrow_pieces.get(&public_key)? {
+                Some(p) => Some(deserialize(&p)?),
+                None => None,
+            };
+            let liveness_key = match self.liveness_keys.get(&public_key)? {
+                Some(k) => Some(read_point(&k)?),
+                None => None,
+            };
+            let beneficiary_keys = match self.beneficiary_keys.get(&public_key)? {
+                Some(k) => deserialize(&k)?,
+                None => vec![],
+            };
+            mutations.push(Mutation::AddServerSecretShare {
+                public_key: public_key.to_vec(),
+                server_secret_share: <P::Scalar as ECScalar>::from(&BigInt::from_bytes(&secret)),
+                beneficiaries,
+                escrow_piece,
+                liveness_key,
+                beneficiary_keys,
+            });
+        }
+        mutations.push(Mutation::SetPingCounter(self.get_ping_counter().await?));
+        if let Some(pinged_at) = self.get_last_ping_time().await? {
+            mutations.push(Mutation::SetLastPingTime(pinged_at));
+        }
+        if let Some(progress) = self.get_claim_progress().await? {
+            mutations.push(Mutation::SetClaimProgress(progress));
+        }
+        if let Some(challenge) = self.get_challenge().await? {
+            mutations.push(Mutation::SetChallenge(challenge));
+        }
+        for entry in self.claim_sessions.iter() {
+            let (public_key, session) = entry?;
+            mutations.push(Mutation::SetClaimSession {
+                public_key: public_key.to_vec(),
+                session: deserialize(&session)?,
+            });
+        }
+        for entry in self.devices.iter() {
+            let (fingerprint, device) = entry?;
+            mutations.push(Mutation::SetDevice {
+                fingerprint: DeviceFingerprint::from_bytes(&fingerprint).ok_or_else(|| {
+                    StoreError::Corrupted("invalid device fingerprint".to_string())
+                })?,
+                device: deserialize(&device)?,
+            });
+        }
+        for entry in self.audit.iter() {
+            let (_seq, record) = entry?;
+            mutations.push(Mutation::AppendAuditRecord(deserialize(&record)?));
+        }
+        Ok(mutations)
+    }
+
+    async fn apply_mutation(&self, mutation: Mutation<P>) -> Result<(), StoreError> {
+        match mutation {
+            Mutation::AddServerSecretShare {
+                public_key,
+                server_secret_share,
+                beneficiaries,
+                escrow_piece,
+                liveness_key,
+                beneficiary_keys,
+            } => {
+                let server_secret_share = server_secret_share.to_big_int().to_bytes();
+                let beneficiaries = beneficiaries.map(|b| serialize(&b)).transpose()?;
+                let escrow_piece = escrow_piece.map(|p| serialize(&p)).transpose()?;
+                let liveness_key = liveness_key.map(|k| k.pk_to_key_slice());
+                let beneficiary_keys = serialize_keys(&beneficiary_keys)?;
+                let result: sled::transaction::TransactionResult<(), StoreError> = (
+                    &self.secrets,
+                    &self.beneficiaries,
+                    &self.escrow_pieces,
+                    &self.liveness_keys,
+                    &self.beneficiary_keys,
+                )
+                    .transaction(|(secrets, heirs, pieces, liveness_keys, auth_keys)| {
+                        // Shares are never overwritten, so existing share is the same
+                        if secrets.get(&public_key)?.is_some() {
+                            return Ok(());
+                        }
+                        secrets.insert(public_key.as_slice(), server_secret_share.as_slice())?;
+                        if let Some(beneficiaries) = &beneficiaries {
+                            heirs.insert(public_key.as_slice(), beneficiaries.as_slice())?;
+                        }
+                        if let Some(escrow_piece) = &escrow_piece {
+                            pieces.insert(public_key.as_slice(), escrow_piece.as_slice())?;
+                        }
+                        if let Some(liveness_key) = &liveness_key {
+                            liveness_keys.insert(public_key.as_slice(), liveness_key.as_slice())?;
+                        }
+                        if let Some(keys) = &beneficiary_keys {
+                            auth_keys.insert(public_key.as_slice(), keys.as_slice())?;
+                        }
+                        Ok(())
+                    });
+                result.map_err(transaction_error)?;
+                self.db.flush_async().await?;
+            }
+            Mutation::SetPingCounter(new_counter) => {
+                let result = self.meta.transaction(|tx| {
+                    let counter = match tx.get(COUNTER_ROW)? {
+                        Some(value) => {
+                            read_counter(value).ok_or_else(|| abort(invalid_counter()))?
+                        }
+                        None => 0,
+                    };
+                    if new_counter > counter {
+                        tx.insert(COUNTER_ROW, &new_counter.to_le_bytes())?;
+                        tx.remove(CHALLENGE_ROW)?;
+                        tx.remove(CLAIM_PROGRESS_ROW)?;
+                    }
+                    Ok(())
+                });
+                result.map_err(transaction_error)?;
+                self.meta.flush_async().await?;
+            }
+            Mutation::SetLastPingTime(pinged_at) => {
+                let result = self.meta.transaction(|tx| {
+                    let last_ping = match tx.get(LAST_PING_ROW)? {
+                        Some(value) => read_time(value).ok_or_else(|| abort(invalid_time()))?,
+                        None => 0,
+                    };
+                    if pinged_at > last_ping {
+                        tx.insert(LAST_PING_ROW, &pinged_at.to_le_bytes())?;
+                    }
+                    Ok(())
+                });
+                result.map_err(transaction_error)?;
+                self.meta.flush_async().await?;
+            }
+            Mutation::SetChallenge(challenge) => {
+                let serialized = serialize(&challenge)?;
+                let result = self.meta.transaction(|tx| {
+                    let counter = match tx.get(COUNTER_ROW)? {
+                        Some(value) => {
+                            read_counter(value).ok_or_else(|| abort(invalid_counter()))?
+                        }
+                        None => 0,
+                    };
+                    let progress: Option<ClaimProgress> = match tx.get(CLAIM_PROGRESS_ROW)? {
+                        Some(p) => Some(deserialize(&p).map_err(abort)?),
+                        None => None,
+                    };
+                    let rounds_completed = progress
+                        .filter(|p| p.challenge_id == counter)
+                        .map(|p| p.rounds_completed)
+                        .unwrap_or(0);
+                    // Challenge set before the latest ping or of a completed round is outdated
+                    if challenge.id == counter && challenge.round >= rounds_completed {
+                        tx.insert(CHALLENGE_ROW, serialized.as_slice())?;
+                    }
+                    Ok(())
+                });
+                result.map_err(transaction_error)?;
+                self.meta.flush_async().await?;
+            }
+            Mutation::SetClaimProgress(progress) => {
+                let serialized = serialize(&progress)?;
+                let result = self.meta.transaction(|tx| {
+                    let counter = match tx.get(COUNTER_ROW)? {
+                        Some(value) => {
+                            read_counter(value).ok_or_else(|| abort(invalid_counter()))?
+                        }
+                        None => 0,
+                    };
+                    if progress.challenge_id != counter {
+                        return Ok(());
+                    }
+                    if let Some(current) = tx.get(CLAIM_PROGRESS_ROW)? {
+                        let current: ClaimProgress = deserialize(&current).map_err(abort)?;
+                        if current.challenge_id == counter
+                            && current.rounds_completed >= progress.rounds_completed
+                        {
+                            return Ok(());
+                        }
+                    }
+                    if let Some(challenge) = tx.get(CHALLENGE_ROW)? {
+                        let challenge: Challenge = deserialize(&challenge).map_err(abort)?;
+                        if challenge.round < progress.rounds_completed {
+                            tx.remove(CHALLENGE_ROW)?;
+                        }
+                    }
+                    tx.insert(CLAIM_PROGRESS_ROW, serialized.as_slice())?;
+                    Ok(())
+                });
+                result.map_err(transaction_error)?;
+                self.meta.flush_async().await?;
+            }
+            Mutation::SetClaimSession {
+                public_key,
+                session,
+            } => {
+                let serialized = serialize(&session)?;
+                let result = self.claim_sessions.transaction(|tx| {
+                    if let Some(current) = tx.get(&public_key)? {
+                        let current: ClaimSession = deserialize(&current).map_err(abort)?;
+                        if current.challenge_id > session.challenge_id {
+                            return Ok(());
+                        }
+                    }
+                    tx.insert(public_key.as_slice(), serialized.as_slice())?;
+                    Ok(())
+                });
+                result.map_err(transaction_error)?;
+                self.claim_sessions.flush_async().await?;
+            }
+            Mutation::SetDevice {
+                fingerprint,
+                device,
+            } => {
+                let serialized = serialize(&device)?;
+                let result = self.devices.transaction(|tx| {
+                    if let Some(current) = tx.get(fingerprint.0)? {
+                        let current: Device = deserialize(&current).map_err(abort)?;
+                        if current.revoked_at.is_some() {
+                            return Ok(());
+                        }
+                    }
+                    tx.insert(&fingerprint.0[..], serialized.as_slice())?;
+                    Ok(())
+                });
+                result.map_err(transaction_error)?;
+                self.devices.flush_async().await?;
+            }
+            Mutation::SetShareEncryptionKey(key) => {
+                self.meta
+                    .insert(SHARE_ENCRYPTION_KEY_ROW, key.to_big_int().to_bytes())?;
+                self.meta.flush_async().await?;
+            }
+            Mutation::AppendAuditRecord(record) => {
+                let serialized = serialize(&record)?;
+                let result = (&self.audit, &self.meta).transaction(|(audit, meta)| {
+                    // Records are never rewritten, and might arrive out of order
+                    if audit.get(&record.seq.to_be_bytes()[..])?.is_some() {
+                        return Ok(());
+                    }
+                    audit.insert(&record.seq.to_be_bytes()[..], serialized.as_slice())?;
+                    let last: Option<AuditRecord> = match meta.get(AUDIT_HEAD_ROW)? {
+                        Some(last) => Some(deserialize(&last).map_err(abort)?),
+                        None => None,
+                    };
+                    if last.map_or(true, |last| last.seq < record.seq) {
+                        meta.insert(AUDIT_HEAD_ROW, serialized.as_slice())?;
+                    }
+                    Ok(())
+                });
+                result.map_err(transaction_error)?;
+                self.db.flush_async().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<sled::Error> for StoreError {
+    fn from(error: sled::Error) -> Self {
+        match error {
+            sled::Error::Corruption { .. } => StoreError::Corrupted(error.to_string()),
+            error => StoreError::Io(Box::new(error)),
+        }
+    }
+}
+
+fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>, StoreError> {
+    serde_json::to_vec(value).map_err(|e| StoreError::Corrupted(e.to_string()))
+}
+
+fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, StoreError> {
+    serde_json::from_slice(bytes).map_err(|e| StoreError::Corrupted(e.to_string()))
+}
+
+/// Serializes keys unless there are none, so a share without keys has no row
+fn serialize_keys<P: Serialize>(keys: &[P]) -> Result<Option<Vec<u8>>, StoreError> {
+    if keys.is_empty() {
+        Ok(None)
+    } else {
+        serialize(&keys).map(Some)
+    }
+}
+
+fn read_point<P: ECPoint>(bytes: &[u8]) -> Result<P, StoreError> {
+    P::from_bytes(bytes).map_err(|e| StoreError::Corrupted(format!("invalid point: {:?}", e)))
+}
+
+fn invalid_counter() -> StoreError {
+    StoreError::Corrupted("invalid internal counter representation".to_string())
+}
+
+fn abort(error: StoreError) -> sled::transaction::ConflictableTransactionError<StoreError> {
+    sled::transaction::ConflictableTransactionError::Abort(error)
+}
+
+fn transaction_error(error: sled::transaction::TransactionError<StoreError>) -> StoreError {
+    match error {
+        sled::transaction::TransactionError::Storage(e) => e.into(),
+        sled::transaction::TransactionError::Abort(e) => e,
+    }
+}
+
+fn invalid_time() -> StoreError {
+    StoreError::Corrupted("invalid time representation".to_string())
+}
+
+fn read_time(value: impl AsRef<[u8]>) -> Option<u64> {
+    let mut time = [0u8; size_of::<u64>()];
+    if value.as_ref().len() != time.len() {
+        return None;
+    }
+    time.copy_from_slice(value.as_ref());
+    Some(u64::from_le_bytes(time))
+}
+
+fn read_notification_id(value: &[u8]) -> Option<u64> {
+    let mut id = [0u8; size_of::<u64>()];
+    if value.len() != id.len() {
+        return None;
+    }
+    id.copy_from_slice(value);
+    Some(u64::from_be_bytes(id))
 }
 
 fn read_counter(value: impl AsRef<[u8]>) -> Option<u128> {
@@ -227,20 +965,29 @@ mod persistent_store_should {
     //! cargo test -- --test-threads=1
     //! ```
 
+    use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
     use curv::elliptic::curves::secp256_k1::{FE, GE};
-
-    use super::{PersistentStore, SledDB};
-    use crate::persistent_store::{Challenge, SetChallengeError};
+    use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+
+    use super::{PersistentStore, SledDB, CHALLENGE_ROW};
+    use crate::audit::{AuditEntry, AuditEvent, AuditRecord};
+    use crate::delay::Scheme;
+    use crate::escrow::EscrowPiece;
+    use crate::persistent_store::{
+        Challenge, ClaimProgress, DeliveryOutcome, Device, Mutation, QueuedNotification, Reminder,
+        SetChallengeError, ShareRecord, StoreError,
+    };
+    use crate::testators::{AccountId, DeviceFingerprint};
 
     type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
     lazy_static::lazy_static! {
-        static ref TEST_CHALLENGE: rsa_vdf::UnsolvedVDF = serde_json::from_str(r#"{"x":[1,[1885652591,17533517,2416140196,2102789474,1234557046,817216195,3655015316,1960318755]],"setup":{"t":[1,[1]],"N":[1,[1773823066,1567367735,2844690069,1588019752,2702647890,3059924173,848501649,223024724,2163570840,2072740969,1358934230,1511233973,
+        static ref TEST_CHALLENGE: serde_json::Value = serde_json::from_str(r#"{"x":[1,[1885652591,17533517,2416140196,2102789474,1234557046,817216195,3655015316,1960318755]],"setup":{"t":[1,[1]],"N":[1,[1773823066,1567367735,2844690069,1588019752,2702647890,3059924173,848501649,223024724,2163570840,2072740969,1358934230,1511233973,
             1752724635,151106506,1523033053,1067477923,3213627708,3064750367,2075312732,3562018252,3325444970,1512641256,864984444,2809702737,3651009371,2221401360,275820096,663498737,435288944,1585439220,1588009357,1510728236,940021168,2602478749,3724471822,3448406120,11694078,1826577040,1068252436,4269783695,1368464316,221410714,
             1030199234,3308526525,1260113467,2369328081,3577035636,4031188375,1583697031,3949780996,2720748085,592794227,2159723444,4203311255,3605012052,1627223175,4268320010,284996006,2647917898,581059137,1412522909,565643573,2889868497,1949977675,3743467154,415346208,1343833549,1239430359,1205288764,2873335642,306140568,3126333856,3699539930,
             2339062667,4155918278,2144840660,129551188,3473357580,2613403541,3623004311,4102378124,4195837820,1704853223,3494605739,1289102284,790118878,3140362153,4074244823,2165367006,1463503520,1950189779,918791135,993369283,1745484251,2283179809,2103362647,3547909303,695986644,110026536,739667823,2385954605,270665668,489991280,4048372725,3736558612,3012062106,
             2018621034,1961692024,1988997307,1955843961,4061859667,3454845030,2413872493,348929559,3360622357,703116902,2977021943,2487377900,3130531837,3582467802,2945231790,3252620296,2015103974,2520537720,3174050848,3301510410,890415117,1324004857]]}}"#).unwrap();
-        static ref TEST_CHALLENGE2: rsa_vdf::UnsolvedVDF = serde_json::from_str(r#"{"x":[1,[3116596062,3429917154,380242391,3128311776,375988634,2463438077,1261325488,1057941260]],"setup":{"t":[1,[1]],"N":[1,[3854412618,1010835532,4065591491,2102292353,92598763,3630267993,143952233,588018618,1202143563,3763776711,3919622616,3736236944,3019104952,1097139037,510520483,
+        static ref TEST_CHALLENGE2: serde_json::Value = serde_json::from_str(r#"{"x":[1,[3116596062,3429917154,380242391,3128311776,375988634,2463438077,1261325488,1057941260]],"setup":{"t":[1,[1]],"N":[1,[3854412618,1010835532,4065591491,2102292353,92598763,3630267993,143952233,588018618,1202143563,3763776711,3919622616,3736236944,3019104952,1097139037,510520483,
             271524075,3750259967,338801097,3794457835,2616369307,2866577222,245019226,2857969932,2016285347,276111206,3518919836,1380023137,958480093,263236300,599239382,305388945,1684573828,3463971268,316587571,1308623964,3691975973,2110410231,20498320,3356443829,674970788,3158083955,2646109807,3973618680,1238793822,1613530525,2983843458,4198294090,2887288985,2305795058,
             1751043043,2360218609,675115021,2501880185,1137358181,1494832832,2977761473,1333077743,3908083095,3619922994,2477774598,1851774614,1986803699,654430673,2707032804,119999426,498239492,3923952010,960922580,3428006508,3717810843,819867535,802712456,3136895363,4206124604,392998340,3857199510,600699560,2956093857,4246036936,643980699,3054689974,3960330879,3022125176,
             1943348789,3511717571,951114303,4292692076,1563420755,2429423300,753953050,4244039215,3048110674,3107149417,3949931034,1819737890,2960219730,3228815506,1153460208,1768140778,2477772898,4115217101,234882067,2038431153,2965796120,1258007420,2929630642,2716201379,1549162426,2990350555,253519902,3056441647,275891275,3919792223,1398616677,2520384442,2301934163,2404379140,
@@ -257,7 +1004,7 @@ mod persistent_store_should {
     async fn create_new_store() -> Result<()> {
         let dir = tempfile::tempdir()?;
         let store = SledDB::<GE>::open(dir.path().join("store")).await?;
-        let _counter = store.increase_ping_counter().await?;
+        let _counter = store.increase_ping_counter(0).await?;
         dir.close()?;
         Ok(())
     }
@@ -266,7 +1013,7 @@ mod persistent_store_should {
     async fn open_existing_store() -> Result<()> {
         let dir = tempfile::tempdir()?;
         let store = SledDB::<GE>::open(dir.path().join("store")).await?;
-        let counter_expected = store.increase_ping_counter().await?;
+        let counter_expected = store.increase_ping_counter(0).await?;
         drop(store);
 
         let store = SledDB::<GE>::open(dir.path().join("store")).await?;
@@ -289,7 +1036,7 @@ mod persistent_store_should {
         let (store, _guard) = open_store().await?;
 
         for counter_expected in 1..=10 {
-            let counter_actual = store.increase_ping_counter().await?;
+            let counter_actual = store.increase_ping_counter(0).await?;
             assert_eq!(counter_expected, counter_actual);
             let another_counter_actual = store.get_ping_counter().await?;
             assert_eq!(counter_expected, another_counter_actual);
@@ -298,13 +1045,102 @@ mod persistent_store_should {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn remember_last_ping_time() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+        assert_eq!(store.get_last_ping_time().await?, None);
+
+        store.increase_ping_counter(100).await?;
+        assert_eq!(store.get_last_ping_time().await?, Some(100));
+        // Time never goes back
+        store.increase_ping_counter(50).await?;
+        assert_eq!(store.get_last_ping_time().await?, Some(100));
+
+        store.apply_mutation(Mutation::SetLastPingTime(200)).await?;
+        store.apply_mutation(Mutation::SetLastPingTime(150)).await?;
+        assert_eq!(store.get_last_ping_time().await?, Some(200));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn save_reminders() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+        let reminder = |last_ping_at, index| Reminder {
+            last_ping_at,
+            index,
+            sent_at: 0,
+            token: format!("{}-{}", last_ping_at, index),
+            acknowledged_at: None,
+            deliveries: vec![("file".to_string(), DeliveryOutcome::Pending)]
+                .into_iter()
+                .collect(),
+        };
+
+        store.save_reminder(reminder(100, 1)).await?;
+        store.save_reminder(reminder(100, 0)).await?;
+        store.save_reminder(reminder(200, 0)).await?;
+        assert_eq!(
+            store.list_reminders(100).await?,
+            vec![reminder(100, 0), reminder(100, 1)]
+        );
+
+        let delivered = Reminder {
+            deliveries: vec![("file".to_string(), DeliveryOutcome::Delivered { at: 10 })]
+                .into_iter()
+                .collect(),
+            ..reminder(200, 0)
+        };
+        store.save_reminder(delivered.clone()).await?;
+        assert_eq!(store.list_reminders(200).await?, vec![delivered]);
+        assert_eq!(store.list_reminders(300).await?, vec![]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn queue_notifications() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+        let notification = |sink: &str| QueuedNotification {
+            sink: sink.to_string(),
+            notification: serde_json::json!({"event": "challenge_issued"}),
+            attempts: 0,
+            next_attempt_at: 0,
+        };
+
+        let first = store.enqueue_notification(notification("webhook")).await?;
+        let second = store.enqueue_notification(notification("email")).await?;
+        assert_eq!(
+            store.queued_notifications().await?,
+            vec![
+                (first, notification("webhook")),
+                (second, notification("email"))
+            ]
+        );
+
+        let retried = QueuedNotification {
+            attempts: 1,
+            next_attempt_at: 30,
+            ..notification("webhook")
+        };
+        store
+            .update_queued_notification(first, Some(retried.clone()))
+            .await?;
+        store.update_queued_notification(second, None).await?;
+        assert_eq!(store.queued_notifications().await?, vec![(first, retried)]);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn set_challenge() -> Result<()> {
         let (store, _guard) = open_store().await?;
 
         let challenge = Challenge {
             id: 0,
+            scheme: Scheme::RsaVdf,
             challenge: TEST_CHALLENGE.clone(),
+            round: 0,
         };
         store.set_challenge(challenge.clone()).await?;
 
@@ -314,19 +1150,45 @@ mod persistent_store_should {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn read_challenge_without_scheme_as_rsa_vdf() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+
+        // Challenges stored before schemes were introduced have no `scheme` field
+        let legacy_challenge = serde_json::json!({ "id": 0, "challenge": *TEST_CHALLENGE });
+        store
+            .meta
+            .insert(CHALLENGE_ROW, serde_json::to_vec(&legacy_challenge)?)?;
+
+        let stored_challenge = store.get_challenge().await?;
+        let expected = Challenge {
+            id: 0,
+            scheme: Scheme::RsaVdf,
+            challenge: TEST_CHALLENGE.clone(),
+            round: 0,
+        };
+        assert_eq!(Some(expected), stored_challenge);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn not_allow_set_challenge_twice() -> Result<()> {
         let (store, _guard) = open_store().await?;
 
         let challenge1 = Challenge {
             id: 0,
+            scheme: Scheme::RsaVdf,
             challenge: TEST_CHALLENGE.clone(),
+            round: 0,
         };
         store.set_challenge(challenge1.clone()).await?;
 
         let challenge2 = Challenge {
             id: 0,
+            scheme: Scheme::RsaVdf,
             challenge: TEST_CHALLENGE2.clone(),
+            round: 0,
         };
         let result = store.set_challenge(challenge2.clone()).await;
         if let Err(SetChallengeError::AlreadySet(actual_challenge)) = result {
@@ -347,11 +1209,13 @@ mod persistent_store_should {
 
         let challenge1 = Challenge {
             id: 0,
+            scheme: Scheme::RsaVdf,
             challenge: TEST_CHALLENGE.clone(),
+            round: 0,
         };
         store.set_challenge(challenge1.clone()).await?;
 
-        store.increase_ping_counter().await?;
+        store.increase_ping_counter(0).await?;
 
         let stored_challenge = store.get_challenge().await?;
         assert_eq!(stored_challenge, None);
@@ -365,15 +1229,19 @@ mod persistent_store_should {
 
         let challenge1 = Challenge {
             id: 0,
+            scheme: Scheme::RsaVdf,
             challenge: TEST_CHALLENGE.clone(),
+            round: 0,
         };
         store.set_challenge(challenge1.clone()).await?;
 
-        store.increase_ping_counter().await?;
+        store.increase_ping_counter(0).await?;
 
         let challenge2 = Challenge {
             id: 1,
+            scheme: Scheme::RsaVdf,
             challenge: TEST_CHALLENGE2.clone(),
+            round: 0,
         };
         store.set_challenge(challenge2.clone()).await?;
 
@@ -383,12 +1251,401 @@ mod persistent_store_should {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn remember_beneficiary_commitments() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+
+        let (commitments, _pieces) = VerifiableSS::<GE>::share(1, 3, &CLIENT_SHARE_SK);
+        store
+            .add_server_secret_share(ShareRecord {
+                beneficiaries: Some(commitments.clone()),
+                ..ShareRecord::new(JOINT_PK.clone(), SERVER_SHARE_SK.clone())
+            })
+            .await?;
+
+        let sealed = store.get_server_secret_share(JOINT_PK.clone()).await?;
+        assert_eq!(
+            Some(&commitments),
+            sealed.as_ref().and_then(|s| s.beneficiaries())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remember_escrow_piece() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+
+        let (commitments, pieces) = VerifiableSS::<GE>::share(1, 3, &SERVER_SHARE_SK);
+        let piece = EscrowPiece {
+            index: 2,
+            commitments,
+        };
+        store
+            .add_server_secret_share(ShareRecord {
+                escrow_piece: Some(piece.clone()),
+                ..ShareRecord::new(JOINT_PK.clone(), pieces[1])
+            })
+            .await?;
+
+        let sealed = store.get_server_secret_share(JOINT_PK.clone()).await?;
+        let stored_piece = sealed.as_ref().and_then(|s| s.escrow_piece());
+        assert_eq!(Some(piece.index), stored_piece.map(|p| p.index));
+        assert_eq!(
+            Some(&piece.commitments),
+            stored_piece.map(|p| &p.commitments)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remember_liveness_key() -> Result<()> {
+        let (primary, _guard1) = open_store().await?;
+        let (standby, _guard2) = open_store().await?;
+
+        let liveness_key = GE::generator() * FE::new_random();
+        primary
+            .add_server_secret_share(ShareRecord {
+                liveness_key: Some(liveness_key),
+                ..ShareRecord::new(JOINT_PK.clone(), SERVER_SHARE_SK.clone())
+            })
+            .await?;
+        for mutation in primary.snapshot().await? {
+            standby.apply_mutation(mutation).await?;
+        }
+
+        for store in &[primary, standby] {
+            let sealed = store.get_server_secret_share(JOINT_PK.clone()).await?;
+            assert_eq!(
+                Some(&liveness_key),
+                sealed.as_ref().and_then(|s| s.liveness_key())
+            );
+            assert_eq!(
+                store.shares_with_liveness_keys().await?,
+                vec![JOINT_PK.clone()]
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remember_beneficiary_keys() -> Result<()> {
+        let (primary, _guard1) = open_store().await?;
+        let (standby, _guard2) = open_store().await?;
+
+        let keys = vec![
+            GE::generator() * FE::new_random(),
+            GE::generator() * FE::new_random(),
+        ];
+        primary
+            .add_server_secret_share(ShareRecord {
+                beneficiary_keys: keys.clone(),
+                ..ShareRecord::new(JOINT_PK.clone(), SERVER_SHARE_SK.clone())
+            })
+            .await?;
+        // Registering other keys for the same share is rejected
+        let result = primary
+            .add_server_secret_share(ShareRecord::new(JOINT_PK.clone(), SERVER_SHARE_SK.clone()))
+            .await;
+        assert!(matches!(result, Err(StoreError::AlreadyExists(_))));
+        for mutation in primary.snapshot().await? {
+            standby.apply_mutation(mutation).await?;
+        }
+
+        for store in &[primary, standby] {
+            let sealed = store.get_server_secret_share(JOINT_PK.clone()).await?;
+            assert_eq!(
+                Some(keys.as_slice()),
+                sealed.as_ref().map(|s| s.beneficiary_keys())
+            );
+            assert_eq!(
+                store.shares_with_beneficiary_keys().await?,
+                vec![JOINT_PK.clone()]
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn append_audit_entries() -> Result<()> {
+        let (primary, _guard1) = open_store().await?;
+        let (standby, _guard2) = open_store().await?;
+        assert_eq!(primary.last_audit_record().await?, None);
+
+        let entry = |event| AuditEntry::new(event, Some(&b"public key"[..]), "anonymous testator");
+        let first = primary
+            .append_audit_entry(entry(AuditEvent::ShareSaved))
+            .await?;
+        let second = primary
+            .append_audit_entry(entry(AuditEvent::ShareVerified))
+            .await?;
+        assert_eq!(
+            second,
+            AuditRecord::chain(Some(&first), second.entry.clone())
+        );
+        assert_eq!(primary.last_audit_record().await?, Some(second.clone()));
+        assert_eq!(
+            primary.audit_records(0, 10).await?,
+            vec![first.clone(), second.clone()]
+        );
+        assert_eq!(primary.audit_records(1, 10).await?, vec![second.clone()]);
+        assert_eq!(primary.audit_records(0, 1).await?, vec![first.clone()]);
+
+        // Records are applied regardless of their order, and never rewritten
+        standby
+            .apply_mutation(Mutation::AppendAuditRecord(second.clone()))
+            .await?;
+        for mutation in primary.snapshot().await? {
+            standby.apply_mutation(mutation).await?;
+        }
+        assert_eq!(
+            standby.audit_records(0, 10).await?,
+            vec![first, second.clone()]
+        );
+        assert_eq!(standby.last_audit_record().await?, Some(second));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restore_snapshot_on_another_store() -> Result<()> {
+        let (primary, _guard1) = open_store().await?;
+        let (standby, _guard2) = open_store().await?;
+
+        let share_key = primary.get_or_generate_share_encryption_key().await?;
+        primary
+            .add_server_secret_share(ShareRecord::new(JOINT_PK.clone(), SERVER_SHARE_SK.clone()))
+            .await?;
+        primary.increase_ping_counter(0).await?;
+        let challenge = Challenge {
+            id: 1,
+            scheme: Scheme::RsaVdf,
+            challenge: TEST_CHALLENGE.clone(),
+            round: 0,
+        };
+        primary.set_challenge(challenge.clone()).await?;
+
+        for mutation in primary.snapshot().await? {
+            standby.apply_mutation(mutation).await?;
+        }
+
+        assert_eq!(
+            share_key,
+            standby.get_or_generate_share_encryption_key().await?
+        );
+        let sealed = standby.get_server_secret_share(JOINT_PK.clone()).await?;
+        assert_eq!(
+            Some(SERVER_SHARE_SK.clone()),
+            sealed.map(|s| s.secret_share().clone())
+        );
+        assert_eq!(standby.get_ping_counter().await?, 1);
+        assert_eq!(standby.get_challenge().await?, Some(challenge));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn never_decrease_ping_counter_on_replication() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+
+        store.apply_mutation(Mutation::SetPingCounter(5)).await?;
+        store.apply_mutation(Mutation::SetPingCounter(3)).await?;
+        assert_eq!(store.get_ping_counter().await?, 5);
+
+        // Challenge issued before the latest ping is ignored
+        let challenge = Challenge {
+            id: 4,
+            scheme: Scheme::RsaVdf,
+            challenge: TEST_CHALLENGE.clone(),
+            round: 0,
+        };
+        store
+            .apply_mutation(Mutation::SetChallenge(challenge))
+            .await?;
+        assert_eq!(store.get_challenge().await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn issue_challenges_of_claim_rounds_in_order() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+
+        let round0 = Challenge {
+            id: 0,
+            scheme: Scheme::RsaVdf,
+            challenge: TEST_CHALLENGE.clone(),
+            round: 0,
+        };
+        let round1 = Challenge {
+            id: 0,
+            scheme: Scheme::RsaVdf,
+            challenge: TEST_CHALLENGE2.clone(),
+            round: 1,
+        };
+        match store.set_challenge(round1.clone()).await {
+            Err(SetChallengeError::MismatchedRound) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        store.set_challenge(round0.clone()).await?;
+
+        let progress = store.complete_claim_round(&round0, 100).await?;
+        let expected = ClaimProgress {
+            challenge_id: 0,
+            rounds_completed: 1,
+            last_round_completed_at: 100,
+        };
+        assert_eq!(progress, Some(expected.clone()));
+        assert_eq!(store.get_claim_progress().await?, Some(expected));
+        assert_eq!(store.get_challenge().await?, None);
+
+        // Round can't be completed twice
+        assert_eq!(store.complete_claim_round(&round0, 200).await?, None);
+        match store.set_challenge(round0).await {
+            Err(SetChallengeError::MismatchedRound) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        store.set_challenge(round1.clone()).await?;
+        assert_eq!(store.get_challenge().await?, Some(round1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn abort_claim_rounds_after_increasing_ping_counter() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+
+        let challenge = Challenge {
+            id: 0,
+            scheme: Scheme::RsaVdf,
+            challenge: TEST_CHALLENGE.clone(),
+            round: 0,
+        };
+        store.set_challenge(challenge.clone()).await?;
+        store.complete_claim_round(&challenge, 100).await?;
+        store.increase_ping_counter(0).await?;
+
+        assert_eq!(store.get_claim_progress().await?, None);
+        let challenge = Challenge { id: 1, ..challenge };
+        store.set_challenge(challenge.clone()).await?;
+        assert_eq!(store.get_challenge().await?, Some(challenge));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ignore_challenge_of_completed_round_on_replication() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+
+        let challenge = Challenge {
+            id: 0,
+            scheme: Scheme::RsaVdf,
+            challenge: TEST_CHALLENGE.clone(),
+            round: 0,
+        };
+        let progress = ClaimProgress {
+            challenge_id: 0,
+            rounds_completed: 1,
+            last_round_completed_at: 100,
+        };
+        store
+            .apply_mutation(Mutation::SetClaimProgress(progress.clone()))
+            .await?;
+        store
+            .apply_mutation(Mutation::SetChallenge(challenge))
+            .await?;
+        assert_eq!(store.get_challenge().await?, None);
+        assert_eq!(store.get_claim_progress().await?, Some(progress));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn collect_claim_contributions_per_challenge() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+
+        store.add_claim_contribution(JOINT_PK.clone(), 3, 1).await?;
+        let session = store.add_claim_contribution(JOINT_PK.clone(), 3, 2).await?;
+        assert_eq!(session.contributors, [1, 2].iter().copied().collect());
+
+        // Contribution to a newer challenge restarts the session
+        let session = store.add_claim_contribution(JOINT_PK.clone(), 4, 2).await?;
+        assert_eq!(session.challenge_id, 4);
+        assert_eq!(session.contributors, [2].iter().copied().collect());
+
+        // Contribution to an older challenge is ignored
+        let session = store.add_claim_contribution(JOINT_PK.clone(), 3, 1).await?;
+        assert_eq!(session.challenge_id, 4);
+        assert_eq!(session.contributors, [2].iter().copied().collect());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn persist_share_encryption_key() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = SledDB::<GE>::open(dir.path().join("store")).await?;
+        let key_expected = store.get_or_generate_share_encryption_key().await?;
+        assert_eq!(
+            key_expected,
+            store.get_or_generate_share_encryption_key().await?
+        );
+        drop(store);
+
+        let store = SledDB::<GE>::open(dir.path().join("store")).await?;
+        let key_actual = store.get_or_generate_share_encryption_key().await?;
+        assert_eq!(key_expected, key_actual);
+
+        dir.close()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keep_devices_revoked() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+        let account = AccountId([1; 16]);
+        let fingerprint = DeviceFingerprint([2; 32]);
+        let device = Device {
+            account,
+            enrolled_at: 10,
+            revoked_at: None,
+        };
+
+        assert_eq!(
+            store.enroll_device(fingerprint, device.clone()).await?,
+            device
+        );
+        let revoked = store.revoke_device(fingerprint, 20).await?;
+        assert_eq!(revoked.as_ref().and_then(|d| d.revoked_at), Some(20));
+
+        // Neither enrolling the device again nor replicating outdated record brings it back
+        let enrolled = store.enroll_device(fingerprint, device.clone()).await?;
+        assert_eq!(enrolled.revoked_at, Some(20));
+        store
+            .apply_mutation(Mutation::SetDevice {
+                fingerprint,
+                device: device.clone(),
+            })
+            .await?;
+        assert_eq!(store.get_device(fingerprint).await?, revoked);
+        assert_eq!(
+            store.list_devices(account).await?,
+            vec![(fingerprint, revoked.unwrap())]
+        );
+        assert!(store.list_devices(AccountId([3; 16])).await?.is_empty());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn remember_server_secret_share() -> Result<()> {
         let (store, _guard) = open_store().await?;
 
         store
-            .add_server_secret_share(JOINT_PK.clone(), SERVER_SHARE_SK.clone())
+            .add_server_secret_share(ShareRecord::new(JOINT_PK.clone(), SERVER_SHARE_SK.clone()))
             .await?;
 
         let actual_sk = store.get_server_secret_share(JOINT_PK.clone()).await?;
@@ -415,12 +1672,32 @@ mod persistent_store_should {
         let (store, _guard) = open_store().await?;
 
         store
-            .add_server_secret_share(JOINT_PK.clone(), SERVER_SHARE_SK.clone())
+            .add_server_secret_share(ShareRecord::new(JOINT_PK.clone(), SERVER_SHARE_SK.clone()))
             .await?;
         let result = store
-            .add_server_secret_share(JOINT_PK.clone(), CLIENT_SHARE_SK.clone())
+            .add_server_secret_share(ShareRecord::new(JOINT_PK.clone(), CLIENT_SHARE_SK.clone()))
             .await;
-        assert!(result.is_err());
+        assert!(matches!(result, Err(StoreError::AlreadyExists(_))));
+
+        let actual_sk = store.get_server_secret_share(JOINT_PK.clone()).await?;
+        assert_eq!(
+            Some(SERVER_SHARE_SK.clone()),
+            actual_sk.as_ref().map(|sk| sk.secret_share().clone())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn accept_same_server_share_twice() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+
+        store
+            .add_server_secret_share(ShareRecord::new(JOINT_PK.clone(), SERVER_SHARE_SK.clone()))
+            .await?;
+        store
+            .add_server_secret_share(ShareRecord::new(JOINT_PK.clone(), SERVER_SHARE_SK.clone()))
+            .await?;
 
         let actual_sk = store.get_server_secret_share(JOINT_PK.clone()).await?;
         assert_eq!(
@@ -0,0 +1,22 @@
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetAttestationRequest {
+    /// Client-chosen nonce guaranteeing freshness of evidence
+    #[prost(bytes = "vec", tag = "1")]
+    pub nonce: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Attestation {
+    /// Evidence format, defines how Evidence must be verified
+    #[prost(string, tag = "1")]
+    pub format: ::prost::alloc::string::String,
+    /// Evidence produced by attestation provider. Its report data is
+    /// SHA-512("zengo-will/attestation/v1" || CertificateHash || ShareEncryptionKey || Nonce)
+    #[prost(bytes = "vec", tag = "2")]
+    pub evidence: ::prost::alloc::vec::Vec<u8>,
+    /// SHA-256 hash of Will's DER-encoded TLS certificate
+    #[prost(bytes = "vec", tag = "3")]
+    pub certificate_hash: ::prost::alloc::vec::Vec<u8>,
+    /// Will's share encryption public key
+    #[prost(bytes = "vec", tag = "4")]
+    pub share_encryption_key: ::prost::alloc::vec::Vec<u8>,
+}
@@ -0,0 +1,467 @@
+/// Proves that request is made by a beneficiary of the share of `PublicKey`. Required in every
+/// request if Will is started with `--require-beneficiary-auth`.
+///
+/// Schnorr signature made by auth key registered with the share (see
+/// `testator.v2.SaveServerShareRequest.BeneficiaryAuthKeys`). Signed message is
+/// "zengo-will/beneficiary-auth" || Nonce || PublicKey, where public key is compressed (33 bytes)
+/// regardless of its encoding in the request.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BeneficiaryAuth {
+    /// Nonce issued by `GetAuthNonce`
+    #[prost(bytes = "vec", tag = "1")]
+    pub nonce: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub auth_key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub commitment: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub response: ::prost::alloc::vec::Vec<u8>,
+}
+/// GetAuthNonce
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetAuthNonceRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AuthNonce {
+    /// Single-use nonce to be signed in `BeneficiaryAuth`
+    #[prost(bytes = "vec", tag = "1")]
+    pub nonce: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub expires_in_seconds: u64,
+}
+/// VerifyServerShare
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VerifyServerShareRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub client_public_share: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    pub auth: ::core::option::Option<BeneficiaryAuth>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VerifyServerShareResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub server_public_share: ::prost::alloc::vec::Vec<u8>,
+    /// Set if Will holds only a piece of server share. Then ServerPublicShare is public piece.
+    #[prost(message, optional, tag = "2")]
+    pub escrow_piece: ::core::option::Option<EscrowPieceInfo>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EscrowPieceInfo {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(uint32, tag = "2")]
+    pub threshold: u32,
+    #[prost(uint32, tag = "3")]
+    pub servers_count: u32,
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub commitments: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+/// GetChallenge
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetChallengeRequest {
+    /// Share the challenge is requested for. Required only to authenticate the request.
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "2")]
+    pub auth: ::core::option::Option<BeneficiaryAuth>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Challenge {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    /// Delay scheme the challenge was issued with: "rsa-vdf", "wesolowski" or "hash-chain"
+    #[prost(string, tag = "2")]
+    pub scheme: ::prost::alloc::string::String,
+    /// How long solving the challenge is expected to take on reference hardware, 0 if unknown
+    #[prost(uint64, tag = "5")]
+    pub expected_delay_seconds: u64,
+    /// Round of the claim the challenge is issued for, starting from 0
+    #[prost(uint32, tag = "6")]
+    pub round: u32,
+    /// Number of challenges to solve before server share is released
+    #[prost(uint32, tag = "7")]
+    pub rounds: u32,
+    #[prost(oneof = "challenge::Input", tags = "3, 4")]
+    pub input: ::core::option::Option<challenge::Input>,
+}
+/// Nested message and enum types in `Challenge`.
+pub mod challenge {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Input {
+        /// Set for "rsa-vdf" and "wesolowski" schemes
+        #[prost(message, tag = "3")]
+        Vdf(super::VdfChallenge),
+        /// Set for "hash-chain" scheme
+        #[prost(message, tag = "4")]
+        HashChain(super::HashChainChallenge),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VdfSetup {
+    /// RSA modulus N
+    #[prost(bytes = "vec", tag = "1")]
+    pub modulus: ::prost::alloc::vec::Vec<u8>,
+    /// Number of sequential squarings
+    #[prost(uint64, tag = "2")]
+    pub t: u64,
+}
+/// Solution is y = x^(2^T) mod N along with proof of its correctness
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VdfChallenge {
+    #[prost(message, optional, tag = "1")]
+    pub setup: ::core::option::Option<VdfSetup>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub x: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct VdfSolution {
+    #[prost(bytes = "vec", tag = "1")]
+    pub y: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub proof: ::prost::alloc::vec::Vec<u8>,
+}
+/// Solution is SHA-256 applied Iterations times to Seed
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HashChainChallenge {
+    #[prost(bytes = "vec", tag = "1")]
+    pub seed: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub iterations: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HashChainSolution {
+    #[prost(bytes = "vec", tag = "1")]
+    pub output: ::prost::alloc::vec::Vec<u8>,
+    /// Chain values after Iterations * i / 256 applications, for i in 1..255
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub checkpoints: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+/// ObtainServerSecretShare
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ObtainServerSecretShareRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: ::prost::alloc::vec::Vec<u8>,
+    /// Not used if beneficiary's share is split between heirs
+    #[prost(bytes = "vec", tag = "2")]
+    pub client_public_share: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    pub solved_challenge: ::core::option::Option<Challenge>,
+    /// Set if beneficiary's share is split between heirs
+    #[prost(message, optional, tag = "6")]
+    pub contribution: ::core::option::Option<HeirContribution>,
+    #[prost(message, optional, tag = "7")]
+    pub auth: ::core::option::Option<BeneficiaryAuth>,
+    /// Not set if heir only contributes to a claim
+    #[prost(oneof = "obtain_server_secret_share_request::Solution", tags = "4, 5")]
+    pub solution: ::core::option::Option<obtain_server_secret_share_request::Solution>,
+}
+/// Nested message and enum types in `ObtainServerSecretShareRequest`.
+pub mod obtain_server_secret_share_request {
+    /// Not set if heir only contributes to a claim
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Solution {
+        #[prost(message, tag = "4")]
+        Vdf(super::VdfSolution),
+        #[prost(message, tag = "5")]
+        HashChain(super::HashChainSolution),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ObtainServerSecretShareResponse {
+    /// Empty if not enough heirs contributed to the claim yet, or not all rounds of the claim are
+    /// completed
+    #[prost(bytes = "vec", tag = "1")]
+    pub server_secret_share: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint32, tag = "2")]
+    pub contributions_collected: u32,
+    #[prost(uint32, tag = "3")]
+    pub contributions_required: u32,
+    /// Index of the piece if Will holds only a piece of server share, 0 otherwise
+    #[prost(uint32, tag = "4")]
+    pub escrow_piece_index: u32,
+    #[prost(uint32, tag = "5")]
+    pub rounds_completed: u32,
+    #[prost(uint32, tag = "6")]
+    pub rounds_required: u32,
+    /// Challenge of the next round can be obtained via GetChallenge in that time
+    #[prost(uint64, tag = "7")]
+    pub next_round_delay_seconds: u64,
+}
+/// Heir proves knowledge of its piece `x`, i.e. Schnorr proof for `PublicPiece = G * x` bound to
+/// message "zengo-will/heir-contribution/v2" || PublicKey || challenge id (LE u128) ||
+/// Round (LE u32) || Index (LE u32)
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeirContribution {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub public_piece: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub proof_commitment: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub proof_response: ::prost::alloc::vec::Vec<u8>,
+}
+#[doc = r" Generated server implementations."]
+pub mod beneficiary_api_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with BeneficiaryApiServer."]
+    #[async_trait]
+    pub trait BeneficiaryApi: Send + Sync + 'static {
+        async fn verify_server_share(
+            &self,
+            request: tonic::Request<super::VerifyServerShareRequest>,
+        ) -> Result<tonic::Response<super::VerifyServerShareResponse>, tonic::Status>;
+        async fn get_challenge(
+            &self,
+            request: tonic::Request<super::GetChallengeRequest>,
+        ) -> Result<tonic::Response<super::Challenge>, tonic::Status>;
+        async fn obtain_server_secret_share(
+            &self,
+            request: tonic::Request<super::ObtainServerSecretShareRequest>,
+        ) -> Result<tonic::Response<super::ObtainServerSecretShareResponse>, tonic::Status>;
+        async fn get_attestation(
+            &self,
+            request: tonic::Request<super::super::super::attestation::GetAttestationRequest>,
+        ) -> Result<tonic::Response<super::super::super::attestation::Attestation>, tonic::Status>;
+        async fn get_auth_nonce(
+            &self,
+            request: tonic::Request<super::GetAuthNonceRequest>,
+        ) -> Result<tonic::Response<super::AuthNonce>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct BeneficiaryApiServer<T: BeneficiaryApi> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: BeneficiaryApi> BeneficiaryApiServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for BeneficiaryApiServer<T>
+    where
+        T: BeneficiaryApi,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/beneficiary.v2.BeneficiaryAPI/VerifyServerShare" => {
+                    #[allow(non_camel_case_types)]
+                    struct VerifyServerShareSvc<T: BeneficiaryApi>(pub Arc<T>);
+                    impl<T: BeneficiaryApi>
+                        tonic::server::UnaryService<super::VerifyServerShareRequest>
+                        for VerifyServerShareSvc<T>
+                    {
+                        type Response = super::VerifyServerShareResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::VerifyServerShareRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).verify_server_share(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = VerifyServerShareSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/beneficiary.v2.BeneficiaryAPI/GetChallenge" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetChallengeSvc<T: BeneficiaryApi>(pub Arc<T>);
+                    impl<T: BeneficiaryApi> tonic::server::UnaryService<super::GetChallengeRequest>
+                        for GetChallengeSvc<T>
+                    {
+                        type Response = super::Challenge;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetChallengeRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_challenge(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetChallengeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/beneficiary.v2.BeneficiaryAPI/ObtainServerSecretShare" => {
+                    #[allow(non_camel_case_types)]
+                    struct ObtainServerSecretShareSvc<T: BeneficiaryApi>(pub Arc<T>);
+                    impl<T: BeneficiaryApi>
+                        tonic::server::UnaryService<super::ObtainServerSecretShareRequest>
+                        for ObtainServerSecretShareSvc<T>
+                    {
+                        type Response = super::ObtainServerSecretShareResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ObtainServerSecretShareRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut =
+                                async move { (*inner).obtain_server_secret_share(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ObtainServerSecretShareSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/beneficiary.v2.BeneficiaryAPI/GetAttestation" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetAttestationSvc<T: BeneficiaryApi>(pub Arc<T>);
+                    impl<T: BeneficiaryApi>
+                        tonic::server::UnaryService<
+                            super::super::super::attestation::GetAttestationRequest,
+                        > for GetAttestationSvc<T>
+                    {
+                        type Response = super::super::super::attestation::Attestation;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::super::super::attestation::GetAttestationRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_attestation(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetAttestationSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/beneficiary.v2.BeneficiaryAPI/GetAuthNonce" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetAuthNonceSvc<T: BeneficiaryApi>(pub Arc<T>);
+                    impl<T: BeneficiaryApi> tonic::server::UnaryService<super::GetAuthNonceRequest>
+                        for GetAuthNonceSvc<T>
+                    {
+                        type Response = super::AuthNonce;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetAuthNonceRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_auth_nonce(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetAuthNonceSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: BeneficiaryApi> Clone for BeneficiaryApiServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: BeneficiaryApi> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: BeneficiaryApi> tonic::transport::NamedService for BeneficiaryApiServer<T> {
+        const NAME: &'static str = "beneficiary.v2.BeneficiaryAPI";
+    }
+}
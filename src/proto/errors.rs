@@ -0,0 +1,81 @@
+/// Attached as details to every error status returned by beneficiary and testator APIs
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ErrorDetails {
+    #[prost(enumeration = "Reason", tag = "1")]
+    pub reason: i32,
+    /// Set if request is expected to succeed when retried in that many seconds
+    #[prost(uint64, tag = "2")]
+    pub retry_after_seconds: u64,
+    /// Set if error relates to the challenge currently issued
+    #[prost(message, optional, tag = "3")]
+    pub current_challenge: ::core::option::Option<CurrentChallenge>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CurrentChallenge {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(uint32, tag = "2")]
+    pub round: u32,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Reason {
+    Unspecified = 0,
+    /// Will failed to handle the request, e.g. persistent store returned an error
+    Internal = 1,
+    /// Request is malformed
+    InvalidRequest = 2,
+    /// Will doesn't hold a share matching provided key and beneficiary's share or heir's
+    /// contribution. Whether Will holds any share for the key is not revealed.
+    ShareNotFound = 3,
+    /// Testator is online: there's no claim in progress, or testator pinged Will since the
+    /// challenge was issued
+    TestatorOnline = 4,
+    /// Challenges can't be issued until delay setup is done
+    DelaySetupInProgress = 5,
+    DelaySetupFailed = 6,
+    /// Challenge of the next claim round isn't issued yet
+    NextRoundPending = 7,
+    /// Round of the solved challenge is already completed
+    RoundAlreadyCompleted = 8,
+    /// Solved challenge differs from the one currently issued
+    ChallengeMismatch = 9,
+    MalformedSolution = 10,
+    IncorrectSolution = 11,
+    NotEnoughContributions = 12,
+    /// Too many solutions are being verified at once
+    VerifierBusy = 13,
+    /// Claim progressed concurrently with the request, it should be retried
+    Conflict = 14,
+    AttestationNotConfigured = 15,
+    /// Requested API version is disabled on this Will
+    ApiVersionDisabled = 16,
+    /// A different server share is already saved for the same public key
+    AlreadyExists = 17,
+    /// Testator's device has to be enrolled before calling this method
+    DeviceNotEnrolled = 18,
+    /// Testator's device certificate is revoked
+    DeviceRevoked = 19,
+    /// Enrollment token is unknown, already used or expired
+    InvalidEnrollmentToken = 20,
+    /// Ping nonce is unknown, already used or expired, or ping signature doesn't verify
+    InvalidPingSignature = 21,
+    /// Will accepts only pings signed with liveness keys of testator's shares
+    PingSignatureRequired = 22,
+    /// Beneficiary auth nonce is unknown, already used or expired, auth key isn't registered with
+    /// the share, or signature doesn't verify
+    InvalidBeneficiaryAuth = 23,
+    /// Will serves only beneficiaries authenticated with auth keys registered by testator
+    BeneficiaryAuthRequired = 24,
+    /// Client sent too many requests, it should retry in `RetryAfterSeconds`
+    RateLimited = 25,
+    /// Claims on the share failed too many times, it's locked out for `RetryAfterSeconds`
+    ClaimLockedOut = 26,
+    /// Reminder token is unknown, or the reminder was sent before the last ping
+    InvalidReminderToken = 27,
+    /// Testator pinged within the inactivity deadline, so the will isn't claimable for
+    /// `RetryAfterSeconds`
+    TestatorActive = 28,
+    /// Will's store change wasn't acknowledged by enough standbys, the request should be retried
+    NotReplicated = 29,
+}
@@ -1,2 +1,19 @@
-pub mod beneficiary;
-pub mod testator;
+pub mod attestation;
+pub mod errors;
+pub mod replication;
+
+pub mod beneficiary {
+    include!("beneficiary.rs");
+
+    pub mod v2 {
+        include!("beneficiary.v2.rs");
+    }
+}
+
+pub mod testator {
+    include!("testator.rs");
+
+    pub mod v2 {
+        include!("testator.v2.rs");
+    }
+}
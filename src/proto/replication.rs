@@ -0,0 +1,198 @@
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Ack {
+    #[prost(uint64, tag = "1")]
+    pub sequence: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Mutation {
+    /// Sequence number of live mutation, 0 for mutations reproducing store state
+    #[prost(uint64, tag = "1")]
+    pub sequence: u64,
+    /// JSON-serialized mutation
+    #[prost(bytes = "vec", tag = "2")]
+    pub mutation: ::prost::alloc::vec::Vec<u8>,
+}
+#[doc = r" Generated client implementations."]
+pub mod replication_api_client {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    pub struct ReplicationApiClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl ReplicationApiClient<tonic::transport::Channel> {
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> ReplicationApiClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::ResponseBody: Body + HttpBody + Send + 'static,
+        T::Error: Into<StdError>,
+        <T::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = tonic::client::Grpc::with_interceptor(inner, interceptor);
+            Self { inner }
+        }
+        #[doc = " Streams mutations of primary's store to a standby. Stream starts with mutations reproducing"]
+        #[doc = " current state of the store, followed by live mutations as they're committed. Standby"]
+        #[doc = " acknowledges every live mutation once it's applied."]
+        pub async fn replicate(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::Ack>,
+        ) -> Result<tonic::Response<tonic::codec::Streaming<super::Mutation>>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/replication.ReplicationAPI/Replicate");
+            self.inner
+                .streaming(request.into_streaming_request(), path, codec)
+                .await
+        }
+    }
+    impl<T: Clone> Clone for ReplicationApiClient<T> {
+        fn clone(&self) -> Self {
+            Self {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+    impl<T> std::fmt::Debug for ReplicationApiClient<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "ReplicationApiClient {{ ... }}")
+        }
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod replication_api_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with ReplicationApiServer."]
+    #[async_trait]
+    pub trait ReplicationApi: Send + Sync + 'static {
+        #[doc = "Server streaming response type for the Replicate method."]
+        type ReplicateStream: Stream<Item = Result<super::Mutation, tonic::Status>>
+            + Send
+            + Sync
+            + 'static;
+        #[doc = " Streams mutations of primary's store to a standby. Stream starts with mutations reproducing"]
+        #[doc = " current state of the store, followed by live mutations as they're committed. Standby"]
+        #[doc = " acknowledges every live mutation once it's applied."]
+        async fn replicate(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::Ack>>,
+        ) -> Result<tonic::Response<Self::ReplicateStream>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct ReplicationApiServer<T: ReplicationApi> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: ReplicationApi> ReplicationApiServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for ReplicationApiServer<T>
+    where
+        T: ReplicationApi,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/replication.ReplicationAPI/Replicate" => {
+                    #[allow(non_camel_case_types)]
+                    struct ReplicateSvc<T: ReplicationApi>(pub Arc<T>);
+                    impl<T: ReplicationApi> tonic::server::StreamingService<super::Ack> for ReplicateSvc<T> {
+                        type Response = super::Mutation;
+                        type ResponseStream = T::ReplicateStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<tonic::Streaming<super::Ack>>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).replicate(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1;
+                        let inner = inner.0;
+                        let method = ReplicateSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: ReplicationApi> Clone for ReplicationApiServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: ReplicationApi> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: ReplicationApi> tonic::transport::NamedService for ReplicationApiServer<T> {
+        const NAME: &'static str = "replication.ReplicationAPI";
+    }
+}
@@ -3,16 +3,73 @@
 pub struct PingRequest {}
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PongResponse {}
+/// GetServerKey
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetServerKeyRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ServerKey {
+    /// Will's public key used to encrypt server secret share
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: ::prost::alloc::vec::Vec<u8>,
+    /// Signature of the public key made by Will's TLS private key. Empty if Will runs without TLS.
+    #[prost(bytes = "vec", tag = "2")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+}
 /// SaveServerShare
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SaveServerShareRequest {
     #[prost(bytes = "vec", tag = "1")]
     pub public_key: ::prost::alloc::vec::Vec<u8>,
+    /// Plaintext server secret share. Deprecated in favour of EncryptedServerSecretShare.
     #[prost(bytes = "vec", tag = "2")]
     pub server_secret_share: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    pub encrypted_server_secret_share: ::core::option::Option<EncryptedShare>,
+    /// Set if beneficiary's share is split between several heirs
+    #[prost(message, optional, tag = "4")]
+    pub beneficiaries: ::core::option::Option<BeneficiaryCommitments>,
+    /// Set if server share is split between several Wills and the share sent is only a piece of it
+    #[prost(message, optional, tag = "5")]
+    pub escrow_piece: ::core::option::Option<EscrowPiece>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SaveServerShareResponse {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EncryptedShare {
+    #[prost(bytes = "vec", tag = "1")]
+    pub ephemeral_key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub nonce: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub ciphertext: ::prost::alloc::vec::Vec<u8>,
+}
+/// Feldman commitments to polynomial which splits beneficiary's share between heirs. Any
+/// `Threshold` of `HeirsCount` heirs must contribute to claim server share. Commitments[0] is
+/// beneficiary's public share.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BeneficiaryCommitments {
+    #[prost(uint32, tag = "1")]
+    pub threshold: u32,
+    #[prost(uint32, tag = "2")]
+    pub heirs_count: u32,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub commitments: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+/// Describes a piece of server share `s` split between `ServersCount` Wills, any `Threshold` of which
+/// are required to reconstruct it. Commitments are made to splitting polynomial in base of
+/// beneficiary's public share `Q`: `Commitments[j] = Q * a_j`, so Commitments[0] must be equal to
+/// PublicKey.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EscrowPiece {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(uint32, tag = "2")]
+    pub threshold: u32,
+    #[prost(uint32, tag = "3")]
+    pub servers_count: u32,
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub commitments: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
 #[doc = r" Generated server implementations."]
 pub mod testator_api_server {
     #![allow(unused_variables, dead_code, missing_docs)]
@@ -24,10 +81,18 @@ pub mod testator_api_server {
             &self,
             request: tonic::Request<super::PingRequest>,
         ) -> Result<tonic::Response<super::PongResponse>, tonic::Status>;
+        async fn get_server_key(
+            &self,
+            request: tonic::Request<super::GetServerKeyRequest>,
+        ) -> Result<tonic::Response<super::ServerKey>, tonic::Status>;
         async fn save_server_share(
             &self,
             request: tonic::Request<super::SaveServerShareRequest>,
         ) -> Result<tonic::Response<super::SaveServerShareResponse>, tonic::Status>;
+        async fn get_attestation(
+            &self,
+            request: tonic::Request<super::super::attestation::GetAttestationRequest>,
+        ) -> Result<tonic::Response<super::super::attestation::Attestation>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct TestatorApiServer<T: TestatorApi> {
@@ -92,6 +157,39 @@ pub mod testator_api_server {
                     };
                     Box::pin(fut)
                 }
+                "/testator.TestatorAPI/GetServerKey" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetServerKeySvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi> tonic::server::UnaryService<super::GetServerKeyRequest>
+                        for GetServerKeySvc<T>
+                    {
+                        type Response = super::ServerKey;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetServerKeyRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_server_key(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetServerKeySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/testator.TestatorAPI/SaveServerShare" => {
                     #[allow(non_camel_case_types)]
                     struct SaveServerShareSvc<T: TestatorApi>(pub Arc<T>);
@@ -125,6 +223,43 @@ pub mod testator_api_server {
                     };
                     Box::pin(fut)
                 }
+                "/testator.TestatorAPI/GetAttestation" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetAttestationSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi>
+                        tonic::server::UnaryService<
+                            super::super::attestation::GetAttestationRequest,
+                        > for GetAttestationSvc<T>
+                    {
+                        type Response = super::super::attestation::Attestation;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::super::attestation::GetAttestationRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_attestation(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetAttestationSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
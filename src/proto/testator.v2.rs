@@ -0,0 +1,751 @@
+/// Ping-Pong
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PingRequest {
+    /// Nonce issued by `GetPingNonce`. Required if the ping is signed.
+    #[prost(bytes = "vec", tag = "1")]
+    pub nonce: ::prost::alloc::vec::Vec<u8>,
+    /// Prove possession of testator's shares. Ping is rejected if any signature is invalid.
+    #[prost(message, repeated, tag = "2")]
+    pub signatures: ::prost::alloc::vec::Vec<LivenessSignature>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PongResponse {}
+/// Schnorr signature made by liveness key registered with shares of all `PublicKeys` (see
+/// `SaveServerShareRequest.LivenessKey`). Signed message is
+/// "zengo-will/ping" || Nonce || PublicKeys[0] || PublicKeys[1] || ..., where public keys are
+/// compressed (33 bytes) regardless of their encoding in the request.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LivenessSignature {
+    /// Joint public keys the ping is made for
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub public_keys: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub commitment: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub response: ::prost::alloc::vec::Vec<u8>,
+}
+/// GetPingNonce
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetPingNonceRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PingNonce {
+    /// Single-use nonce to be signed in `PingRequest`
+    #[prost(bytes = "vec", tag = "1")]
+    pub nonce: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub expires_in_seconds: u64,
+}
+/// KeepAlive
+///
+/// Heartbeat is a `PingRequest`. Signed heartbeat must sign the latest `NextNonce` sent on the same
+/// stream, rather than a nonce issued by `GetPingNonce`.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeepAliveEvent {
+    /// Set if the event acknowledges a heartbeat
+    #[prost(bool, tag = "1")]
+    pub acknowledged: bool,
+    /// How often Will recommends sending heartbeats
+    #[prost(uint64, tag = "2")]
+    pub recommended_interval_seconds: u64,
+    /// Nonce to sign the next heartbeat with
+    #[prost(bytes = "vec", tag = "3")]
+    pub next_nonce: ::prost::alloc::vec::Vec<u8>,
+    /// Set if something happened that testator should know about
+    #[prost(message, optional, tag = "4")]
+    pub warning: ::core::option::Option<KeepAliveWarning>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeepAliveWarning {
+    #[prost(enumeration = "WarningKind", tag = "1")]
+    pub kind: i32,
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+}
+/// GetServerKey
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetServerKeyRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ServerKey {
+    /// Will's public key used to encrypt server secret share
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: ::prost::alloc::vec::Vec<u8>,
+    /// Signature of the public key made by Will's TLS private key. Empty if Will runs without TLS.
+    #[prost(bytes = "vec", tag = "2")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+}
+/// SaveServerShare
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SaveServerShareRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub public_key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "2")]
+    pub encrypted_server_secret_share: ::core::option::Option<EncryptedShare>,
+    /// Set if beneficiary's share is split between several heirs
+    #[prost(message, optional, tag = "3")]
+    pub beneficiaries: ::core::option::Option<BeneficiaryCommitments>,
+    /// Set if server share is split between several Wills and the share sent is only a piece of it
+    #[prost(message, optional, tag = "4")]
+    pub escrow_piece: ::core::option::Option<EscrowPiece>,
+    /// Public key signing pings made for this share, see `LivenessSignature`. Its secret key should be
+    /// derived from testator's wallet share, so a ping proves possession of the share rather than of a
+    /// device.
+    #[prost(bytes = "vec", tag = "5")]
+    pub liveness_key: ::prost::alloc::vec::Vec<u8>,
+    /// Public keys authenticating beneficiaries who claim this share, see
+    /// `beneficiary.v2.BeneficiaryAuth`. Might be beneficiary's public share or a dedicated key.
+    #[prost(bytes = "vec", repeated, tag = "6")]
+    pub beneficiary_auth_keys: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SaveServerShareResponse {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EncryptedShare {
+    #[prost(bytes = "vec", tag = "1")]
+    pub ephemeral_key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub nonce: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub ciphertext: ::prost::alloc::vec::Vec<u8>,
+}
+/// Feldman commitments to polynomial which splits beneficiary's share between heirs. Any
+/// `Threshold` of `HeirsCount` heirs must contribute to claim server share. Commitments[0] is
+/// beneficiary's public share.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BeneficiaryCommitments {
+    #[prost(uint32, tag = "1")]
+    pub threshold: u32,
+    #[prost(uint32, tag = "2")]
+    pub heirs_count: u32,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub commitments: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+/// Describes a piece of server share `s` split between `ServersCount` Wills, any `Threshold` of which
+/// are required to reconstruct it. Commitments are made to splitting polynomial in base of
+/// beneficiary's public share `Q`: `Commitments[j] = Q * a_j`, so Commitments[0] must be equal to
+/// PublicKey.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EscrowPiece {
+    #[prost(uint32, tag = "1")]
+    pub index: u32,
+    #[prost(uint32, tag = "2")]
+    pub threshold: u32,
+    #[prost(uint32, tag = "3")]
+    pub servers_count: u32,
+    #[prost(bytes = "vec", repeated, tag = "4")]
+    pub commitments: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+/// Enroll
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EnrollRequest {
+    /// Token issued by `CreateEnrollmentToken` to add the device to an existing account. If empty,
+    /// a new account is created.
+    #[prost(bytes = "vec", tag = "1")]
+    pub enrollment_token: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EnrollResponse {
+    #[prost(bytes = "vec", tag = "1")]
+    pub account_id: ::prost::alloc::vec::Vec<u8>,
+    /// SHA-256 of SubjectPublicKeyInfo of the device certificate
+    #[prost(bytes = "vec", tag = "2")]
+    pub device_fingerprint: ::prost::alloc::vec::Vec<u8>,
+}
+/// CreateEnrollmentToken
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreateEnrollmentTokenRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EnrollmentToken {
+    /// Single-use token
+    #[prost(bytes = "vec", tag = "1")]
+    pub token: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub expires_in_seconds: u64,
+}
+/// ListDevices
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListDevicesRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListDevicesResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub devices: ::prost::alloc::vec::Vec<Device>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Device {
+    #[prost(bytes = "vec", tag = "1")]
+    pub fingerprint: ::prost::alloc::vec::Vec<u8>,
+    /// Unix time the device was enrolled at
+    #[prost(uint64, tag = "2")]
+    pub enrolled_at: u64,
+    /// Unix time the device was revoked at, or 0 if it isn't revoked
+    #[prost(uint64, tag = "3")]
+    pub revoked_at: u64,
+}
+/// RevokeDevice
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RevokeDeviceRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub fingerprint: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RevokeDeviceResponse {}
+/// AcknowledgeReminder
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AcknowledgeReminderRequest {
+    /// Token carried by the reminder. Only reminders sent since the last ping can be acknowledged.
+    #[prost(string, tag = "1")]
+    pub token: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AcknowledgeReminderResponse {}
+/// GetAuditCheckpoint
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetAuditCheckpointRequest {}
+/// Audit log had `Length` records, the last one hashed to `Head`
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AuditCheckpoint {
+    #[prost(uint64, tag = "1")]
+    pub length: u64,
+    /// Hash of the last record, or 32 zero bytes if the log is empty
+    #[prost(bytes = "vec", tag = "2")]
+    pub head: ::prost::alloc::vec::Vec<u8>,
+    /// Unix time the checkpoint was signed at
+    #[prost(uint64, tag = "3")]
+    pub signed_at: u64,
+    /// Signature of `"zengo-will/audit-checkpoint" || Length || Head || SignedAt` (integers are 8
+    /// bytes big-endian) made by Will's TLS key, or empty if Will runs without TLS
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum WarningKind {
+    Unspecified = 0,
+    /// Beneficiary is claiming the share. Next heartbeat aborts the claim.
+    ChallengeIssued = 1,
+}
+#[doc = r" Generated server implementations."]
+pub mod testator_api_server {
+    #![allow(unused_variables, dead_code, missing_docs)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with TestatorApiServer."]
+    #[async_trait]
+    pub trait TestatorApi: Send + Sync + 'static {
+        async fn ping(
+            &self,
+            request: tonic::Request<super::PingRequest>,
+        ) -> Result<tonic::Response<super::PongResponse>, tonic::Status>;
+        async fn get_ping_nonce(
+            &self,
+            request: tonic::Request<super::GetPingNonceRequest>,
+        ) -> Result<tonic::Response<super::PingNonce>, tonic::Status>;
+        #[doc = "Server streaming response type for the KeepAlive method."]
+        type KeepAliveStream: Stream<Item = Result<super::KeepAliveEvent, tonic::Status>>
+            + Send
+            + Sync
+            + 'static;
+        #[doc = " Keeps testator alive over a single long-lived stream. Every heartbeat is counted as a ping"]
+        #[doc = " and acknowledged. Will also pushes warnings, e.g. when a challenge is issued."]
+        async fn keep_alive(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::PingRequest>>,
+        ) -> Result<tonic::Response<Self::KeepAliveStream>, tonic::Status>;
+        async fn get_server_key(
+            &self,
+            request: tonic::Request<super::GetServerKeyRequest>,
+        ) -> Result<tonic::Response<super::ServerKey>, tonic::Status>;
+        async fn save_server_share(
+            &self,
+            request: tonic::Request<super::SaveServerShareRequest>,
+        ) -> Result<tonic::Response<super::SaveServerShareResponse>, tonic::Status>;
+        async fn get_attestation(
+            &self,
+            request: tonic::Request<super::super::super::attestation::GetAttestationRequest>,
+        ) -> Result<tonic::Response<super::super::super::attestation::Attestation>, tonic::Status>;
+        async fn enroll(
+            &self,
+            request: tonic::Request<super::EnrollRequest>,
+        ) -> Result<tonic::Response<super::EnrollResponse>, tonic::Status>;
+        async fn create_enrollment_token(
+            &self,
+            request: tonic::Request<super::CreateEnrollmentTokenRequest>,
+        ) -> Result<tonic::Response<super::EnrollmentToken>, tonic::Status>;
+        async fn list_devices(
+            &self,
+            request: tonic::Request<super::ListDevicesRequest>,
+        ) -> Result<tonic::Response<super::ListDevicesResponse>, tonic::Status>;
+        async fn revoke_device(
+            &self,
+            request: tonic::Request<super::RevokeDeviceRequest>,
+        ) -> Result<tonic::Response<super::RevokeDeviceResponse>, tonic::Status>;
+        /// Acknowledges liveness reminder sent before the inactivity deadline. Counted as a ping.
+        async fn acknowledge_reminder(
+            &self,
+            request: tonic::Request<super::AcknowledgeReminderRequest>,
+        ) -> Result<tonic::Response<super::AcknowledgeReminderResponse>, tonic::Status>;
+        /// Returns the current checkpoint of the audit log of share and claim events, signed by Will
+        async fn get_audit_checkpoint(
+            &self,
+            request: tonic::Request<super::GetAuditCheckpointRequest>,
+        ) -> Result<tonic::Response<super::AuditCheckpoint>, tonic::Status>;
+    }
+    #[derive(Debug)]
+    pub struct TestatorApiServer<T: TestatorApi> {
+        inner: _Inner<T>,
+    }
+    struct _Inner<T>(Arc<T>, Option<tonic::Interceptor>);
+    impl<T: TestatorApi> TestatorApiServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, None);
+            Self { inner }
+        }
+        pub fn with_interceptor(inner: T, interceptor: impl Into<tonic::Interceptor>) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner, Some(interceptor.into()));
+            Self { inner }
+        }
+    }
+    impl<T, B> Service<http::Request<B>> for TestatorApiServer<T>
+    where
+        T: TestatorApi,
+        B: HttpBody + Send + Sync + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/testator.v2.TestatorAPI/Ping" => {
+                    #[allow(non_camel_case_types)]
+                    struct PingSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi> tonic::server::UnaryService<super::PingRequest> for PingSvc<T> {
+                        type Response = super::PongResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PingRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).ping(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = PingSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/GetPingNonce" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetPingNonceSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi> tonic::server::UnaryService<super::GetPingNonceRequest>
+                        for GetPingNonceSvc<T>
+                    {
+                        type Response = super::PingNonce;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetPingNonceRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_ping_nonce(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetPingNonceSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/KeepAlive" => {
+                    #[allow(non_camel_case_types)]
+                    struct KeepAliveSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi> tonic::server::StreamingService<super::PingRequest> for KeepAliveSvc<T> {
+                        type Response = super::KeepAliveEvent;
+                        type ResponseStream = T::KeepAliveStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<tonic::Streaming<super::PingRequest>>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).keep_alive(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1;
+                        let inner = inner.0;
+                        let method = KeepAliveSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/GetServerKey" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetServerKeySvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi> tonic::server::UnaryService<super::GetServerKeyRequest>
+                        for GetServerKeySvc<T>
+                    {
+                        type Response = super::ServerKey;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetServerKeyRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_server_key(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetServerKeySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/SaveServerShare" => {
+                    #[allow(non_camel_case_types)]
+                    struct SaveServerShareSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi> tonic::server::UnaryService<super::SaveServerShareRequest>
+                        for SaveServerShareSvc<T>
+                    {
+                        type Response = super::SaveServerShareResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SaveServerShareRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).save_server_share(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = SaveServerShareSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/GetAttestation" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetAttestationSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi>
+                        tonic::server::UnaryService<
+                            super::super::super::attestation::GetAttestationRequest,
+                        > for GetAttestationSvc<T>
+                    {
+                        type Response = super::super::super::attestation::Attestation;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<
+                                super::super::super::attestation::GetAttestationRequest,
+                            >,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_attestation(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetAttestationSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/Enroll" => {
+                    #[allow(non_camel_case_types)]
+                    struct EnrollSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi> tonic::server::UnaryService<super::EnrollRequest> for EnrollSvc<T> {
+                        type Response = super::EnrollResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::EnrollRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).enroll(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = EnrollSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/CreateEnrollmentToken" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateEnrollmentTokenSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi>
+                        tonic::server::UnaryService<super::CreateEnrollmentTokenRequest>
+                        for CreateEnrollmentTokenSvc<T>
+                    {
+                        type Response = super::EnrollmentToken;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateEnrollmentTokenRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut =
+                                async move { (*inner).create_enrollment_token(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = CreateEnrollmentTokenSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/ListDevices" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListDevicesSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi> tonic::server::UnaryService<super::ListDevicesRequest> for ListDevicesSvc<T> {
+                        type Response = super::ListDevicesResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListDevicesRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).list_devices(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ListDevicesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/RevokeDevice" => {
+                    #[allow(non_camel_case_types)]
+                    struct RevokeDeviceSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi> tonic::server::UnaryService<super::RevokeDeviceRequest>
+                        for RevokeDeviceSvc<T>
+                    {
+                        type Response = super::RevokeDeviceResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RevokeDeviceRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).revoke_device(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = RevokeDeviceSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/AcknowledgeReminder" => {
+                    #[allow(non_camel_case_types)]
+                    struct AcknowledgeReminderSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi>
+                        tonic::server::UnaryService<super::AcknowledgeReminderRequest>
+                        for AcknowledgeReminderSvc<T>
+                    {
+                        type Response = super::AcknowledgeReminderResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AcknowledgeReminderRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).acknowledge_reminder(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = AcknowledgeReminderSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/testator.v2.TestatorAPI/GetAuditCheckpoint" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetAuditCheckpointSvc<T: TestatorApi>(pub Arc<T>);
+                    impl<T: TestatorApi>
+                        tonic::server::UnaryService<super::GetAuditCheckpointRequest>
+                        for GetAuditCheckpointSvc<T>
+                    {
+                        type Response = super::AuditCheckpoint;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetAuditCheckpointRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_audit_checkpoint(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetAuditCheckpointSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(tonic::body::BoxBody::empty())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: TestatorApi> Clone for TestatorApiServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self { inner }
+        }
+    }
+    impl<T: TestatorApi> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone(), self.1.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: TestatorApi> tonic::transport::NamedService for TestatorApiServer<T> {
+        const NAME: &'static str = "testator.v2.TestatorAPI";
+    }
+}
@@ -0,0 +1,306 @@
+//! Throttling of requests
+//!
+//! [RateLimiter] gives every key (e.g. peer address or public key) a token bucket refilled at a
+//! constant rate. [Lockout] blocks a key for a while after repeated failures.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Number of keys tracked before the ones that don't hold anything back are dropped
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// Time to wait for attempts in progress to finish before a new one can begin
+const IN_PROGRESS_DELAY: Duration = Duration::from_secs(1);
+
+/// Allows `requests` per `period`, e.g. `60/1m`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Budget {
+    pub requests: u32,
+    pub period: Duration,
+}
+
+impl FromStr for Budget {
+    type Err = InvalidBudget;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        let requests = parts.next().ok_or(InvalidBudget)?;
+        let period = parts.next().ok_or(InvalidBudget)?;
+        let requests: u32 = requests.trim().parse().map_err(|_| InvalidBudget)?;
+        let period = parse_duration::parse(period.trim()).map_err(|_| InvalidBudget)?;
+        if requests == 0 || period == Duration::from_secs(0) {
+            return Err(InvalidBudget);
+        }
+        Ok(Self { requests, period })
+    }
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{:?}", self.requests, self.period)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("budget must be `<requests>/<period>`, e.g. `60/1m`")]
+pub struct InvalidBudget;
+
+/// Token buckets of every key
+pub struct RateLimiter<K> {
+    budget: Budget,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            buckets: Default::default(),
+        }
+    }
+
+    /// Takes a token of `key`. Returns how long to wait for the next token if there's none.
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: K, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(self.budget.requests);
+        let per_token = self.budget.period.as_secs_f64() / capacity;
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated_at);
+            (bucket.tokens + elapsed.as_secs_f64() / per_token).min(capacity)
+        };
+
+        let mut buckets = self.buckets.lock().expect("poisoned");
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // Full bucket is the same as no bucket
+            buckets.retain(|_, bucket| refill(bucket) < capacity);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        bucket.tokens = refill(bucket);
+        bucket.updated_at = now;
+        if bucket.tokens >= 1. {
+            bucket.tokens -= 1.;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1. - bucket.tokens) * per_token))
+        }
+    }
+}
+
+/// Locks out a key for `duration` once it fails `max_failures` times in a row
+///
+/// Attempts in progress count as failures until they finish, so concurrent attempts can't make
+/// more than `max_failures` failures before the key is locked out.
+pub struct Lockout<K> {
+    max_failures: u32,
+    duration: Duration,
+    keys: Mutex<HashMap<K, Failures>>,
+}
+
+#[derive(Default)]
+struct Failures {
+    count: u32,
+    in_progress: u32,
+    locked_until: Option<Instant>,
+}
+
+impl Failures {
+    fn holds_back(&self, now: Instant) -> bool {
+        self.in_progress > 0 || self.locked_until.map_or(false, |until| until > now)
+    }
+}
+
+/// How attempt started by [Lockout::begin] ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// Neither success nor failure, e.g. attempt failed because of server state
+    Inconclusive,
+}
+
+impl<K: Hash + Eq> Lockout<K> {
+    pub fn new(max_failures: u32, duration: Duration) -> Self {
+        Self {
+            max_failures,
+            duration,
+            keys: Default::default(),
+        }
+    }
+
+    /// Starts an attempt of `key`, which must be [finished](Self::finish). Returns how long to
+    /// wait if `key` is locked out, or if attempts in progress may lock it out.
+    pub fn begin(&self, key: K) -> Result<(), Duration> {
+        self.begin_at(key, Instant::now())
+    }
+
+    fn begin_at(&self, key: K, now: Instant) -> Result<(), Duration> {
+        let mut keys = self.keys.lock().expect("poisoned");
+        if keys.len() >= PRUNE_THRESHOLD && !keys.contains_key(&key) {
+            // Failures of keys that aren't held back are forgotten like full buckets
+            keys.retain(|_, failures| failures.holds_back(now));
+            if keys.len() >= PRUNE_THRESHOLD {
+                return Ok(());
+            }
+        }
+        let failures = keys.entry(key).or_default();
+        match failures.locked_until {
+            Some(until) if until > now => return Err(until - now),
+            _ => (),
+        }
+        if failures.count + failures.in_progress >= self.max_failures {
+            return Err(IN_PROGRESS_DELAY);
+        }
+        failures.in_progress += 1;
+        Ok(())
+    }
+
+    /// Finishes an attempt of `key` started by [begin](Self::begin). Failure locks `key` out if
+    /// it failed too many times, success forgets its failures. Returns whether `key` got locked
+    /// out.
+    pub fn finish(&self, key: &K, outcome: Outcome) -> bool {
+        self.finish_at(key, outcome, Instant::now())
+    }
+
+    fn finish_at(&self, key: &K, outcome: Outcome, now: Instant) -> bool {
+        let mut keys = self.keys.lock().expect("poisoned");
+        // Attempt isn't tracked if it began while too many keys were held back
+        let failures = match keys.get_mut(key) {
+            Some(failures) => failures,
+            None => return false,
+        };
+        failures.in_progress = failures.in_progress.saturating_sub(1);
+        let mut locked_out = false;
+        match outcome {
+            Outcome::Success => failures.count = 0,
+            Outcome::Failure => {
+                failures.count += 1;
+                if failures.count >= self.max_failures {
+                    failures.count = 0;
+                    failures.locked_until = Some(now + self.duration);
+                    locked_out = true;
+                }
+            }
+            Outcome::Inconclusive => (),
+        }
+        if failures.count == 0 && !failures.holds_back(now) {
+            keys.remove(key);
+        }
+        locked_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_budget() {
+        assert_eq!(
+            "60/1m".parse::<Budget>().unwrap(),
+            Budget {
+                requests: 60,
+                period: Duration::from_secs(60)
+            }
+        );
+        assert!("60".parse::<Budget>().is_err());
+        assert!("0/1m".parse::<Budget>().is_err());
+        assert!("1/0s".parse::<Budget>().is_err());
+    }
+
+    #[test]
+    fn bucket_is_exhausted_and_refilled() {
+        let limiter = RateLimiter::new(Budget {
+            requests: 2,
+            period: Duration::from_secs(10),
+        });
+        let start = Instant::now();
+
+        assert_eq!(limiter.check_at("a", start), Ok(()));
+        assert_eq!(limiter.check_at("a", start), Ok(()));
+        assert_eq!(limiter.check_at("a", start), Err(Duration::from_secs(5)));
+        // Other keys have their own budgets
+        assert_eq!(limiter.check_at("b", start), Ok(()));
+
+        assert_eq!(
+            limiter.check_at("a", start + Duration::from_secs(5)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn lock_out_after_repeated_failures() {
+        let lockout = Lockout::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        let attempt = |key, outcome| -> Result<bool, Duration> {
+            lockout.begin_at(key, start)?;
+            Ok(lockout.finish_at(&key, outcome, start))
+        };
+
+        assert_eq!(attempt("a", Outcome::Failure), Ok(false));
+        assert_eq!(attempt("a", Outcome::Inconclusive), Ok(false));
+        assert_eq!(attempt("a", Outcome::Failure), Ok(true));
+        assert_eq!(lockout.begin_at("a", start), Err(Duration::from_secs(60)));
+        assert_eq!(
+            lockout.begin_at("a", start + Duration::from_secs(60)),
+            Ok(())
+        );
+
+        // Success resets failures
+        attempt("b", Outcome::Failure).unwrap();
+        attempt("b", Outcome::Success).unwrap();
+        assert_eq!(attempt("b", Outcome::Failure), Ok(false));
+    }
+
+    #[test]
+    fn attempts_in_progress_count_as_failures() {
+        let lockout = Lockout::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert_eq!(lockout.begin_at("a", start), Ok(()));
+        assert_eq!(lockout.begin_at("a", start), Ok(()));
+        assert_eq!(lockout.begin_at("a", start), Err(IN_PROGRESS_DELAY));
+        assert!(!lockout.finish_at(&"a", Outcome::Failure, start));
+        assert!(lockout.finish_at(&"a", Outcome::Failure, start));
+        assert_eq!(lockout.begin_at("a", start), Err(Duration::from_secs(60)));
+
+        // Finished attempts aren't tracked
+        assert_eq!(lockout.begin_at("b", start), Ok(()));
+        lockout.finish_at(&"b", Outcome::Inconclusive, start);
+        assert!(!lockout.keys.lock().unwrap().contains_key(&"b"));
+    }
+
+    #[test]
+    fn lockout_is_bounded() {
+        let lockout = Lockout::new(1, Duration::from_secs(60));
+        let start = Instant::now();
+
+        for key in 0..PRUNE_THRESHOLD {
+            lockout.begin_at(key, start).unwrap();
+            assert!(lockout.finish_at(&key, Outcome::Failure, start));
+        }
+        // Keys locked out aren't dropped, so new keys aren't tracked until they expire
+        assert_eq!(lockout.begin_at(PRUNE_THRESHOLD, start), Ok(()));
+        assert!(!lockout.finish_at(&PRUNE_THRESHOLD, Outcome::Failure, start));
+        assert_eq!(lockout.begin_at(0, start), Err(Duration::from_secs(60)));
+
+        let expired = start + Duration::from_secs(60);
+        lockout.begin_at(PRUNE_THRESHOLD, expired).unwrap();
+        assert!(lockout.finish_at(&PRUNE_THRESHOLD, Outcome::Failure, expired));
+        assert_eq!(lockout.keys.lock().unwrap().len(), 1);
+    }
+}
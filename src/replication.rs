@@ -0,0 +1,574 @@
+//! Primary/standby replication of Will store
+//!
+//! Primary streams every committed store mutation to connected standbys and doesn't reply to a
+//! request until required number of standbys acknowledged mutations it made. Therefore a ping
+//! acknowledged to testator is present at that many standbys, and ping counter of the standby
+//! that's ahead of others doesn't go backwards once it's promoted.
+//!
+//! If mutation isn't acknowledged in time (e.g. fewer standbys than required are connected),
+//! request fails, so client retries it. Mutation stays committed at primary: connected standbys
+//! receive it late, and others within store snapshot once they connect.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::SinkExt;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::{broadcast, watch};
+use tonic::transport::Channel;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{info, warn};
+
+use curv::elliptic::curves::traits::ECPoint;
+
+use crate::audit::{AuditEntry, AuditRecord};
+use crate::persistent_store::{
+    Challenge, ClaimProgress, ClaimSession, Device, Mutation, PersistentStore, QueuedNotification,
+    Reminder, SetChallengeError, ShareRecord, StoreError,
+};
+use crate::proto::replication::{
+    replication_api_client::ReplicationApiClient, replication_api_server::ReplicationApi, Ack,
+    Mutation as MutationMsg,
+};
+use crate::sealed::Sealed;
+use crate::testators::{AccountId, DeviceFingerprint};
+
+/// How long primary waits for standbys to acknowledge a mutation
+const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+/// How many live mutations might be buffered for a standby before it's disconnected
+const LIVE_MUTATIONS_CAPACITY: usize = 1024;
+/// Delay before standby reconnects to primary
+const RECONNECT_DELAY: Duration = Duration::from_secs(3);
+
+/// Serialized mutation along with its sequence number
+type LiveMutation = (u64, Arc<Vec<u8>>);
+
+/// Log of live mutations made by primary
+pub struct ReplicationLog {
+    /// Guards sequence numbers assignment, so mutations are broadcasted in order
+    sequence: Mutex<u64>,
+    live: broadcast::Sender<LiveMutation>,
+    /// The latest sequence number acknowledged by each connected standby
+    acked: Mutex<HashMap<u64, u64>>,
+    acked_changed: watch::Sender<()>,
+    acked_changed_rx: watch::Receiver<()>,
+    next_standby_id: AtomicU64,
+    required_acks: usize,
+}
+
+impl ReplicationLog {
+    /// Constructs a log. Every mutation must be acknowledged by at least `required_acks`
+    /// standbys.
+    pub fn new(required_acks: usize) -> Self {
+        let (live, _) = broadcast::channel(LIVE_MUTATIONS_CAPACITY);
+        let (acked_changed, acked_changed_rx) = watch::channel(());
+        Self {
+            sequence: Mutex::new(0),
+            live,
+            acked: Mutex::new(HashMap::new()),
+            acked_changed,
+            acked_changed_rx,
+            next_standby_id: AtomicU64::new(0),
+            required_acks,
+        }
+    }
+
+    /// Sends mutation to standbys and waits until enough of them acknowledge it
+    pub async fn publish<P>(&self, mutation: &Mutation<P>) -> Result<(), ReplicationError>
+    where
+        P: ECPoint + Serialize,
+        P::Scalar: Serialize,
+    {
+        let serialized = Arc::new(serde_json::to_vec(mutation)?);
+        let (sequence, sent) = {
+            let mut sequence = self.sequence.lock().expect("poisoned");
+            *sequence += 1;
+            let sent = self.live.send((*sequence, serialized)).is_ok();
+            (*sequence, sent)
+        };
+        if !sent && self.required_acks > 0 {
+            // No standby is connected
+            return Err(ReplicationError::NotEnoughAcks);
+        }
+
+        let mut acked_changed = self.acked_changed_rx.clone();
+        let wait_for_acks = async {
+            loop {
+                if self.acks_count(sequence) >= self.required_acks {
+                    return Ok(());
+                }
+                if acked_changed.changed().await.is_err() {
+                    return Err(ReplicationError::NotEnoughAcks);
+                }
+            }
+        };
+        tokio::time::timeout(ACK_TIMEOUT, wait_for_acks)
+            .await
+            .map_err(|_| ReplicationError::NotEnoughAcks)?
+    }
+
+    fn acks_count(&self, sequence: u64) -> usize {
+        let acked = self.acked.lock().expect("poisoned");
+        acked.values().filter(|&&acked| acked >= sequence).count()
+    }
+
+    /// Registers a standby and subscribes it to live mutations
+    ///
+    /// Standby must be subscribed before taking store snapshot, so that no mutation is lost.
+    fn subscribe(&self) -> (u64, broadcast::Receiver<LiveMutation>) {
+        let id = self.next_standby_id.fetch_add(1, Ordering::Relaxed);
+        (id, self.live.subscribe())
+    }
+
+    fn ack(&self, standby: u64, sequence: u64) {
+        let mut acked = self.acked.lock().expect("poisoned");
+        let standby_acked = acked.entry(standby).or_insert(0);
+        *standby_acked = (*standby_acked).max(sequence);
+        drop(acked);
+        let _ = self.acked_changed.send(());
+    }
+
+    fn disconnect(&self, standby: u64) {
+        self.acked.lock().expect("poisoned").remove(&standby);
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplicationError {
+    #[error("mutation wasn't acknowledged by enough standbys")]
+    NotEnoughAcks,
+    #[error("serialize mutation: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+impl From<ReplicationError> for StoreError {
+    fn from(error: ReplicationError) -> Self {
+        StoreError::NotReplicated(Box::new(error))
+    }
+}
+
+/// Store that replicates every committed mutation to standbys
+///
+/// Store without replication log doesn't replicate anything and behaves like underlying store.
+#[derive(Clone)]
+pub struct ReplicatedStore<S> {
+    inner: S,
+    log: Option<Arc<ReplicationLog>>,
+}
+
+impl<S> ReplicatedStore<S> {
+    pub fn new(inner: S, log: Option<Arc<ReplicationLog>>) -> Self {
+        Self { inner, log }
+    }
+
+    /// Replicates committed mutation. Mutation can't be rolled back at this point, so failure is
+    /// returned to make the client retry the request.
+    async fn publish<P>(&self, mutation: impl FnOnce() -> Mutation<P>) -> Result<(), StoreError>
+    where
+        P: ECPoint + Serialize,
+        P::Scalar: Serialize,
+    {
+        if let Some(log) = &self.log {
+            log.publish(&mutation()).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S, P> PersistentStore<P> for ReplicatedStore<S>
+where
+    S: PersistentStore<P>,
+    P: ECPoint + Clone + Serialize + Send + Sync + 'static,
+    P::Scalar: Clone + Serialize + Send + Sync,
+{
+    async fn open(path: PathBuf) -> Result<Self, StoreError> {
+        Ok(Self::new(S::open(path).await?, None))
+    }
+
+    async fn add_server_secret_share(&self, share: ShareRecord<P>) -> Result<(), StoreError> {
+        let mutation = Mutation::AddServerSecretShare {
+            public_key: share.public_key.pk_to_key_slice(),
+            server_secret_share: share.server_secret_share.clone(),
+            beneficiaries: share.beneficiaries.clone(),
+            escrow_piece: share.escrow_piece.clone(),
+            liveness_key: share.liveness_key.clone(),
+            beneficiary_keys: share.beneficiary_keys.clone(),
+        };
+        self.inner.add_server_secret_share(share).await?;
+        self.publish(|| mutation).await?;
+        Ok(())
+    }
+
+    async fn get_server_secret_share(
+        &self,
+        public_key: P,
+    ) -> Result<Option<Sealed<P>>, StoreError> {
+        self.inner.get_server_secret_share(public_key).await
+    }
+
+    async fn shares_with_liveness_keys(&self) -> Result<Vec<P>, StoreError> {
+        self.inner.shares_with_liveness_keys().await
+    }
+
+    async fn shares_with_beneficiary_keys(&self) -> Result<Vec<P>, StoreError> {
+        self.inner.shares_with_beneficiary_keys().await
+    }
+
+    async fn increase_ping_counter(&self, pinged_at: u64) -> Result<u128, StoreError> {
+        let counter = self.inner.increase_ping_counter(pinged_at).await?;
+        self.publish(|| Mutation::<P>::SetPingCounter(counter))
+            .await?;
+        self.publish(|| Mutation::<P>::SetLastPingTime(pinged_at))
+            .await?;
+        Ok(counter)
+    }
+
+    async fn get_ping_counter(&self) -> Result<u128, StoreError> {
+        self.inner.get_ping_counter().await
+    }
+
+    async fn get_last_ping_time(&self) -> Result<Option<u64>, StoreError> {
+        self.inner.get_last_ping_time().await
+    }
+
+    async fn set_challenge(&self, challenge: Challenge) -> Result<(), SetChallengeError> {
+        self.inner.set_challenge(challenge.clone()).await?;
+        self.publish(|| Mutation::<P>::SetChallenge(challenge))
+            .await
+            .map_err(SetChallengeError::Store)?;
+        Ok(())
+    }
+
+    async fn get_challenge(&self) -> Result<Option<Challenge>, StoreError> {
+        self.inner.get_challenge().await
+    }
+
+    async fn complete_claim_round(
+        &self,
+        challenge: &Challenge,
+        completed_at: u64,
+    ) -> Result<Option<ClaimProgress>, StoreError> {
+        let progress = self
+            .inner
+            .complete_claim_round(challenge, completed_at)
+            .await?;
+        if let Some(progress) = &progress {
+            self.publish(|| Mutation::<P>::SetClaimProgress(progress.clone()))
+                .await?;
+        }
+        Ok(progress)
+    }
+
+    async fn get_claim_progress(&self) -> Result<Option<ClaimProgress>, StoreError> {
+        self.inner.get_claim_progress().await
+    }
+
+    async fn add_claim_contribution(
+        &self,
+        public_key: P,
+        challenge_id: u128,
+        heir_index: u32,
+    ) -> Result<ClaimSession, StoreError> {
+        let public_key_bytes = public_key.pk_to_key_slice();
+        let session = self
+            .inner
+            .add_claim_contribution(public_key, challenge_id, heir_index)
+            .await?;
+        self.publish(|| Mutation::<P>::SetClaimSession {
+            public_key: public_key_bytes,
+            session: session.clone(),
+        })
+        .await?;
+        Ok(session)
+    }
+
+    /// Share encryption key isn't replicated as a live mutation: it's generated at startup before
+    /// any standby is connected, and standbys receive it within store snapshot.
+    async fn get_or_generate_share_encryption_key(&self) -> Result<P::Scalar, StoreError> {
+        self.inner.get_or_generate_share_encryption_key().await
+    }
+
+    async fn enroll_device(
+        &self,
+        fingerprint: DeviceFingerprint,
+        device: Device,
+    ) -> Result<Device, StoreError> {
+        let device = self.inner.enroll_device(fingerprint, device).await?;
+        self.publish(|| Mutation::<P>::SetDevice {
+            fingerprint,
+            device: device.clone(),
+        })
+        .await?;
+        Ok(device)
+    }
+
+    async fn get_device(
+        &self,
+        fingerprint: DeviceFingerprint,
+    ) -> Result<Option<Device>, StoreError> {
+        self.inner.get_device(fingerprint).await
+    }
+
+    async fn list_devices(
+        &self,
+        account: AccountId,
+    ) -> Result<Vec<(DeviceFingerprint, Device)>, StoreError> {
+        self.inner.list_devices(account).await
+    }
+
+    async fn revoke_device(
+        &self,
+        fingerprint: DeviceFingerprint,
+        revoked_at: u64,
+    ) -> Result<Option<Device>, StoreError> {
+        let device = self.inner.revoke_device(fingerprint, revoked_at).await?;
+        if let Some(device) = &device {
+            self.publish(|| Mutation::<P>::SetDevice {
+                fingerprint,
+                device: device.clone(),
+            })
+            .await?;
+        }
+        Ok(device)
+    }
+
+    async fn enqueue_notification(
+        &self,
+        notification: QueuedNotification,
+    ) -> Result<u64, StoreError> {
+        self.inner.enqueue_notification(notification).await
+    }
+
+    async fn queued_notifications(&self) -> Result<Vec<(u64, QueuedNotification)>, StoreError> {
+        self.inner.queued_notifications().await
+    }
+
+    async fn update_queued_notification(
+        &self,
+        id: u64,
+        notification: Option<QueuedNotification>,
+    ) -> Result<(), StoreError> {
+        self.inner
+            .update_queued_notification(id, notification)
+            .await
+    }
+
+    async fn save_reminder(&self, reminder: Reminder) -> Result<(), StoreError> {
+        self.inner.save_reminder(reminder).await
+    }
+
+    async fn list_reminders(&self, last_ping_at: u64) -> Result<Vec<Reminder>, StoreError> {
+        self.inner.list_reminders(last_ping_at).await
+    }
+
+    async fn append_audit_entry(&self, entry: AuditEntry) -> Result<AuditRecord, StoreError> {
+        let record = self.inner.append_audit_entry(entry).await?;
+        self.publish(|| Mutation::<P>::AppendAuditRecord(record.clone()))
+            .await?;
+        Ok(record)
+    }
+
+    async fn audit_records(&self, from: u64, limit: usize) -> Result<Vec<AuditRecord>, StoreError> {
+        self.inner.audit_records(from, limit).await
+    }
+
+    async fn last_audit_record(&self) -> Result<Option<AuditRecord>, StoreError> {
+        self.inner.last_audit_record().await
+    }
+
+    async fn snapshot(&self) -> Result<Vec<Mutation<P>>, StoreError> {
+        self.inner.snapshot().await
+    }
+
+    async fn apply_mutation(&self, mutation: Mutation<P>) -> Result<(), StoreError> {
+        self.inner.apply_mutation(mutation).await
+    }
+}
+
+/// Serves replication stream to standbys
+pub struct ReplicationServer<S, P> {
+    store: S,
+    log: Arc<ReplicationLog>,
+    _ph: PhantomData<fn() -> P>,
+}
+
+impl<S, P> ReplicationServer<S, P> {
+    pub fn new(store: S, log: Arc<ReplicationLog>) -> Self {
+        Self {
+            store,
+            log,
+            _ph: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<S, P> ReplicationApi for ReplicationServer<S, P>
+where
+    P: ECPoint + Serialize + Send + Sync + 'static,
+    P::Scalar: Serialize,
+    S: PersistentStore<P> + 'static,
+{
+    type ReplicateStream = mpsc::Receiver<Result<MutationMsg, Status>>;
+
+    async fn replicate(
+        &self,
+        request: Request<Streaming<Ack>>,
+    ) -> Result<Response<Self::ReplicateStream>, Status> {
+        let mut acks = request.into_inner();
+        let (standby, mut live) = self.log.subscribe();
+        let snapshot = self
+            .store
+            .snapshot()
+            .await
+            .map_err(|e| Status::internal(format!("take store snapshot: {}", e)))?;
+        let snapshot = snapshot
+            .iter()
+            .map(serde_json::to_vec)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Status::internal(format!("serialize store snapshot: {}", e)))?;
+        info!(standby, "Standby connected");
+
+        let (mut tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            for mutation in snapshot {
+                let msg = MutationMsg {
+                    sequence: 0,
+                    mutation,
+                };
+                if tx.send(Ok(msg)).await.is_err() {
+                    return;
+                }
+            }
+            loop {
+                let msg = match live.recv().await {
+                    Ok((sequence, mutation)) => Ok(MutationMsg {
+                        sequence,
+                        mutation: mutation.as_ref().clone(),
+                    }),
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        Err(Status::aborted("standby is lagging behind"))
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let lagged = msg.is_err();
+                if tx.send(msg).await.is_err() || lagged {
+                    return;
+                }
+            }
+        });
+
+        let log = self.log.clone();
+        tokio::spawn(async move {
+            loop {
+                match acks.message().await {
+                    Ok(Some(ack)) => log.ack(standby, ack.sequence),
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!(standby, "Standby connection failed: {}", e);
+                        break;
+                    }
+                }
+            }
+            log.disconnect(standby);
+            info!(standby, "Standby disconnected");
+        });
+
+        Ok(Response::new(rx))
+    }
+}
+
+/// Follows the primary, applying its mutations to the store. Never returns.
+pub async fn follow<S, P>(primary: tonic::transport::Endpoint, store: S)
+where
+    S: PersistentStore<P>,
+    P: ECPoint + DeserializeOwned,
+    P::Scalar: DeserializeOwned,
+{
+    loop {
+        match follow_once(&primary, &store).await {
+            Ok(()) => warn!("Primary closed replication stream"),
+            Err(e) => warn!("Replication failed: {:#}", e),
+        }
+        match store.get_ping_counter().await {
+            Ok(counter) => info!("Standby is at ping counter {}", counter),
+            Err(e) => warn!("Retrieve ping counter: {}", e),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn follow_once<S, P>(primary: &tonic::transport::Endpoint, store: &S) -> anyhow::Result<()>
+where
+    S: PersistentStore<P>,
+    P: ECPoint + DeserializeOwned,
+    P::Scalar: DeserializeOwned,
+{
+    use anyhow::Context;
+
+    let channel: Channel = primary.connect().await.context("connect to primary")?;
+    let mut client = ReplicationApiClient::new(channel);
+    let (mut acks, acks_rx) = mpsc::channel(16);
+    let mut mutations = client
+        .replicate(Request::new(acks_rx))
+        .await
+        .context("subscribe to replication stream")?
+        .into_inner();
+    info!("Connected to primary");
+
+    while let Some(msg) = mutations.message().await.context("receive mutation")? {
+        let mutation: Mutation<P> =
+            serde_json::from_slice(&msg.mutation).context("parse mutation")?;
+        store
+            .apply_mutation(mutation)
+            .await
+            .context("apply mutation")?;
+        if msg.sequence != 0 {
+            acks.send(Ack {
+                sequence: msg.sequence,
+            })
+            .await
+            .context("acknowledge mutation")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::elliptic::curves::secp256_k1::GE;
+
+    use super::{ReplicationError, ReplicationLog};
+    use crate::persistent_store::Mutation;
+
+    #[tokio::test]
+    async fn publish_waits_for_standby_ack() {
+        let log = std::sync::Arc::new(ReplicationLog::new(1));
+        let (standby, mut live) = log.subscribe();
+
+        let standby_log = log.clone();
+        let standby_task = tokio::spawn(async move {
+            let (sequence, _mutation) = live.recv().await.unwrap();
+            standby_log.ack(standby, sequence);
+        });
+
+        log.publish(&Mutation::<GE>::SetPingCounter(1))
+            .await
+            .unwrap();
+        standby_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn publish_fails_without_standbys() {
+        let log = ReplicationLog::new(1);
+        let result = log.publish(&Mutation::<GE>::SetPingCounter(1)).await;
+        assert!(matches!(result, Err(ReplicationError::NotEnoughAcks)));
+    }
+}
@@ -0,0 +1,475 @@
+//! Revocation of testator client certificates
+//!
+//! Certificates are revoked either by CRLs issued by testator CA, or by serial numbers listed in
+//! a local file which `revoke-testator-cert` command appends to. Both are reloaded periodically,
+//! and revoked certificates are rejected during TLS handshake, so a stolen device is cut off
+//! without replacing `--testator-ca` or restarting Will.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, ensure, Context};
+use rustls::{
+    AllowAnyAuthenticatedClient, Certificate, ClientCertVerified, ClientCertVerifier,
+    DistinguishedNames, RootCertStore, ServerConfig, TLSError,
+};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::der;
+
+/// ecdsa-with-SHA256
+const ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+/// ecdsa-with-SHA384
+const ECDSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+/// sha256WithRSAEncryption
+const RSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+/// sha384WithRSAEncryption
+const RSA_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+/// sha512WithRSAEncryption
+const RSA_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+/// id-Ed25519
+const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+
+/// Serial number of a certificate
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Serial(Vec<u8>);
+
+impl Serial {
+    /// Takes contents of DER-encoded INTEGER. Leading zeros are dropped, so the same serial
+    /// written with and without sign octet is equal.
+    pub fn from_be_bytes(bytes: &[u8]) -> Self {
+        let significant = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+        Self(bytes[significant..].to_vec())
+    }
+
+    /// Parses hex serial, optionally separated by colons as printed by `openssl`
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let mut digits = hex
+            .chars()
+            .filter(|c| *c != ':')
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>()?;
+        if digits.is_empty() {
+            return None;
+        }
+        if digits.len() % 2 == 1 {
+            digits.insert(0, 0);
+        }
+        let bytes: Vec<u8> = digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect();
+        Some(Self::from_be_bytes(&bytes))
+    }
+
+    /// Extracts serial from DER-encoded X.509 certificate
+    pub fn of_certificate(certificate: &[u8]) -> Option<Self> {
+        let fields = der::tbs_certificate_fields(certificate)?;
+        let (serial, _) = der::expect(fields, der::INTEGER)?;
+        Some(Self::from_be_bytes(serial.contents))
+    }
+}
+
+impl fmt::Display for Serial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "00");
+        }
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+/// Serials of currently revoked testator certificates, shared with TLS verifier
+#[derive(Clone, Default)]
+pub struct RevokedSerials(Arc<RwLock<HashSet<Serial>>>);
+
+impl RevokedSerials {
+    pub fn contains(&self, serial: &Serial) -> bool {
+        self.0.read().expect("poisoned").contains(serial)
+    }
+
+    /// Replaces revoked serials. Returns `false` if they didn't change.
+    pub fn replace(&self, serials: HashSet<Serial>) -> bool {
+        let mut current = self.0.write().expect("poisoned");
+        if *current == serials {
+            return false;
+        }
+        *current = serials;
+        true
+    }
+}
+
+/// Where revoked serials are loaded from
+pub struct RevocationSources {
+    crls: Vec<PathBuf>,
+    revoked_serials: Option<PathBuf>,
+    /// DER-encoded certificates of testator CA, which must have signed the CRLs
+    issuers: Vec<Vec<u8>>,
+}
+
+impl RevocationSources {
+    pub fn new(
+        crls: Vec<PathBuf>,
+        revoked_serials: Option<PathBuf>,
+        testator_ca_pem: &[u8],
+    ) -> anyhow::Result<Self> {
+        let issuers: Vec<_> = pem::parse_many(testator_ca_pem)
+            .into_iter()
+            .map(|pem| pem.contents)
+            .collect();
+        ensure!(
+            !issuers.is_empty(),
+            "testator ca doesn't contain certificates"
+        );
+        Ok(Self {
+            crls,
+            revoked_serials,
+            issuers,
+        })
+    }
+
+    /// Reads serials revoked by all CRLs and the local list
+    pub async fn load(&self) -> anyhow::Result<HashSet<Serial>> {
+        let mut serials = HashSet::new();
+        for path in &self.crls {
+            let crl = fs::read(path)
+                .await
+                .with_context(|| format!("read CRL {}", path.display()))?;
+            // CRL might be either PEM or DER encoded
+            let crl = match pem::parse(&crl) {
+                Ok(pem) => pem.contents,
+                Err(_) => crl,
+            };
+            serials.extend(
+                parse_crl(&crl, &self.issuers)
+                    .with_context(|| format!("parse CRL {}", path.display()))?,
+            );
+        }
+        if let Some(path) = &self.revoked_serials {
+            serials.extend(read_revoked_serials(path).await?);
+        }
+        Ok(serials)
+    }
+}
+
+/// Reloads revoked serials every `interval`. Never returns.
+pub async fn reload_periodically(
+    sources: RevocationSources,
+    revoked: RevokedSerials,
+    interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        match sources.load().await {
+            Ok(serials) => {
+                let count = serials.len();
+                if revoked.replace(serials) {
+                    info!(count, "Revoked testator certificates are reloaded");
+                }
+            }
+            Err(e) => warn!(
+                "Reload revoked testator certificates, keeping previous ones: {:#}",
+                e
+            ),
+        }
+    }
+}
+
+/// Appends serial to the file of revoked serials unless it's already there. Returns `false` if
+/// the serial was already revoked.
+pub async fn add_revoked_serial(path: &Path, serial: &Serial) -> anyhow::Result<bool> {
+    if read_revoked_serials(path).await?.contains(serial) {
+        return Ok(false);
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("open {}", path.display()))?;
+    file.write_all(format!("{}\n", serial).as_bytes())
+        .await
+        .with_context(|| format!("write {}", path.display()))?;
+    file.sync_all()
+        .await
+        .with_context(|| format!("write {}", path.display()))?;
+    Ok(true)
+}
+
+/// Reads file listing one hex serial per line. Empty lines and lines starting with `#` are
+/// ignored. Missing file lists nothing.
+async fn read_revoked_serials(path: &Path) -> anyhow::Result<HashSet<Serial>> {
+    let list = match fs::read_to_string(path).await {
+        Ok(list) => list,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+    };
+    parse_revoked_serials(&list).with_context(|| format!("parse {}", path.display()))
+}
+
+fn parse_revoked_serials(list: &str) -> anyhow::Result<HashSet<Serial>> {
+    list.lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            Serial::from_hex(line).ok_or_else(|| anyhow!("invalid serial at line {}", i + 1))
+        })
+        .collect()
+}
+
+/// Returns serials revoked by DER-encoded CRL, which must be signed by one of `issuers`
+fn parse_crl(crl: &[u8], issuers: &[Vec<u8>]) -> anyhow::Result<HashSet<Serial>> {
+    let malformed = || anyhow!("malformed CRL");
+    let (crl, _) = der::expect(crl, der::SEQUENCE).ok_or_else(malformed)?;
+    let (tbs, rest) = der::expect(crl.contents, der::SEQUENCE).ok_or_else(malformed)?;
+    let (algorithm, rest) = der::expect(rest, der::SEQUENCE).ok_or_else(malformed)?;
+    let (signature, _) = der::expect(rest, der::BIT_STRING).ok_or_else(malformed)?;
+    // Signature is a whole number of octets, so number of unused bits is always 0
+    let signature = match signature.contents.split_first() {
+        Some((0, signature)) => signature,
+        _ => return Err(malformed()),
+    };
+    verify_signature(algorithm.contents, tbs.raw, signature, issuers)?;
+
+    // TBSCertList: version (optional), signature, issuer, thisUpdate, nextUpdate (optional),
+    // revokedCertificates (optional), crlExtensions (optional)
+    let fields = der::skip_optional(tbs.contents, der::INTEGER);
+    let (_signature, fields) = der::expect(fields, der::SEQUENCE).ok_or_else(malformed)?;
+    let (_issuer, fields) = der::expect(fields, der::SEQUENCE).ok_or_else(malformed)?;
+    let (_this_update, fields) = der::read(fields).ok_or_else(malformed)?;
+    let fields = der::skip_optional(fields, der::UTC_TIME);
+    let fields = der::skip_optional(fields, der::GENERALIZED_TIME);
+
+    let mut serials = HashSet::new();
+    if let Some((revoked, _)) = der::expect(fields, der::SEQUENCE) {
+        let mut entries = revoked.contents;
+        while !entries.is_empty() {
+            let (entry, rest) = der::expect(entries, der::SEQUENCE).ok_or_else(malformed)?;
+            let (serial, _) = der::expect(entry.contents, der::INTEGER).ok_or_else(malformed)?;
+            serials.insert(Serial::from_be_bytes(serial.contents));
+            entries = rest;
+        }
+    }
+    Ok(serials)
+}
+
+fn verify_signature(
+    algorithm: &[u8],
+    message: &[u8],
+    signature: &[u8],
+    issuers: &[Vec<u8>],
+) -> anyhow::Result<()> {
+    let (oid, _) = der::expect(algorithm, der::OID).ok_or_else(|| anyhow!("malformed CRL"))?;
+    // Curve of ECDSA key isn't a part of the algorithm, so every curve is tried
+    let algorithms: &[&webpki::SignatureAlgorithm] = match oid.contents {
+        ECDSA_SHA256 => &[&webpki::ECDSA_P256_SHA256, &webpki::ECDSA_P384_SHA256],
+        ECDSA_SHA384 => &[&webpki::ECDSA_P256_SHA384, &webpki::ECDSA_P384_SHA384],
+        RSA_SHA256 => &[&webpki::RSA_PKCS1_2048_8192_SHA256],
+        RSA_SHA384 => &[&webpki::RSA_PKCS1_2048_8192_SHA384],
+        RSA_SHA512 => &[&webpki::RSA_PKCS1_2048_8192_SHA512],
+        ED25519 => &[&webpki::ED25519],
+        _ => bail!("unsupported CRL signature algorithm"),
+    };
+    let verified = issuers
+        .iter()
+        .filter_map(|issuer| webpki::EndEntityCert::from(issuer).ok())
+        .any(|issuer| {
+            algorithms
+                .iter()
+                .any(|alg| issuer.verify_signature(alg, message, signature).is_ok())
+        });
+    ensure!(verified, "CRL is not signed by testator CA");
+    Ok(())
+}
+
+/// Client certificate verifier that rejects revoked certificates
+struct RevocationCheckingVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+    revoked: RevokedSerials,
+}
+
+impl ClientCertVerifier for RevocationCheckingVerifier {
+    fn client_auth_root_subjects(
+        &self,
+        sni: Option<&webpki::DNSName>,
+    ) -> Option<DistinguishedNames> {
+        self.inner.client_auth_root_subjects(sni)
+    }
+
+    fn verify_client_cert(
+        &self,
+        presented_certs: &[Certificate],
+        sni: Option<&webpki::DNSName>,
+    ) -> Result<ClientCertVerified, TLSError> {
+        let verified = self.inner.verify_client_cert(presented_certs, sni)?;
+        let serial = presented_certs
+            .first()
+            .and_then(|certificate| Serial::of_certificate(&certificate.0))
+            .ok_or_else(|| TLSError::General("malformed client certificate".to_string()))?;
+        if self.revoked.contains(&serial) {
+            warn!(%serial, "Revoked testator certificate is rejected");
+            return Err(TLSError::General(
+                "client certificate is revoked".to_string(),
+            ));
+        }
+        Ok(verified)
+    }
+}
+
+/// Builds TLS config of testator API which rejects certificates with `revoked` serials
+pub fn testator_tls_config(
+    cert_pem: &[u8],
+    key_pem: &[u8],
+    testator_ca_pem: &[u8],
+    revoked: RevokedSerials,
+) -> anyhow::Result<ServerConfig> {
+    let mut roots = RootCertStore::empty();
+    let (valid, _invalid) = roots
+        .add_pem_file(&mut &testator_ca_pem[..])
+        .map_err(|()| anyhow!("parse testator ca"))?;
+    ensure!(valid > 0, "testator ca doesn't contain valid certificates");
+    let verifier = RevocationCheckingVerifier {
+        inner: AllowAnyAuthenticatedClient::new(roots),
+        revoked,
+    };
+
+    let certs = rustls::internal::pemfile::certs(&mut &cert_pem[..])
+        .map_err(|()| anyhow!("parse server certificate"))?;
+    let mut keys = rustls::internal::pemfile::pkcs8_private_keys(&mut &key_pem[..])
+        .map_err(|()| anyhow!("parse server private key"))?;
+    if keys.is_empty() {
+        keys = rustls::internal::pemfile::rsa_private_keys(&mut &key_pem[..])
+            .map_err(|()| anyhow!("parse server private key"))?;
+    }
+    let key = keys
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("server private key is not found"))?;
+
+    let mut config = ServerConfig::new(Arc::new(verifier));
+    config
+        .set_single_cert(certs, key)
+        .context("set server certificate")?;
+    // gRPC runs over HTTP/2
+    config.set_protocols(&[b"h2".to_vec()]);
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+    use super::*;
+
+    fn tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let length = contents.len();
+        let mut element = vec![tag];
+        if length < 0x80 {
+            element.push(length as u8);
+        } else {
+            element.extend_from_slice(&[0x82, (length >> 8) as u8, length as u8]);
+        }
+        element.extend_from_slice(contents);
+        element
+    }
+
+    fn ca() -> (Vec<u8>, EcdsaKeyPair) {
+        let key_pair = rcgen::KeyPair::generate(&rcgen::PKCS_ECDSA_P256_SHA256).unwrap();
+        let signing_key =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &key_pair.serialize_der())
+                .unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["testator-ca".to_string()]);
+        params.alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        params.key_pair = Some(key_pair);
+        let certificate = rcgen::Certificate::from_params(params)
+            .unwrap()
+            .serialize_der()
+            .unwrap();
+        (certificate, signing_key)
+    }
+
+    fn crl(signing_key: &EcdsaKeyPair, revoked: &[&[u8]]) -> Vec<u8> {
+        let algorithm = tlv(der::SEQUENCE, &tlv(der::OID, ECDSA_SHA256));
+        let time = tlv(der::UTC_TIME, b"210101000000Z");
+        let entries: Vec<u8> = revoked
+            .iter()
+            .flat_map(|serial| {
+                tlv(
+                    der::SEQUENCE,
+                    &[tlv(der::INTEGER, serial), time.clone()].concat(),
+                )
+            })
+            .collect();
+        let tbs = tlv(
+            der::SEQUENCE,
+            &[
+                tlv(der::INTEGER, &[1]),
+                algorithm.clone(),
+                tlv(der::SEQUENCE, &[]),
+                time.clone(),
+                time.clone(),
+                tlv(der::SEQUENCE, &entries),
+            ]
+            .concat(),
+        );
+        let signature = signing_key
+            .sign(&ring::rand::SystemRandom::new(), &tbs)
+            .unwrap();
+        let signature = tlv(der::BIT_STRING, &[&[0], signature.as_ref()].concat());
+        tlv(der::SEQUENCE, &[tbs, algorithm, signature].concat())
+    }
+
+    #[test]
+    fn crl_revokes_listed_serials() {
+        let (ca, signing_key) = ca();
+        let crl = crl(&signing_key, &[&[0x00, 0x80, 0x01], &[0x05]]);
+
+        let serials = parse_crl(&crl, &[ca]).unwrap();
+        assert_eq!(
+            serials,
+            vec![
+                Serial::from_hex("8001").unwrap(),
+                Serial::from_hex("5").unwrap()
+            ]
+            .into_iter()
+            .collect()
+        );
+
+        let (another_ca, _) = self::ca();
+        assert!(parse_crl(&crl, &[another_ca]).is_err());
+    }
+
+    #[test]
+    fn serial_of_certificate_matches_hex() {
+        let mut params = rcgen::CertificateParams::new(vec!["phone".to_string()]);
+        params.serial_number = Some(0x0a1b_2c3d);
+        let certificate = rcgen::Certificate::from_params(params)
+            .unwrap()
+            .serialize_der()
+            .unwrap();
+        assert_eq!(
+            Serial::of_certificate(&certificate),
+            Serial::from_hex("0A:1B:2C:3D")
+        );
+    }
+
+    #[test]
+    fn parse_list_of_revoked_serials() {
+        let serials = parse_revoked_serials("# stolen phone\n0a1b\n\n  00:ff  \n").unwrap();
+        assert_eq!(
+            serials,
+            vec![
+                Serial::from_hex("a1b").unwrap(),
+                Serial::from_hex("ff").unwrap()
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert!(parse_revoked_serials("0a1b\nnot a serial\n").is_err());
+    }
+}
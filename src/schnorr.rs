@@ -0,0 +1,71 @@
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::BigInt;
+use ring::digest;
+
+/// Schnorr proof of knowledge of a discrete log, bound to a message
+///
+/// Proves knowledge of `x` such that `X = G * x` without revealing `x`. Proof can't be reused
+/// for another message, so binding it to a server-chosen context (e.g. a challenge id) prevents
+/// replaying it.
+#[derive(Clone, Debug)]
+pub struct SchnorrProof<P: ECPoint> {
+    pub commitment: P,
+    pub response: P::Scalar,
+}
+
+impl<P> SchnorrProof<P>
+where
+    P: ECPoint + Clone,
+    P::Scalar: Clone,
+{
+    #[cfg(test)]
+    pub fn prove(secret: &P::Scalar, message: &[u8]) -> Self {
+        let nonce = P::Scalar::new_random();
+        let commitment = P::generator() * nonce.clone();
+        let public = P::generator() * secret.clone();
+        let challenge = challenge(&commitment, &public, message);
+        Self {
+            commitment,
+            response: nonce + challenge * secret.clone(),
+        }
+    }
+
+    pub fn verify(&self, public: &P, message: &[u8]) -> bool {
+        let challenge = challenge(&self.commitment, public, message);
+        P::generator() * self.response.clone()
+            == self.commitment.clone() + public.clone() * challenge
+    }
+}
+
+/// Computes `SHA-256(commitment || public || message) mod q`
+fn challenge<P: ECPoint>(commitment: &P, public: &P, message: &[u8]) -> P::Scalar {
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    ctx.update(&commitment.pk_to_key_slice());
+    ctx.update(&public.pk_to_key_slice());
+    ctx.update(message);
+    let hash = BigInt::from_bytes(ctx.finish().as_ref());
+    <P::Scalar as ECScalar>::from(&hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::elliptic::curves::secp256_k1::{FE, GE};
+    use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+
+    use super::SchnorrProof;
+
+    #[test]
+    fn verify_valid_proof() {
+        let secret = FE::new_random();
+        let proof = SchnorrProof::<GE>::prove(&secret, b"message");
+        assert!(proof.verify(&(GE::generator() * secret), b"message"));
+    }
+
+    #[test]
+    fn reject_proof_for_another_message() {
+        let secret = FE::new_random();
+        let proof = SchnorrProof::<GE>::prove(&secret, b"message");
+        assert!(!proof.verify(&(GE::generator() * secret), b"another message"));
+    }
+}
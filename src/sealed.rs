@@ -1,6 +1,10 @@
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use curv::elliptic::curves::traits::ECPoint;
 
-use crate::persistent_store::Challenge;
+use crate::delay::verifier::VerifiedSolution;
+use crate::delay::InvalidSolution;
+use crate::escrow::EscrowPiece;
+use crate::persistent_store::{Challenge, ClaimSession};
 
 /// Seals server's secret share
 ///
@@ -8,23 +12,82 @@ use crate::persistent_store::Challenge;
 pub struct Sealed<P: ECPoint> {
     public_key: P,
     server_share: P::Scalar,
+    beneficiaries: Option<VerifiableSS<P>>,
+    escrow_piece: Option<EscrowPiece<P>>,
+    liveness_key: Option<P>,
+    beneficiary_keys: Vec<P>,
 }
 
 impl<P> Sealed<P>
 where
-    P: ECPoint,
+    P: ECPoint + Clone,
     P::Scalar: Clone,
 {
     pub fn new(public_key: P, server_secret: P::Scalar) -> Self {
         Self {
             public_key,
             server_share: server_secret,
+            beneficiaries: None,
+            escrow_piece: None,
+            liveness_key: None,
+            beneficiary_keys: vec![],
         }
     }
 
+    /// Marks that beneficiary's share is split between several heirs, so share can only be
+    /// obtained via [open_threshold](Self::open_threshold)
+    pub fn with_beneficiaries(mut self, commitments: VerifiableSS<P>) -> Self {
+        self.beneficiaries = Some(commitments);
+        self
+    }
+
+    /// Commitments to beneficiary's share if it's split between several heirs
+    pub fn beneficiaries(&self) -> Option<&VerifiableSS<P>> {
+        self.beneficiaries.as_ref()
+    }
+
+    /// Marks that sealed share is only a piece of server share distributed across several Wills
+    pub fn with_escrow_piece(mut self, piece: EscrowPiece<P>) -> Self {
+        self.escrow_piece = Some(piece);
+        self
+    }
+
+    /// Index and commitments of the piece if server share is distributed across several Wills
+    pub fn escrow_piece(&self) -> Option<&EscrowPiece<P>> {
+        self.escrow_piece.as_ref()
+    }
+
+    /// Sets key which testator signs pings made for this share with
+    pub fn with_liveness_key(mut self, key: P) -> Self {
+        self.liveness_key = Some(key);
+        self
+    }
+
+    /// Key which testator signs pings made for this share with, if registered
+    pub fn liveness_key(&self) -> Option<&P> {
+        self.liveness_key.as_ref()
+    }
+
+    /// Sets keys which beneficiaries authenticate with when claiming this share
+    pub fn with_beneficiary_keys(mut self, keys: Vec<P>) -> Self {
+        self.beneficiary_keys = keys;
+        self
+    }
+
+    /// Keys which beneficiaries authenticate with, empty if none is registered
+    pub fn beneficiary_keys(&self) -> &[P] {
+        &self.beneficiary_keys
+    }
+
     /// Verifies that client share matches server share
+    ///
+    /// If sealed share is a piece of server share, verifies that it's consistent with commitments
+    /// bound to the public key.
     fn verify(&self, client_share_pk: P) -> bool {
-        client_share_pk * self.server_share.clone() == self.public_key
+        match &self.escrow_piece {
+            Some(piece) => piece.verify_piece(&self.server_share, client_share_pk),
+            None => client_share_pk * self.server_share.clone() == self.public_key,
+        }
     }
 
     pub fn verify_and_proof(&self, client_share_pk: P) -> Option<P> {
@@ -38,27 +101,57 @@ where
     /// Tries to open sealed secret share
     ///
     /// Secret share will only be obtained if it matches a client's share and client provided
-    /// correct challenge solution.
+    /// solution of current challenge.
     pub fn open(
         self,
         current_challenge: &Challenge,
         solved_challenge: &Challenge,
-        challenge_solution: rsa_vdf::SolvedVDF,
+        challenge_solution: &VerifiedSolution,
         client_share_pk: P,
     ) -> Result<P::Scalar, OpenError> {
         if current_challenge.id > solved_challenge.id {
             Err(OpenError::OldChallenge)
         } else if current_challenge != solved_challenge {
             Err(OpenError::InvalidChallenge)
-        } else if let Err(reason) = challenge_solution.verify(&current_challenge.challenge) {
-            Err(OpenError::IncorrectSolution(reason))
-        } else if !self.verify(client_share_pk) {
+        } else if !challenge_solution.solves(current_challenge) {
+            Err(OpenError::IncorrectSolution(InvalidSolution::Incorrect))
+        } else if self.beneficiaries.is_some() || !self.verify(client_share_pk) {
             Err(OpenError::ClientShareDoesntMatchServerShare)
         } else {
             Ok(self.server_share)
         }
     }
 
+    /// Tries to open sealed secret share which beneficiary side is split between several heirs
+    ///
+    /// Secret share will only be obtained if enough heirs contributed to the claim session bound
+    /// to current challenge, and client provided correct challenge solution.
+    pub fn open_threshold(
+        self,
+        current_challenge: &Challenge,
+        solved_challenge: &Challenge,
+        challenge_solution: &VerifiedSolution,
+        claim_session: &ClaimSession,
+    ) -> Result<P::Scalar, OpenError> {
+        let beneficiaries = match &self.beneficiaries {
+            Some(b) => b,
+            None => return Err(OpenError::ClientShareDoesntMatchServerShare),
+        };
+        if current_challenge.id > solved_challenge.id {
+            Err(OpenError::OldChallenge)
+        } else if current_challenge != solved_challenge {
+            Err(OpenError::InvalidChallenge)
+        } else if claim_session.challenge_id != current_challenge.id
+            || claim_session.contributors.len() < beneficiaries.parameters.threshold + 1
+        {
+            Err(OpenError::NotEnoughContributions)
+        } else if !challenge_solution.solves(current_challenge) {
+            Err(OpenError::IncorrectSolution(InvalidSolution::Incorrect))
+        } else {
+            Ok(self.server_share)
+        }
+    }
+
     /// Exposes underlying secret share. For tests only.
     #[cfg(test)]
     pub fn secret_share(&self) -> &P::Scalar {
@@ -68,7 +161,8 @@ where
 
 pub enum OpenError {
     ClientShareDoesntMatchServerShare,
-    IncorrectSolution(rsa_vdf::utilities::ErrorReason),
+    IncorrectSolution(InvalidSolution),
     OldChallenge,
     InvalidChallenge,
+    NotEnoughContributions,
 }
@@ -0,0 +1,167 @@
+//! Authentication of beneficiaries with auth keys registered by testator
+
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::BigInt;
+use tonic::{Code, Request, Response, Status};
+
+use crate::beneficiaries::{auth_message, Claimant, AUTH_NONCE_TTL};
+use crate::delay::rounds::unix_time;
+use crate::persistent_store::PersistentStore;
+use crate::proto::beneficiary::v2 as v2b;
+use crate::proto::errors::Reason;
+use crate::schnorr::SchnorrProof;
+
+use super::{status, BeneficiaryServer, ErrorStatus};
+
+impl<S, P> BeneficiaryServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    pub(super) async fn get_auth_nonce(
+        &self,
+        _request: Request<v2b::GetAuthNonceRequest>,
+    ) -> Result<Response<v2b::AuthNonce>, Status> {
+        let nonce = self
+            .auth_nonces
+            .issue(unix_time())
+            .map_err(|e| status::internal(format!("generate auth nonce: {}", e)))?;
+        Ok(Response::new(v2b::AuthNonce {
+            nonce,
+            expires_in_seconds: AUTH_NONCE_TTL.as_secs(),
+        }))
+    }
+
+    /// Verifies that request is made by beneficiary of the share of `public_key`, and uses up the
+    /// nonce. Returns auth key the request is authenticated with.
+    ///
+    /// Request without `auth` is rejected if the share registered beneficiary auth keys, or if
+    /// authentication is required.
+    pub(super) async fn authenticate(
+        &self,
+        public_key: &[u8],
+        auth: Option<&v2b::BeneficiaryAuth>,
+    ) -> Result<Option<Vec<u8>>, Status> {
+        let auth = match auth {
+            Some(auth) => auth,
+            None => {
+                return self
+                    .allow_unauthenticated(Some(public_key))
+                    .await
+                    .map(|()| None)
+            }
+        };
+        let public_key = P::from_bytes(public_key)
+            .map_err(|_e| status::invalid_request("invalid public key"))?;
+        let auth_key = P::from_bytes(&auth.auth_key)
+            .map_err(|_e| status::invalid_request("invalid auth key"))?;
+        let commitment = P::from_bytes(&auth.commitment)
+            .map_err(|_e| status::invalid_request("invalid auth commitment"))?;
+        let response = BigInt::from_bytes(&auth.response);
+        if response >= P::Scalar::q() {
+            return Err(status::invalid_request("invalid auth response"));
+        }
+        let proof = SchnorrProof {
+            commitment,
+            response: <P::Scalar as ECScalar>::from(&response),
+        };
+
+        let share = self
+            .store
+            .get_server_secret_share(public_key.clone())
+            .await
+            .map_err(|e| status::store_error("retrieving server share", e))?;
+        // Unknown share is indistinguishable from a share the key isn't registered with
+        let registered = share.as_ref().map_or(false, |share| {
+            share
+                .beneficiary_keys()
+                .iter()
+                .any(|key| key.pk_to_key_slice() == auth_key.pk_to_key_slice())
+        });
+        if !registered {
+            return Err(invalid_beneficiary_auth(
+                "auth key isn't registered with the share",
+            ));
+        }
+        if !proof.verify(&auth_key, &auth_message(&auth.nonce, &public_key)) {
+            return Err(invalid_beneficiary_auth("auth signature doesn't verify"));
+        }
+        // Nonce is used up only by a valid signature, so forged requests can't burn nonces of
+        // beneficiaries
+        if !self.auth_nonces.redeem(&auth.nonce, unix_time()) {
+            return Err(invalid_beneficiary_auth(
+                "auth nonce is unknown, already used or expired",
+            ));
+        }
+        Ok(Some(auth_key.pk_to_key_slice()))
+    }
+
+    /// Lets unauthenticated request concerning share of `public_key` through unless the share
+    /// registered beneficiary auth keys, or authentication is required. Request that doesn't name
+    /// a share is let through only if no share registered auth keys.
+    ///
+    /// Request concerning a share with auth keys is rejected as if there's no such share, so
+    /// shares that require auth can't be told apart from unknown ones.
+    pub(super) async fn allow_unauthenticated(
+        &self,
+        public_key: Option<&[u8]>,
+    ) -> Result<(), Status> {
+        if self.require_auth {
+            return Err(auth_required());
+        }
+        match public_key {
+            Some(public_key) => {
+                let public_key = P::from_bytes(public_key)
+                    .map_err(|_e| status::invalid_request("invalid public key"))?;
+                let share = self
+                    .store
+                    .get_server_secret_share(public_key)
+                    .await
+                    .map_err(|e| status::store_error("retrieving server share", e))?;
+                if share.map_or(false, |share| !share.beneficiary_keys().is_empty()) {
+                    return Err(status::share_not_found());
+                }
+            }
+            None => {
+                let registered = !self
+                    .store
+                    .shares_with_beneficiary_keys()
+                    .await
+                    .map_err(|e| status::store_error("retrieving shares with auth keys", e))?
+                    .is_empty();
+                if registered {
+                    return Err(auth_required());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Identifies beneficiary that made the request with `auth_key` it's authenticated with
+pub(super) fn claimant<T>(request: &Request<T>, auth_key: Option<Vec<u8>>) -> Claimant {
+    Claimant {
+        auth_key,
+        peer: request.remote_addr().map(|addr| addr.ip()),
+    }
+}
+
+fn auth_required() -> Status {
+    ErrorStatus::new(
+        Code::Unauthenticated,
+        Reason::BeneficiaryAuthRequired,
+        "request must be authenticated with beneficiary auth key",
+    )
+    .into()
+}
+
+fn invalid_beneficiary_auth(message: &str) -> Status {
+    ErrorStatus::new(
+        Code::PermissionDenied,
+        Reason::InvalidBeneficiaryAuth,
+        message,
+    )
+    .into()
+}
@@ -0,0 +1,43 @@
+//! Signed checkpoints of the audit log
+
+use curv::elliptic::curves::traits::ECPoint;
+use tonic::{Request, Response, Status};
+
+use crate::audit::{Checkpoint, CHECKPOINT_CONTEXT};
+use crate::delay::rounds::unix_time;
+use crate::persistent_store::PersistentStore;
+use crate::proto::testator::v2 as v2t;
+
+use super::{status, TestatorServer};
+
+impl<S, P> TestatorServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    pub(super) async fn get_audit_checkpoint(
+        &self,
+        _request: Request<v2t::GetAuditCheckpointRequest>,
+    ) -> Result<Response<v2t::AuditCheckpoint>, Status> {
+        let last = self
+            .store
+            .last_audit_record()
+            .await
+            .map_err(|e| status::store_error("retrieving the last audit record", e))?;
+        let checkpoint = Checkpoint::of(last.as_ref());
+        let signed_at = unix_time();
+        let signature = match &self.checkpoint_signer {
+            Some(signer) => signer
+                .sign_with_context(CHECKPOINT_CONTEXT, &checkpoint.message(signed_at))
+                .map_err(|_| status::internal("sign audit checkpoint"))?,
+            None => vec![],
+        };
+        Ok(Response::new(v2t::AuditCheckpoint {
+            length: checkpoint.length,
+            head: checkpoint.head.to_vec(),
+            signed_at,
+            signature,
+        }))
+    }
+}
@@ -0,0 +1,241 @@
+//! Enrollment of testator devices, and resolving device that made a request
+
+use curv::elliptic::curves::traits::ECPoint;
+use tonic::{Code, Request, Response, Status};
+use tracing::info;
+
+use crate::delay::rounds::unix_time;
+use crate::persistent_store::{Device, PersistentStore};
+use crate::proto::errors::Reason;
+use crate::proto::testator::v2 as v2t;
+use crate::testators::{AccountId, Caller, DeviceFingerprint, ENROLLMENT_TOKEN_TTL};
+
+use super::{status, ErrorStatus, TestatorServer};
+
+impl<S, P> TestatorServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    /// Resolves testator that made the request from its client certificate
+    ///
+    /// Revoked devices are always rejected. Unenrolled devices are rejected only if enrollment is
+    /// required.
+    pub(super) async fn authenticate<T>(&self, request: &Request<T>) -> Result<Caller, Status> {
+        let caller = self.identify(request).await?;
+        self.authorize(caller)
+    }
+
+    /// Authenticates the caller again, e.g. on every message of a long-lived stream, so a device
+    /// revoked meanwhile is rejected
+    pub(super) async fn reauthenticate(&self, caller: Caller) -> Result<Caller, Status> {
+        let caller = match caller {
+            Caller::Device { fingerprint, .. } | Caller::Unenrolled(fingerprint) => {
+                self.resolve_device(fingerprint).await?
+            }
+            Caller::Anonymous => Caller::Anonymous,
+        };
+        self.authorize(caller)
+    }
+
+    fn authorize(&self, caller: Caller) -> Result<Caller, Status> {
+        match caller {
+            caller @ Caller::Device { .. } => Ok(caller),
+            caller if !self.require_enrollment => Ok(caller),
+            _ => Err(device_not_enrolled()),
+        }
+    }
+
+    /// Like [authenticate](Self::authenticate), but never rejects unenrolled devices
+    async fn identify<T>(&self, request: &Request<T>) -> Result<Caller, Status> {
+        let certificates = match request.peer_certs() {
+            Some(certificates) => certificates,
+            None => return Ok(Caller::Anonymous),
+        };
+        // Client's own certificate goes first, followed by intermediate CAs
+        let fingerprint = match certificates.first() {
+            Some(certificate) => DeviceFingerprint::of_certificate(certificate.get_ref())
+                .ok_or_else(|| status::invalid_request("malformed client certificate"))?,
+            None => return Ok(Caller::Anonymous),
+        };
+        self.resolve_device(fingerprint).await
+    }
+
+    /// Looks up device by its fingerprint. Revoked device is rejected.
+    async fn resolve_device(&self, fingerprint: DeviceFingerprint) -> Result<Caller, Status> {
+        let device = self
+            .store
+            .get_device(fingerprint)
+            .await
+            .map_err(|e| status::store_error("retrieving device", e))?;
+        match device {
+            Some(Device {
+                revoked_at: Some(_),
+                ..
+            }) => Err(device_revoked()),
+            Some(device) => Ok(Caller::Device {
+                account: device.account,
+                fingerprint,
+            }),
+            None => Ok(Caller::Unenrolled(fingerprint)),
+        }
+    }
+
+    /// Returns account of the device that made the request, which must be enrolled
+    async fn enrolled_account<T>(&self, request: &Request<T>) -> Result<AccountId, Status> {
+        match self.identify(request).await? {
+            Caller::Device { account, .. } => Ok(account),
+            Caller::Unenrolled(_) | Caller::Anonymous => Err(device_not_enrolled()),
+        }
+    }
+
+    pub(super) async fn enroll(
+        &self,
+        request: Request<v2t::EnrollRequest>,
+    ) -> Result<Response<v2t::EnrollResponse>, Status> {
+        let caller = self.identify(&request).await?;
+        let fingerprint = match caller {
+            Caller::Device { fingerprint, .. } | Caller::Unenrolled(fingerprint) => fingerprint,
+            Caller::Anonymous => {
+                return Err(ErrorStatus::new(
+                    Code::Unauthenticated,
+                    Reason::InvalidRequest,
+                    "client certificate is required to enroll",
+                )
+                .into())
+            }
+        };
+        let token = &request.get_ref().enrollment_token;
+        let account = match caller {
+            _ if !token.is_empty() => self.enrollment_tokens.redeem(token).ok_or_else(|| {
+                Status::from(ErrorStatus::new(
+                    Code::PermissionDenied,
+                    Reason::InvalidEnrollmentToken,
+                    "enrollment token is unknown, already used or expired",
+                ))
+            })?,
+            // Enrolling the same device again is not an error
+            Caller::Device { account, .. } => account,
+            _ => AccountId::random().map_err(|_| status::internal("generate account id"))?,
+        };
+
+        let device = self
+            .store
+            .enroll_device(
+                fingerprint,
+                Device {
+                    account,
+                    enrolled_at: unix_time(),
+                    revoked_at: None,
+                },
+            )
+            .await
+            .map_err(|e| status::store_error("enrolling device", e))?;
+        if device.revoked_at.is_some() {
+            return Err(device_revoked());
+        }
+        if device.account != account {
+            return Err(ErrorStatus::new(
+                Code::AlreadyExists,
+                Reason::AlreadyExists,
+                "device is enrolled to another account",
+            )
+            .into());
+        }
+        info!(%fingerprint, %account, "Device is enrolled");
+
+        Ok(Response::new(v2t::EnrollResponse {
+            account_id: account.0.to_vec(),
+            device_fingerprint: fingerprint.0.to_vec(),
+        }))
+    }
+
+    pub(super) async fn create_enrollment_token(
+        &self,
+        request: Request<v2t::CreateEnrollmentTokenRequest>,
+    ) -> Result<Response<v2t::EnrollmentToken>, Status> {
+        let account = self.enrolled_account(&request).await?;
+        let token = self
+            .enrollment_tokens
+            .issue(account)
+            .map_err(|_| status::internal("generate enrollment token"))?;
+        Ok(Response::new(v2t::EnrollmentToken {
+            token: token.to_vec(),
+            expires_in_seconds: ENROLLMENT_TOKEN_TTL.as_secs(),
+        }))
+    }
+
+    pub(super) async fn list_devices(
+        &self,
+        request: Request<v2t::ListDevicesRequest>,
+    ) -> Result<Response<v2t::ListDevicesResponse>, Status> {
+        let account = self.enrolled_account(&request).await?;
+        let devices = self
+            .store
+            .list_devices(account)
+            .await
+            .map_err(|e| status::store_error("listing devices", e))?;
+        Ok(Response::new(v2t::ListDevicesResponse {
+            devices: devices
+                .into_iter()
+                .map(|(fingerprint, device)| v2t::Device {
+                    fingerprint: fingerprint.0.to_vec(),
+                    enrolled_at: device.enrolled_at,
+                    revoked_at: device.revoked_at.unwrap_or(0),
+                })
+                .collect(),
+        }))
+    }
+
+    pub(super) async fn revoke_device(
+        &self,
+        request: Request<v2t::RevokeDeviceRequest>,
+    ) -> Result<Response<v2t::RevokeDeviceResponse>, Status> {
+        let account = self.enrolled_account(&request).await?;
+        let fingerprint = DeviceFingerprint::from_bytes(&request.get_ref().fingerprint)
+            .ok_or_else(|| status::invalid_request("invalid device fingerprint"))?;
+        let device = self
+            .store
+            .get_device(fingerprint)
+            .await
+            .map_err(|e| status::store_error("retrieving device", e))?;
+        // Devices of other accounts are indistinguishable from unknown ones
+        match device {
+            Some(device) if device.account == account => (),
+            _ => {
+                return Err(ErrorStatus::new(
+                    Code::NotFound,
+                    Reason::DeviceNotEnrolled,
+                    "device is not found",
+                )
+                .into())
+            }
+        }
+        self.store
+            .revoke_device(fingerprint, unix_time())
+            .await
+            .map_err(|e| status::store_error("revoking device", e))?;
+        info!(%fingerprint, %account, "Device is revoked");
+
+        Ok(Response::new(v2t::RevokeDeviceResponse {}))
+    }
+}
+
+fn device_not_enrolled() -> Status {
+    ErrorStatus::new(
+        Code::PermissionDenied,
+        Reason::DeviceNotEnrolled,
+        "device is not enrolled",
+    )
+    .into()
+}
+
+fn device_revoked() -> Status {
+    ErrorStatus::new(
+        Code::PermissionDenied,
+        Reason::DeviceRevoked,
+        "device certificate is revoked",
+    )
+    .into()
+}
@@ -0,0 +1,199 @@
+//! Keepalive sessions: heartbeats over a single bidirectional stream
+
+use std::sync::Arc;
+
+use curv::elliptic::curves::traits::ECPoint;
+use futures::channel::mpsc;
+use futures::SinkExt;
+use tokio::sync::watch;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{debug, info};
+
+use crate::liveness::random_nonce;
+use crate::persistent_store::PersistentStore;
+use crate::proto::testator::{self as v1t, v2 as v2t};
+use crate::testators::Caller;
+
+use super::liveness::invalid_ping_signature;
+use super::{status, TestatorServer};
+
+/// Stream of events sent to testator during keepalive session
+pub type KeepAliveEvents = mpsc::Receiver<Result<v2t::KeepAliveEvent, Status>>;
+
+/// Notifies keepalive sessions that a challenge is issued
+///
+/// Shared by beneficiary and testator servers. Notification carries no data: sessions read the
+/// challenge from the store, so a spurious notification is harmless.
+#[derive(Clone)]
+pub struct ChallengeEvents {
+    sender: Arc<watch::Sender<()>>,
+    receiver: watch::Receiver<()>,
+}
+
+impl Default for ChallengeEvents {
+    fn default() -> Self {
+        let (sender, receiver) = watch::channel(());
+        Self {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+}
+
+impl ChallengeEvents {
+    pub fn notify(&self) {
+        // Can't fail as `self` holds a receiver
+        let _ = self.sender.send(());
+    }
+
+    fn subscribe(&self) -> watch::Receiver<()> {
+        self.receiver.clone()
+    }
+}
+
+impl<S, P> TestatorServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    /// Starts keepalive session of authenticated `caller`
+    pub(super) fn keep_alive(
+        &self,
+        caller: Caller,
+        request: Request<Streaming<v2t::PingRequest>>,
+    ) -> Result<Response<KeepAliveEvents>, Status> {
+        let session = KeepAliveSession {
+            server: self.clone(),
+            caller,
+            nonce: random_nonce().map_err(|_| status::internal("generate ping nonce"))?,
+            warned: None,
+        };
+        let (events, stream) = mpsc::channel(4);
+        tokio::spawn(session.run(request.into_inner(), events));
+        Ok(Response::new(stream))
+    }
+}
+
+struct KeepAliveSession<S, P: ECPoint> {
+    server: TestatorServer<S, P>,
+    caller: Caller,
+    /// Nonce the next signed heartbeat must sign
+    nonce: [u8; 32],
+    /// Id and round of the challenge testator is already warned about
+    warned: Option<(u128, u32)>,
+}
+
+impl<S, P> KeepAliveSession<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    async fn run(
+        mut self,
+        mut heartbeats: Streaming<v2t::PingRequest>,
+        events: mpsc::Sender<Result<v2t::KeepAliveEvent, Status>>,
+    ) {
+        info!(caller = %self.caller, "Keepalive session started");
+        let mut challenges = self.server.challenge_events.subscribe();
+        // The first event tells the nonce and recommended interval, and warns about a challenge
+        // issued before the session started
+        let mut event = Some(match self.challenge_warning().await {
+            Ok(warning) => Ok(self.event(false, warning)),
+            Err(e) => Err(e),
+        });
+        loop {
+            // Nothing is sent if there's no new challenge to warn about
+            if let Some(event) = event.take() {
+                // Session ends on the first error
+                let failed = event.is_err();
+                if events.send(event).await.is_err() || failed {
+                    break;
+                }
+            }
+            event = tokio::select! {
+                heartbeat = heartbeats.message() => match heartbeat {
+                    Ok(Some(heartbeat)) => Some(self.heartbeat(heartbeat).await),
+                    Ok(None) => break,
+                    Err(e) => {
+                        debug!(caller = %self.caller, "Keepalive stream failed: {}", e);
+                        break;
+                    }
+                },
+                Ok(()) = challenges.changed() => match self.challenge_warning().await {
+                    Ok(Some(warning)) => Some(Ok(self.event(false, Some(warning)))),
+                    Ok(None) => None,
+                    Err(e) => Some(Err(e)),
+                },
+            };
+        }
+        info!(caller = %self.caller, "Keepalive session ended");
+    }
+
+    /// Counts heartbeat as a ping
+    async fn heartbeat(
+        &mut self,
+        heartbeat: v2t::PingRequest,
+    ) -> Result<v2t::KeepAliveEvent, Status> {
+        self.caller = self.server.reauthenticate(self.caller).await?;
+        let mut signed_for = vec![];
+        if !heartbeat.signatures.is_empty() {
+            if heartbeat.nonce != self.nonce {
+                return Err(invalid_ping_signature(
+                    "heartbeat must sign the latest nonce sent on the stream",
+                ));
+            }
+            signed_for = self
+                .server
+                .verify_ping_signatures(&self.nonce, &heartbeat.signatures)
+                .await?;
+        }
+        self.server
+            .ping(&signed_for, Request::new(v1t::PingRequest {}))
+            .await?;
+
+        self.nonce = random_nonce().map_err(|_| status::internal("generate ping nonce"))?;
+        // Ping invalidates the challenge testator was warned about
+        self.warned = None;
+        Ok(self.event(true, None))
+    }
+
+    /// Returns warning about currently issued challenge, unless testator is already warned about it
+    async fn challenge_warning(&mut self) -> Result<Option<v2t::KeepAliveWarning>, Status> {
+        let challenge = self
+            .server
+            .store
+            .get_challenge()
+            .await
+            .map_err(|e| status::store_error("retrieving challenge", e))?;
+        let challenge = match challenge {
+            Some(challenge) => challenge,
+            None => return Ok(None),
+        };
+        if self.warned == Some((challenge.id, challenge.round)) {
+            return Ok(None);
+        }
+        self.warned = Some((challenge.id, challenge.round));
+        Ok(Some(v2t::KeepAliveWarning {
+            kind: v2t::WarningKind::ChallengeIssued as i32,
+            message: format!(
+                "beneficiary is claiming the share, challenge of round {} is issued",
+                challenge.round
+            ),
+        }))
+    }
+
+    fn event(
+        &self,
+        acknowledged: bool,
+        warning: Option<v2t::KeepAliveWarning>,
+    ) -> v2t::KeepAliveEvent {
+        v2t::KeepAliveEvent {
+            acknowledged,
+            recommended_interval_seconds: self.server.keepalive_interval.as_secs(),
+            next_nonce: self.nonce.to_vec(),
+            warning,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Pings signed with liveness keys of testator's shares
+
+use curv::arithmetic::Converter;
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::BigInt;
+use tonic::{Code, Request, Response, Status};
+
+use crate::liveness::{ping_message, PING_NONCE_TTL};
+use crate::persistent_store::PersistentStore;
+use crate::proto::errors::Reason;
+use crate::proto::testator::v2 as v2t;
+use crate::schnorr::SchnorrProof;
+
+use super::{status, ErrorStatus, TestatorServer};
+
+impl<S, P> TestatorServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    pub(super) async fn get_ping_nonce(
+        &self,
+        _request: Request<v2t::GetPingNonceRequest>,
+    ) -> Result<Response<v2t::PingNonce>, Status> {
+        let nonce = self
+            .ping_nonces
+            .issue()
+            .map_err(status::issue_nonce_error)?;
+        Ok(Response::new(v2t::PingNonce {
+            nonce: nonce.to_vec(),
+            expires_in_seconds: PING_NONCE_TTL.as_secs(),
+        }))
+    }
+
+    /// Verifies every signature of the ping and uses up its nonce
+    ///
+    /// Returns public keys covered by the signatures, which are empty if ping isn't signed at all.
+    pub(super) async fn verify_ping(&self, ping: &v2t::PingRequest) -> Result<Vec<P>, Status> {
+        if ping.signatures.is_empty() {
+            return Ok(vec![]);
+        }
+        if !self.ping_nonces.redeem(&ping.nonce) {
+            return Err(invalid_ping_signature(
+                "ping nonce is unknown, already used or expired",
+            ));
+        }
+        self.verify_ping_signatures(&ping.nonce, &ping.signatures)
+            .await
+    }
+
+    /// Verifies signatures made over `nonce`, which must be checked to be fresh by the caller
+    pub(super) async fn verify_ping_signatures(
+        &self,
+        nonce: &[u8],
+        signatures: &[v2t::LivenessSignature],
+    ) -> Result<Vec<P>, Status> {
+        let mut signed_for = vec![];
+        for signature in signatures {
+            let public_keys = self.verify_liveness_signature(nonce, signature).await?;
+            signed_for.extend(public_keys);
+        }
+        Ok(signed_for)
+    }
+
+    /// Returns public keys covered by the signature
+    async fn verify_liveness_signature(
+        &self,
+        nonce: &[u8],
+        signature: &v2t::LivenessSignature,
+    ) -> Result<Vec<P>, Status> {
+        let public_keys = signature
+            .public_keys
+            .iter()
+            .map(|public_key| P::from_bytes(public_key))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_e| status::invalid_request("invalid public key"))?;
+        let mut liveness_key: Option<P> = None;
+        for public_key in &public_keys {
+            let share = self
+                .store
+                .get_server_secret_share(public_key.clone())
+                .await
+                .map_err(|e| status::store_error("retrieving server share", e))?;
+            let key = match share.as_ref().and_then(|share| share.liveness_key()) {
+                Some(key) => key.clone(),
+                None => {
+                    return Err(invalid_ping_signature(
+                        "liveness key isn't registered for the public key",
+                    ))
+                }
+            };
+            match &liveness_key {
+                Some(k) if k.pk_to_key_slice() != key.pk_to_key_slice() => {
+                    return Err(invalid_ping_signature(
+                        "public keys are registered with different liveness keys",
+                    ))
+                }
+                Some(_) => (),
+                None => liveness_key = Some(key),
+            }
+        }
+        let liveness_key = liveness_key
+            .ok_or_else(|| status::invalid_request("signature doesn't cover any public key"))?;
+
+        let commitment = P::from_bytes(&signature.commitment)
+            .map_err(|_e| status::invalid_request("invalid signature commitment"))?;
+        let response = BigInt::from_bytes(&signature.response);
+        if response >= P::Scalar::q() {
+            return Err(status::invalid_request("invalid signature response"));
+        }
+        let proof = SchnorrProof {
+            commitment,
+            response: <P::Scalar as ECScalar>::from(&response),
+        };
+        if !proof.verify(&liveness_key, &ping_message(nonce, &public_keys)) {
+            return Err(invalid_ping_signature("ping signature doesn't verify"));
+        }
+        Ok(public_keys)
+    }
+}
+
+pub(super) fn invalid_ping_signature(message: &str) -> Status {
+    ErrorStatus::new(
+        Code::PermissionDenied,
+        Reason::InvalidPingSignature,
+        message,
+    )
+    .into()
+}
@@ -0,0 +1,1249 @@
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::sync::Arc;
+use std::time::Duration;
+
+use curv::arithmetic::{Converter, Zero};
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+    ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::BigInt;
+use tonic::{Code, Request, Response, Status};
+use tracing::info;
+
+use crate::attestation::Attestor;
+use crate::audit::{self, AuditEvent};
+use crate::beneficiaries::{self, AuthNonces, Claimant, Contribution};
+use crate::delay::rounds::{unix_time, ClaimRounds};
+use crate::delay::setup::{DelaySetup, NotReady};
+use crate::delay::verifier::{VerifiedSolution, Verifier, VerifyError};
+use crate::delay::{self, InvalidSolution, Scheme};
+use crate::escrow;
+use crate::liveness::PingNonces;
+use crate::notifications::reminders::Reminders;
+use crate::notifications::{Event, Notifier};
+use crate::persistent_store::{
+    ClaimProgress, PersistentStore, SetChallengeError, ShareRecord, StoreError,
+};
+use crate::proto::attestation::{Attestation, GetAttestationRequest};
+use crate::proto::beneficiary::{
+    Challenge, EscrowPieceInfo, GetChallengeRequest, HeirContribution,
+    ObtainServerSecretShareRequest, ObtainServerSecretShareResponse, VerifyServerShareRequest,
+    VerifyServerShareResponse,
+};
+use crate::proto::errors::Reason;
+use crate::proto::testator::{
+    BeneficiaryCommitments, EscrowPiece, GetServerKeyRequest, PingRequest, PongResponse,
+    SaveServerShareRequest, SaveServerShareResponse, ServerKey,
+};
+use crate::schnorr::SchnorrProof;
+use crate::sealed::OpenError;
+use crate::share_encryption::{ShareDecryptionKey, TlsKeySigner};
+use crate::testators::{Caller, EnrollmentTokens};
+
+mod beneficiary_auth;
+mod checkpoints;
+mod enrollment;
+mod keepalive;
+mod liveness;
+mod pings;
+mod rate_limit;
+mod reminders;
+mod status;
+mod v1;
+mod v2;
+
+pub use keepalive::ChallengeEvents;
+pub use pings::CoalescedPings;
+pub use rate_limit::{BeneficiaryLimits, BeneficiaryLimitsLayer};
+use status::ErrorStatus;
+pub use v1::{reject_calls as reject_v1_calls, DeprecationHook};
+
+/// Serves beneficiary API. Cloned server shares its state with the original, so single server
+/// backs every API version.
+#[derive(Clone)]
+pub struct BeneficiaryServer<S, P> {
+    delay: DelaySetup,
+    rounds: ClaimRounds,
+    verifier: Verifier,
+    store: S,
+    attestor: Option<Arc<Attestor>>,
+    deprecation_hook: DeprecationHook,
+    challenge_events: ChallengeEvents,
+    coalesced_pings: Option<CoalescedPings>,
+    auth_nonces: Arc<AuthNonces>,
+    require_auth: bool,
+    inactivity_deadline: Option<Duration>,
+    notifier: Option<Notifier<S, P>>,
+    _ph: PhantomData<fn() -> P>,
+}
+
+impl<S, P> BeneficiaryServer<S, P>
+where
+    S: PersistentStore<P>,
+    P: ECPoint,
+{
+    /// Constructs beneficiary server
+    ///
+    /// New challenges are issued with `delay` scheme once its setup is done. Challenges issued with
+    /// other schemes are still accepted while they're up-to-date. Server share is released once
+    /// challenges of all `rounds` are solved. Solutions are verified by `verifier`.
+    pub fn new(
+        delay: DelaySetup,
+        rounds: ClaimRounds,
+        verifier: Verifier,
+        persistent_store: S,
+        attestor: Option<Arc<Attestor>>,
+    ) -> Self {
+        Self {
+            delay,
+            rounds,
+            verifier,
+            store: persistent_store,
+            attestor,
+            deprecation_hook: v1::warn_once(),
+            challenge_events: Default::default(),
+            coalesced_pings: None,
+            auth_nonces: Default::default(),
+            require_auth: false,
+            inactivity_deadline: None,
+            notifier: None,
+            _ph: PhantomData,
+        }
+    }
+
+    /// Sets hook called on every request to deprecated v1 API. By default, a warning is logged once
+    /// per method.
+    pub fn with_deprecation_hook(self, hook: DeprecationHook) -> Self {
+        Self {
+            deprecation_hook: hook,
+            ..self
+        }
+    }
+
+    /// Notifies testator's keepalive sessions about issued challenges via `events`, which must be
+    /// shared with [TestatorServer::with_challenge_events]
+    pub fn with_challenge_events(self, events: ChallengeEvents) -> Self {
+        Self {
+            challenge_events: events,
+            ..self
+        }
+    }
+
+    /// Persists pings coalesced by [TestatorServer::with_coalesced_pings] before issuing a
+    /// challenge. `pings` must be shared with testator server.
+    pub fn with_coalesced_pings(self, pings: CoalescedPings) -> Self {
+        Self {
+            coalesced_pings: Some(pings),
+            ..self
+        }
+    }
+
+    /// Rejects requests that aren't authenticated with beneficiary auth keys registered with the
+    /// share, including all v1 requests except attestation. By default, unauthenticated requests
+    /// are rejected only if they concern a share that registered auth keys.
+    pub fn with_required_auth(self) -> Self {
+        Self {
+            require_auth: true,
+            ..self
+        }
+    }
+
+    /// Issues no challenge until testator hasn't pinged for `deadline`, so the will isn't
+    /// claimable before then. Saving a share counts as a ping. By default, challenge is issued
+    /// regardless of the last ping.
+    pub fn with_inactivity_deadline(self, deadline: Duration) -> Self {
+        Self {
+            inactivity_deadline: Some(deadline),
+            ..self
+        }
+    }
+
+    /// Notifies testator via `notifier` when challenge is issued, claim round is completed, or
+    /// server share is released
+    pub fn with_notifier(self, notifier: Notifier<S, P>) -> Self {
+        Self {
+            notifier: Some(notifier),
+            ..self
+        }
+    }
+
+    async fn notify(&self, event: Event) {
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(event).await
+        }
+    }
+
+    /// Rejects claim if testator pinged within the inactivity deadline
+    async fn check_inactivity_deadline(&self) -> Result<(), Status> {
+        let deadline = match self.inactivity_deadline {
+            Some(deadline) => deadline,
+            None => return Ok(()),
+        };
+        let last_ping_at = self
+            .store
+            .get_last_ping_time()
+            .await
+            .map_err(|e| status::store_error("retrieving last ping time", e))?;
+        // Saving a share counts as a ping, so there's no share to claim if testator never pinged
+        let claimable_at = match last_ping_at {
+            Some(at) => at.saturating_add(deadline.as_secs()),
+            None => return Ok(()),
+        };
+        let now = unix_time();
+        if now < claimable_at {
+            return Err(ErrorStatus::new(
+                Code::FailedPrecondition,
+                Reason::TestatorActive,
+                "testator pinged within the inactivity deadline",
+            )
+            .retry_after(Duration::from_secs(claimable_at - now))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Records completion of claim round unless solved challenge is the final one
+    ///
+    /// Returns `None` if challenge is the final one, so server share should be released.
+    async fn complete_round(
+        &self,
+        challenge: &crate::persistent_store::Challenge,
+        solution: &VerifiedSolution,
+        public_key: &[u8],
+        claimant: &Claimant,
+    ) -> Result<Option<ClaimProgress>, Status> {
+        if self.rounds.is_final(challenge.round) {
+            return Ok(None);
+        }
+        if !solution.solves(challenge) {
+            return Err(open_error_status(
+                OpenError::InvalidChallenge,
+                Some(challenge),
+            ));
+        }
+        let progress = self
+            .store
+            .complete_claim_round(challenge, unix_time())
+            .await
+            .map_err(|e| status::store_error("completing claim round", e))?
+            .ok_or_else(|| {
+                Status::from(ErrorStatus::new(
+                    Code::Aborted,
+                    Reason::Conflict,
+                    "claim round was completed concurrently or aborted by ping",
+                ))
+            })?;
+        audit::record(
+            &self.store,
+            AuditEvent::ClaimRoundCompleted,
+            Some(public_key),
+            claimant,
+        )
+        .await;
+        self.notify(Event::ClaimRoundCompleted {
+            rounds_completed: progress.rounds_completed,
+            rounds_required: self.rounds.rounds,
+        })
+        .await;
+        Ok(Some(progress))
+    }
+
+    /// Explains why there's no challenge to solve
+    async fn no_challenge_status(&self) -> Status {
+        match self.store.get_claim_progress().await {
+            Ok(Some(progress)) => ErrorStatus::new(
+                Code::FailedPrecondition,
+                Reason::NextRoundPending,
+                format!(
+                    "round {} of the claim is completed, challenge of the next round isn't issued yet",
+                    progress.rounds_completed
+                ),
+            )
+            .retry_after(self.rounds.wait_before_next_round(&progress, unix_time()))
+            .progress(&progress)
+            .into(),
+            Ok(None) => testator_online(None),
+            Err(e) => status::store_error("retrieving claim progress", e),
+        }
+    }
+}
+
+/// Implementation of beneficiary API shared by all its versions
+impl<S, P> BeneficiaryServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    async fn verify_server_share(
+        &self,
+        claimant: Claimant,
+        request: Request<VerifyServerShareRequest>,
+    ) -> Result<Response<VerifyServerShareResponse>, Status> {
+        let request = request.into_inner();
+        let public_key = match P::from_bytes(&request.public_key) {
+            Ok(pk) => pk,
+            Err(_) => return Err(status::invalid_request("invalid joint public key")),
+        };
+        let client_public_share = match P::from_bytes(&request.client_public_share) {
+            Ok(ps) => ps,
+            Err(_) => return Err(status::invalid_request("invalid client public share")),
+        };
+
+        let public_key_bytes = public_key.pk_to_key_slice();
+        let server_share = match self.store.get_server_secret_share(public_key).await {
+            Ok(Some(ss)) => ss,
+            Ok(None) => return Err(status::share_not_found()),
+            Err(e) => {
+                return Err(status::store_error(
+                    "getting server share from persistent store",
+                    e,
+                ))
+            }
+        };
+        let proof = match server_share.verify_and_proof(client_public_share) {
+            Some(p) => p,
+            None => return Err(status::share_not_found()),
+        };
+        let proof_bytes = proof.pk_to_key_slice();
+        let escrow_piece = server_share.escrow_piece().map(|piece| EscrowPieceInfo {
+            index: piece.index,
+            threshold: piece.commitments.parameters.threshold as u32 + 1,
+            servers_count: piece.commitments.parameters.share_count as u32,
+            commitments: piece
+                .commitments
+                .commitments
+                .iter()
+                .map(|c| c.pk_to_key_slice())
+                .collect(),
+        });
+        // Anyone knowing public key can verify a share without auth, recording such
+        // verifications would let them flood the log
+        if claimant.auth_key.is_some() {
+            audit::record(
+                &self.store,
+                AuditEvent::ShareVerified,
+                Some(&public_key_bytes),
+                claimant,
+            )
+            .await;
+        }
+        Ok(Response::new(VerifyServerShareResponse {
+            server_public_share: proof_bytes,
+            escrow_piece,
+        }))
+    }
+
+    async fn get_challenge(
+        &self,
+        claimant: Claimant,
+        _request: Request<GetChallengeRequest>,
+    ) -> Result<Response<Challenge>, Status> {
+        match self.store.get_challenge().await {
+            Ok(Some(challenge)) => {
+                let expected_delay = self.delay.get().ok().and_then(|d| d.expected_delay);
+                return encode_challenge(&challenge, expected_delay, self.rounds)
+                    .map(Response::new);
+            }
+            Err(e) => return Err(status::store_error("retrieving challenge", e)),
+            Ok(None) => (),
+        }
+        let delay = self.delay.get().map_err(not_ready_status)?;
+        // Pings received before the challenge is issued must not abort it, and pings received
+        // after must see it
+        let _pings = match &self.coalesced_pings {
+            Some(pings) => Some(
+                pings
+                    .lock(&self.store)
+                    .await
+                    .map_err(|e| status::store_error("persisting pings", e))?,
+            ),
+            None => None,
+        };
+        self.check_inactivity_deadline().await?;
+        let id = self
+            .store
+            .get_ping_counter()
+            .await
+            .map_err(|e| status::store_error("retrieving ping counter", e))?;
+        let progress = self
+            .store
+            .get_claim_progress()
+            .await
+            .map_err(|e| status::store_error("retrieving claim progress", e))?;
+        let round = match progress {
+            Some(progress) => {
+                let wait = self.rounds.wait_before_next_round(&progress, unix_time());
+                if wait > Duration::from_secs(0) {
+                    return Err(ErrorStatus::new(
+                        Code::Unavailable,
+                        Reason::NextRoundPending,
+                        format!(
+                            "round {} of the claim is completed, next round starts in {}s",
+                            progress.rounds_completed,
+                            wait.as_secs()
+                        ),
+                    )
+                    .retry_after(wait)
+                    .progress(&progress)
+                    .into());
+                }
+                progress.rounds_completed
+            }
+            None => 0,
+        };
+        let challenge = crate::persistent_store::Challenge {
+            id,
+            scheme: delay.challenges.scheme(),
+            challenge: delay.challenges.pick_challenge(),
+            round,
+        };
+        let challenge = match self.store.set_challenge(challenge.clone()).await {
+            Ok(()) => {
+                self.challenge_events.notify();
+                // Challenge is shared by all shares, so it isn't bound to a public key
+                audit::record(&self.store, AuditEvent::ChallengeIssued, None, claimant).await;
+                self.notify(Event::ChallengeIssued {
+                    round: challenge.round,
+                })
+                .await;
+                challenge
+            }
+            Err(SetChallengeError::AlreadySet(challenge)) => challenge,
+            Err(e) => return Err(set_challenge_error_status(e)),
+        };
+
+        encode_challenge(&challenge, delay.expected_delay, self.rounds).map(Response::new)
+    }
+
+    async fn obtain_server_secret_share(
+        &self,
+        claimant: Claimant,
+        request: Request<ObtainServerSecretShareRequest>,
+    ) -> Result<Response<ObtainServerSecretShareResponse>, Status> {
+        let request = request.into_inner();
+
+        let public_key = P::from_bytes(&request.public_key)
+            .map_err(|_e| status::invalid_request("invalid public key"))?;
+        let public_key_bytes = public_key.pk_to_key_slice();
+
+        let solved_challenge = request
+            .solved_challenge
+            .ok_or_else(|| status::invalid_request("solved challenge is not provided"))?;
+        let solved_challenge = parse_challenge(&solved_challenge)?;
+
+        // Heir might only contribute to a claim without solving a challenge
+        let challenge_solution = match request.native_solution {
+            Some(solution) => Some(
+                delay::decode_solution(
+                    solved_challenge.scheme,
+                    &solved_challenge.challenge,
+                    &solution,
+                )
+                .map_err(|e| match e {
+                    InvalidSolution::MalformedChallenge => {
+                        status::invalid_request("invalid solved challenge")
+                    }
+                    e => open_error_status(OpenError::IncorrectSolution(e), None),
+                })?,
+            ),
+            None if request.solution.is_empty() && request.contribution.is_some() => None,
+            None => Some(request.solution),
+        };
+        // Request is parsed before retrieving the share, so errors don't reveal whether Will
+        // holds a share for the key
+        let contribution = match &request.contribution {
+            Some(contribution) => Some(parse_contribution::<P>(contribution)?),
+            None => None,
+        };
+        let client_public_share = match contribution {
+            Some(_) => None,
+            None => Some(
+                P::from_bytes(&request.client_public_share)
+                    .map_err(|_e| status::invalid_request("invalid client public share"))?,
+            ),
+        };
+
+        let current_challenge = self
+            .store
+            .get_challenge()
+            .await
+            .map_err(|e| status::store_error("retrieving current challenge", e))?;
+        let current_challenge = match current_challenge {
+            Some(challenge) => challenge,
+            None => return Err(self.no_challenge_status().await),
+        };
+        if current_challenge.id > solved_challenge.id {
+            return Err(open_error_status(
+                OpenError::OldChallenge,
+                Some(&current_challenge),
+            ));
+        } else if current_challenge.id == solved_challenge.id
+            && current_challenge.round > solved_challenge.round
+        {
+            return Err(ErrorStatus::new(
+                Code::FailedPrecondition,
+                Reason::RoundAlreadyCompleted,
+                format!(
+                    "round {} of the claim is already completed",
+                    solved_challenge.round + 1
+                ),
+            )
+            .challenge(&current_challenge)
+            .into());
+        } else if current_challenge != solved_challenge {
+            return Err(open_error_status(
+                OpenError::InvalidChallenge,
+                Some(&current_challenge),
+            ));
+        }
+        let secret = self
+            .store
+            .get_server_secret_share(public_key.clone())
+            .await
+            .map_err(|e| status::store_error("retrieving server secret share", e))?
+            .ok_or_else(status::share_not_found)?;
+        let escrow_piece_index = secret.escrow_piece().map(|p| p.index).unwrap_or(0);
+
+        let (contribution, challenge_solution) =
+            match (contribution, client_public_share, challenge_solution) {
+                (Some(contribution), _, challenge_solution) => (contribution, challenge_solution),
+                (None, Some(client_public_share), Some(challenge_solution)) => {
+                    // Cheap checks go before expensive verification of the solution
+                    if secret.beneficiaries().is_some()
+                        || secret
+                            .verify_and_proof(client_public_share.clone())
+                            .is_none()
+                    {
+                        return Err(open_error_status(
+                            OpenError::ClientShareDoesntMatchServerShare,
+                            Some(&current_challenge),
+                        ));
+                    }
+                    let challenge_solution = self
+                        .verifier
+                        .verify(&current_challenge, challenge_solution)
+                        .await
+                        .map_err(|e| verify_error_status(e, &current_challenge))?;
+                    if let Some(progress) = self
+                        .complete_round(
+                            &current_challenge,
+                            &challenge_solution,
+                            &public_key_bytes,
+                            &claimant,
+                        )
+                        .await?
+                    {
+                        return Ok(Response::new(ObtainServerSecretShareResponse {
+                            server_secret_share: vec![],
+                            contributions_collected: 0,
+                            contributions_required: 0,
+                            escrow_piece_index,
+                            rounds_completed: progress.rounds_completed,
+                            rounds_required: self.rounds.rounds,
+                            next_round_delay_seconds: self.rounds.interval.as_secs(),
+                        }));
+                    }
+                    let server_share = secret
+                        .open(
+                            &current_challenge,
+                            &solved_challenge,
+                            &challenge_solution,
+                            client_public_share,
+                        )
+                        .map_err(|e| open_error_status(e, Some(&current_challenge)))?;
+                    // Share isn't returned unless its release is audited
+                    audit::append(
+                        &self.store,
+                        AuditEvent::ShareReleased,
+                        Some(&public_key_bytes),
+                        &claimant,
+                    )
+                    .await
+                    .map_err(|e| status::store_error("auditing share release", e))?;
+                    self.notify(Event::share_released(&request.public_key))
+                        .await;
+                    return Ok(Response::new(ObtainServerSecretShareResponse {
+                        server_secret_share: server_share.to_big_int().to_bytes(),
+                        contributions_collected: 0,
+                        contributions_required: 0,
+                        escrow_piece_index,
+                        rounds_completed: current_challenge.round + 1,
+                        rounds_required: self.rounds.rounds,
+                        next_round_delay_seconds: 0,
+                    }));
+                }
+                _ => return Err(status::invalid_request("invalid solution")),
+            };
+
+        // Beneficiary's share is split between heirs. Contribution that doesn't verify is
+        // indistinguishable from a missing share, as its author isn't proven to be an heir.
+        let beneficiaries = secret.beneficiaries().ok_or_else(status::share_not_found)?;
+        contribution
+            .verify(
+                beneficiaries,
+                &request.public_key,
+                current_challenge.id,
+                current_challenge.round,
+            )
+            .map_err(|_e| status::share_not_found())?;
+        let session = self
+            .store
+            .add_claim_contribution(public_key, current_challenge.id, contribution.index)
+            .await
+            .map_err(|e| status::store_error("recording claim contribution", e))?;
+
+        let contributions_required = beneficiaries.parameters.threshold as u32 + 1;
+        let contributions_collected = session.contributors.len() as u32;
+        let challenge_solution = match challenge_solution {
+            Some(s) if contributions_collected >= contributions_required => s,
+            _ => {
+                return Ok(Response::new(ObtainServerSecretShareResponse {
+                    server_secret_share: vec![],
+                    contributions_collected,
+                    contributions_required,
+                    escrow_piece_index,
+                    rounds_completed: current_challenge.round,
+                    rounds_required: self.rounds.rounds,
+                    next_round_delay_seconds: 0,
+                }))
+            }
+        };
+        let challenge_solution = self
+            .verifier
+            .verify(&current_challenge, challenge_solution)
+            .await
+            .map_err(|e| verify_error_status(e, &current_challenge))?;
+        if let Some(progress) = self
+            .complete_round(
+                &current_challenge,
+                &challenge_solution,
+                &public_key_bytes,
+                &claimant,
+            )
+            .await?
+        {
+            return Ok(Response::new(ObtainServerSecretShareResponse {
+                server_secret_share: vec![],
+                contributions_collected,
+                contributions_required,
+                escrow_piece_index,
+                rounds_completed: progress.rounds_completed,
+                rounds_required: self.rounds.rounds,
+                next_round_delay_seconds: self.rounds.interval.as_secs(),
+            }));
+        }
+        let server_share = secret
+            .open_threshold(
+                &current_challenge,
+                &solved_challenge,
+                &challenge_solution,
+                &session,
+            )
+            .map_err(|e| open_error_status(e, Some(&current_challenge)))?;
+        // Share isn't returned unless its release is audited
+        audit::append(
+            &self.store,
+            AuditEvent::ShareReleased,
+            Some(&public_key_bytes),
+            &claimant,
+        )
+        .await
+        .map_err(|e| status::store_error("auditing share release", e))?;
+        self.notify(Event::share_released(&request.public_key))
+            .await;
+        Ok(Response::new(ObtainServerSecretShareResponse {
+            server_secret_share: server_share.to_big_int().to_bytes(),
+            contributions_collected,
+            contributions_required,
+            escrow_piece_index,
+            rounds_completed: current_challenge.round + 1,
+            rounds_required: self.rounds.rounds,
+            next_round_delay_seconds: 0,
+        }))
+    }
+
+    async fn get_attestation(
+        &self,
+        request: Request<GetAttestationRequest>,
+    ) -> Result<Response<Attestation>, Status> {
+        attest(self.attestor.as_deref(), request.into_inner())
+    }
+}
+
+/// Serves testator API. Like [BeneficiaryServer], cloned server shares its state with the
+/// original.
+#[derive(Clone)]
+pub struct TestatorServer<S, P: ECPoint> {
+    store: S,
+    share_key: Arc<ShareDecryptionKey<P>>,
+    share_key_signature: Vec<u8>,
+    attestor: Option<Arc<Attestor>>,
+    deprecation_hook: DeprecationHook,
+    enrollment_tokens: Arc<EnrollmentTokens>,
+    require_enrollment: bool,
+    ping_nonces: Arc<PingNonces>,
+    require_signed_pings: bool,
+    challenge_events: ChallengeEvents,
+    keepalive_interval: Duration,
+    coalesced_pings: Option<CoalescedPings>,
+    reminders: Option<Reminders<S, P>>,
+    checkpoint_signer: Option<Arc<TlsKeySigner>>,
+}
+
+impl<S, P: ECPoint> TestatorServer<S, P> {
+    /// Constructs testator server
+    ///
+    /// `share_key_signature` is a signature of `share_key` public key made by Will's TLS key
+    /// (see [TlsKeySigner](crate::share_encryption::TlsKeySigner)), or empty if Will runs without
+    /// TLS.
+    pub fn new(
+        persistent_store: S,
+        share_key: ShareDecryptionKey<P>,
+        share_key_signature: Vec<u8>,
+        attestor: Option<Arc<Attestor>>,
+    ) -> Self {
+        Self {
+            store: persistent_store,
+            share_key: Arc::new(share_key),
+            share_key_signature,
+            attestor,
+            deprecation_hook: v1::warn_once(),
+            enrollment_tokens: Default::default(),
+            require_enrollment: false,
+            ping_nonces: Default::default(),
+            require_signed_pings: false,
+            challenge_events: Default::default(),
+            keepalive_interval: Duration::from_secs(60 * 60),
+            coalesced_pings: None,
+            reminders: None,
+            checkpoint_signer: None,
+        }
+    }
+
+    /// Sets hook called on every request to deprecated v1 API. By default, a warning is logged once
+    /// per method.
+    pub fn with_deprecation_hook(self, hook: DeprecationHook) -> Self {
+        Self {
+            deprecation_hook: hook,
+            ..self
+        }
+    }
+
+    /// Rejects requests of devices that aren't enrolled, except for enrollment itself. By default,
+    /// only revoked devices are rejected.
+    pub fn with_required_enrollment(self) -> Self {
+        Self {
+            require_enrollment: true,
+            ..self
+        }
+    }
+
+    /// Ignores pings that aren't signed with liveness keys, so only testator possessing its shares
+    /// keeps them alive. By default, unsigned pings are counted as well.
+    pub fn with_required_ping_signatures(self) -> Self {
+        Self {
+            require_signed_pings: true,
+            ..self
+        }
+    }
+
+    /// Receives notifications about challenges issued by [BeneficiaryServer], and warns testator
+    /// about them in keepalive sessions
+    pub fn with_challenge_events(self, events: ChallengeEvents) -> Self {
+        Self {
+            challenge_events: events,
+            ..self
+        }
+    }
+
+    /// Sets interval between heartbeats recommended to testator in keepalive sessions. Defaults to
+    /// 1 hour.
+    pub fn with_keepalive_interval(self, interval: Duration) -> Self {
+        Self {
+            keepalive_interval: interval,
+            ..self
+        }
+    }
+
+    /// Coalesces pings received while no claim is in progress instead of persisting each of them.
+    /// `pings` must be shared with [BeneficiaryServer::with_coalesced_pings] and liveness
+    /// reminders, and persisted periodically with [CoalescedPings::persist_periodically]. By
+    /// default, every ping is persisted before it's acknowledged.
+    pub fn with_coalesced_pings(self, pings: CoalescedPings) -> Self {
+        Self {
+            coalesced_pings: Some(pings),
+            ..self
+        }
+    }
+
+    /// Accepts acknowledgements of liveness reminders sent by `reminders`. By default, every
+    /// acknowledgement is rejected.
+    pub fn with_reminders(self, reminders: Reminders<S, P>) -> Self {
+        Self {
+            reminders: Some(reminders),
+            ..self
+        }
+    }
+
+    /// Signs checkpoints of the audit log with Will's TLS key. By default, checkpoints aren't
+    /// signed.
+    pub fn with_checkpoint_signer(self, signer: Arc<TlsKeySigner>) -> Self {
+        Self {
+            checkpoint_signer: Some(signer),
+            ..self
+        }
+    }
+}
+
+/// Implementation of testator API shared by all its versions
+impl<S, P> TestatorServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    /// Counts the ping. `signed_for` lists public keys covered by its verified signatures.
+    async fn ping(
+        &self,
+        signed_for: &[P],
+        _request: Request<PingRequest>,
+    ) -> Result<Response<PongResponse>, Status> {
+        self.check_ping_signed(signed_for).await?;
+        if let Err(e) = self.record_ping().await {
+            Err(status::store_error("increasing of ping counter", e))
+        } else {
+            Ok(Response::new(PongResponse {}))
+        }
+    }
+
+    async fn record_ping(&self) -> Result<(), StoreError> {
+        match &self.coalesced_pings {
+            Some(pings) => pings.record(&self.store).await,
+            None => self
+                .store
+                .increase_ping_counter(unix_time())
+                .await
+                .map(|_| ()),
+        }
+    }
+
+    /// Rejects ping that isn't signed for every share with a liveness key, or unsigned ping if
+    /// pings must be signed
+    async fn check_ping_signed(&self, signed_for: &[P]) -> Result<(), Status> {
+        if self.require_signed_pings && signed_for.is_empty() {
+            return Err(ping_signature_required(
+                "ping must be signed with liveness key",
+            ));
+        }
+        let shares = self
+            .store
+            .shares_with_liveness_keys()
+            .await
+            .map_err(|e| status::store_error("retrieving shares with liveness keys", e))?;
+        let signed_for: Vec<_> = signed_for.iter().map(|pk| pk.pk_to_key_slice()).collect();
+        if shares
+            .iter()
+            .any(|share| !signed_for.contains(&share.pk_to_key_slice()))
+        {
+            return Err(ping_signature_required(
+                "ping must be signed with liveness keys of all shares that registered one",
+            ));
+        }
+        Ok(())
+    }
+
+    async fn get_server_key(
+        &self,
+        _request: Request<GetServerKeyRequest>,
+    ) -> Result<Response<ServerKey>, Status> {
+        Ok(Response::new(ServerKey {
+            public_key: self.share_key.public_key().pk_to_key_slice(),
+            signature: self.share_key_signature.clone(),
+        }))
+    }
+
+    async fn save_server_share(
+        &self,
+        caller: Caller,
+        liveness_key: Option<P>,
+        beneficiary_keys: Vec<P>,
+        request: Request<SaveServerShareRequest>,
+    ) -> Result<Response<SaveServerShareResponse>, Status> {
+        let request = request.into_inner();
+        let public_key = match P::from_bytes(&request.public_key) {
+            Ok(pk) => pk,
+            Err(e) => {
+                return Err(status::invalid_request(format!(
+                    "invalid public key: {:?}",
+                    e
+                )))
+            }
+        };
+        let server_secret_share = match request.encrypted_server_secret_share {
+            Some(_) if !request.server_secret_share.is_empty() => {
+                return Err(status::invalid_request(
+                    "both plaintext and encrypted shares are provided",
+                ))
+            }
+            Some(encrypted) => {
+                let ephemeral_key = P::from_bytes(&encrypted.ephemeral_key)
+                    .map_err(|_e| status::invalid_request("invalid ephemeral key"))?;
+                self.share_key
+                    .decrypt(
+                        ephemeral_key,
+                        &encrypted.nonce,
+                        &encrypted.ciphertext,
+                        &request.public_key,
+                    )
+                    .map_err(|e| status::invalid_request(format!("decrypt secret share: {}", e)))?
+            }
+            None => request.server_secret_share,
+        };
+        let server_secret_share = BigInt::from_bytes(&server_secret_share);
+        if BigInt::zero() >= server_secret_share {
+            return Err(status::invalid_request("invalid secret share"));
+        }
+        let server_secret_share = <P::Scalar as ECScalar>::from(&server_secret_share);
+
+        let beneficiaries = match &request.beneficiaries {
+            Some(b) => Some(parse_beneficiary_commitments::<P>(b)?),
+            None => None,
+        };
+        let escrow_piece = match &request.escrow_piece {
+            Some(piece) => Some(parse_escrow_piece::<P>(piece)?),
+            None => None,
+        };
+        if let (Some(beneficiaries), None) = (&beneficiaries, &escrow_piece) {
+            // Otherwise server share is just a piece, and it's checked against escrow commitments
+            if !beneficiaries::commitments_match_public_key(
+                beneficiaries,
+                &server_secret_share,
+                &public_key,
+            ) {
+                return Err(status::invalid_request(
+                    "beneficiary commitments don't match public key",
+                ));
+            }
+        }
+        if let Some(piece) = &escrow_piece {
+            piece
+                .validate(&public_key)
+                .map_err(|e| status::invalid_request(format!("invalid escrow piece: {}", e)))?;
+            // Beneficiary's public share is known if it's split between heirs, so piece can be
+            // checked right away
+            if let Some(beneficiaries) = &beneficiaries {
+                if !piece.verify_piece(&server_secret_share, beneficiaries.commitments[0].clone()) {
+                    return Err(status::invalid_request(
+                        "escrow piece doesn't match its commitments",
+                    ));
+                }
+            }
+        }
+
+        let public_key_bytes = public_key.pk_to_key_slice();
+        if let Err(e) = self
+            .store
+            .add_server_secret_share(ShareRecord {
+                beneficiaries,
+                escrow_piece,
+                liveness_key,
+                beneficiary_keys,
+                ..ShareRecord::new(public_key, server_secret_share)
+            })
+            .await
+        {
+            return Err(status::store_error("adding share to persistent store", e));
+        }
+        info!(%caller, "Server share is saved");
+        // Testator is alive when it saves a share, so inactivity deadline starts no earlier than
+        // that, even if testator never pings
+        self.record_ping()
+            .await
+            .map_err(|e| status::store_error("increasing of ping counter", e))?;
+        audit::record(
+            &self.store,
+            AuditEvent::ShareSaved,
+            Some(&public_key_bytes),
+            caller,
+        )
+        .await;
+
+        Ok(Response::new(SaveServerShareResponse {}))
+    }
+
+    async fn get_attestation(
+        &self,
+        request: Request<GetAttestationRequest>,
+    ) -> Result<Response<Attestation>, Status> {
+        attest(self.attestor.as_deref(), request.into_inner())
+    }
+}
+
+/// Maps error of opening the share. `current` is the challenge currently issued, if retrieved.
+fn open_error_status(
+    error: OpenError,
+    current: Option<&crate::persistent_store::Challenge>,
+) -> Status {
+    let error_status = match error {
+        OpenError::ClientShareDoesntMatchServerShare => return status::share_not_found(),
+        OpenError::OldChallenge => return testator_online(current),
+        OpenError::InvalidChallenge => ErrorStatus::new(
+            Code::InvalidArgument,
+            Reason::ChallengeMismatch,
+            "solved challenge is different from what was required to solve",
+        ),
+        OpenError::IncorrectSolution(InvalidSolution::MalformedSolution) => ErrorStatus::new(
+            Code::InvalidArgument,
+            Reason::MalformedSolution,
+            "invalid solution",
+        ),
+        OpenError::IncorrectSolution(InvalidSolution::MalformedChallenge) => {
+            return status::internal("stored challenge is malformed")
+        }
+        OpenError::IncorrectSolution(InvalidSolution::Incorrect) => ErrorStatus::new(
+            Code::InvalidArgument,
+            Reason::IncorrectSolution,
+            "incorrect solution",
+        ),
+        OpenError::NotEnoughContributions => ErrorStatus::new(
+            Code::FailedPrecondition,
+            Reason::NotEnoughContributions,
+            "not enough heirs contributed to the claim",
+        ),
+    };
+    match current {
+        Some(challenge) => error_status.challenge(challenge).into(),
+        None => error_status.into(),
+    }
+}
+
+fn verify_error_status(error: VerifyError, current: &crate::persistent_store::Challenge) -> Status {
+    match error {
+        VerifyError::Invalid(e) => {
+            open_error_status(OpenError::IncorrectSolution(e), Some(current))
+        }
+        VerifyError::Busy => ErrorStatus::new(
+            Code::ResourceExhausted,
+            Reason::VerifierBusy,
+            "too many solutions are being verified, retry later",
+        )
+        .into(),
+        VerifyError::Aborted => status::internal("verification of solution was aborted"),
+    }
+}
+
+/// Maps error of issuing a challenge. `AlreadySet` is usually not an error: the challenge already
+/// set is returned instead.
+fn set_challenge_error_status(error: SetChallengeError) -> Status {
+    match error {
+        SetChallengeError::AlreadySet(challenge) => ErrorStatus::new(
+            Code::Aborted,
+            Reason::Conflict,
+            "challenge was issued concurrently, retry",
+        )
+        .challenge(&challenge)
+        .into(),
+        SetChallengeError::Outdated => testator_online(None),
+        SetChallengeError::MismatchedId => status::internal("challenge.id > ping_counter"),
+        SetChallengeError::MismatchedRound => ErrorStatus::new(
+            Code::Aborted,
+            Reason::Conflict,
+            "claim progressed concurrently, retry",
+        )
+        .into(),
+        SetChallengeError::Store(e) => status::store_error("setting challenge", e),
+    }
+}
+
+fn not_ready_status(error: NotReady) -> Status {
+    let reason = match &error {
+        NotReady::InProgress { .. } => Reason::DelaySetupInProgress,
+        NotReady::Failed(_) => Reason::DelaySetupFailed,
+    };
+    ErrorStatus::new(Code::Unavailable, reason, error.to_string()).into()
+}
+
+fn testator_online(current: Option<&crate::persistent_store::Challenge>) -> Status {
+    let error_status = ErrorStatus::new(
+        Code::FailedPrecondition,
+        Reason::TestatorOnline,
+        "ZenGo server is online",
+    );
+    match current {
+        Some(challenge) => error_status.challenge(challenge).into(),
+        None => error_status.into(),
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn encode_challenge(
+    challenge: &crate::persistent_store::Challenge,
+    expected_delay: Option<Duration>,
+    rounds: ClaimRounds,
+) -> Result<Challenge, Status> {
+    let number = u64::try_from(challenge.id)
+        .map_err(|_e| status::internal("challenge id doesn't fit u64"))?;
+    let input = delay::encode_challenge(challenge.scheme, &challenge.challenge)
+        .map_err(|e| status::internal(format!("encode challenge: {}", e)))?;
+    Ok(Challenge {
+        id: challenge.id.to_le_bytes().to_vec(),
+        number,
+        input: Some(input),
+        challenge: serde_json::to_vec(&challenge.challenge)
+            .map_err(|e| status::internal(format!("serialize challenge: {}", e)))?,
+        scheme: challenge.scheme.to_string(),
+        expected_delay_seconds: expected_delay.map(|d| d.as_secs()).unwrap_or(0),
+        round: challenge.round,
+        rounds: rounds.rounds.max(challenge.round + 1),
+    })
+}
+
+/// Parses challenge sent back by beneficiary. Either serialized or protobuf encoding of challenge
+/// might be used, as well as either encoding of its id.
+#[allow(clippy::result_large_err)]
+fn parse_challenge(challenge: &Challenge) -> Result<crate::persistent_store::Challenge, Status> {
+    let id = if challenge.id.is_empty() {
+        u128::from(challenge.number)
+    } else {
+        let mut id = [0u8; size_of::<u128>()];
+        if challenge.id.len() != id.len() {
+            return Err(status::invalid_request("invalid solved challenge id"));
+        }
+        id.copy_from_slice(&challenge.id);
+        u128::from_le_bytes(id)
+    };
+    let scheme = if challenge.scheme.is_empty() {
+        Scheme::default()
+    } else {
+        challenge
+            .scheme
+            .parse()
+            .map_err(|_e| status::invalid_request("unknown challenge scheme"))?
+    };
+    let serialized = match &challenge.input {
+        Some(input) if challenge.challenge.is_empty() => delay::decode_challenge(scheme, input)
+            .map_err(|_e| status::invalid_request("invalid solved challenge"))?,
+        _ => serde_json::from_slice(&challenge.challenge)
+            .map_err(|_e| status::invalid_request("invalid solved challenge"))?,
+    };
+    Ok(crate::persistent_store::Challenge {
+        id,
+        scheme,
+        challenge: serialized,
+        round: challenge.round,
+    })
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_contribution<P>(contribution: &HeirContribution) -> Result<Contribution<P>, Status>
+where
+    P: ECPoint,
+{
+    let public_piece = P::from_bytes(&contribution.public_piece)
+        .map_err(|_e| status::invalid_request("invalid public piece"))?;
+    let commitment = P::from_bytes(&contribution.proof_commitment)
+        .map_err(|_e| status::invalid_request("invalid proof commitment"))?;
+    let response = BigInt::from_bytes(&contribution.proof_response);
+    if response >= P::Scalar::q() {
+        return Err(status::invalid_request("invalid proof response"));
+    }
+    Ok(Contribution {
+        index: contribution.index,
+        public_piece,
+        proof: SchnorrProof {
+            commitment,
+            response: <P::Scalar as ECScalar>::from(&response),
+        },
+    })
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_beneficiary_commitments<P>(
+    commitments: &BeneficiaryCommitments,
+) -> Result<VerifiableSS<P>, Status>
+where
+    P: ECPoint,
+{
+    let parsed = commitments
+        .commitments
+        .iter()
+        .map(|c| P::from_bytes(c))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_e| status::invalid_request("invalid beneficiary commitment"))?;
+    let threshold = (commitments.threshold as usize)
+        .checked_sub(1)
+        .ok_or_else(|| status::invalid_request("heirs threshold must be at least 1"))?;
+    let commitments = VerifiableSS {
+        parameters: ShamirSecretSharing {
+            threshold,
+            share_count: commitments.heirs_count as usize,
+        },
+        commitments: parsed,
+    };
+    if !beneficiaries::validate_commitments(&commitments) {
+        return Err(status::invalid_request(
+            "beneficiary commitments don't describe a valid threshold sharing",
+        ));
+    }
+    Ok(commitments)
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_escrow_piece<P>(piece: &EscrowPiece) -> Result<escrow::EscrowPiece<P>, Status>
+where
+    P: ECPoint,
+{
+    let commitments = piece
+        .commitments
+        .iter()
+        .map(|c| P::from_bytes(c))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_e| status::invalid_request("invalid escrow piece commitment"))?;
+    let threshold = (piece.threshold as usize)
+        .checked_sub(1)
+        .ok_or_else(|| status::invalid_request("escrow threshold must be at least 1"))?;
+    Ok(escrow::EscrowPiece {
+        index: piece.index,
+        commitments: VerifiableSS {
+            parameters: ShamirSecretSharing {
+                threshold,
+                share_count: piece.servers_count as usize,
+            },
+            commitments,
+        },
+    })
+}
+
+#[allow(clippy::result_large_err)]
+fn attest(
+    attestor: Option<&Attestor>,
+    request: GetAttestationRequest,
+) -> Result<Response<Attestation>, Status> {
+    let attestor = attestor.ok_or_else(|| {
+        Status::from(ErrorStatus::new(
+            Code::Unimplemented,
+            Reason::AttestationNotConfigured,
+            "attestation is not configured",
+        ))
+    })?;
+    if request.nonce.is_empty() || request.nonce.len() > 64 {
+        return Err(status::invalid_request("nonce must be 1 to 64 bytes long"));
+    }
+    attestor
+        .attest(&request.nonce)
+        .map(Response::new)
+        .map_err(|e| status::internal(e.to_string()))
+}
+
+fn ping_signature_required(message: &str) -> Status {
+    ErrorStatus::new(
+        Code::PermissionDenied,
+        Reason::PingSignatureRequired,
+        message,
+    )
+    .into()
+}
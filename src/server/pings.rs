@@ -0,0 +1,177 @@
+//! Coalesced persistence of pings
+//!
+//! Persisting a ping takes a transaction and a flush (and replication to standbys). With many
+//! keepalive clients, pings might instead be coalesced in memory and persisted at most once per
+//! interval. Ping received while claim is in progress is persisted before it's acknowledged, as it
+//! aborts the claim. Pending pings are persisted before a challenge is issued, so they never abort
+//! a claim started after them.
+//!
+//! That trades durability for throughput: coalesced ping is acknowledged while it exists only in
+//! memory, so it's lost if Will crashes or fails over before the next flush. Then the latest
+//! persisted ping is older than the one testator made, which brings inactivity deadline closer by
+//! up to the flush interval. Coalescing is therefore opt-in.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use curv::elliptic::curves::traits::ECPoint;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::warn;
+
+use crate::delay::rounds::unix_time;
+use crate::persistent_store::{PersistentStore, StoreError};
+
+/// Pings acknowledged but not persisted yet
+///
+/// Must be shared by [BeneficiaryServer](super::BeneficiaryServer) and
+/// [TestatorServer](super::TestatorServer): challenge is issued while pings are locked out, so a
+/// ping is either coalesced before the challenge or sees it.
+#[derive(Clone, Default)]
+pub struct CoalescedPings {
+    /// Unix time of the latest pending ping, if any
+    pending: Arc<Mutex<Option<u64>>>,
+}
+
+/// Locks out pings while held
+pub(super) type PingsLock<'a> = MutexGuard<'a, Option<u64>>;
+
+impl CoalescedPings {
+    /// Records a ping. Once it returns, ping might be acknowledged.
+    pub(super) async fn record<S, P>(&self, store: &S) -> Result<(), StoreError>
+    where
+        S: PersistentStore<P>,
+        P: ECPoint,
+    {
+        let mut pending = self.pending.lock().await;
+        let pinged_at = unix_time();
+        // Challenge isn't issued while pings are pending, so there's no claim to abort
+        if pending.is_some() {
+            *pending = Some(pinged_at);
+            return Ok(());
+        }
+        let claim_in_progress =
+            store.get_challenge().await?.is_some() || store.get_claim_progress().await?.is_some();
+        if claim_in_progress {
+            store.increase_ping_counter(pinged_at).await?;
+        } else {
+            *pending = Some(pinged_at);
+        }
+        Ok(())
+    }
+
+    /// Persists pending pings and locks out new ones until returned lock is dropped
+    pub(super) async fn lock<S, P>(&self, store: &S) -> Result<PingsLock<'_>, StoreError>
+    where
+        S: PersistentStore<P>,
+        P: ECPoint,
+    {
+        let mut pending = self.pending.lock().await;
+        if let Some(pinged_at) = *pending {
+            store.increase_ping_counter(pinged_at).await?;
+            *pending = None;
+        }
+        Ok(pending)
+    }
+
+    /// Persists pending pings
+    pub async fn persist<S, P>(&self, store: &S) -> Result<(), StoreError>
+    where
+        S: PersistentStore<P>,
+        P: ECPoint,
+    {
+        self.lock(store).await.map(|_| ())
+    }
+
+    /// Returns Unix time of the latest ping, including pending one
+    pub async fn last_ping_time<S, P>(&self, store: &S) -> Result<Option<u64>, StoreError>
+    where
+        S: PersistentStore<P>,
+        P: ECPoint,
+    {
+        let pending = *self.pending.lock().await;
+        match pending {
+            Some(pinged_at) => Ok(Some(pinged_at)),
+            None => store.get_last_ping_time().await,
+        }
+    }
+
+    /// Persists pending pings every `interval`
+    ///
+    /// `store` must be the one used by servers, i.e. the replicated one if Will is a primary, so
+    /// persisted pings reach standbys.
+    pub async fn persist_periodically<S, P>(self, store: S, interval: Duration)
+    where
+        S: PersistentStore<P>,
+        P: ECPoint,
+    {
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(e) = self.persist(&store).await {
+                warn!("Persist coalesced pings: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::elliptic::curves::secp256_k1::GE;
+
+    use super::*;
+    use crate::persistent_store::sled::SledDB;
+    use crate::persistent_store::Challenge;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    async fn open_store() -> Result<(SledDB<GE>, tempfile::TempDir)> {
+        let dir = tempfile::tempdir()?;
+        let store = SledDB::open(dir.path().join("store")).await?;
+        Ok((store, dir))
+    }
+
+    #[tokio::test]
+    async fn coalesce_pings_until_persisted() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+        let pings = CoalescedPings::default();
+
+        pings.record(&store).await?;
+        pings.record(&store).await?;
+        assert_eq!(store.get_ping_counter().await?, 0);
+        assert_eq!(store.get_last_ping_time().await?, None);
+        let pinged_at = pings.last_ping_time(&store).await?;
+        assert!(pinged_at.is_some());
+
+        pings.persist(&store).await?;
+        assert_eq!(store.get_ping_counter().await?, 1);
+        assert_eq!(store.get_last_ping_time().await?, pinged_at);
+        pings.persist(&store).await?;
+        assert_eq!(store.get_ping_counter().await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn persist_ping_following_challenge_before_acknowledging_it() -> Result<()> {
+        let (store, _guard) = open_store().await?;
+        let pings = CoalescedPings::default();
+
+        pings.record(&store).await?;
+        {
+            let _lock = pings.lock(&store).await?;
+            let id = store.get_ping_counter().await?;
+            assert_eq!(id, 1);
+            store
+                .set_challenge(Challenge {
+                    id,
+                    scheme: Default::default(),
+                    challenge: serde_json::json!({}),
+                    round: 0,
+                })
+                .await?;
+        }
+
+        pings.record(&store).await?;
+        assert_eq!(store.get_challenge().await?, None);
+        assert_eq!(store.get_ping_counter().await?, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+//! Rate limiting of beneficiary requests and lockout of repeatedly failing claims, applied to whole
+//! beneficiary API services by [BeneficiaryLimitsLayer]
+
+use std::marker::PhantomData;
+use std::mem;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use curv::elliptic::curves::traits::ECPoint;
+use derivative::Derivative;
+use futures::future::BoxFuture;
+use hyper::body::HttpBody;
+use prost::Message;
+use tonic::body::BoxBody;
+use tonic::transport::{Body, NamedService};
+use tonic::{Code, Interceptor, Request, Status};
+use tower::{Layer, Service};
+use tracing::warn;
+
+use crate::proto::errors::Reason;
+use crate::rate_limit::{Budget, Lockout, Outcome, RateLimiter};
+
+use super::ErrorStatus;
+
+/// Largest request naming a share that is read. Solution takes at most 64 KiB, the rest of request
+/// is much smaller.
+const MAX_REQUEST_SIZE: usize = 128 * 1024;
+
+/// Share of a public key requested by a peer. Public key alone is known to anyone, so limits keyed
+/// by it would let anyone exhaust them for legitimate beneficiaries.
+type PeerShare = (Vec<u8>, Option<IpAddr>);
+
+/// Budgets of beneficiary requests and lockout of failing claims. Nothing is limited by default.
+#[derive(Default)]
+pub struct BeneficiaryLimits {
+    per_peer: Option<RateLimiter<IpAddr>>,
+    per_key: Option<RateLimiter<PeerShare>>,
+    claim_lockout: Option<Lockout<PeerShare>>,
+}
+
+impl BeneficiaryLimits {
+    /// Limits requests coming from one IP address
+    pub fn per_peer(self, budget: Budget) -> Self {
+        Self {
+            per_peer: Some(RateLimiter::new(budget)),
+            ..self
+        }
+    }
+
+    /// Limits requests from one IP address concerning share of one public key
+    pub fn per_key(self, budget: Budget) -> Self {
+        Self {
+            per_key: Some(RateLimiter::new(budget)),
+            ..self
+        }
+    }
+
+    /// Rejects claims on a share from one IP address for `duration` once `max_failures` claims in
+    /// a row are rejected because of incorrect solution or malformed request
+    pub fn claim_lockout(self, max_failures: u32, duration: Duration) -> Self {
+        Self {
+            claim_lockout: Some(Lockout::new(max_failures, duration)),
+            ..self
+        }
+    }
+
+    /// Takes a token from budget of request peer
+    fn throttle_peer<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        if let (Some(limiter), Some(peer)) = (&self.per_peer, request.remote_addr()) {
+            limiter
+                .check(peer.ip())
+                .map_err(|delay| rate_limited("too many requests from this address", delay))?;
+        }
+        Ok(())
+    }
+
+    /// Takes a token from budget of `share`
+    fn throttle_share(&self, share: &PeerShare) -> Result<(), Status> {
+        if let Some(limiter) = &self.per_key {
+            limiter
+                .check(share.clone())
+                .map_err(|delay| rate_limited("too many requests for this share", delay))?;
+        }
+        Ok(())
+    }
+
+    /// Lets a claim on `share` through unless it's locked out
+    fn begin_claim(self: &Arc<Self>, share: PeerShare) -> Result<Option<PendingClaim>, Status> {
+        let lockout = match &self.claim_lockout {
+            Some(lockout) => lockout,
+            None => return Ok(None),
+        };
+        if let Err(delay) = lockout.begin(share.clone()) {
+            return Err(ErrorStatus::new(
+                Code::ResourceExhausted,
+                Reason::ClaimLockedOut,
+                "too many failed or pending claims on this share",
+            )
+            .retry_after(round_up(delay))
+            .into());
+        }
+        Ok(Some(PendingClaim {
+            limits: self.clone(),
+            share,
+            outcome: Outcome::Inconclusive,
+        }))
+    }
+}
+
+/// Claim let through by claim lockout. It counts as a failure until it's dropped with its outcome.
+struct PendingClaim {
+    limits: Arc<BeneficiaryLimits>,
+    share: PeerShare,
+    outcome: Outcome,
+}
+
+impl Drop for PendingClaim {
+    fn drop(&mut self) {
+        if let Some(lockout) = &self.limits.claim_lockout {
+            if lockout.finish(&self.share, self.outcome) {
+                warn!("Claims on a share are locked out after repeated failures");
+            }
+        }
+    }
+}
+
+/// Share named by request, passed from [Limited] to
+/// [interceptor](BeneficiaryLimitsLayer::interceptor) in request extensions
+struct NamedShare {
+    key: Vec<u8>,
+    /// Set for claims, receives the claim let through by the interceptor
+    claim: Option<Arc<Mutex<Option<PendingClaim>>>>,
+}
+
+/// Applies [BeneficiaryLimits] to every request of beneficiary API service, whichever RPC it is
+///
+/// Peer address isn't exposed to tower services by tonic, so the layer only reads the share a
+/// request names, and the limits are applied by [interceptor](Self::interceptor), which the wrapped
+/// service must be constructed with. The layer then counts failed claims.
+pub struct BeneficiaryLimitsLayer<P> {
+    limits: Arc<BeneficiaryLimits>,
+    _ph: PhantomData<fn() -> P>,
+}
+
+impl<P> BeneficiaryLimitsLayer<P> {
+    pub fn new(limits: Arc<BeneficiaryLimits>) -> Self {
+        Self {
+            limits,
+            _ph: PhantomData,
+        }
+    }
+
+    /// Throttles every request by its peer address, and requests naming a share by the share and
+    /// the peer. Locks out failing claims.
+    pub fn interceptor(&self) -> Interceptor {
+        let limits = self.limits.clone();
+        Interceptor::new(move |request| {
+            limits.throttle_peer(&request)?;
+            if let Some(named) = request.extensions().get::<NamedShare>() {
+                let share = (
+                    named.key.clone(),
+                    request.remote_addr().map(|peer| peer.ip()),
+                );
+                limits.throttle_share(&share)?;
+                if let Some(claim) = &named.claim {
+                    *claim.lock().expect("poisoned") = limits.begin_claim(share)?;
+                }
+            }
+            Ok(request)
+        })
+    }
+}
+
+impl<S, P> Layer<S> for BeneficiaryLimitsLayer<P> {
+    type Service = Limited<S, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Limited {
+            inner,
+            _ph: PhantomData,
+        }
+    }
+}
+
+/// Beneficiary API service wrapped by [BeneficiaryLimitsLayer]
+#[derive(Derivative)]
+#[derivative(Clone(bound = "S: Clone"))]
+pub struct Limited<S, P> {
+    inner: S,
+    _ph: PhantomData<fn() -> P>,
+}
+
+impl<S: NamedService, P> NamedService for Limited<S, P> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, P> Service<http::Request<Body>> for Limited<S, P>
+where
+    S: Service<http::Request<Body>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Send,
+    P: ECPoint,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        // Service that is polled ready must be the one called
+        let clone = self.inner.clone();
+        let mut inner = mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            let method = request.uri().path().rsplit('/').next().unwrap_or_default();
+            let (names_share, is_claim) = match method {
+                "VerifyServerShare" | "GetChallenge" => (true, false),
+                "ObtainServerSecretShare" => (true, true),
+                _ => (false, false),
+            };
+            if !names_share {
+                return inner.call(request).await;
+            }
+
+            // Request is buffered to read its public key, tonic would buffer it anyway
+            let (parts, body) = request.into_parts();
+            let body = match read_body(body).await {
+                Ok(body) => body,
+                Err(status) => return Ok(status.to_http()),
+            };
+            let key = public_key_of(&body).and_then(|public_key| key_of::<P>(&public_key));
+            let mut request = http::Request::from_parts(parts, Body::from(body));
+            let key = match key {
+                Some(key) => key,
+                // Malformed request is rejected by the service
+                None => return inner.call(request).await,
+            };
+            let claim = if is_claim { Some(Arc::default()) } else { None };
+            request.extensions_mut().insert(NamedShare {
+                key,
+                claim: claim.clone(),
+            });
+
+            let result = inner.call(request).await;
+            let pending = claim.and_then(|claim| claim.lock().expect("poisoned").take());
+            if let (Some(mut pending), Ok(response)) = (pending, &result) {
+                // Failed call has its status in headers, successful one has it in trailers
+                pending.outcome = match response_code(response) {
+                    None => Outcome::Success,
+                    Some(code) if is_failed_claim(code) => Outcome::Failure,
+                    Some(_) => Outcome::Inconclusive,
+                };
+            }
+            result
+        })
+    }
+}
+
+/// Buffers request `body` unless it's larger than [MAX_REQUEST_SIZE]
+async fn read_body(mut body: Body) -> Result<Vec<u8>, Status> {
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| Status::cancelled("request body is interrupted"))?;
+        if buffer.len() + chunk.len() > MAX_REQUEST_SIZE {
+            return Err(Status::resource_exhausted("request is too large"));
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(buffer)
+}
+
+/// Every beneficiary request naming a share has its `PublicKey` as the first field
+#[derive(Clone, PartialEq, ::prost::Message)]
+struct NamesShare {
+    #[prost(bytes = "vec", tag = "1")]
+    public_key: Vec<u8>,
+}
+
+/// Reads public key of uncompressed gRPC request `body`
+fn public_key_of(body: &[u8]) -> Option<Vec<u8>> {
+    const HEADER_LEN: usize = 5;
+    if body.len() < HEADER_LEN || body[0] != 0 {
+        return None;
+    }
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&body[1..HEADER_LEN]);
+    let message = body[HEADER_LEN..].get(..u32::from_be_bytes(len) as usize)?;
+    NamesShare::decode(message).ok().map(|m| m.public_key)
+}
+
+/// Returns status code of response to failed call
+fn response_code(response: &http::Response<BoxBody>) -> Option<Code> {
+    let code = response
+        .headers()
+        .get("grpc-status")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    Some(Code::from_i32(code)).filter(|code| *code != Code::Ok)
+}
+
+/// Normalizes encoding of public key, so the same share can't be limited under different keys
+fn key_of<P: ECPoint>(public_key: &[u8]) -> Option<Vec<u8>> {
+    P::from_bytes(public_key)
+        .ok()
+        .map(|point| point.pk_to_key_slice())
+}
+
+/// Tells whether claim is rejected because of beneficiary's fault rather than server state
+fn is_failed_claim(code: Code) -> bool {
+    matches!(
+        code,
+        Code::InvalidArgument | Code::NotFound | Code::PermissionDenied
+    )
+}
+
+fn rate_limited(message: &str, delay: Duration) -> Status {
+    ErrorStatus::new(Code::ResourceExhausted, Reason::RateLimited, message)
+        .retry_after(round_up(delay))
+        .into()
+}
+
+/// Retry hint is whole seconds, it mustn't be earlier than the limit is lifted
+fn round_up(delay: Duration) -> Duration {
+    Duration::from_secs(delay.as_secs() + u64::from(delay.subsec_nanos() > 0))
+}
@@ -0,0 +1,46 @@
+//! Acknowledging liveness reminders sent before the inactivity deadline
+
+use curv::elliptic::curves::traits::ECPoint;
+use tonic::{Code, Request, Response, Status};
+
+use crate::persistent_store::PersistentStore;
+use crate::proto::errors::Reason;
+use crate::proto::testator::v2 as v2t;
+use crate::proto::testator::PingRequest;
+
+use super::{status, ErrorStatus, TestatorServer};
+
+impl<S, P> TestatorServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    /// Marks reminder as acknowledged, and counts it as a ping
+    pub(super) async fn acknowledge_reminder(
+        &self,
+        request: Request<v2t::AcknowledgeReminderRequest>,
+    ) -> Result<Response<v2t::AcknowledgeReminderResponse>, Status> {
+        let reminders = self.reminders.as_ref().ok_or_else(invalid_reminder_token)?;
+        // Acknowledgement isn't signed, so it's rejected wherever unsigned pings are
+        self.check_ping_signed(&[]).await?;
+        let acknowledged = reminders
+            .acknowledge(&request.get_ref().token)
+            .await
+            .map_err(|e| status::store_error("acknowledging reminder", e))?;
+        if !acknowledged {
+            return Err(invalid_reminder_token());
+        }
+        self.ping(&[], Request::new(PingRequest {})).await?;
+        Ok(Response::new(v2t::AcknowledgeReminderResponse {}))
+    }
+}
+
+fn invalid_reminder_token() -> Status {
+    ErrorStatus::new(
+        Code::InvalidArgument,
+        Reason::InvalidReminderToken,
+        "reminder token is invalid or outdated",
+    )
+    .into()
+}
@@ -0,0 +1,149 @@
+//! Error statuses carrying machine-readable [ErrorDetails]
+//!
+//! Clients decode `ErrorDetails` from status details instead of matching messages, which are
+//! meant for humans only.
+
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use bytes::Bytes;
+use prost::Message;
+use tonic::{Code, Status};
+
+use crate::liveness::{IssueNonceError, PING_NONCE_TTL};
+use crate::persistent_store::{Challenge, ClaimProgress, StoreError};
+use crate::proto::errors::{CurrentChallenge, ErrorDetails, Reason};
+
+/// Builds error status with details attached
+pub struct ErrorStatus {
+    code: Code,
+    message: String,
+    details: ErrorDetails,
+}
+
+impl ErrorStatus {
+    pub fn new(code: Code, reason: Reason, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: ErrorDetails {
+                reason: reason as i32,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Tells client to retry in `delay`
+    pub fn retry_after(mut self, delay: Duration) -> Self {
+        self.details.retry_after_seconds = delay.as_secs();
+        self
+    }
+
+    /// Tells client which challenge is currently issued
+    pub fn challenge(mut self, challenge: &Challenge) -> Self {
+        self.details.current_challenge =
+            u64::try_from(challenge.id).ok().map(|id| CurrentChallenge {
+                id,
+                round: challenge.round,
+            });
+        self
+    }
+
+    /// Tells client which round of the claim is up next
+    pub fn progress(mut self, progress: &ClaimProgress) -> Self {
+        self.details.current_challenge =
+            u64::try_from(progress.challenge_id)
+                .ok()
+                .map(|id| CurrentChallenge {
+                    id,
+                    round: progress.rounds_completed,
+                });
+        self
+    }
+}
+
+impl From<ErrorStatus> for Status {
+    fn from(status: ErrorStatus) -> Self {
+        let mut details = Vec::with_capacity(status.details.encoded_len());
+        status
+            .details
+            .encode(&mut details)
+            .expect("vector has enough capacity");
+        Status::with_details(status.code, status.message, Bytes::from(details))
+    }
+}
+
+pub fn internal(message: impl Into<String>) -> Status {
+    ErrorStatus::new(Code::Internal, Reason::Internal, message).into()
+}
+
+pub fn invalid_request(message: impl Into<String>) -> Status {
+    ErrorStatus::new(Code::InvalidArgument, Reason::InvalidRequest, message).into()
+}
+
+/// Maps failure to issue a ping or auth nonce
+pub fn issue_nonce_error(error: IssueNonceError) -> Status {
+    match error {
+        IssueNonceError::TooMany => ErrorStatus::new(
+            Code::ResourceExhausted,
+            Reason::RateLimited,
+            "too many nonces are outstanding",
+        )
+        .retry_after(PING_NONCE_TTL)
+        .into(),
+        IssueNonceError::Rng(_) => internal("generate nonce"),
+    }
+}
+
+/// Maps failure of persistent store, `action` tells what the store was asked to do
+pub fn store_error(action: &str, error: StoreError) -> Status {
+    let (code, reason) = match &error {
+        StoreError::AlreadyExists(_) => (Code::AlreadyExists, Reason::AlreadyExists),
+        StoreError::Conflict(_) => (Code::Aborted, Reason::Conflict),
+        StoreError::Corrupted(_) => (Code::DataLoss, Reason::Internal),
+        StoreError::Io(_) => (Code::Internal, Reason::Internal),
+        StoreError::NotReplicated(_) => (Code::Unavailable, Reason::NotReplicated),
+    };
+    ErrorStatus::new(
+        code,
+        reason,
+        format!("{} resulted in error: {}", action, error),
+    )
+    .into()
+}
+
+/// Returned whenever there's no share matching the request. Message and details are the same
+/// whether there's no share for given public key at all, or the share doesn't match beneficiary's
+/// share or heir's contribution.
+pub fn share_not_found() -> Status {
+    ErrorStatus::new(Code::NotFound, Reason::ShareNotFound, "not found").into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn details_are_attached_to_status() {
+        let challenge = Challenge {
+            id: 3,
+            scheme: Default::default(),
+            challenge: serde_json::Value::Null,
+            round: 1,
+        };
+        let status: Status = ErrorStatus::new(Code::Unavailable, Reason::NextRoundPending, "wait")
+            .retry_after(Duration::from_secs(60))
+            .challenge(&challenge)
+            .into();
+
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(status.message(), "wait");
+        let details = ErrorDetails::decode(status.details()).unwrap();
+        assert_eq!(details.reason(), Reason::NextRoundPending);
+        assert_eq!(details.retry_after_seconds, 60);
+        assert_eq!(
+            details.current_challenge,
+            Some(CurrentChallenge { id: 3, round: 1 })
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Deprecated v1 API: `beneficiary.BeneficiaryAPI` and `testator.TestatorAPI`
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use curv::elliptic::curves::traits::ECPoint;
+use tonic::{Code, Request, Response, Status};
+use tracing::warn;
+
+use crate::persistent_store::PersistentStore;
+use crate::proto::attestation::{Attestation, GetAttestationRequest};
+use crate::proto::beneficiary::beneficiary_api_server::BeneficiaryApi;
+use crate::proto::beneficiary::{
+    Challenge, GetChallengeRequest, ObtainServerSecretShareRequest,
+    ObtainServerSecretShareResponse, VerifyServerShareRequest, VerifyServerShareResponse,
+};
+use crate::proto::errors::Reason;
+use crate::proto::testator::testator_api_server::TestatorApi;
+use crate::proto::testator::{
+    GetServerKeyRequest, PingRequest, PongResponse, SaveServerShareRequest,
+    SaveServerShareResponse, ServerKey,
+};
+
+use super::beneficiary_auth::claimant;
+use super::{BeneficiaryServer, ErrorStatus, TestatorServer};
+
+/// Called with full gRPC method name (e.g. `/testator.TestatorAPI/Ping`) before handling every v1
+/// request. Returned error is sent back to the client instead of handling the request.
+pub type DeprecationHook = Arc<dyn Fn(&'static str) -> Result<(), Status> + Send + Sync>;
+
+/// Hook logging a warning on the first call of every v1 method
+pub(super) fn warn_once() -> DeprecationHook {
+    let warned = Mutex::new(HashSet::new());
+    Arc::new(move |method: &'static str| {
+        let first_call = warned
+            .lock()
+            .map(|mut warned| warned.insert(method))
+            .unwrap_or(false);
+        if first_call {
+            warn!(
+                method,
+                "Deprecated v1 API is called, clients should move to v2"
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Hook rejecting every v1 request, so clients have to use v2
+pub fn reject_calls() -> DeprecationHook {
+    Arc::new(|method: &'static str| {
+        Err(ErrorStatus::new(
+            Code::Unimplemented,
+            Reason::ApiVersionDisabled,
+            format!("{} is disabled, use v2 API", method),
+        )
+        .into())
+    })
+}
+
+#[async_trait]
+impl<S, P> BeneficiaryApi for BeneficiaryServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    async fn verify_server_share(
+        &self,
+        request: Request<VerifyServerShareRequest>,
+    ) -> Result<Response<VerifyServerShareResponse>, Status> {
+        (self.deprecation_hook)("/beneficiary.BeneficiaryAPI/VerifyServerShare")?;
+        self.allow_unauthenticated(Some(&request.get_ref().public_key))
+            .await?;
+        let claimant = claimant(&request, None);
+        BeneficiaryServer::verify_server_share(self, claimant, request).await
+    }
+
+    async fn get_challenge(
+        &self,
+        request: Request<GetChallengeRequest>,
+    ) -> Result<Response<Challenge>, Status> {
+        (self.deprecation_hook)("/beneficiary.BeneficiaryAPI/GetChallenge")?;
+        self.allow_unauthenticated(None).await?;
+        let claimant = claimant(&request, None);
+        BeneficiaryServer::get_challenge(self, claimant, request).await
+    }
+
+    async fn obtain_server_secret_share(
+        &self,
+        request: Request<ObtainServerSecretShareRequest>,
+    ) -> Result<Response<ObtainServerSecretShareResponse>, Status> {
+        (self.deprecation_hook)("/beneficiary.BeneficiaryAPI/ObtainServerSecretShare")?;
+        self.allow_unauthenticated(Some(&request.get_ref().public_key))
+            .await?;
+        let claimant = claimant(&request, None);
+        BeneficiaryServer::obtain_server_secret_share(self, claimant, request).await
+    }
+
+    async fn get_attestation(
+        &self,
+        request: Request<GetAttestationRequest>,
+    ) -> Result<Response<Attestation>, Status> {
+        (self.deprecation_hook)("/beneficiary.BeneficiaryAPI/GetAttestation")?;
+        BeneficiaryServer::get_attestation(self, request).await
+    }
+}
+
+#[async_trait]
+impl<S, P> TestatorApi for TestatorServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
+        (self.deprecation_hook)("/testator.TestatorAPI/Ping")?;
+        self.authenticate(&request).await?;
+        TestatorServer::ping(self, &[], request).await
+    }
+
+    async fn get_server_key(
+        &self,
+        request: Request<GetServerKeyRequest>,
+    ) -> Result<Response<ServerKey>, Status> {
+        (self.deprecation_hook)("/testator.TestatorAPI/GetServerKey")?;
+        self.authenticate(&request).await?;
+        TestatorServer::get_server_key(self, request).await
+    }
+
+    async fn save_server_share(
+        &self,
+        request: Request<SaveServerShareRequest>,
+    ) -> Result<Response<SaveServerShareResponse>, Status> {
+        (self.deprecation_hook)("/testator.TestatorAPI/SaveServerShare")?;
+        let caller = self.authenticate(&request).await?;
+        TestatorServer::save_server_share(self, caller, None, vec![], request).await
+    }
+
+    async fn get_attestation(
+        &self,
+        request: Request<GetAttestationRequest>,
+    ) -> Result<Response<Attestation>, Status> {
+        (self.deprecation_hook)("/testator.TestatorAPI/GetAttestation")?;
+        self.authenticate(&request).await?;
+        TestatorServer::get_attestation(self, request).await
+    }
+}
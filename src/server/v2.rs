@@ -0,0 +1,464 @@
+//! v2 API: `beneficiary.v2.BeneficiaryAPI` and `testator.v2.TestatorAPI`
+//!
+//! Requests are converted to their v1 counterparts and handled by the same logic, so both versions
+//! behave alike. Unlike v1, v2 uses only protobuf encoding of challenges and solutions, and doesn't
+//! accept plaintext server share. Enrollment of testator devices, signed pings and authentication
+//! of beneficiaries are served by v2 only.
+
+use async_trait::async_trait;
+use curv::elliptic::curves::traits::ECPoint;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::persistent_store::PersistentStore;
+use crate::proto::attestation::{Attestation, GetAttestationRequest};
+use crate::proto::beneficiary::{self as v1b, v2 as v2b};
+use crate::proto::testator::{self as v1t, v2 as v2t};
+
+use super::beneficiary_auth::claimant;
+use super::keepalive::KeepAliveEvents;
+use super::{status, BeneficiaryServer, TestatorServer};
+
+#[async_trait]
+impl<S, P> v2b::beneficiary_api_server::BeneficiaryApi for BeneficiaryServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    async fn verify_server_share(
+        &self,
+        request: Request<v2b::VerifyServerShareRequest>,
+    ) -> Result<Response<v2b::VerifyServerShareResponse>, Status> {
+        let auth = request.get_ref().auth.as_ref();
+        let auth_key = self
+            .authenticate(&request.get_ref().public_key, auth)
+            .await?;
+        let claimant = claimant(&request, auth_key);
+        BeneficiaryServer::verify_server_share(self, claimant, convert_request(request))
+            .await
+            .map(convert_response)
+    }
+
+    async fn get_challenge(
+        &self,
+        request: Request<v2b::GetChallengeRequest>,
+    ) -> Result<Response<v2b::Challenge>, Status> {
+        let auth = request.get_ref().auth.as_ref();
+        let auth_key = self
+            .authenticate(&request.get_ref().public_key, auth)
+            .await?;
+        let claimant = claimant(&request, auth_key);
+        BeneficiaryServer::get_challenge(self, claimant, convert_request(request))
+            .await
+            .map(convert_response)
+    }
+
+    async fn obtain_server_secret_share(
+        &self,
+        request: Request<v2b::ObtainServerSecretShareRequest>,
+    ) -> Result<Response<v2b::ObtainServerSecretShareResponse>, Status> {
+        let auth = request.get_ref().auth.as_ref();
+        let auth_key = self
+            .authenticate(&request.get_ref().public_key, auth)
+            .await?;
+        let claimant = claimant(&request, auth_key);
+        BeneficiaryServer::obtain_server_secret_share(self, claimant, convert_request(request))
+            .await
+            .map(convert_response)
+    }
+
+    async fn get_attestation(
+        &self,
+        request: Request<GetAttestationRequest>,
+    ) -> Result<Response<Attestation>, Status> {
+        BeneficiaryServer::get_attestation(self, request).await
+    }
+
+    async fn get_auth_nonce(
+        &self,
+        request: Request<v2b::GetAuthNonceRequest>,
+    ) -> Result<Response<v2b::AuthNonce>, Status> {
+        BeneficiaryServer::get_auth_nonce(self, request).await
+    }
+}
+
+#[async_trait]
+impl<S, P> v2t::testator_api_server::TestatorApi for TestatorServer<S, P>
+where
+    P: ECPoint + Clone + Send + Sync + 'static,
+    P::Scalar: Clone + Send + Sync,
+    S: PersistentStore<P> + 'static,
+{
+    async fn ping(
+        &self,
+        request: Request<v2t::PingRequest>,
+    ) -> Result<Response<v2t::PongResponse>, Status> {
+        self.authenticate(&request).await?;
+        let signed_for = self.verify_ping(request.get_ref()).await?;
+        TestatorServer::ping(self, &signed_for, convert_request(request))
+            .await
+            .map(convert_response)
+    }
+
+    async fn get_ping_nonce(
+        &self,
+        request: Request<v2t::GetPingNonceRequest>,
+    ) -> Result<Response<v2t::PingNonce>, Status> {
+        self.authenticate(&request).await?;
+        TestatorServer::get_ping_nonce(self, request).await
+    }
+
+    type KeepAliveStream = KeepAliveEvents;
+
+    async fn keep_alive(
+        &self,
+        request: Request<Streaming<v2t::PingRequest>>,
+    ) -> Result<Response<Self::KeepAliveStream>, Status> {
+        let caller = self.authenticate(&request).await?;
+        TestatorServer::keep_alive(self, caller, request)
+    }
+
+    async fn get_server_key(
+        &self,
+        request: Request<v2t::GetServerKeyRequest>,
+    ) -> Result<Response<v2t::ServerKey>, Status> {
+        self.authenticate(&request).await?;
+        TestatorServer::get_server_key(self, convert_request(request))
+            .await
+            .map(convert_response)
+    }
+
+    async fn save_server_share(
+        &self,
+        request: Request<v2t::SaveServerShareRequest>,
+    ) -> Result<Response<v2t::SaveServerShareResponse>, Status> {
+        let caller = self.authenticate(&request).await?;
+        if request.get_ref().encrypted_server_secret_share.is_none() {
+            return Err(status::invalid_request(
+                "encrypted server secret share is not provided",
+            ));
+        }
+        let liveness_key = match &request.get_ref().liveness_key {
+            key if key.is_empty() => None,
+            key => Some(
+                P::from_bytes(key).map_err(|_e| status::invalid_request("invalid liveness key"))?,
+            ),
+        };
+        let beneficiary_keys = request
+            .get_ref()
+            .beneficiary_auth_keys
+            .iter()
+            .map(|key| P::from_bytes(key))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_e| status::invalid_request("invalid beneficiary auth key"))?;
+        TestatorServer::save_server_share(
+            self,
+            caller,
+            liveness_key,
+            beneficiary_keys,
+            convert_request(request),
+        )
+        .await
+        .map(convert_response)
+    }
+
+    async fn get_attestation(
+        &self,
+        request: Request<GetAttestationRequest>,
+    ) -> Result<Response<Attestation>, Status> {
+        self.authenticate(&request).await?;
+        TestatorServer::get_attestation(self, request).await
+    }
+
+    async fn enroll(
+        &self,
+        request: Request<v2t::EnrollRequest>,
+    ) -> Result<Response<v2t::EnrollResponse>, Status> {
+        TestatorServer::enroll(self, request).await
+    }
+
+    async fn create_enrollment_token(
+        &self,
+        request: Request<v2t::CreateEnrollmentTokenRequest>,
+    ) -> Result<Response<v2t::EnrollmentToken>, Status> {
+        TestatorServer::create_enrollment_token(self, request).await
+    }
+
+    async fn list_devices(
+        &self,
+        request: Request<v2t::ListDevicesRequest>,
+    ) -> Result<Response<v2t::ListDevicesResponse>, Status> {
+        TestatorServer::list_devices(self, request).await
+    }
+
+    async fn revoke_device(
+        &self,
+        request: Request<v2t::RevokeDeviceRequest>,
+    ) -> Result<Response<v2t::RevokeDeviceResponse>, Status> {
+        TestatorServer::revoke_device(self, request).await
+    }
+
+    async fn acknowledge_reminder(
+        &self,
+        request: Request<v2t::AcknowledgeReminderRequest>,
+    ) -> Result<Response<v2t::AcknowledgeReminderResponse>, Status> {
+        self.authenticate(&request).await?;
+        TestatorServer::acknowledge_reminder(self, request).await
+    }
+
+    async fn get_audit_checkpoint(
+        &self,
+        request: Request<v2t::GetAuditCheckpointRequest>,
+    ) -> Result<Response<v2t::AuditCheckpoint>, Status> {
+        self.authenticate(&request).await?;
+        TestatorServer::get_audit_checkpoint(self, request).await
+    }
+}
+
+/// Converts request message keeping request metadata
+fn convert_request<T, U: From<T>>(request: Request<T>) -> Request<U> {
+    let metadata = request.metadata().clone();
+    let mut converted = Request::new(U::from(request.into_inner()));
+    *converted.metadata_mut() = metadata;
+    converted
+}
+
+fn convert_response<T, U: From<T>>(response: Response<T>) -> Response<U> {
+    Response::new(U::from(response.into_inner()))
+}
+
+impl From<v2b::VerifyServerShareRequest> for v1b::VerifyServerShareRequest {
+    fn from(r: v2b::VerifyServerShareRequest) -> Self {
+        Self {
+            public_key: r.public_key,
+            client_public_share: r.client_public_share,
+        }
+    }
+}
+
+impl From<v1b::VerifyServerShareResponse> for v2b::VerifyServerShareResponse {
+    fn from(r: v1b::VerifyServerShareResponse) -> Self {
+        Self {
+            server_public_share: r.server_public_share,
+            escrow_piece: r.escrow_piece.map(|piece| v2b::EscrowPieceInfo {
+                index: piece.index,
+                threshold: piece.threshold,
+                servers_count: piece.servers_count,
+                commitments: piece.commitments,
+            }),
+        }
+    }
+}
+
+impl From<v2b::GetChallengeRequest> for v1b::GetChallengeRequest {
+    fn from(_r: v2b::GetChallengeRequest) -> Self {
+        Self {}
+    }
+}
+
+impl From<v1b::Challenge> for v2b::Challenge {
+    fn from(c: v1b::Challenge) -> Self {
+        use v1b::challenge::Input;
+        Self {
+            id: c.number,
+            scheme: c.scheme,
+            input: c.input.map(|input| match input {
+                Input::Vdf(vdf) => v2b::challenge::Input::Vdf(vdf.into()),
+                Input::HashChain(hc) => v2b::challenge::Input::HashChain(hc.into()),
+            }),
+            expected_delay_seconds: c.expected_delay_seconds,
+            round: c.round,
+            rounds: c.rounds,
+        }
+    }
+}
+
+impl From<v2b::Challenge> for v1b::Challenge {
+    fn from(c: v2b::Challenge) -> Self {
+        use v2b::challenge::Input;
+        Self {
+            id: vec![],
+            challenge: vec![],
+            number: c.id,
+            scheme: c.scheme,
+            input: c.input.map(|input| match input {
+                Input::Vdf(vdf) => v1b::challenge::Input::Vdf(vdf.into()),
+                Input::HashChain(hc) => v1b::challenge::Input::HashChain(hc.into()),
+            }),
+            expected_delay_seconds: c.expected_delay_seconds,
+            round: c.round,
+            rounds: c.rounds,
+        }
+    }
+}
+
+impl From<v1b::VdfChallenge> for v2b::VdfChallenge {
+    fn from(c: v1b::VdfChallenge) -> Self {
+        Self {
+            setup: c.setup.map(|setup| v2b::VdfSetup {
+                modulus: setup.modulus,
+                t: setup.t,
+            }),
+            x: c.x,
+        }
+    }
+}
+
+impl From<v2b::VdfChallenge> for v1b::VdfChallenge {
+    fn from(c: v2b::VdfChallenge) -> Self {
+        Self {
+            setup: c.setup.map(|setup| v1b::VdfSetup {
+                modulus: setup.modulus,
+                t: setup.t,
+            }),
+            x: c.x,
+        }
+    }
+}
+
+impl From<v1b::HashChainChallenge> for v2b::HashChainChallenge {
+    fn from(c: v1b::HashChainChallenge) -> Self {
+        Self {
+            seed: c.seed,
+            iterations: c.iterations,
+        }
+    }
+}
+
+impl From<v2b::HashChainChallenge> for v1b::HashChainChallenge {
+    fn from(c: v2b::HashChainChallenge) -> Self {
+        Self {
+            seed: c.seed,
+            iterations: c.iterations,
+        }
+    }
+}
+
+impl From<v2b::ObtainServerSecretShareRequest> for v1b::ObtainServerSecretShareRequest {
+    fn from(r: v2b::ObtainServerSecretShareRequest) -> Self {
+        use v1b::obtain_server_secret_share_request::NativeSolution;
+        use v2b::obtain_server_secret_share_request::Solution;
+        Self {
+            public_key: r.public_key,
+            client_public_share: r.client_public_share,
+            solved_challenge: r.solved_challenge.map(Into::into),
+            solution: vec![],
+            contribution: r.contribution.map(|c| v1b::HeirContribution {
+                index: c.index,
+                public_piece: c.public_piece,
+                proof_commitment: c.proof_commitment,
+                proof_response: c.proof_response,
+            }),
+            native_solution: r.solution.map(|solution| match solution {
+                Solution::Vdf(s) => NativeSolution::VdfSolution(v1b::VdfSolution {
+                    y: s.y,
+                    proof: s.proof,
+                }),
+                Solution::HashChain(s) => {
+                    NativeSolution::HashChainSolution(v1b::HashChainSolution {
+                        output: s.output,
+                        checkpoints: s.checkpoints,
+                    })
+                }
+            }),
+        }
+    }
+}
+
+impl From<v1b::ObtainServerSecretShareResponse> for v2b::ObtainServerSecretShareResponse {
+    fn from(r: v1b::ObtainServerSecretShareResponse) -> Self {
+        Self {
+            server_secret_share: r.server_secret_share,
+            contributions_collected: r.contributions_collected,
+            contributions_required: r.contributions_required,
+            escrow_piece_index: r.escrow_piece_index,
+            rounds_completed: r.rounds_completed,
+            rounds_required: r.rounds_required,
+            next_round_delay_seconds: r.next_round_delay_seconds,
+        }
+    }
+}
+
+impl From<v2t::PingRequest> for v1t::PingRequest {
+    fn from(_r: v2t::PingRequest) -> Self {
+        Self {}
+    }
+}
+
+impl From<v1t::PongResponse> for v2t::PongResponse {
+    fn from(_r: v1t::PongResponse) -> Self {
+        Self {}
+    }
+}
+
+impl From<v2t::GetServerKeyRequest> for v1t::GetServerKeyRequest {
+    fn from(_r: v2t::GetServerKeyRequest) -> Self {
+        Self {}
+    }
+}
+
+impl From<v1t::ServerKey> for v2t::ServerKey {
+    fn from(k: v1t::ServerKey) -> Self {
+        Self {
+            public_key: k.public_key,
+            signature: k.signature,
+        }
+    }
+}
+
+impl From<v2t::SaveServerShareRequest> for v1t::SaveServerShareRequest {
+    fn from(r: v2t::SaveServerShareRequest) -> Self {
+        Self {
+            public_key: r.public_key,
+            server_secret_share: vec![],
+            encrypted_server_secret_share: r.encrypted_server_secret_share.map(|share| {
+                v1t::EncryptedShare {
+                    ephemeral_key: share.ephemeral_key,
+                    nonce: share.nonce,
+                    ciphertext: share.ciphertext,
+                }
+            }),
+            beneficiaries: r.beneficiaries.map(|b| v1t::BeneficiaryCommitments {
+                threshold: b.threshold,
+                heirs_count: b.heirs_count,
+                commitments: b.commitments,
+            }),
+            escrow_piece: r.escrow_piece.map(|piece| v1t::EscrowPiece {
+                index: piece.index,
+                threshold: piece.threshold,
+                servers_count: piece.servers_count,
+                commitments: piece.commitments,
+            }),
+        }
+    }
+}
+
+impl From<v1t::SaveServerShareResponse> for v2t::SaveServerShareResponse {
+    fn from(_r: v1t::SaveServerShareResponse) -> Self {
+        Self {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenge_survives_v1_v2_roundtrip() {
+        let challenge = v2b::Challenge {
+            id: 42,
+            scheme: "hash-chain".to_string(),
+            input: Some(v2b::challenge::Input::HashChain(v2b::HashChainChallenge {
+                seed: vec![1; 32],
+                iterations: 1000,
+            })),
+            expected_delay_seconds: 60,
+            round: 1,
+            rounds: 3,
+        };
+        let v1 = v1b::Challenge::from(challenge.clone());
+        assert!(v1.id.is_empty());
+        assert_eq!(v1.number, 42);
+        assert_eq!(v2b::Challenge::from(v1), challenge);
+    }
+}
@@ -0,0 +1,184 @@
+use curv::elliptic::curves::traits::ECPoint;
+use ring::{aead, hkdf, signature};
+
+/// Domain separator for key derivation. Must be kept in sync with client implementation.
+const KDF_INFO: &[u8] = b"zengo-will/share-encryption/v1";
+/// Domain separator prepended to encryption public key before signing it with TLS key
+pub const SERVER_KEY_SIGNING_CONTEXT: &[u8] = b"zengo-will/server-key/v1";
+
+/// Will's key used by testators to encrypt server secret share
+///
+/// Share is encrypted with ECIES: testator picks an ephemeral key, derives a symmetric
+/// ChaCha20-Poly1305 key from a DH shared point via HKDF-SHA256, and encrypts the share using
+/// joint public key as associated data. Therefore the share is only revealed inside Will process
+/// and never appears in plaintext at TLS-terminating proxies.
+pub struct ShareDecryptionKey<P: ECPoint> {
+    secret: P::Scalar,
+    public: P,
+}
+
+impl<P> ShareDecryptionKey<P>
+where
+    P: ECPoint + Clone,
+    P::Scalar: Clone,
+{
+    pub fn from_secret(secret: P::Scalar) -> Self {
+        let public = P::generator() * secret.clone();
+        Self { secret, public }
+    }
+
+    pub fn public_key(&self) -> &P {
+        &self.public
+    }
+
+    /// Decrypts a share encrypted to this key
+    ///
+    /// `associated_data` must match the one used at encryption, i.e. serialized joint public key.
+    pub fn decrypt(
+        &self,
+        ephemeral_key: P,
+        nonce: &[u8],
+        ciphertext: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, DecryptError> {
+        let shared_point = ephemeral_key.clone() * self.secret.clone();
+        let key = derive_key(&ephemeral_key, &shared_point)?;
+        let nonce = aead::Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| DecryptError::InvalidNonce)?;
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = key
+            .open_in_place(nonce, aead::Aad::from(associated_data), &mut in_out)
+            .map_err(|_| DecryptError::InvalidCiphertext)?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+fn derive_key<P: ECPoint>(
+    ephemeral_key: &P,
+    shared_point: &P,
+) -> Result<aead::LessSafeKey, DecryptError> {
+    let salt = hkdf::Salt::new(hkdf::HKDF_SHA256, &ephemeral_key.pk_to_key_slice());
+    let prk = salt.extract(&shared_point.pk_to_key_slice());
+    let okm = prk
+        .expand(&[KDF_INFO], &aead::CHACHA20_POLY1305)
+        .map_err(|_| DecryptError::KeyDerivation)?;
+    Ok(aead::LessSafeKey::new(aead::UnboundKey::from(okm)))
+}
+
+/// Encrypts a share to Will's public key. Mirrors client implementation, used in tests.
+#[cfg(test)]
+pub fn encrypt<P>(
+    server_key: &P,
+    plaintext: &[u8],
+    associated_data: &[u8],
+) -> (P, [u8; aead::NONCE_LEN], Vec<u8>)
+where
+    P: ECPoint + Clone,
+    P::Scalar: Clone,
+{
+    use curv::elliptic::curves::traits::ECScalar;
+    use ring::rand::SecureRandom;
+
+    let ephemeral_secret = P::Scalar::new_random();
+    let ephemeral_key = P::generator() * ephemeral_secret.clone();
+    let shared_point = server_key.clone() * ephemeral_secret;
+    let key = derive_key(&ephemeral_key, &shared_point).unwrap();
+
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    ring::rand::SystemRandom::new().fill(&mut nonce).unwrap();
+    let mut in_out = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        aead::Nonce::assume_unique_for_key(nonce),
+        aead::Aad::from(associated_data),
+        &mut in_out,
+    )
+    .unwrap();
+    (ephemeral_key, nonce, in_out)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecryptError {
+    #[error("invalid nonce")]
+    InvalidNonce,
+    #[error("key derivation failed")]
+    KeyDerivation,
+    #[error("ciphertext cannot be decrypted")]
+    InvalidCiphertext,
+}
+
+/// Signs Will's public keys with TLS private key, binding them to TLS certificate
+pub enum TlsKeySigner {
+    Ecdsa(signature::EcdsaKeyPair),
+    Rsa(signature::RsaKeyPair),
+}
+
+impl TlsKeySigner {
+    /// Parses PKCS#8 DER-encoded private key. Supports ECDSA P-256, ECDSA P-384 and RSA keys.
+    pub fn from_pkcs8(der: &[u8]) -> Result<Self, ring::error::KeyRejected> {
+        signature::EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_ASN1_SIGNING, der)
+            .or_else(|_| {
+                signature::EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P384_SHA384_ASN1_SIGNING, der)
+            })
+            .map(TlsKeySigner::Ecdsa)
+            .or_else(|_| signature::RsaKeyPair::from_pkcs8(der).map(TlsKeySigner::Rsa))
+    }
+
+    /// Parses PEM-encoded PKCS#8 private key
+    pub fn from_pem(pem: &[u8]) -> anyhow::Result<Self> {
+        let pem = pem::parse(pem)?;
+        if pem.tag != "PRIVATE KEY" {
+            anyhow::bail!("expected PKCS#8 private key, got {}", pem.tag)
+        }
+        Self::from_pkcs8(&pem.contents).map_err(|e| anyhow::anyhow!("invalid private key: {}", e))
+    }
+
+    /// Signs `SERVER_KEY_SIGNING_CONTEXT || message`
+    pub fn sign(&self, message: &[u8]) -> Result<Vec<u8>, ring::error::Unspecified> {
+        self.sign_with_context(SERVER_KEY_SIGNING_CONTEXT, message)
+    }
+
+    /// Signs `context || message`, so signatures made for different purposes can't be confused
+    pub fn sign_with_context(
+        &self,
+        context: &[u8],
+        message: &[u8],
+    ) -> Result<Vec<u8>, ring::error::Unspecified> {
+        let rng = ring::rand::SystemRandom::new();
+        let msg = [context, message].concat();
+        match self {
+            TlsKeySigner::Ecdsa(key) => Ok(key.sign(&rng, &msg)?.as_ref().to_vec()),
+            TlsKeySigner::Rsa(key) => {
+                let mut sig = vec![0u8; key.public_modulus_len()];
+                key.sign(&signature::RSA_PKCS1_SHA256, &rng, &msg, &mut sig)?;
+                Ok(sig)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use curv::elliptic::curves::secp256_k1::{FE, GE};
+    use curv::elliptic::curves::traits::ECScalar;
+
+    use super::{encrypt, ShareDecryptionKey};
+
+    #[test]
+    fn decrypt_encrypted_share() {
+        let key = ShareDecryptionKey::<GE>::from_secret(FE::new_random());
+        let (ephemeral_key, nonce, ciphertext) = encrypt(key.public_key(), b"share", b"pk");
+        let plaintext = key
+            .decrypt(ephemeral_key, &nonce, &ciphertext, b"pk")
+            .unwrap();
+        assert_eq!(plaintext, b"share");
+    }
+
+    #[test]
+    fn reject_share_bound_to_another_public_key() {
+        let key = ShareDecryptionKey::<GE>::from_secret(FE::new_random());
+        let (ephemeral_key, nonce, ciphertext) = encrypt(key.public_key(), b"share", b"pk");
+        assert!(key
+            .decrypt(ephemeral_key, &nonce, &ciphertext, b"another pk")
+            .is_err());
+    }
+}
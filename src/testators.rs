@@ -0,0 +1,194 @@
+//! Testator accounts and their devices
+//!
+//! Testator authenticates with a client certificate issued by `--testator-ca`. Every certificate
+//! is a device identified by SHA-256 fingerprint of its SubjectPublicKeyInfo, so a certificate
+//! renewed for the same key remains the same device. The first device of a testator enrolls on its
+//! own, which creates an account. Further devices join the account with a single-use enrollment
+//! token issued to one of its enrolled devices. Any device of the account might revoke any other
+//! one, and revoked device can't be enrolled again.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::der;
+
+/// How long enrollment token might be used after it's issued
+pub const ENROLLMENT_TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Identifier of testator's account
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+pub struct AccountId(pub [u8; 16]);
+
+impl AccountId {
+    pub fn random() -> Result<Self, ring::error::Unspecified> {
+        let mut id = [0u8; 16];
+        SystemRandom::new().fill(&mut id)?;
+        Ok(Self(id))
+    }
+}
+
+impl fmt::Display for AccountId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_hex(f, &self.0)
+    }
+}
+
+/// SHA-256 of SubjectPublicKeyInfo of device certificate
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+pub struct DeviceFingerprint(pub [u8; 32]);
+
+impl DeviceFingerprint {
+    /// Computes fingerprint of DER-encoded X.509 certificate. Returns `None` if certificate is
+    /// malformed.
+    pub fn of_certificate(certificate: &[u8]) -> Option<Self> {
+        let spki = subject_public_key_info(certificate)?;
+        let mut fingerprint = [0u8; 32];
+        fingerprint.copy_from_slice(digest::digest(&digest::SHA256, spki).as_ref());
+        Some(Self(fingerprint))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 32 {
+            return None;
+        }
+        let mut fingerprint = [0u8; 32];
+        fingerprint.copy_from_slice(bytes);
+        Some(Self(fingerprint))
+    }
+}
+
+impl fmt::Display for DeviceFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_hex(f, &self.0)
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+}
+
+/// Testator that made a request
+#[derive(Clone, Copy, Debug)]
+pub enum Caller {
+    /// Device enrolled to the account
+    Device {
+        account: AccountId,
+        fingerprint: DeviceFingerprint,
+    },
+    /// Device presented a certificate issued by testator CA, but isn't enrolled
+    Unenrolled(DeviceFingerprint),
+    /// No client certificate is presented, i.e. Will runs without `--testator-ca`
+    Anonymous,
+}
+
+impl fmt::Display for Caller {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Caller::Device {
+                account,
+                fingerprint,
+            } => write!(f, "device {} of account {}", fingerprint, account),
+            Caller::Unenrolled(fingerprint) => write!(f, "unenrolled device {}", fingerprint),
+            Caller::Anonymous => write!(f, "anonymous testator"),
+        }
+    }
+}
+
+/// Issued enrollment tokens
+///
+/// Tokens are short-lived, so they're kept in memory only: a token issued before restart or
+/// failover has to be issued again.
+#[derive(Default)]
+pub struct EnrollmentTokens {
+    issued: Mutex<HashMap<[u8; 32], (AccountId, Instant)>>,
+}
+
+impl EnrollmentTokens {
+    /// Issues a token adding a device to `account`, valid for [ENROLLMENT_TOKEN_TTL]
+    pub fn issue(&self, account: AccountId) -> Result<[u8; 32], ring::error::Unspecified> {
+        let mut token = [0u8; 32];
+        SystemRandom::new().fill(&mut token)?;
+        let mut issued = self.issued.lock().expect("poisoned");
+        issued.retain(|_, (_, expires_at)| *expires_at > Instant::now());
+        issued.insert(token, (account, Instant::now() + ENROLLMENT_TOKEN_TTL));
+        Ok(token)
+    }
+
+    /// Uses up the token. Returns account it was issued for, or `None` if token is unknown or
+    /// expired.
+    pub fn redeem(&self, token: &[u8]) -> Option<AccountId> {
+        if token.len() != 32 {
+            return None;
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(token);
+        let (account, expires_at) = self.issued.lock().expect("poisoned").remove(&key)?;
+        if expires_at <= Instant::now() {
+            return None;
+        }
+        Some(account)
+    }
+}
+
+/// Extracts DER-encoded SubjectPublicKeyInfo from DER-encoded X.509 certificate
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let mut fields = der::tbs_certificate_fields(certificate)?;
+    // Skip serialNumber, signature, issuer, validity and subject
+    for _ in 0..5 {
+        let (_, rest) = der::read(fields)?;
+        fields = rest;
+    }
+    let (spki, _) = der::expect(fields, der::SEQUENCE)?;
+    Some(spki.raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn certificate(name: &str, key_pair: rcgen::KeyPair) -> Vec<u8> {
+        let mut params = rcgen::CertificateParams::new(vec![name.to_string()]);
+        params.alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+        params.key_pair = Some(key_pair);
+        rcgen::Certificate::from_params(params)
+            .unwrap()
+            .serialize_der()
+            .unwrap()
+    }
+
+    #[test]
+    fn fingerprint_depends_only_on_public_key() {
+        let key_pair = rcgen::KeyPair::generate(&rcgen::PKCS_ECDSA_P256_SHA256).unwrap();
+        let same_key_pair = rcgen::KeyPair::from_pem(&key_pair.serialize_pem()).unwrap();
+        let another_key_pair = rcgen::KeyPair::generate(&rcgen::PKCS_ECDSA_P256_SHA256).unwrap();
+
+        let fingerprint = DeviceFingerprint::of_certificate(&certificate("phone", key_pair));
+        let renewed = DeviceFingerprint::of_certificate(&certificate("tablet", same_key_pair));
+        let another = DeviceFingerprint::of_certificate(&certificate("phone", another_key_pair));
+
+        assert!(fingerprint.is_some());
+        assert_eq!(fingerprint, renewed);
+        assert_ne!(fingerprint, another);
+        assert_eq!(
+            DeviceFingerprint::of_certificate(b"not a certificate"),
+            None
+        );
+    }
+
+    #[test]
+    fn enrollment_token_is_single_use() {
+        let tokens = EnrollmentTokens::default();
+        let account = AccountId::random().unwrap();
+        let token = tokens.issue(account).unwrap();
+
+        assert_eq!(tokens.redeem(&token), Some(account));
+        assert_eq!(tokens.redeem(&token), None);
+        assert_eq!(tokens.redeem(&[0u8; 32]), None);
+    }
+}